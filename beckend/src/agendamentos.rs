@@ -1,13 +1,19 @@
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use std::sync::{Arc, Mutex};
 use rusqlite::Connection;
 
-use crate::models::Agendamento;
+use crate::models::{Agendamento, ConflitoHorario, EtapaAgendamento, RegrasAgenda, RemarcacaoAgendamento};
 use crate::db;
+use crate::config;
+use crate::espera;
+use crate::notificacoes;
+use crate::patch;
+use crate::recursos;
+use crate::webhooks;
 use serde::Deserialize;
 use serde_json::Value as JsonValue;
 use chrono::{NaiveDateTime, DateTime, Utc, TimeZone};
@@ -32,13 +38,28 @@ pub async fn listar_agendamentos_api(State(conn): State<Db>) -> Json<Vec<Agendam
     Json(agendamentos)
 }
 
-pub async fn obter_agendamento_api(Path(id): Path<i32>, State(conn): State<Db>) -> Json<Option<Agendamento>> {
+pub async fn obter_agendamento_api(Path(id): Path<i32>, State(conn): State<Db>) -> (HeaderMap, Json<Option<Agendamento>>) {
     let conn = conn.lock().unwrap();
     match db::buscar_agendamento_por_id(&conn, id) {
-        Ok(ag) => Json(Some(ag)),
+        Ok(ag) => {
+            let versao = patch::versao(&conn, "agendamentos", id).unwrap_or(None);
+            (patch::cabecalho_etag(versao), Json(Some(ag)))
+        }
         Err(e) => {
             eprintln!("Agendamento não encontrado ou erro: {}", e);
-            Json(None)
+            (HeaderMap::new(), Json(None))
+        }
+    }
+}
+
+/// 409 quando algum recurso exigido pelos serviços já está preso nesse horário.
+fn conferir_recursos(conn: &Connection, agendamento: &Agendamento, ignorar: Option<i32>) -> Result<(), (StatusCode, Json<ApiResponse<Agendamento>>)> {
+    match recursos::conflito_de_recursos(conn, agendamento, ignorar) {
+        Ok(None) => Ok(()),
+        Ok(Some(recurso)) => Err((StatusCode::CONFLICT, Json(ApiResponse { success: false, message: recursos::mensagem_conflito(&recurso), data: None }))),
+        Err(e) => {
+            eprintln!("Erro ao verificar recursos: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao verificar recursos: {}", e), data: None })))
         }
     }
 }
@@ -50,10 +71,14 @@ pub async fn criar_agendamento_api(State(conn): State<Db>, Json(agendamento): Js
     // backend expects a chrono::NaiveDateTime, we accept a lightweight DTO and parse here.
     // (The DTO handling is done below in criar_agendamento_api_incoming)
     let conn = conn.lock().unwrap();
+    if let Err(resposta) = conferir_recursos(&conn, &agendamento, None) {
+        return resposta;
+    }
     match db::salvar_agendamento(&conn, &agendamento) {
         Ok(id) => {
             let mut saved = agendamento.clone();
             saved.id = Some(id);
+            webhooks::emitir_ou_logar(&conn, webhooks::EVENTO_AGENDAMENTO_CRIADO, &saved);
             let response = ApiResponse { success: true, message: "Agendamento criado com sucesso!".to_string(), data: Some(saved) };
             (StatusCode::CREATED, Json(response))
         }
@@ -76,7 +101,7 @@ pub struct IncomingAgendamento {
     pub concluido: bool,
 }
 
-fn parse_data_hora(value: &JsonValue) -> Result<NaiveDateTime, String> {
+pub(crate) fn parse_data_hora(value: &JsonValue) -> Result<NaiveDateTime, String> {
     match value {
         JsonValue::Number(n) => {
             if let Some(ts) = n.as_i64() {
@@ -127,33 +152,63 @@ pub async fn criar_agendamento_api_incoming(State(conn): State<Db>, Json(incomin
         preco: incoming.preco,
         concluido: incoming.concluido,
     };
-
-    match db::salvar_agendamento(&conn, &agendamento) {
-        Ok(id) => {
-            let mut saved = agendamento.clone();
-            saved.id = Some(id);
+    match criar_agendamento(&conn, &agendamento) {
+        Ok(saved) => {
             let response = ApiResponse { success: true, message: "Agendamento criado com sucesso!".to_string(), data: Some(saved) };
             (StatusCode::CREATED, Json(response))
         }
+        Err((status, message)) => (status, Json(ApiResponse { success: false, message, data: None })),
+    }
+}
+
+/// Falha de uma operação sobre o agendamento, já com o status HTTP (usado também pelo lote).
+pub(crate) type Falha = (StatusCode, String);
+
+/// Grava um agendamento novo (conferindo os recursos) e emite `agendamento.criado`.
+pub(crate) fn criar_agendamento(conn: &Connection, agendamento: &Agendamento) -> Result<Agendamento, Falha> {
+    match recursos::conflito_de_recursos(conn, agendamento, None) {
+        Ok(None) => {}
+        Ok(Some(recurso)) => return Err((StatusCode::CONFLICT, recursos::mensagem_conflito(&recurso))),
+        Err(e) => {
+            eprintln!("Erro ao verificar recursos: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao verificar recursos: {}", e)));
+        }
+    }
+    match db::salvar_agendamento(conn, agendamento) {
+        Ok(id) => {
+            let saved = Agendamento { id: Some(id), ..agendamento.clone() };
+            webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_CRIADO, &saved);
+            Ok(saved)
+        }
         Err(e) => {
             eprintln!("Erro ao criar agendamento: {}", e);
-            let response: ApiResponse<Agendamento> = ApiResponse { success: false, message: format!("Erro ao criar agendamento: {}", e), data: None };
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao criar agendamento: {}", e)))
         }
     }
 }
 
 pub async fn atualizar_agendamento_api(Path(id): Path<i32>, State(conn): State<Db>, Json(agendamento): Json<Agendamento>) -> (StatusCode, Json<ApiResponse<Agendamento>>) {
     let conn = conn.lock().unwrap();
+    if let Err(resposta) = conferir_recursos(&conn, &agendamento, Some(id)) {
+        return resposta;
+    }
+    let anterior = db::buscar_agendamento_por_id(&conn, id).ok().map(|a| a.data_hora);
 
-    let res = db::atualizar_agendamento(
-        &conn,
-        id,
-        Some(agendamento.data_hora),
-        Some(agendamento.servicos_ids.clone()),
-        Some(agendamento.preco),
-        Some(agendamento.concluido),
-    );
+    // A alteração e o registro da remarcação entram juntos.
+    let res = db::em_savepoint(&conn, |conn| {
+        db::atualizar_agendamento(
+            conn,
+            id,
+            Some(agendamento.data_hora),
+            Some(agendamento.servicos_ids.clone()),
+            Some(agendamento.preco),
+            Some(agendamento.concluido),
+        )?;
+        match anterior.filter(|a| *a != agendamento.data_hora) {
+            Some(anterior) => db::registrar_remarcacao(conn, id, anterior, agendamento.data_hora, db::ORIGEM_EDICAO, None),
+            None => Ok(()),
+        }
+    });
 
     if let Err(e) = res {
         eprintln!("Erro ao atualizar agendamento: {}", e);
@@ -163,6 +218,7 @@ pub async fn atualizar_agendamento_api(Path(id): Path<i32>, State(conn): State<D
 
     match db::buscar_agendamento_por_id(&conn, id) {
         Ok(updated) => {
+            webhooks::emitir_ou_logar(&conn, webhooks::EVENTO_AGENDAMENTO_ATUALIZADO, &updated);
             let response = ApiResponse { success: true, message: "Agendamento atualizado com sucesso!".to_string(), data: Some(updated) };
             (StatusCode::OK, Json(response))
         }
@@ -174,10 +230,274 @@ pub async fn atualizar_agendamento_api(Path(id): Path<i32>, State(conn): State<D
     }
 }
 
+/// PATCH /agendamentos/:id — merge-patch sobre o agendamento: `{"concluido": true}` marca
+/// como feito sem reenviar o resto. `data_hora` aceita os formatos da criação; o cliente não
+/// muda. Só os campos alterados vão para `db::atualizar_agendamento`.
+pub async fn patch_agendamento_api(
+    Path(id): Path<i32>,
+    State(conn): State<Db>,
+    headers: HeaderMap,
+    Json(alteracoes): Json<JsonValue>,
+) -> (StatusCode, HeaderMap, Json<ApiResponse<Agendamento>>) {
+    let resposta = |status: StatusCode, versao: Option<i64>, message: String, data: Option<Agendamento>| {
+        (status, patch::cabecalho_etag(versao), Json(ApiResponse { success: status.is_success(), message, data }))
+    };
+    let conn = conn.lock().unwrap();
+    let atual = match db::buscar_agendamento_por_id(&conn, id) {
+        Ok(agendamento) => agendamento,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return resposta(StatusCode::NOT_FOUND, None, format!("Agendamento {} não encontrado.", id), None),
+        Err(e) => {
+            eprintln!("Erro ao buscar agendamento: {}", e);
+            return resposta(StatusCode::INTERNAL_SERVER_ERROR, None, format!("Erro ao buscar agendamento: {}", e), None);
+        }
+    };
+    let versao = match patch::versao(&conn, "agendamentos", id) {
+        Ok(v) => v.unwrap_or(1),
+        Err(e) => {
+            eprintln!("Erro ao buscar versão do agendamento: {}", e);
+            return resposta(StatusCode::INTERNAL_SERVER_ERROR, None, format!("Erro ao buscar agendamento: {}", e), None);
+        }
+    };
+    if !patch::if_match_confere(&headers, versao) {
+        return resposta(StatusCode::CONFLICT, Some(versao), "O agendamento foi alterado por outra pessoa; recarregue e tente de novo.".to_string(), Some(atual));
+    }
+
+    match alterar_agendamento(&conn, &atual, alteracoes) {
+        Ok(None) => resposta(StatusCode::OK, Some(versao), "Nada a alterar.".to_string(), Some(atual)),
+        Ok(Some(atualizado)) => {
+            let nova_versao = patch::versao(&conn, "agendamentos", id).unwrap_or(None);
+            resposta(StatusCode::OK, nova_versao, "Agendamento atualizado com sucesso!".to_string(), Some(atualizado))
+        }
+        Err((status, mensagem)) => resposta(status, Some(versao), mensagem, None),
+    }
+}
+
+/// Aplica o merge-patch ao agendamento `atual`: só os campos alterados vão para
+/// `db::atualizar_agendamento`, com a conferência de recursos, o histórico de remarcação e o
+/// webhook. `Ok(None)` quando o patch não muda nada.
+pub(crate) fn alterar_agendamento(conn: &Connection, atual: &Agendamento, mut alteracoes: JsonValue) -> Result<Option<Agendamento>, Falha> {
+    let id = atual.id.unwrap_or_default();
+    if let Some(valor) = alteracoes.get_mut("data_hora").filter(|v| !v.is_null()) {
+        match parse_data_hora(valor) {
+            Ok(dt) => *valor = serde_json::to_value(dt).unwrap_or(JsonValue::Null),
+            Err(msg) => return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid data_hora: {}", msg))),
+        }
+    }
+    let novo: Agendamento = patch::mesclar(atual, &alteracoes, &["id", "cliente_id"]).map_err(|m| (StatusCode::BAD_REQUEST, m))?;
+    if novo.servicos_ids.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "O agendamento precisa de pelo menos um serviço.".to_string()));
+    }
+
+    let horario = Some(novo.data_hora).filter(|dt| *dt != atual.data_hora);
+    let servicos = Some(novo.servicos_ids.clone()).filter(|s| *s != atual.servicos_ids);
+    let preco = Some(novo.preco).filter(|p| *p != atual.preco);
+    let concluido = Some(novo.concluido).filter(|c| *c != atual.concluido);
+    if horario.is_none() && servicos.is_none() && preco.is_none() && concluido.is_none() {
+        return Ok(None);
+    }
+    if horario.is_some() || servicos.is_some() {
+        if let Err((status, Json(erro))) = conferir_recursos(conn, &novo, Some(id)) {
+            return Err((status, erro.message));
+        }
+    }
+
+    let gravado = db::em_savepoint(conn, |conn| {
+        db::atualizar_agendamento(conn, id, horario, servicos, preco, concluido)?;
+        match horario {
+            Some(nova) => db::registrar_remarcacao(conn, id, atual.data_hora, nova, db::ORIGEM_EDICAO, None),
+            None => Ok(()),
+        }
+    });
+    if let Err(e) = gravado {
+        eprintln!("Erro ao atualizar agendamento: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao atualizar agendamento: {}", e)));
+    }
+
+    match db::buscar_agendamento_por_id(conn, id) {
+        Ok(atualizado) => {
+            webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_ATUALIZADO, &atualizado);
+            Ok(Some(atualizado))
+        }
+        Err(e) => {
+            eprintln!("Erro ao buscar agendamento atualizado: {}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Agendamento atualizado porém falha ao recuperá-lo: {}", e)))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReagendarQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Deserialize)]
+pub struct PedidoReagendamento {
+    /// Novo início, nos mesmos formatos aceitos na criação.
+    pub data_hora: JsonValue,
+    /// Avisar o cliente pelo canal de notificações.
+    #[serde(default)]
+    pub notificar: bool,
+    pub motivo: Option<String>,
+    /// Só para recusar (ver `recusar_profissional`).
+    pub profissional_id: Option<JsonValue>,
+}
+
+#[derive(serde::Serialize)]
+pub struct ResultadoReagendamento {
+    pub agendamento: Agendamento,
+    pub data_hora_anterior: NaiveDateTime,
+    /// Falso na prévia (`dry_run`) e quando há conflitos.
+    pub aplicado: bool,
+    pub conflitos: Vec<ConflitoHorario>,
+    pub notificacao_id: Option<i64>,
+}
+
+fn resposta_reagendar(status: StatusCode, mensagem: String, data: Option<ResultadoReagendamento>) -> (StatusCode, Json<ApiResponse<ResultadoReagendamento>>) {
+    (status, Json(ApiResponse { success: status.is_success(), message: mensagem, data }))
+}
+
+/// POST /agendamentos/:id/reagendar?dry_run=true — move o agendamento para outro início,
+/// conferindo expediente, outros agendamentos, bloqueios e recursos. Com conflito responde
+/// 409 e a lista; com `dry_run` só mostra o que aconteceria. A agenda é única: pedir troca
+/// de profissional (`profissional_id`) responde 400.
+pub async fn reagendar_agendamento_api(
+    Path(id): Path<i32>,
+    Query(q): Query<ReagendarQuery>,
+    State(conn): State<Db>,
+    Json(pedido): Json<PedidoReagendamento>,
+) -> (StatusCode, Json<ApiResponse<ResultadoReagendamento>>) {
+    if let Err((status, mensagem)) = recusar_profissional("profissional_id", &pedido.profissional_id) {
+        return resposta_reagendar(status, mensagem, None);
+    }
+    let nova = match parse_data_hora(&pedido.data_hora) {
+        Ok(dt) => dt,
+        Err(msg) => return resposta_reagendar(StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid data_hora: {}", msg), None),
+    };
+    let conn = conn.lock().unwrap();
+    let atual = match db::buscar_agendamento_por_id(&conn, id) {
+        Ok(agendamento) => agendamento,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return resposta_reagendar(StatusCode::NOT_FOUND, format!("Agendamento {} não encontrado.", id), None),
+        Err(e) => {
+            eprintln!("Erro ao buscar agendamento: {}", e);
+            return resposta_reagendar(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao buscar agendamento: {}", e), None);
+        }
+    };
+    let motivo = pedido.motivo.as_deref().map(str::trim).filter(|m| !m.is_empty());
+    let (status, mensagem, resultado) = remarcar(&conn, &atual, nova, motivo, pedido.notificar, !q.dry_run);
+    resposta_reagendar(status, mensagem, resultado)
+}
+
+/// Confere o novo horário e, com `aplicar`, grava a remarcação, o histórico, o webhook e o
+/// aviso ao cliente. Os conflitos voltam no resultado junto com o 409.
+pub(crate) fn remarcar(
+    conn: &Connection,
+    atual: &Agendamento,
+    nova: NaiveDateTime,
+    motivo: Option<&str>,
+    notificar: bool,
+    aplicar: bool,
+) -> (StatusCode, String, Option<ResultadoReagendamento>) {
+    let id = atual.id.unwrap_or_default();
+    let status = match db::status_agendamento(conn, id) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("Erro ao buscar status do agendamento: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao buscar status do agendamento: {}", e), None);
+        }
+    };
+    if atual.concluido || status == db::STATUS_AGENDAMENTO_CANCELADO {
+        return (StatusCode::CONFLICT, "Agendamento concluído ou cancelado não pode ser remarcado.".to_string(), None);
+    }
+    if nova == atual.data_hora {
+        return (StatusCode::BAD_REQUEST, "O agendamento já está nesse horário.".to_string(), None);
+    }
+
+    let agora = config::agora();
+    let conflitos = match db::conflitos_no_horario(conn, nova, &atual.servicos_ids, Some(id), agora) {
+        Ok(conflitos) => conflitos,
+        Err(e) => {
+            let (status, Json(erro)) = erro_disponibilidade::<()>(e);
+            return (status, erro.message, None);
+        }
+    };
+    let mut resultado = ResultadoReagendamento {
+        agendamento: Agendamento { data_hora: nova, ..atual.clone() },
+        data_hora_anterior: atual.data_hora,
+        aplicado: false,
+        conflitos,
+        notificacao_id: None,
+    };
+    if !resultado.conflitos.is_empty() {
+        let mensagem = format!("{} conflito(s): o agendamento não pode ir para esse horário.", resultado.conflitos.len());
+        return (StatusCode::CONFLICT, mensagem, Some(resultado));
+    }
+    if !aplicar {
+        return (StatusCode::OK, "Horário livre: o agendamento pode ser remarcado.".to_string(), Some(resultado));
+    }
+
+    let gravado = db::remarcar_agendamento(conn, id, atual.data_hora, nova, db::ORIGEM_REAGENDAR, motivo)
+        .and_then(|_| db::buscar_agendamento_por_id(conn, id));
+    let remarcado = match gravado {
+        Ok(agendamento) => agendamento,
+        Err(e) => {
+            eprintln!("Erro ao remarcar agendamento: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao remarcar agendamento: {}", e), None);
+        }
+    };
+    webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_ATUALIZADO, &remarcado);
+    if notificar {
+        resultado.notificacao_id = notificacoes::enfileirar_aviso_remarcacao(conn, &remarcado, atual.data_hora, agora).unwrap_or_else(|e| {
+            eprintln!("Erro ao avisar remarcação: {}", e);
+            None
+        });
+    }
+    resultado.agendamento = remarcado;
+    resultado.aplicado = true;
+    (StatusCode::OK, "Agendamento remarcado com sucesso!".to_string(), Some(resultado))
+}
+
+/// GET /agendamentos/:id/historico — remarcações do agendamento, da mais antiga para a mais recente.
+pub async fn historico_agendamento_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<RemarcacaoAgendamento>>>) {
+    let conn = conn.lock().unwrap();
+    if let Err(rusqlite::Error::QueryReturnedNoRows) = db::buscar_agendamento_por_id(&conn, id) {
+        return (StatusCode::NOT_FOUND, Json(ApiResponse { success: false, message: format!("Agendamento {} não encontrado.", id), data: None }));
+    }
+    match db::listar_remarcacoes(&conn, id) {
+        Ok(lista) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} remarcação(ões).", lista.len()), data: Some(lista) })),
+        Err(e) => {
+            eprintln!("Erro ao listar histórico do agendamento: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao listar histórico do agendamento: {}", e), data: None }))
+        }
+    }
+}
+
+/// GET /agendamentos/:id/linha_do_tempo — serviços e fases com início e fim, indicando
+/// as pausas em que o profissional fica livre.
+pub async fn linha_do_tempo_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<EtapaAgendamento>>>) {
+    let conn = conn.lock().unwrap();
+    match db::linha_do_tempo(&conn, id) {
+        Ok(etapas) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} etapa(s).", etapas.len()), data: Some(etapas) })),
+        Err(rusqlite::Error::QueryReturnedNoRows) => (StatusCode::NOT_FOUND, Json(ApiResponse { success: false, message: format!("Agendamento {} não encontrado.", id), data: None })),
+        Err(e) => {
+            eprintln!("Erro ao montar linha do tempo: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao montar linha do tempo: {}", e), data: None }))
+        }
+    }
+}
+
 pub async fn excluir_agendamento_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
     let conn = conn.lock().unwrap();
+    // Lido antes da exclusão: vai no payload do webhook e define a vaga da lista de espera.
+    let anterior = db::buscar_agendamento_por_id(&conn, id).ok();
     match db::excluir_agendamento(&conn, id) {
-        Ok(_) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Agendamento excluído com sucesso!".to_string(), data: None })),
+        Ok(_) => {
+            if let Some(agendamento) = &anterior {
+                webhooks::emitir_ou_logar(&conn, webhooks::EVENTO_AGENDAMENTO_CANCELADO, agendamento);
+                espera::vaga_liberada(&conn, agendamento);
+            }
+            (StatusCode::OK, Json(ApiResponse { success: true, message: "Agendamento excluído com sucesso!".to_string(), data: None }))
+        }
         Err(e) => {
             eprintln!("Erro ao excluir agendamento: {}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao excluir agendamento: {}", e), data: None }))
@@ -191,24 +511,145 @@ pub struct AvailabilityQuery {
     pub duracao_min: Option<i64>,
     pub buffer_min: Option<i64>,
     pub granularity_min: Option<i64>,
+    /// IDs separados por vírgula. Quando informado, duração, folgas, alinhamento,
+    /// antecedência mínima e horizonte vêm dos serviços e de `regras_agenda`.
+    pub servicos_ids: Option<String>,
 }
 
-pub async fn availability_api(Query(q): Query<AvailabilityQuery>, State(conn): State<Db>) -> Json<HashMap<String, Vec<String>>> {
-    let conn = conn.lock().unwrap();
-    let dur = q.duracao_min.unwrap_or(30);
-    let buffer = q.buffer_min.unwrap_or(15);
-    let gran = q.granularity_min.unwrap_or(15);
-
-    match db::calcular_disponibilidade(&conn, &q.date, dur, buffer, gran) {
-        Ok(slots) => {
-            let mut map = HashMap::new();
-            map.insert("slots".to_string(), slots);
-            Json(map)
+/// GET /availability?date=&duracao_min=&buffer_min=&granularity_min=&servicos_ids= — horários
+/// livres do dia. Data, números ou serviços inválidos respondem 400.
+pub async fn availability_api(Query(q): Query<AvailabilityQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<String>>>) {
+    let data = match chrono::NaiveDate::parse_from_str(q.date.trim(), "%Y-%m-%d") {
+        Ok(data) => data,
+        Err(_) => return requisicao_invalida(format!("Data inválida '{}'. Use AAAA-MM-DD.", q.date)),
+    };
+    let resultado = if let Some(lista) = q.servicos_ids.as_deref() {
+        let ids = match ler_servicos_ids(Some(lista)) {
+            Ok(ids) => ids,
+            Err(mensagem) => return requisicao_invalida(mensagem),
+        };
+        let conn = conn.lock().unwrap();
+        db::pedido_para_servicos(&conn, &ids, crate::config::agora())
+            .and_then(|pedido| db::horarios_disponiveis(&conn, data, &pedido))
+    } else {
+        let padrao = &crate::config::atual().agenda;
+        let dur = q.duracao_min.unwrap_or(padrao.duracao_padrao_min);
+        let buffer = q.buffer_min.unwrap_or(padrao.buffer_min);
+        let gran = q.granularity_min.unwrap_or(padrao.granularidade_min);
+        if dur <= 0 || gran <= 0 || buffer < 0 {
+            return requisicao_invalida("duracao_min e granularity_min devem ser positivos, e buffer_min não pode ser negativo.".to_string());
         }
-        Err(e) => {
-            eprintln!("Erro ao calcular disponibilidade: {}", e);
-            Json(HashMap::new())
+        let conn = conn.lock().unwrap();
+        db::calcular_disponibilidade(&conn, &data.format("%Y-%m-%d").to_string(), dur, buffer, gran)
+    };
+
+    match resultado {
+        Ok(slots) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} horário(s) livre(s).", slots.len()), data: Some(slots) })),
+        Err(e) => erro_disponibilidade(e),
+    }
+}
+
+/// "1,2,3" -> [1, 2, 3]; ausente ou vazio -> [].
+fn ler_servicos_ids(lista: Option<&str>) -> Result<Vec<i32>, String> {
+    lista.unwrap_or("").split(',').map(str::trim).filter(|s| !s.is_empty())
+        .map(|s| s.parse().map_err(|_| format!("servicos_ids inválido: '{}'.", s)))
+        .collect()
+}
+
+/// Maior período aceito por `/availability/range`.
+const MAX_DIAS_PERIODO: i64 = 62;
+
+#[derive(Deserialize)]
+pub struct PeriodoQuery {
+    pub inicio: chrono::NaiveDate,
+    pub fim: chrono::NaiveDate,
+    pub servicos_ids: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct DiaDisponivel {
+    pub data: chrono::NaiveDate,
+    pub slots: Vec<String>,
+}
+
+pub(crate) fn requisicao_invalida<T>(mensagem: String) -> (StatusCode, Json<ApiResponse<T>>) {
+    (StatusCode::BAD_REQUEST, Json(ApiResponse { success: false, message: mensagem, data: None }))
+}
+
+fn erro_disponibilidade<T>(e: rusqlite::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    if let rusqlite::Error::QueryReturnedNoRows = e {
+        return requisicao_invalida("Serviço não encontrado em servicos_ids.".to_string());
+    }
+    eprintln!("Erro ao calcular disponibilidade: {}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao calcular disponibilidade: {}", e), data: None }))
+}
+
+/// GET /availability/range?inicio=&fim=&servicos_ids= — horários livres agrupados por dia,
+/// com as mesmas regras de `/availability?servicos_ids=`.
+pub async fn availability_range_api(Query(q): Query<PeriodoQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<DiaDisponivel>>>) {
+    if q.fim < q.inicio {
+        return requisicao_invalida("fim não pode ser anterior a inicio.".to_string());
+    }
+    if (q.fim - q.inicio).num_days() >= MAX_DIAS_PERIODO {
+        return requisicao_invalida(format!("O período pode ter no máximo {} dias.", MAX_DIAS_PERIODO));
+    }
+    let ids = match ler_servicos_ids(q.servicos_ids.as_deref()) {
+        Ok(ids) => ids,
+        Err(mensagem) => return requisicao_invalida(mensagem),
+    };
+    let conn = conn.lock().unwrap();
+    let resultado = db::pedido_para_servicos(&conn, &ids, crate::config::agora())
+        .and_then(|pedido| db::disponibilidade_no_periodo(&conn, q.inicio, q.fim, &pedido));
+    match resultado {
+        Ok(dias) => {
+            let dias: Vec<DiaDisponivel> = dias.into_iter().map(|(data, slots)| DiaDisponivel { data, slots }).collect();
+            let total: usize = dias.iter().map(|d| d.slots.len()).sum();
+            (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} horário(s) livre(s) em {} dia(s).", total, dias.len()), data: Some(dias) }))
         }
+        Err(e) => erro_disponibilidade(e),
+    }
+}
+
+/// A agenda é única e não há profissionais cadastrados: quem escolhe um profissional (`campo`)
+/// é recusado com 400 em vez de ignorado. Todas as rotas que recebem o campo passam por aqui.
+pub(crate) fn recusar_profissional<T>(campo: &str, valor: &Option<T>) -> Result<(), (StatusCode, String)> {
+    match valor {
+        Some(_) => Err((StatusCode::BAD_REQUEST, format!("A agenda é única e não há profissionais cadastrados: {} não é aceito.", campo))),
+        None => Ok(()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProximoQuery {
+    pub servicos_ids: Option<String>,
+    /// Quantos horários devolver (padrão 5, máximo 50).
+    pub quantidade: Option<usize>,
+    /// Só para recusar (ver `recusar_profissional`).
+    pub profissional_id: Option<String>,
+}
+
+/// GET /availability/proximo?servicos_ids=&quantidade= — os primeiros horários livres
+/// a partir de agora, até o horizonte de `regras_agenda`. A agenda é única: com
+/// `profissional_id` responde 400.
+pub async fn availability_proximo_api(Query(q): Query<ProximoQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<String>>>) {
+    if let Err((status, mensagem)) = recusar_profissional("profissional_id", &q.profissional_id) {
+        return (status, Json(ApiResponse { success: false, message: mensagem, data: None }));
+    }
+    let ids = match ler_servicos_ids(q.servicos_ids.as_deref()) {
+        Ok(ids) => ids,
+        Err(mensagem) => return requisicao_invalida(mensagem),
+    };
+    let quantidade = q.quantidade.unwrap_or(5).clamp(1, 50);
+    let conn = conn.lock().unwrap();
+    let agora = crate::config::agora();
+    let resultado = db::buscar_regras_agenda(&conn).and_then(|regras| {
+        let pedido = db::pedido_para_servicos(&conn, &ids, agora)?;
+        db::proximos_horarios(&conn, agora.date(), regras.horizonte_dias + 1, quantidade, &pedido)
+    });
+    match resultado {
+        Ok(slots) if slots.is_empty() => (StatusCode::OK, Json(ApiResponse { success: true, message: "Nenhum horário livre dentro do horizonte da agenda.".to_string(), data: Some(slots) })),
+        Ok(slots) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} próximo(s) horário(s) livre(s).", slots.len()), data: Some(slots) })),
+        Err(e) => erro_disponibilidade(e),
     }
 }
 
@@ -248,3 +689,75 @@ pub async fn criar_work_window_api(State(conn): State<Db>, Json(payload): Json<N
         Err(e) => { eprintln!("Erro salvando work_window: {}", e); (StatusCode::INTERNAL_SERVER_ERROR, Json(HashMap::new())) }
     }
 }
+
+pub async fn obter_regras_agenda_api(State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<RegrasAgenda>>) {
+    let conn = conn.lock().unwrap();
+    match db::buscar_regras_agenda(&conn) {
+        Ok(regras) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Regras da agenda.".to_string(), data: Some(regras) })),
+        Err(e) => {
+            eprintln!("Erro ao buscar regras da agenda: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao buscar regras da agenda: {}", e), data: None }))
+        }
+    }
+}
+
+pub async fn atualizar_regras_agenda_api(State(conn): State<Db>, Json(regras): Json<RegrasAgenda>) -> (StatusCode, Json<ApiResponse<RegrasAgenda>>) {
+    if regras.antecedencia_minima_min < 0 || regras.horizonte_dias <= 0 {
+        let response = ApiResponse { success: false, message: "antecedencia_minima_min não pode ser negativa e horizonte_dias deve ser maior que zero.".to_string(), data: None };
+        return (StatusCode::BAD_REQUEST, Json(response));
+    }
+    let conn = conn.lock().unwrap();
+    match db::salvar_regras_agenda(&conn, &regras) {
+        Ok(_) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Regras da agenda atualizadas com sucesso!".to_string(), data: Some(regras) })),
+        Err(e) => {
+            eprintln!("Erro ao salvar regras da agenda: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao salvar regras da agenda: {}", e), data: None }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_profissional_recusado_com_400_em_todas_as_rotas() {
+        let conn: Db = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+        db::criar_tabelas(&conn.lock().unwrap()).unwrap();
+
+        let pedido = PedidoReagendamento { data_hora: JsonValue::from("2030-01-05T10:00:00"), notificar: false, motivo: None, profissional_id: Some(JsonValue::from(2)) };
+        let (status, _) = reagendar_agendamento_api(Path(1), Query(ReagendarQuery { dry_run: true }), State(conn.clone()), Json(pedido)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let q = ProximoQuery { servicos_ids: None, quantidade: None, profissional_id: Some("2".into()) };
+        let (status, Json(resposta)) = availability_proximo_api(Query(q), State(conn)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(resposta.message.contains("profissional_id"));
+    }
+
+    #[tokio::test]
+    async fn test_disponibilidade_do_dia_responde_400_para_pedido_invalido() {
+        let conn: Db = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+        db::criar_tabelas(&conn.lock().unwrap()).unwrap();
+        let consulta = |date: &str, servicos_ids: Option<&str>, granularity_min: Option<i64>| AvailabilityQuery {
+            date: date.into(),
+            duracao_min: None,
+            buffer_min: None,
+            granularity_min,
+            servicos_ids: servicos_ids.map(str::to_string),
+        };
+
+        let (status, _) = availability_api(Query(consulta("05/01/2030", None, None)), State(conn.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = availability_api(Query(consulta("2030-01-05", Some("1,x"), None)), State(conn.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, Json(resposta)) = availability_api(Query(consulta("2030-01-05", Some("99"), None)), State(conn.clone())).await;
+        assert_eq!((status, resposta.message.as_str()), (StatusCode::BAD_REQUEST, "Serviço não encontrado em servicos_ids."));
+        let (status, _) = availability_api(Query(consulta("2030-01-05", None, Some(0))), State(conn.clone())).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let (status, Json(resposta)) = availability_api(Query(consulta("2030-01-05", None, None)), State(conn)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(resposta.data.is_some());
+    }
+}
@@ -0,0 +1,128 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::db::{self, FiltroAuditoria};
+use crate::models::RegistroAuditoria;
+
+type Db = Arc<Mutex<Connection>>;
+
+/// Ator usado quando nenhum foi definido para o processo (ex.: testes).
+const ATOR_SISTEMA: &str = "sistema";
+
+static ATOR_PADRAO: OnceLock<String> = OnceLock::new();
+
+tokio::task_local! {
+    /// Ator da requisição HTTP em andamento (preenchido pelo middleware).
+    static ATOR_REQUISICAO: String;
+}
+
+/// Define o ator do processo: "cli" no modo terminal, "api" no servidor.
+pub fn definir_ator_padrao(ator: &str) {
+    let _ = ATOR_PADRAO.set(ator.to_string());
+}
+
+/// Quem está fazendo a alteração: o usuário da requisição atual, ou o ator do processo.
+pub fn ator_atual() -> String {
+    ATOR_REQUISICAO
+        .try_with(|ator| ator.clone())
+        .unwrap_or_else(|_| ATOR_PADRAO.get().map(String::as_str).unwrap_or(ATOR_SISTEMA).to_string())
+}
+
+/// Executa `futuro` com um ator fixo, ignorando `X-Usuario` (ex.: rotas públicas).
+pub async fn como_ator<T>(ator: &str, futuro: impl std::future::Future<Output = T>) -> T {
+    ATOR_REQUISICAO.scope(ator.to_string(), futuro).await
+}
+
+/// Middleware que identifica o autor das alterações pelo cabeçalho `X-Usuario`
+/// (o sistema ainda não tem login; sem o cabeçalho o ator é o padrão do processo).
+pub async fn identificar_ator(req: Request, next: Next) -> Response {
+    let ator = req
+        .headers()
+        .get("x-usuario")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.chars().take(64).collect::<String>())
+        .unwrap_or_else(ator_atual);
+    ATOR_REQUISICAO.scope(ator, next.run(req)).await
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AuditoriaQuery {
+    pub entidade: Option<String>,
+    pub id: Option<i64>,
+    pub inicio: Option<String>,
+    pub fim: Option<String>,
+    pub limite: Option<i64>,
+}
+
+/// Aceita "YYYY-MM-DD" ou "YYYY-MM-DDTHH:MM:SS". Datas sem hora cobrem o dia inteiro.
+fn parse_limite(valor: &str, fim_do_dia: bool) -> Result<NaiveDateTime, String> {
+    if let Ok(dt) = NaiveDateTime::parse_from_str(valor, "%Y-%m-%dT%H:%M:%S") {
+        return Ok(dt);
+    }
+    let data = NaiveDate::parse_from_str(valor, "%Y-%m-%d").map_err(|_| format!("Data inválida: '{}'", valor))?;
+    Ok(if fim_do_dia {
+        data.and_hms_opt(23, 59, 59).unwrap()
+    } else {
+        data.and_hms_opt(0, 0, 0).unwrap()
+    })
+}
+
+/// GET /auditoria?entidade=&id=&inicio=&fim=
+///
+/// Só para o dono (`X-Owner-Token`): os retratos antes/depois trazem dados pessoais.
+pub async fn listar_auditoria_api(
+    headers: HeaderMap,
+    Query(q): Query<AuditoriaQuery>,
+    State(conn): State<Db>,
+) -> (StatusCode, Json<ApiResponse<Vec<RegistroAuditoria>>>) {
+    if let Err((status, msg)) = crate::backup::autorizar_dono(&headers) {
+        return (status, Json(ApiResponse { success: false, message: msg, data: None }));
+    }
+    let inicio = q.inicio.as_deref().map(|v| parse_limite(v, false)).transpose();
+    let fim = q.fim.as_deref().map(|v| parse_limite(v, true)).transpose();
+    let (inicio, fim) = match (inicio, fim) {
+        (Ok(i), Ok(f)) => (i, f),
+        (Err(msg), _) | (_, Err(msg)) => {
+            let response = ApiResponse { success: false, message: msg, data: None };
+            return (StatusCode::BAD_REQUEST, Json(response));
+        }
+    };
+
+    let filtro = FiltroAuditoria {
+        entidade: q.entidade,
+        entidade_id: q.id,
+        inicio,
+        fim,
+        limite: q.limite.unwrap_or(200),
+    };
+
+    let conn = conn.lock().unwrap();
+    match db::listar_auditoria(&conn, &filtro) {
+        Ok(registros) => {
+            let response = ApiResponse { success: true, message: format!("{} registro(s) de auditoria.", registros.len()), data: Some(registros) };
+            (StatusCode::OK, Json(response))
+        }
+        Err(e) => {
+            eprintln!("Erro ao listar auditoria: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao listar auditoria: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
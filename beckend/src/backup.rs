@@ -0,0 +1,354 @@
+use axum::{
+    extract::{Path as PathParam, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Datelike, NaiveDateTime};
+use hmac::{Hmac, Mac};
+use rusqlite::{backup::Progress, Connection, DatabaseName, OpenFlags};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config;
+use crate::db;
+
+type Db = Arc<Mutex<Connection>>;
+
+const PREFIXO: &str = "Banco-";
+/// Com milissegundos, para dois backups no mesmo segundo não gravarem no mesmo arquivo.
+const FORMATO_DATA: &str = "%Y%m%d-%H%M%S%.3f";
+/// Formato dos backups feitos antes dos milissegundos; ainda entram na listagem e na rotação.
+const FORMATO_DATA_ANTIGO: &str = "%Y%m%d-%H%M%S";
+
+/// Configuração efetiva dos backups (seção `[backup]` da configuração).
+#[derive(Debug, Clone)]
+pub struct ConfigBackup {
+    pub diretorio: PathBuf,
+    pub manter_diarios: usize,
+    pub manter_semanais: usize,
+    /// Intervalo do backup automático em horas (0 desliga).
+    pub intervalo_horas: u64,
+}
+
+impl ConfigBackup {
+    pub fn da_config() -> Self {
+        let config = config::atual();
+        ConfigBackup {
+            diretorio: config.diretorio_backups(),
+            manter_diarios: config.backup.manter_diarios,
+            manter_semanais: config.backup.manter_semanais,
+            intervalo_horas: config.backup.intervalo_horas,
+        }
+    }
+}
+
+/// Roda `PRAGMA integrity_check` no arquivo, sem alterá-lo.
+pub fn verificar_integridade(arquivo: &Path) -> Result<(), String> {
+    let conn = Connection::open_with_flags(arquivo, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Não foi possível abrir {}: {}", arquivo.display(), e))?;
+    let resultado: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("Falha na verificação de integridade: {}", e))?;
+    if resultado == "ok" {
+        Ok(())
+    } else {
+        Err(format!("Backup corrompido ({}): {}", arquivo.display(), resultado))
+    }
+}
+
+/// Faz uma cópia online do banco com a API de backup do SQLite e verifica a cópia.
+/// Retorna o caminho do arquivo gerado.
+pub fn fazer_backup(conn: &Connection, diretorio: &Path) -> Result<PathBuf, String> {
+    fs::create_dir_all(diretorio).map_err(|e| format!("Não foi possível criar {}: {}", diretorio.display(), e))?;
+    let data = config::agora().format(FORMATO_DATA).to_string();
+    // No mesmo milissegundo (ou com o relógio voltando), um contador evita sobrescrever.
+    let destino = (1..)
+        .map(|n| match n {
+            1 => diretorio.join(format!("{}{}.db", PREFIXO, data)),
+            n => diretorio.join(format!("{}{}_{}.db", PREFIXO, data, n)),
+        })
+        .find(|caminho| !caminho.exists())
+        .expect("sempre há um nome livre");
+
+    conn.backup(DatabaseName::Main, &destino, None)
+        .map_err(|e| format!("Falha ao copiar o banco: {}", e))?;
+
+    if let Err(e) = verificar_integridade(&destino) {
+        let _ = fs::remove_file(&destino);
+        return Err(e);
+    }
+    Ok(destino)
+}
+
+/// Escolhe quais backups apagar: mantém o mais recente de cada um dos últimos
+/// `manter_diarios` dias e de cada uma das últimas `manter_semanais` semanas.
+pub fn selecionar_para_remover(backups: &[(String, NaiveDateTime)], manter_diarios: usize, manter_semanais: usize) -> Vec<String> {
+    let mut ordenados: Vec<&(String, NaiveDateTime)> = backups.iter().collect();
+    ordenados.sort_by_key(|b| std::cmp::Reverse(b.1));
+
+    let mut manter: HashSet<&str> = HashSet::new();
+    let mut dias = HashSet::new();
+    let mut semanas = HashSet::new();
+    for (nome, data) in &ordenados {
+        if dias.len() < manter_diarios && dias.insert(data.date()) {
+            manter.insert(nome.as_str());
+        }
+        let semana = (data.iso_week().year(), data.iso_week().week());
+        if semanas.len() < manter_semanais && semanas.insert(semana) {
+            manter.insert(nome.as_str());
+        }
+    }
+
+    ordenados
+        .into_iter()
+        .filter(|(nome, _)| !manter.contains(nome.as_str()))
+        .map(|(nome, _)| nome.clone())
+        .collect()
+}
+
+/// Lista os backups da pasta (nome, data), do mais recente para o mais antigo.
+pub fn listar_backups(diretorio: &Path) -> Vec<(String, NaiveDateTime)> {
+    let mut backups: Vec<(String, NaiveDateTime)> = match fs::read_dir(diretorio) {
+        Ok(entradas) => entradas
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let nome = e.file_name().to_string_lossy().to_string();
+                let data = nome.strip_prefix(PREFIXO)?.strip_suffix(".db")?;
+                let data = data.split('_').next()?;
+                let data = NaiveDateTime::parse_from_str(data, FORMATO_DATA)
+                    .or_else(|_| NaiveDateTime::parse_from_str(data, FORMATO_DATA_ANTIGO))
+                    .ok()?;
+                Some((nome, data))
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    backups.sort_by_key(|b| std::cmp::Reverse(b.1));
+    backups
+}
+
+/// Apaga os backups que saíram da janela de retenção. Retorna os nomes apagados.
+pub fn rotacionar_backups(config: &ConfigBackup) -> Vec<String> {
+    let remover = selecionar_para_remover(&listar_backups(&config.diretorio), config.manter_diarios, config.manter_semanais);
+    for nome in &remover {
+        if let Err(e) = fs::remove_file(config.diretorio.join(nome)) {
+            eprintln!("[Backup] Falha ao remover {}: {}", nome, e);
+        }
+    }
+    remover
+}
+
+/// Restaura o banco a partir de um backup. O banco atual é salvo antes na pasta
+/// de backups (`pre-restauracao-*.db`), fora da rotação.
+pub fn restaurar_backup(arquivo: &Path, config: &ConfigBackup) -> Result<PathBuf, String> {
+    verificar_integridade(arquivo)?;
+
+    let mut conn = db::conectar_db().map_err(|e| format!("Não foi possível abrir o banco atual: {}", e))?;
+    fs::create_dir_all(&config.diretorio).map_err(|e| format!("Não foi possível criar {}: {}", config.diretorio.display(), e))?;
+    let copia = config
+        .diretorio
+        .join(format!("pre-restauracao-{}.db", config::agora().format(FORMATO_DATA)));
+    conn.backup(DatabaseName::Main, &copia, None)
+        .map_err(|e| format!("Falha ao salvar o banco atual antes da restauração: {}", e))?;
+
+    conn.restore(DatabaseName::Main, arquivo, None::<fn(Progress)>)
+        .map_err(|e| format!("Falha ao restaurar o backup: {}", e))?;
+    Ok(copia)
+}
+
+/// Faz o backup e a rotação numa thread de bloqueio: a cópia pode demorar e não deve
+/// prender uma thread do runtime assíncrono enquanto segura o banco.
+async fn backup_em_segundo_plano(conn: Db, config: ConfigBackup) -> Result<(PathBuf, Vec<String>), String> {
+    tokio::task::spawn_blocking(move || {
+        let arquivo = {
+            let conn = conn.lock().unwrap();
+            fazer_backup(&conn, &config.diretorio)?
+        };
+        Ok((arquivo, rotacionar_backups(&config)))
+    })
+    .await
+    .map_err(|e| format!("Tarefa de backup interrompida: {}", e))?
+}
+
+/// Executa backups automáticos em segundo plano enquanto o servidor roda.
+pub fn iniciar_backups_automaticos(conn: Db, config: ConfigBackup) {
+    if config.intervalo_horas == 0 {
+        println!("[Backup] Backups automáticos desativados.");
+        return;
+    }
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(Duration::from_secs(config.intervalo_horas * 3600));
+        loop {
+            intervalo.tick().await;
+            match backup_em_segundo_plano(conn.clone(), config.clone()).await {
+                Ok((arquivo, removidos)) => {
+                    println!("[Backup] Backup criado em {} ({} antigo(s) removido(s))", arquivo.display(), removidos.len());
+                }
+                Err(e) => eprintln!("[Backup] Falha no backup automático: {}", e),
+            }
+        }
+    });
+}
+
+/// Compara segredos em tempo constante (não revela o tamanho do prefixo correto).
+pub fn textos_iguais(recebido: &str, esperado: &str) -> bool {
+    recebido.len() == esperado.len() && recebido.bytes().zip(esperado.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Exige o cabeçalho `X-Owner-Token` igual a `seguranca.token_dono` (ou `APP_OWNER_TOKEN`).
+/// Sem token configurado, as rotas do dono ficam bloqueadas.
+pub fn autorizar_dono(headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let esperado = config::atual().seguranca.token_dono.as_str();
+    if esperado.is_empty() {
+        return Err((StatusCode::FORBIDDEN, "Defina seguranca.token_dono (ou APP_OWNER_TOKEN) para usar as rotas do dono.".to_string()));
+    }
+    let recebido = headers.get("x-owner-token").and_then(|v| v.to_str().ok()).unwrap_or("");
+    if textos_iguais(recebido, esperado) {
+        Ok(())
+    } else {
+        Err((StatusCode::UNAUTHORIZED, "Token do dono inválido.".to_string()))
+    }
+}
+
+/// Token de leitura derivado do token do dono (HMAC com a `finalidade`), para links que não
+/// conseguem mandar `X-Owner-Token`, como o feed do calendário e o `EventSource`. Trocar o
+/// token do dono invalida todos. `None` sem token do dono configurado.
+pub fn token_derivado(finalidade: &str) -> Option<String> {
+    let chave = config::atual().seguranca.token_dono.as_str();
+    if chave.is_empty() {
+        return None;
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(chave.as_bytes()).expect("HMAC aceita chave de qualquer tamanho");
+    mac.update(finalidade.as_bytes());
+    Some(crate::webhooks::hex(&mac.finalize().into_bytes()[..16]))
+}
+
+#[derive(serde::Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+#[derive(serde::Serialize)]
+pub struct BackupInfo {
+    pub nome: String,
+    pub criado_em: NaiveDateTime,
+}
+
+/// GET /backup — lista os backups disponíveis.
+pub async fn listar_backups_api(headers: HeaderMap) -> (StatusCode, Json<ApiResponse<Vec<BackupInfo>>>) {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return (status, Json(ApiResponse { success: false, message, data: None }));
+    }
+    let config = ConfigBackup::da_config();
+    let backups = listar_backups(&config.diretorio)
+        .into_iter()
+        .map(|(nome, criado_em)| BackupInfo { nome, criado_em })
+        .collect();
+    (StatusCode::OK, Json(ApiResponse { success: true, message: "Backups disponíveis.".to_string(), data: Some(backups) }))
+}
+
+/// POST /backup — faz um backup agora.
+pub async fn criar_backup_api(headers: HeaderMap, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<BackupInfo>>) {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return (status, Json(ApiResponse { success: false, message, data: None }));
+    }
+    match backup_em_segundo_plano(conn, ConfigBackup::da_config()).await {
+        Ok((arquivo, _)) => {
+            let info = BackupInfo {
+                nome: arquivo.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+                criado_em: config::agora(),
+            };
+            (StatusCode::CREATED, Json(ApiResponse { success: true, message: "Backup criado com sucesso!".to_string(), data: Some(info) }))
+        }
+        Err(e) => {
+            eprintln!("Erro ao criar backup: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao criar backup: {}", e), data: None }))
+        }
+    }
+}
+
+/// GET /backup/:nome — baixa um backup.
+pub async fn baixar_backup_api(headers: HeaderMap, PathParam(nome): PathParam<String>) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return (status, Json(ApiResponse::<()> { success: false, message, data: None })).into_response();
+    }
+    let config = ConfigBackup::da_config();
+    // Só aceitamos nomes que aparecem na listagem (evita acesso a outros arquivos).
+    if !listar_backups(&config.diretorio).iter().any(|(n, _)| *n == nome) {
+        let response = ApiResponse::<()> { success: false, message: format!("Backup '{}' não encontrado.", nome), data: None };
+        return (StatusCode::NOT_FOUND, Json(response)).into_response();
+    }
+    match fs::read(config.diretorio.join(&nome)) {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+                (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", nome)),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => {
+            eprintln!("Erro ao ler backup: {}", e);
+            let response = ApiResponse::<()> { success: false, message: format!("Erro ao ler backup: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response)).into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_rotacao_mantem_diarios_e_semanais() {
+        let backups: Vec<(String, NaiveDateTime)> = vec![
+            ("a".into(), data("2026-10-19 03:00")), // segunda
+            ("a-manual".into(), data("2026-10-19 01:00")),
+            ("b".into(), data("2026-10-18 03:00")), // domingo (semana anterior)
+            ("c".into(), data("2026-10-17 03:00")),
+            ("d".into(), data("2026-10-10 03:00")),
+            ("e".into(), data("2026-10-03 03:00")),
+            ("f".into(), data("2026-09-26 03:00")),
+        ];
+        let mut remover = selecionar_para_remover(&backups, 2, 3);
+        remover.sort();
+        // Diários: a (19) e b (18). Semanais: a (sem. 43), b (sem. 42), d (sem. 41).
+        assert_eq!(remover, vec!["a-manual", "c", "e", "f"]);
+    }
+
+    #[test]
+    fn test_backup_e_integridade() {
+        let dir = std::env::temp_dir().join(format!("agendou-backup-teste-{}", std::process::id()));
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+
+        let arquivo = fazer_backup(&conn, &dir).unwrap();
+        assert!(verificar_integridade(&arquivo).is_ok());
+        assert_eq!(listar_backups(&dir).len(), 1);
+
+        // Vários backups seguidos não se sobrescrevem, e o nome antigo (sem milissegundos) é listado.
+        for _ in 0..3 {
+            fazer_backup(&conn, &dir).unwrap();
+        }
+        fs::copy(&arquivo, dir.join("Banco-20200101-030000.db")).unwrap();
+        let backups = listar_backups(&dir);
+        assert_eq!(backups.len(), 5);
+        assert_eq!(backups.last().unwrap(), &("Banco-20200101-030000.db".to_string(), data("2020-01-01 03:00")));
+
+        fs::write(dir.join("corrompido.db"), b"isto nao e um banco sqlite").unwrap();
+        assert!(verificar_integridade(&dir.join("corrompido.db")).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
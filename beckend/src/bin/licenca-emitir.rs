@@ -0,0 +1,123 @@
+// Ferramenta do fornecedor para emitir licenças do Agendou!.
+// A chave privada fica só com quem emite; o binário da barbearia embute apenas a
+// chave pública (ver `licenca::CHAVE_PUBLICA`).
+//
+//   licenca-emitir gerar-chaves --saida chave-licenca.key
+//   licenca-emitir emitir --chave chave-licenca.key --barbearia centro --plano profissional --dias 365
+
+use chrono::{NaiveDate, Timelike, Utc};
+use clap::{Parser, Subcommand};
+use ed25519_dalek::SigningKey;
+use std::path::PathBuf;
+use std::process::exit;
+
+#[allow(dead_code)]
+#[path = "../licenca/token.rs"]
+mod token;
+
+use token::DadosLicenca;
+
+#[derive(Parser)]
+#[command(name = "licenca-emitir", about = "Emite licenças assinadas (Ed25519) para o Agendou!.")]
+struct Cli {
+    #[command(subcommand)]
+    comando: Comando,
+}
+
+#[derive(Subcommand)]
+enum Comando {
+    /// Gera um novo par de chaves e grava a chave privada no arquivo.
+    GerarChaves {
+        #[arg(long, value_name = "ARQUIVO")]
+        saida: PathBuf,
+    },
+    /// Emite um token de licença assinado.
+    Emitir {
+        /// Arquivo com a chave privada.
+        #[arg(long, value_name = "ARQUIVO")]
+        chave: PathBuf,
+        #[arg(long)]
+        barbearia: String,
+        #[arg(long, default_value = "basico")]
+        plano: String,
+        /// Validade em dias a partir de agora.
+        #[arg(long, conflicts_with = "expira", required_unless_present = "expira")]
+        dias: Option<i64>,
+        /// Último dia de validade (YYYY-MM-DD, até 23:59:59 UTC).
+        #[arg(long)]
+        expira: Option<NaiveDate>,
+        /// Recursos liberados, separados por vírgula.
+        #[arg(long, value_delimiter = ',')]
+        recursos: Vec<String>,
+    },
+}
+
+fn main() {
+    match Cli::parse().comando {
+        Comando::GerarChaves { saida } => {
+            if saida.exists() {
+                eprintln!("❌ {} já existe; não vou sobrescrever uma chave privada.", saida.display());
+                exit(1);
+            }
+            let mut semente = [0u8; 32];
+            if let Err(e) = getrandom::getrandom(&mut semente) {
+                eprintln!("❌ Falha ao gerar números aleatórios: {}", e);
+                exit(1);
+            }
+            let chave = SigningKey::from_bytes(&semente);
+            if let Err(e) = gravar_privada(&saida, &token::chave_para_texto(&chave.to_bytes())) {
+                eprintln!("❌ Falha ao gravar {}: {}", saida.display(), e);
+                exit(1);
+            }
+            println!("✅ Chave privada gravada em {} (guarde com segurança).", saida.display());
+            println!("Chave pública (embutir no binário via AGENDOU_CHAVE_PUBLICA_LICENCA):");
+            println!("{}", token::chave_para_texto(chave.verifying_key().as_bytes()));
+        }
+        Comando::Emitir { chave, barbearia, plano, dias, expira, recursos } => {
+            let chave = match std::fs::read_to_string(&chave).map_err(|e| e.to_string()).and_then(|t| token::chave_privada_de_texto(&t).map_err(|e| e.to_string())) {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("❌ Não foi possível ler a chave privada {}: {}", chave.display(), e);
+                    exit(1);
+                }
+            };
+            let agora = Utc::now().naive_utc().with_nanosecond(0).unwrap();
+            let expira_em = match (dias, expira) {
+                (Some(d), _) if d > 0 => agora + chrono::Duration::days(d),
+                (_, Some(data)) => data.and_hms_opt(23, 59, 59).unwrap(),
+                _ => {
+                    eprintln!("❌ --dias deve ser maior que zero.");
+                    exit(2);
+                }
+            };
+            if expira_em <= agora {
+                eprintln!("❌ A data de expiração já passou.");
+                exit(2);
+            }
+            let dados = DadosLicenca {
+                barbearia,
+                plano,
+                emitida_em: agora,
+                expira_em,
+                recursos: recursos.into_iter().map(|r| r.trim().to_string()).filter(|r| !r.is_empty()).collect(),
+            };
+            let emitido = token::emitir(&dados, &chave);
+            // Confere o que acabou de assinar antes de entregar.
+            debug_assert_eq!(token::verificar(&emitido, &chave.verifying_key()), Ok(dados));
+            println!("{}", emitido);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn gravar_privada(caminho: &std::path::Path, conteudo: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+    let mut arquivo = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(caminho)?;
+    writeln!(arquivo, "{}", conteudo)
+}
+
+#[cfg(not(unix))]
+fn gravar_privada(caminho: &std::path::Path, conteudo: &str) -> std::io::Result<()> {
+    std::fs::write(caminho, format!("{}\n", conteudo))
+}
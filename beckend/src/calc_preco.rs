@@ -1,5 +1,6 @@
+use crate::config;
 use crate::db::listar_agendamentos;
-use chrono::{Datelike, Local};
+use chrono::Datelike;
 use rusqlite::Connection;
 
 /// Calcula o lucro total de todos os agendamentos concluídos
@@ -15,7 +16,7 @@ pub fn calcular_lucro_total(conn: &Connection) -> rusqlite::Result<f64> {
 
 /// Calcula o lucro do mês atual
 pub fn calcular_lucro_mensal(conn: &Connection) -> rusqlite::Result<f64> {
-    let hoje = Local::now().naive_local();
+    let hoje = config::agora();
     let mes = hoje.month();
     let ano = hoje.year();
 
@@ -30,7 +31,7 @@ pub fn calcular_lucro_mensal(conn: &Connection) -> rusqlite::Result<f64> {
 
 /// Calcula o lucro do dia atual
 pub fn calcular_lucro_do_dia(conn: &Connection) -> rusqlite::Result<f64> {
-    let hoje = Local::now().naive_local().date();
+    let hoje = config::agora().date();
     let agendamentos = listar_agendamentos(conn)?;
     let total: f64 = agendamentos
         .into_iter()
@@ -0,0 +1,617 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Utc};
+use rusqlite::{Connection, Result, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::backup::{autorizar_dono, textos_iguais, token_derivado};
+use crate::agendamentos;
+use crate::config;
+use crate::db;
+use crate::models::Agendamento;
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// CALENDÁRIO (iCalendar / RFC 5545)
+// Feed assinável dos próximos agendamentos, .ics por agendamento e importação de
+// eventos externos como bloqueios de agenda (respeitados por `calcular_disponibilidade`).
+// =================================================================================
+
+const PRODID: &str = "-//Agendou//Agenda da Barbearia//PT-BR";
+
+pub fn criar_tabela_bloqueios(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS bloqueios_agenda (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            uid          TEXT NOT NULL UNIQUE,
+            inicio       INTEGER NOT NULL,
+            fim          INTEGER NOT NULL,
+            descricao    TEXT NOT NULL,
+            origem       TEXT NOT NULL,
+            importado_em INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_bloqueios_agenda_inicio ON bloqueios_agenda (inicio)", [])?;
+
+    // Auditoria por gatilhos: importações (novas ou reimportadas com mudança) e exclusões.
+    let retrato = |r: &str| {
+        format!(
+            "json_object('id', {r}.id, 'uid', {r}.uid, 'inicio', {r}.inicio, 'fim', {r}.fim,
+                         'descricao', {r}.descricao, 'origem', {r}.origem)"
+        )
+    };
+    let (novo, antigo) = (retrato("NEW"), retrato("OLD"));
+    let gatilhos = [
+        ("AFTER INSERT", db::ACAO_CRIACAO, "NEW.id", "NULL", novo.as_str()),
+        (
+            "AFTER UPDATE WHEN OLD.inicio IS NOT NEW.inicio OR OLD.fim IS NOT NEW.fim
+                 OR OLD.descricao IS NOT NEW.descricao OR OLD.origem IS NOT NEW.origem",
+            db::ACAO_ATUALIZACAO,
+            "NEW.id",
+            antigo.as_str(),
+            novo.as_str(),
+        ),
+        ("AFTER DELETE", db::ACAO_EXCLUSAO, "OLD.id", antigo.as_str(), "NULL"),
+    ];
+    for (evento, acao, id, antes, depois) in gatilhos {
+        db::GatilhoAuditoria { tabela: "bloqueios_agenda", evento, entidade: "bloqueio_agenda", acao, id, antes, depois }.criar(conn)?;
+    }
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------
+// Exportação
+// ---------------------------------------------------------------------------------
+
+/// Evento pronto para virar VEVENT (horários no fuso da barbearia).
+#[derive(Debug, Clone)]
+pub struct EventoAgenda {
+    pub uid: String,
+    pub inicio: NaiveDateTime,
+    pub fim: NaiveDateTime,
+    pub resumo: String,
+    pub descricao: String,
+}
+
+/// Monta o evento de um agendamento: serviços no título e só o primeiro nome do cliente.
+pub fn evento_do_agendamento(conn: &Connection, agendamento: &Agendamento) -> Result<EventoAgenda> {
+    let id = agendamento.id.unwrap_or_default();
+    let primeiro_nome = db::buscar_cliente_por_id(conn, agendamento.cliente_id)?
+        .and_then(|c| c.nome.split_whitespace().next().map(str::to_string))
+        .unwrap_or_else(|| "Cliente".to_string());
+
+    let mut nomes = Vec::new();
+    let mut duracao = 0i64;
+    for servico_id in &agendamento.servicos_ids {
+        if let Some(servico) = db::buscar_servico_por_id(conn, *servico_id)? {
+            nomes.push(servico.nome);
+            duracao += servico.duracao_min as i64;
+        }
+    }
+    if duracao == 0 {
+        duracao = config::atual().agenda.duracao_padrao_min;
+    }
+    let servicos = if nomes.is_empty() { "Atendimento".to_string() } else { nomes.join(", ") };
+
+    Ok(EventoAgenda {
+        uid: format!("agendamento-{}@agendou", id),
+        inicio: agendamento.data_hora,
+        fim: agendamento.data_hora + Duration::minutes(duracao),
+        resumo: format!("{} - {}", servicos, primeiro_nome),
+        descricao: format!("Agendamento #{}\nCliente: {}\nServiços: {}\nValor: R$ {:.2}", id, primeiro_nome, servicos, agendamento.preco),
+    })
+}
+
+/// Próximos agendamentos pendentes (a partir do início de hoje), em ordem.
+pub fn proximos_eventos(conn: &Connection) -> Result<Vec<EventoAgenda>> {
+    let hoje = config::agora().date().and_hms_opt(0, 0, 0).unwrap();
+    let mut stmt = conn.prepare("SELECT id FROM agendamentos WHERE concluido = 0 AND status != 'cancelado' AND data_hora >= ?1 ORDER BY data_hora")?;
+    let ids: Vec<i32> = stmt.query_map(params![hoje.and_utc().timestamp()], |row| row.get(0))?.collect::<Result<_>>()?;
+    ids.into_iter()
+        .map(|id| db::buscar_agendamento_por_id(conn, id).and_then(|a| evento_do_agendamento(conn, &a)))
+        .collect()
+}
+
+fn escapar(texto: &str) -> String {
+    texto.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+fn data_utc(local: NaiveDateTime) -> String {
+    config::para_utc(local).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Quebra linhas com mais de 75 octetos (RFC 5545, 3.1), sem partir caracteres UTF-8.
+fn dobrar(linha: &str, saida: &mut String) {
+    let mut tamanho = 0;
+    for c in linha.chars() {
+        if tamanho + c.len_utf8() > 75 {
+            saida.push_str("\r\n ");
+            tamanho = 1;
+        }
+        saida.push(c);
+        tamanho += c.len_utf8();
+    }
+    saida.push_str("\r\n");
+}
+
+/// Gera o VCALENDAR com os eventos (horários convertidos para UTC).
+pub fn gerar_ics(nome_calendario: &str, eventos: &[EventoAgenda]) -> String {
+    let carimbo = Utc::now().naive_utc().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut linhas = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escapar(nome_calendario)),
+    ];
+    for evento in eventos {
+        linhas.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", evento.uid),
+            format!("DTSTAMP:{}", carimbo),
+            format!("DTSTART:{}", data_utc(evento.inicio)),
+            format!("DTEND:{}", data_utc(evento.fim)),
+            format!("SUMMARY:{}", escapar(&evento.resumo)),
+            format!("DESCRIPTION:{}", escapar(&evento.descricao)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    linhas.push("END:VCALENDAR".to_string());
+
+    let mut saida = String::new();
+    for linha in &linhas {
+        dobrar(linha, &mut saida);
+    }
+    saida
+}
+
+/// Token do feed da agenda: HMAC derivado do token do dono, para que o link possa ser
+/// assinado no celular sem expor o token do dono. `None` sem token do dono.
+pub fn token_feed() -> Option<String> {
+    token_derivado("agenda.ics:barbearia")
+}
+
+// ---------------------------------------------------------------------------------
+// Importação
+// ---------------------------------------------------------------------------------
+
+/// Evento lido de um .ics externo (horários já no fuso da barbearia).
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventoImportado {
+    pub uid: String,
+    pub inicio: NaiveDateTime,
+    pub fim: NaiveDateTime,
+    pub resumo: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ResumoImportacao {
+    pub importados: usize,
+    pub atualizados: usize,
+    /// Eventos cancelados, livres (TRANSP:TRANSPARENT), recorrentes ou sem horário válido.
+    pub ignorados: usize,
+}
+
+/// Desfaz a dobra de linhas: linhas que começam com espaço ou tab continuam a anterior.
+fn desdobrar(texto: &str) -> Vec<String> {
+    let mut linhas: Vec<String> = Vec::new();
+    for linha in texto.split('\n') {
+        let linha = linha.strip_suffix('\r').unwrap_or(linha);
+        match (linha.strip_prefix([' ', '\t']), linhas.last_mut()) {
+            (Some(resto), Some(anterior)) => anterior.push_str(resto),
+            _ => linhas.push(linha.to_string()),
+        }
+    }
+    linhas
+}
+
+fn desescapar(texto: &str) -> String {
+    let mut saida = String::new();
+    let mut chars = texto.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            saida.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => saida.push('\n'),
+            Some(outro) => saida.push(outro),
+            None => {}
+        }
+    }
+    saida
+}
+
+/// Data ou data-hora do iCalendar. Com `Z` é UTC e vai para o fuso da barbearia; sem
+/// `Z` (hora flutuante ou TZID) é tomada como hora local. Devolve também se é só data.
+fn ler_data(valor: &str) -> Option<(NaiveDateTime, bool)> {
+    let valor = valor.trim();
+    // VALUE=DATE (dia inteiro): só AAAAMMDD.
+    if valor.len() == 8 {
+        let data = NaiveDate::parse_from_str(valor, "%Y%m%d").ok()?;
+        return Some((data.and_hms_opt(0, 0, 0)?, true));
+    }
+    match valor.strip_suffix('Z') {
+        Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok().map(|d| (config::de_utc(d), false)),
+        None => NaiveDateTime::parse_from_str(valor, "%Y%m%dT%H%M%S").ok().map(|d| (d, false)),
+    }
+}
+
+/// DURATION simples (`P1D`, `PT1H30M`, `P1W`...).
+fn ler_duracao(valor: &str) -> Option<Duration> {
+    let valor = valor.trim();
+    let (negativo, valor) = match valor.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, valor.strip_prefix('+').unwrap_or(valor)),
+    };
+    let mut resto = valor.strip_prefix('P')?;
+    let mut total = Duration::zero();
+    let mut no_tempo = false;
+    while !resto.is_empty() {
+        if let Some(r) = resto.strip_prefix('T') {
+            no_tempo = true;
+            resto = r;
+            continue;
+        }
+        let fim_numero = resto.find(|c: char| !c.is_ascii_digit())?;
+        let numero: i64 = resto[..fim_numero].parse().ok()?;
+        let unidade = resto[fim_numero..].chars().next()?;
+        total += match (unidade, no_tempo) {
+            ('W', false) => Duration::weeks(numero),
+            ('D', false) => Duration::days(numero),
+            ('H', true) => Duration::hours(numero),
+            ('M', true) => Duration::minutes(numero),
+            ('S', true) => Duration::seconds(numero),
+            _ => return None,
+        };
+        resto = &resto[fim_numero + 1..];
+    }
+    Some(if negativo { -total } else { total })
+}
+
+/// Lê os VEVENTs de um .ics. Devolve os eventos que ocupam tempo e quantos foram ignorados.
+pub fn ler_eventos(texto: &str) -> (Vec<EventoImportado>, usize) {
+    let mut eventos = Vec::new();
+    let mut ignorados = 0;
+    let mut atual: Option<Vec<(String, String, String)>> = None;
+
+    for linha in desdobrar(texto) {
+        let Some((chave, valor)) = linha.split_once(':') else { continue };
+        let (nome, parametros) = chave.split_once(';').unwrap_or((chave, ""));
+        let nome = nome.to_ascii_uppercase();
+        match (nome.as_str(), valor.trim()) {
+            ("BEGIN", "VEVENT") => atual = Some(Vec::new()),
+            ("END", "VEVENT") => match atual.take().and_then(|props| evento_de(&props)) {
+                Some(evento) => eventos.push(evento),
+                None => ignorados += 1,
+            },
+            _ => {
+                if let Some(props) = atual.as_mut() {
+                    props.push((nome, parametros.to_string(), valor.to_string()));
+                }
+            }
+        }
+    }
+    (eventos, ignorados)
+}
+
+fn evento_de(props: &[(String, String, String)]) -> Option<EventoImportado> {
+    let prop = |nome: &str| props.iter().find(|(n, _, _)| n == nome);
+    let valor = |nome: &str| prop(nome).map(|(_, _, v)| v.trim().to_ascii_uppercase());
+
+    // Recorrências não são expandidas: melhor não bloquear do que bloquear só a primeira.
+    if valor("STATUS").as_deref() == Some("CANCELLED") || valor("TRANSP").as_deref() == Some("TRANSPARENT") || prop("RRULE").is_some() {
+        return None;
+    }
+    let (inicio, dia_inteiro) = prop("DTSTART").and_then(|(_, _, v)| ler_data(v))?;
+    let fim = match (prop("DTEND"), prop("DURATION")) {
+        (Some((_, _, v)), _) => ler_data(v)?.0,
+        (None, Some((_, _, v))) => inicio + ler_duracao(v)?,
+        (None, None) if dia_inteiro => inicio + Duration::days(1),
+        (None, None) => return None,
+    };
+    if fim <= inicio {
+        return None;
+    }
+    let uid = prop("UID").map(|(_, _, v)| v.trim().to_string()).filter(|u| !u.is_empty())
+        .unwrap_or_else(|| format!("sem-uid-{}-{}", inicio.and_utc().timestamp(), fim.and_utc().timestamp()));
+    let resumo = prop("SUMMARY").map(|(_, _, v)| desescapar(v.trim())).unwrap_or_else(|| "Ocupado".to_string());
+    Some(EventoImportado { uid, inicio, fim, resumo })
+}
+
+/// Grava os eventos de um .ics como bloqueios, atualizando os que já vieram antes (pelo UID).
+/// Tudo numa transação: um erro no meio do arquivo não deixa a importação pela metade.
+pub fn importar_ics(conn: &Connection, texto: &str, origem: &str) -> Result<ResumoImportacao> {
+    let (eventos, ignorados) = ler_eventos(texto);
+    let mut resumo = ResumoImportacao { ignorados, ..Default::default() };
+    let tx = conn.unchecked_transaction()?;
+    for evento in eventos {
+        let existia: bool = tx.query_row("SELECT COUNT(*) FROM bloqueios_agenda WHERE uid = ?1", params![evento.uid], |row| row.get::<_, i64>(0))? > 0;
+        tx.execute(
+            "INSERT INTO bloqueios_agenda (uid, inicio, fim, descricao, origem, importado_em) VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(uid) DO UPDATE SET inicio = excluded.inicio, fim = excluded.fim, descricao = excluded.descricao,
+                                            origem = excluded.origem, importado_em = excluded.importado_em",
+            params![evento.uid, evento.inicio.and_utc().timestamp(), evento.fim.and_utc().timestamp(), evento.resumo, origem, db::agora_ts()],
+        )?;
+        if existia { resumo.atualizados += 1 } else { resumo.importados += 1 }
+    }
+    tx.commit()?;
+    Ok(resumo)
+}
+
+/// Intervalos bloqueados (timestamps no formato de `data_hora`) que cruzam [inicio, fim].
+pub fn bloqueios_no_intervalo(conn: &Connection, inicio_ts: i64, fim_ts: i64) -> Result<Vec<(i64, i64)>> {
+    let mut stmt = conn.prepare("SELECT inicio, fim FROM bloqueios_agenda WHERE inicio < ?2 AND fim > ?1")?;
+    let linhas = stmt.query_map(params![inicio_ts, fim_ts], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    linhas.collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Bloqueio {
+    pub id: i64,
+    pub uid: String,
+    pub inicio: NaiveDateTime,
+    pub fim: NaiveDateTime,
+    pub descricao: String,
+    pub origem: String,
+}
+
+/// Bloqueios que terminam a partir de `desde`, em ordem de início.
+pub fn listar_bloqueios(conn: &Connection, desde: NaiveDateTime) -> Result<Vec<Bloqueio>> {
+    let mut stmt = conn.prepare("SELECT id, uid, inicio, fim, descricao, origem FROM bloqueios_agenda WHERE fim > ?1 ORDER BY inicio")?;
+    let linhas = stmt.query_map(params![desde.and_utc().timestamp()], |row| {
+        Ok(Bloqueio {
+            id: row.get(0)?,
+            uid: row.get(1)?,
+            inicio: db::timestamp_para_naive(row.get(2)?),
+            fim: db::timestamp_para_naive(row.get(3)?),
+            descricao: row.get(4)?,
+            origem: row.get(5)?,
+        })
+    })?;
+    linhas.collect()
+}
+
+pub fn excluir_bloqueio(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM bloqueios_agenda WHERE id = ?1", params![id])? > 0)
+}
+
+// ---------------------------------------------------------------------------------
+// API
+// ---------------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn resposta<T: Serialize>(status: StatusCode, message: String, data: Option<T>) -> Response {
+    (status, Json(ApiResponse { success: status.is_success(), message, data })).into_response()
+}
+
+fn calendario(nome_arquivo: Option<&str>, ics: String) -> Response {
+    let mut headers = vec![(header::CONTENT_TYPE, "text/calendar; charset=utf-8".to_string())];
+    if let Some(nome) = nome_arquivo {
+        headers.push((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", nome)));
+    }
+    let mut response = ics.into_response();
+    for (nome, valor) in headers {
+        response.headers_mut().insert(nome, valor.parse().unwrap());
+    }
+    response
+}
+
+#[derive(Deserialize)]
+pub struct FeedQuery {
+    /// Só para recusar: a agenda é única e não há profissionais cadastrados.
+    profissional: Option<String>,
+    token: Option<String>,
+}
+
+fn recusar_profissional(q: &FeedQuery) -> Option<Response> {
+    agendamentos::recusar_profissional("profissional", &q.profissional).err().map(|(status, message)| resposta::<()>(status, message, None))
+}
+
+/// GET /agenda.ics?token= — feed dos próximos agendamentos.
+/// O token vem de `GET /agenda/link` (calendários de celular não enviam cabeçalhos).
+pub async fn feed_ics_api(Query(q): Query<FeedQuery>, State(conn): State<Db>) -> Response {
+    if let Some(recusa) = recusar_profissional(&q) {
+        return recusa;
+    }
+    let Some(esperado) = token_feed() else {
+        return resposta::<()>(StatusCode::FORBIDDEN, "Defina seguranca.token_dono para publicar o feed da agenda.".to_string(), None);
+    };
+    if !textos_iguais(q.token.as_deref().unwrap_or(""), &esperado) {
+        return resposta::<()>(StatusCode::UNAUTHORIZED, "Token do feed inválido.".to_string(), None);
+    }
+
+    let conn = conn.lock().unwrap();
+    match proximos_eventos(&conn) {
+        Ok(eventos) => calendario(None, gerar_ics("Agendou", &eventos)),
+        Err(e) => {
+            eprintln!("Erro ao gerar feed da agenda: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao gerar feed da agenda: {}", e), None)
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LinkFeed {
+    pub url: String,
+}
+
+/// GET /agenda/link — caminho assinado do feed para colar no calendário. Só o dono.
+pub async fn link_feed_api(headers: HeaderMap, Query(q): Query<FeedQuery>) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    if let Some(recusa) = recusar_profissional(&q) {
+        return recusa;
+    }
+    let url = format!("/agenda.ics?token={}", token_feed().unwrap_or_default());
+    resposta(StatusCode::OK, "Assine este endereço no aplicativo de calendário.".to_string(), Some(LinkFeed { url }))
+}
+
+/// GET /agendamentos/:id/ics — o agendamento como anexo .ics.
+pub async fn agendamento_ics_api(Path(id): Path<i32>, State(conn): State<Db>) -> Response {
+    let conn = conn.lock().unwrap();
+    let evento = match db::buscar_agendamento_por_id(&conn, id) {
+        Ok(agendamento) => evento_do_agendamento(&conn, &agendamento),
+        Err(rusqlite::Error::QueryReturnedNoRows) => {
+            return resposta::<()>(StatusCode::NOT_FOUND, format!("Agendamento {} não encontrado.", id), None);
+        }
+        Err(e) => Err(e),
+    };
+    match evento {
+        Ok(evento) => calendario(Some(&format!("agendamento-{}.ics", id)), gerar_ics("Agendou", &[evento])),
+        Err(e) => {
+            eprintln!("Erro ao gerar .ics do agendamento: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao gerar .ics: {}", e), None)
+        }
+    }
+}
+
+/// POST /agenda/importar — corpo é o conteúdo de um .ics; os eventos viram bloqueios. Só o dono.
+pub async fn importar_ics_api(headers: HeaderMap, State(conn): State<Db>, corpo: Bytes) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let Ok(texto) = std::str::from_utf8(&corpo) else {
+        return resposta::<()>(StatusCode::BAD_REQUEST, "O arquivo .ics deve estar em UTF-8.".to_string(), None);
+    };
+    if !texto.contains("BEGIN:VCALENDAR") {
+        return resposta::<()>(StatusCode::BAD_REQUEST, "Conteúdo não parece um arquivo iCalendar (.ics).".to_string(), None);
+    }
+    let conn = conn.lock().unwrap();
+    match importar_ics(&conn, texto, "api") {
+        Ok(resumo) => {
+            let message = format!("{} importado(s), {} atualizado(s), {} ignorado(s).", resumo.importados, resumo.atualizados, resumo.ignorados);
+            resposta(StatusCode::OK, message, Some(resumo))
+        }
+        Err(e) => {
+            eprintln!("Erro ao importar .ics: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao importar .ics: {}", e), None)
+        }
+    }
+}
+
+/// GET /agenda/bloqueios — bloqueios atuais e futuros. Só o dono.
+pub async fn listar_bloqueios_api(headers: HeaderMap, State(conn): State<Db>) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let conn = conn.lock().unwrap();
+    match listar_bloqueios(&conn, config::agora()) {
+        Ok(lista) => resposta(StatusCode::OK, format!("{} bloqueio(s).", lista.len()), Some(lista)),
+        Err(e) => {
+            eprintln!("Erro ao listar bloqueios: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao listar bloqueios: {}", e), None)
+        }
+    }
+}
+
+/// DELETE /agenda/bloqueios/:id — libera o horário. Só o dono.
+pub async fn excluir_bloqueio_api(headers: HeaderMap, Path(id): Path<i64>, State(conn): State<Db>) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let conn = conn.lock().unwrap();
+    match excluir_bloqueio(&conn, id) {
+        Ok(true) => resposta::<()>(StatusCode::OK, "Bloqueio excluído com sucesso!".to_string(), None),
+        Ok(false) => resposta::<()>(StatusCode::NOT_FOUND, format!("Bloqueio {} não encontrado.", id), None),
+        Err(e) => {
+            eprintln!("Erro ao excluir bloqueio: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao excluir bloqueio: {}", e), None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_ler_eventos_do_ics() {
+        let ics = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\n\
+            BEGIN:VEVENT\r\nUID:a\r\nDTSTART;TZID=America/Sao_Paulo:20261020T140000\r\nDTEND;TZID=America/Sao_Paulo:20261020T153000\r\nSUMMARY:Dentista\\, consulta\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:b\r\nDTSTART;VALUE=DATE:20261021\r\nSUMMARY:Folga do \r\n barbeiro\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:c\r\nDTSTART:20261022T090000\r\nDURATION:PT45M\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:d\r\nDTSTART:20261023T090000\r\nDTEND:20261023T100000\r\nSTATUS:CANCELLED\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:e\r\nDTSTART:20261023T090000\r\nDTEND:20261023T100000\r\nRRULE:FREQ=WEEKLY\r\nEND:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let (eventos, ignorados) = ler_eventos(ics);
+        assert_eq!(ignorados, 2);
+        assert_eq!(eventos, vec![
+            EventoImportado { uid: "a".into(), inicio: data("2026-10-20 14:00"), fim: data("2026-10-20 15:30"), resumo: "Dentista, consulta".into() },
+            EventoImportado { uid: "b".into(), inicio: data("2026-10-21 00:00"), fim: data("2026-10-22 00:00"), resumo: "Folga do barbeiro".into() },
+            EventoImportado { uid: "c".into(), inicio: data("2026-10-22 09:00"), fim: data("2026-10-22 09:45"), resumo: "Ocupado".into() },
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_feed_recusa_profissional() {
+        let conn = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+        let q = FeedQuery { profissional: Some("João".into()), token: None };
+        assert_eq!(feed_ics_api(Query(q), State(conn)).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_importacao_bloqueia_disponibilidade_e_feed() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        db::criar_tabela_work_schedule(&conn).unwrap();
+
+        let ics = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:x\nDTSTART:20301014T090000\nDTEND:20301014T110000\nEND:VEVENT\nEND:VCALENDAR\n";
+        let resumo = importar_ics(&conn, ics, "teste").unwrap();
+        assert_eq!((resumo.importados, resumo.atualizados), (1, 0));
+        assert_eq!(importar_ics(&conn, ics, "teste").unwrap().atualizados, 1);
+        assert!(excluir_bloqueio(&conn, 1).unwrap());
+        importar_ics(&conn, ics, "teste").unwrap();
+        // Reimportar sem mudanças não gera entrada; a exclusão e a nova importação, sim.
+        let filtro = db::FiltroAuditoria { entidade: Some("bloqueio_agenda".into()), limite: 10, ..Default::default() };
+        let acoes: Vec<String> = db::listar_auditoria(&conn, &filtro).unwrap().into_iter().map(|r| r.acao).collect();
+        assert_eq!(acoes, [db::ACAO_CRIACAO, db::ACAO_EXCLUSAO, db::ACAO_CRIACAO]);
+
+        // Um erro no segundo evento desfaz também o primeiro.
+        conn.execute_batch("CREATE TEMP TRIGGER falha BEFORE INSERT ON bloqueios_agenda WHEN NEW.uid = 'z' BEGIN SELECT RAISE(ABORT, 'falha'); END;").unwrap();
+        let dois = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:y\nDTSTART:20301015T090000\nDTEND:20301015T100000\nEND:VEVENT\n\
+                    BEGIN:VEVENT\nUID:z\nDTSTART:20301016T090000\nDTEND:20301016T100000\nEND:VEVENT\nEND:VCALENDAR\n";
+        assert!(importar_ics(&conn, dois, "teste").is_err());
+        assert_eq!(conn.query_row("SELECT COUNT(*) FROM bloqueios_agenda WHERE uid = 'y'", [], |row| row.get::<_, i64>(0)).unwrap(), 0);
+
+        let slots = db::calcular_disponibilidade(&conn, "2030-10-14", 30, 0, 30).unwrap();
+        assert!(!slots.iter().any(|s| s.starts_with("2030-10-14T09:") || s.starts_with("2030-10-14T10:")));
+        assert!(slots.contains(&"2030-10-14T08:30:00".to_string()));
+        assert!(slots.contains(&"2030-10-14T11:00:00".to_string()));
+
+        let evento = EventoAgenda {
+            uid: "agendamento-1@agendou".into(),
+            inicio: data("2030-10-14 15:00"),
+            fim: data("2030-10-14 15:30"),
+            resumo: "Corte, Barba - João".into(),
+            descricao: "Uma descrição longa o bastante para precisar ser dobrada em mais de uma linha do arquivo".into(),
+        };
+        let ics = gerar_ics("Agendou", &[evento]);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n") && ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("SUMMARY:Corte\\, Barba - João\r\n"));
+        assert!(ics.split("\r\n").all(|l| l.len() <= 75));
+        // O próprio feed volta pelo importador.
+        let (lidos, _) = ler_eventos(&ics);
+        assert_eq!((lidos[0].inicio, lidos[0].fim), (data("2030-10-14 15:00"), data("2030-10-14 15:30")));
+    }
+}
@@ -0,0 +1,905 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::calendario;
+use crate::calc_preco::{calcular_lucro_do_dia, calcular_lucro_mensal, calcular_lucro_total};
+use crate::config::{self, SobrescritasCli};
+use crate::db;
+use crate::espera;
+use crate::licenca;
+use crate::planilhas;
+use crate::recursos;
+use crate::transferencia;
+use crate::models::{Agendamento, Cliente, Servico};
+
+// =================================================================================
+// LINHA DE COMANDO
+// Subcomandos não interativos sobre as mesmas funções de `db` usadas pelo menu.
+// =================================================================================
+
+/// Códigos de saída dos subcomandos (2 é usado pelo clap para erro de uso).
+pub const SAIDA_OK: i32 = 0;
+pub const SAIDA_ERRO: i32 = 1;
+pub const SAIDA_USO: i32 = 2;
+pub const SAIDA_NAO_ENCONTRADO: i32 = 3;
+pub const SAIDA_CONFLITO: i32 = 4;
+
+#[derive(Parser)]
+#[command(name = "agendamento_barbearia", version, about = "Agendou! — agenda da barbearia (servidor, menu e comandos).")]
+pub struct Cli {
+    #[command(flatten)]
+    pub flags: FlagsConfig,
+
+    /// Sem subcomando, inicia o servidor.
+    #[command(subcommand)]
+    pub comando: Option<Comando>,
+}
+
+#[derive(Args)]
+pub struct FlagsConfig {
+    /// Arquivo de configuração TOML (padrão: agendou.toml, se existir).
+    #[arg(long, global = true, value_name = "ARQUIVO")]
+    pub config: Option<PathBuf>,
+    /// Endereço de escuta do servidor.
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+    /// Porta do servidor.
+    #[arg(long, global = true)]
+    pub porta: Option<u16>,
+    /// Caminho do banco SQLite.
+    #[arg(long, global = true, value_name = "ARQUIVO")]
+    pub db: Option<String>,
+    /// Nível de log (erro, aviso, info, debug).
+    #[arg(long, global = true)]
+    pub log: Option<String>,
+}
+
+impl FlagsConfig {
+    pub fn sobrescritas(&self) -> SobrescritasCli {
+        SobrescritasCli {
+            arquivo: self.config.clone(),
+            host: self.host.clone(),
+            porta: self.porta,
+            db: self.db.clone(),
+            log: self.log.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Comando {
+    /// Inicia o servidor HTTP.
+    Server,
+    /// Abre o menu interativo no terminal.
+    Cli,
+    /// Faz um backup do banco agora.
+    Backup,
+    /// Restaura o banco a partir de um backup (com o servidor parado).
+    Restore { arquivo: PathBuf },
+    /// Configuração do servidor.
+    Config {
+        #[command(subcommand)]
+        acao: AcaoConfig,
+    },
+    /// Cadastro de clientes.
+    Clientes {
+        #[command(subcommand)]
+        acao: AcaoClientes,
+    },
+    /// Cadastro de serviços.
+    Servicos {
+        #[command(subcommand)]
+        acao: AcaoServicos,
+    },
+    /// Agendamentos.
+    Agendamentos {
+        #[command(subcommand)]
+        acao: AcaoAgendamentos,
+    },
+    /// Relatórios de receita.
+    Relatorio {
+        periodo: PeriodoRelatorio,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Licença do sistema.
+    Licenca {
+        #[command(subcommand)]
+        acao: AcaoLicenca,
+    },
+    /// Agenda em iCalendar (.ics).
+    Agenda {
+        #[command(subcommand)]
+        acao: AcaoAgenda,
+    },
+    /// Exporta clientes, serviços ou agendamentos em CSV.
+    Exportar {
+        tipo: TipoExportacao,
+        #[arg(long, value_name = "ARQUIVO")]
+        saida: Option<PathBuf>,
+        /// Separador das colunas: ; , | ou tab.
+        #[arg(long, default_value = ",")]
+        separador: String,
+        /// Clientes: filtra pelo nome.
+        #[arg(long)]
+        busca: Option<String>,
+        /// Agendamentos: primeiro dia (YYYY-MM-DD).
+        #[arg(long)]
+        inicio: Option<NaiveDate>,
+        /// Agendamentos: último dia (YYYY-MM-DD).
+        #[arg(long)]
+        fim: Option<NaiveDate>,
+        /// Agendamentos: só deste cliente.
+        #[arg(long)]
+        cliente: Option<i32>,
+        /// Agendamentos: pendente, confirmado, cancelado ou concluido.
+        #[arg(long)]
+        status: Option<String>,
+    },
+    /// Importa clientes ou serviços de um CSV (confira antes com --dry-run).
+    Importar {
+        tipo: TipoImportacao,
+        arquivo: PathBuf,
+        /// Coluna da planilha para um campo, ex.: --coluna "telefone=Celular" (pode repetir).
+        #[arg(long = "coluna", value_name = "CAMPO=CABEÇALHO")]
+        colunas: Vec<String>,
+        /// Separador das colunas (padrão: detectado pelo cabeçalho).
+        #[arg(long)]
+        separador: Option<String>,
+        /// Só valida e mostra o relatório.
+        #[arg(long)]
+        dry_run: bool,
+        /// Atualiza os cadastros duplicados em vez de ignorá-los.
+        #[arg(long)]
+        atualizar_duplicados: bool,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Cópia completa do banco em JSON, para levar a barbearia para outra máquina.
+    Banco {
+        #[command(subcommand)]
+        acao: AcaoBanco,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TipoExportacao {
+    Clientes,
+    Servicos,
+    Agendamentos,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TipoImportacao {
+    Clientes,
+    Servicos,
+}
+
+#[derive(Subcommand)]
+pub enum AcaoConfig {
+    /// Mostra a configuração efetiva e confirma que é válida.
+    Check,
+}
+
+#[derive(Subcommand)]
+pub enum AcaoClientes {
+    /// Lista os clientes.
+    List {
+        /// Filtra pelo nome.
+        #[arg(long)]
+        busca: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Cadastra um cliente.
+    Add {
+        #[arg(long)]
+        nome: String,
+        #[arg(long)]
+        telefone: String,
+        #[arg(long)]
+        email: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Altera os campos informados de um cliente.
+    Edit {
+        id: i32,
+        #[arg(long)]
+        nome: Option<String>,
+        #[arg(long)]
+        telefone: Option<String>,
+        #[arg(long)]
+        email: Option<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Exclui um cliente sem agendamentos.
+    Rm { id: i32 },
+}
+
+#[derive(Subcommand)]
+pub enum AcaoServicos {
+    /// Lista os serviços.
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+    /// Cadastra um serviço.
+    Add {
+        #[arg(long)]
+        nome: String,
+        #[arg(long)]
+        preco: f64,
+        /// Duração em minutos.
+        #[arg(long, default_value_t = 30)]
+        duracao: i32,
+        /// Limpeza depois do serviço, em minutos (padrão: agenda.buffer_min).
+        #[arg(long)]
+        buffer: Option<i32>,
+        /// Só começa em múltiplos destes minutos (ex.: 30 → :00 e :30).
+        #[arg(long)]
+        alinhamento: Option<i32>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Altera os campos informados de um serviço.
+    Edit {
+        id: i32,
+        #[arg(long)]
+        nome: Option<String>,
+        #[arg(long)]
+        preco: Option<f64>,
+        #[arg(long)]
+        duracao: Option<i32>,
+        #[arg(long)]
+        buffer: Option<i32>,
+        #[arg(long)]
+        alinhamento: Option<i32>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Exclui um serviço.
+    Rm { id: i32 },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum StatusAgendamento {
+    Pendente,
+    Concluido,
+}
+
+#[derive(Subcommand)]
+pub enum AcaoAgendamentos {
+    /// Lista os agendamentos (opcionalmente de um dia).
+    List {
+        /// Dia no formato YYYY-MM-DD.
+        #[arg(long)]
+        data: Option<NaiveDate>,
+        #[arg(long, value_enum)]
+        status: Option<StatusAgendamento>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Cria um agendamento.
+    Add {
+        #[arg(long)]
+        cliente: i32,
+        /// IDs dos serviços separados por vírgula.
+        #[arg(long, value_delimiter = ',', required = true)]
+        servicos: Vec<i32>,
+        /// Data e hora no formato "YYYY-MM-DD HH:MM".
+        #[arg(long, value_parser = parse_data_hora)]
+        data_hora: NaiveDateTime,
+        /// Preço total (padrão: soma dos serviços).
+        #[arg(long)]
+        preco: Option<f64>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Marca um agendamento como concluído.
+    Concluir { id: i32 },
+    /// Exclui um agendamento.
+    Rm { id: i32 },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum PeriodoRelatorio {
+    Total,
+    Mensal,
+    Diario,
+}
+
+#[derive(Subcommand)]
+pub enum AcaoLicenca {
+    /// Valida um token de licença e o instala no banco.
+    Validar { token: String },
+    /// Mostra a licença instalada.
+    Status {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AcaoAgenda {
+    /// Importa um arquivo .ics: os eventos bloqueiam os horários na disponibilidade.
+    Importar {
+        arquivo: PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Exporta os próximos agendamentos para um .ics (ou para a saída padrão).
+    Exportar {
+        #[arg(long, value_name = "ARQUIVO")]
+        saida: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AcaoBanco {
+    /// Exporta todos os cadastros num JSON versionado (ou para a saída padrão).
+    Exportar {
+        #[arg(long, value_name = "ARQUIVO")]
+        saida: Option<PathBuf>,
+    },
+    /// Carrega uma exportação num banco vazio, renumerando os ids.
+    Importar {
+        arquivo: PathBuf,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+fn parse_data_hora(valor: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(valor, "%Y-%m-%d %H:%M").map_err(|_| "use o formato \"YYYY-MM-DD HH:MM\"".to_string())
+}
+
+/// Erro de um subcomando, com o código de saída correspondente.
+#[derive(Debug)]
+pub struct ErroComando {
+    pub codigo: i32,
+    pub mensagem: String,
+}
+
+impl ErroComando {
+    fn novo(codigo: i32, mensagem: impl Into<String>) -> Self {
+        ErroComando { codigo, mensagem: mensagem.into() }
+    }
+}
+
+impl From<rusqlite::Error> for ErroComando {
+    fn from(e: rusqlite::Error) -> Self {
+        ErroComando::novo(SAIDA_ERRO, format!("Erro no banco de dados: {}", e))
+    }
+}
+
+type Resultado = Result<(), ErroComando>;
+
+fn imprimir_json<T: Serialize>(valor: &T) {
+    println!("{}", serde_json::to_string_pretty(valor).unwrap_or_default());
+}
+
+fn abrir_db() -> Result<Connection, ErroComando> {
+    let conn = db::conectar_db()?;
+    db::criar_tabelas(&conn)?;
+    Ok(conn)
+}
+
+/// Executa um subcomando de dados e devolve o código de saída.
+pub fn executar(comando: Comando) -> i32 {
+    let resultado = match comando {
+        Comando::Clientes { acao } => clientes(acao),
+        Comando::Servicos { acao } => servicos(acao),
+        Comando::Agendamentos { acao } => agendamentos(acao),
+        Comando::Relatorio { periodo, json } => relatorio(periodo, json),
+        Comando::Licenca { acao } => licenca_cmd(acao),
+        Comando::Agenda { acao } => agenda(acao),
+        Comando::Exportar { tipo, saida, separador, busca, inicio, fim, cliente, status } => {
+            let filtro = planilhas::FiltroAgendamentos { inicio, fim, cliente_id: cliente, status };
+            exportar(tipo, saida, &separador, busca.as_deref(), &filtro)
+        }
+        Comando::Importar { tipo, arquivo, colunas, separador, dry_run, atualizar_duplicados, json } => {
+            importar(tipo, &arquivo, &colunas, separador.as_deref(), dry_run, atualizar_duplicados, json)
+        }
+        Comando::Banco { acao } => banco(acao),
+        Comando::Backup => backup(),
+        Comando::Restore { arquivo } => restore(&arquivo),
+        Comando::Config { acao: AcaoConfig::Check } => {
+            // Se chegamos aqui, a configuração já foi carregada e validada.
+            println!("# Configuração efetiva (padrões -> arquivo -> APP_* -> flags)");
+            print!("{}", config::atual().para_exibicao());
+            println!("# ✅ Configuração válida.");
+            Ok(())
+        }
+        Comando::Server | Comando::Cli => unreachable!("server e cli são tratados no main"),
+    };
+    match resultado {
+        Ok(()) => SAIDA_OK,
+        Err(e) => {
+            eprintln!("❌ {}", e.mensagem);
+            e.codigo
+        }
+    }
+}
+
+fn backup() -> Resultado {
+    let config = crate::backup::ConfigBackup::da_config();
+    let conn = db::conectar_db()?;
+    let arquivo = crate::backup::fazer_backup(&conn, &config.diretorio).map_err(|e| ErroComando::novo(SAIDA_ERRO, format!("Falha no backup: {}", e)))?;
+    let removidos = crate::backup::rotacionar_backups(&config);
+    println!("✅ Backup criado em {} ({} antigo(s) removido(s)).", arquivo.display(), removidos.len());
+    Ok(())
+}
+
+fn restore(arquivo: &std::path::Path) -> Resultado {
+    println!("⚠️ Pare o servidor antes de restaurar um backup.");
+    let config = crate::backup::ConfigBackup::da_config();
+    let copia = crate::backup::restaurar_backup(arquivo, &config)
+        .map_err(|e| ErroComando::novo(SAIDA_ERRO, format!("Falha na restauração: {}", e)))?;
+    println!("✅ Banco restaurado de {}. O banco anterior foi salvo em {}.", arquivo.display(), copia.display());
+    Ok(())
+}
+
+fn imprimir_cliente(c: &Cliente) {
+    println!(
+        "  [{}] {} - {}{}",
+        c.id.unwrap_or(0),
+        c.nome,
+        c.telefone,
+        c.email.as_deref().filter(|e| !e.trim().is_empty()).map(|e| format!(" | {}", e.trim())).unwrap_or_default()
+    );
+}
+
+fn buscar_cliente(conn: &Connection, id: i32) -> Result<Cliente, ErroComando> {
+    db::buscar_cliente_por_id(conn, id)?.ok_or_else(|| ErroComando::novo(SAIDA_NAO_ENCONTRADO, format!("Cliente com ID {} não encontrado.", id)))
+}
+
+fn clientes(acao: AcaoClientes) -> Resultado {
+    let conn = abrir_db()?;
+    match acao {
+        AcaoClientes::List { busca, json } => {
+            let clientes = match busca {
+                Some(b) => db::listar_clientes_search(&conn, &b, i32::MAX)?,
+                None => db::listar_clientes(&conn)?,
+            };
+            if json {
+                imprimir_json(&clientes);
+            } else if clientes.is_empty() {
+                println!("📭 Nenhum cliente cadastrado.");
+            } else {
+                clientes.iter().for_each(imprimir_cliente);
+            }
+        }
+        AcaoClientes::Add { nome, telefone, email, json } => {
+            if nome.trim().is_empty() || telefone.trim().is_empty() {
+                return Err(ErroComando::novo(SAIDA_USO, "Nome e telefone são obrigatórios."));
+            }
+            let mut cliente = Cliente::new(nome.trim().into(), telefone.trim().into(), email.map(|e| e.trim().to_string()));
+            let id = db::salvar_cliente(&conn, &mut cliente)?;
+            if json { imprimir_json(&cliente) } else { println!("✅ Cliente cadastrado com ID: {}", id) }
+        }
+        AcaoClientes::Edit { id, nome, telefone, email, json } => {
+            let mut cliente = buscar_cliente(&conn, id)?;
+            if let Some(nome) = nome { cliente.nome = nome.trim().into(); }
+            if let Some(telefone) = telefone { cliente.telefone = telefone.trim().into(); }
+            if let Some(email) = email { cliente.email = Some(email.trim().into()).filter(|e: &String| !e.is_empty()); }
+            db::salvar_cliente(&conn, &mut cliente)?;
+            if json { imprimir_json(&cliente) } else { println!("✅ Cliente atualizado com sucesso.") }
+        }
+        AcaoClientes::Rm { id } => {
+            buscar_cliente(&conn, id)?;
+            match db::excluir_cliente(&conn, id) {
+                Ok(()) => println!("✅ Cliente excluído com sucesso."),
+                Err(rusqlite::Error::ExecuteReturnedResults) => {
+                    return Err(ErroComando::novo(
+                        SAIDA_CONFLITO,
+                        "Não foi possível excluir o cliente. Existem agendamentos vinculados a ele.",
+                    ))
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+    Ok(())
+}
+
+fn buscar_servico(conn: &Connection, id: i32) -> Result<Servico, ErroComando> {
+    db::buscar_servico_por_id(conn, id)?.ok_or_else(|| ErroComando::novo(SAIDA_NAO_ENCONTRADO, format!("Serviço com ID {} não encontrado.", id)))
+}
+
+fn servicos(acao: AcaoServicos) -> Resultado {
+    let conn = abrir_db()?;
+    match acao {
+        AcaoServicos::List { json } => {
+            let servicos = db::listar_servicos(&conn)?;
+            if json {
+                imprimir_json(&servicos);
+            } else if servicos.is_empty() {
+                println!("📭 Nenhum serviço cadastrado.");
+            } else {
+                for s in servicos {
+                    println!("  [{}] {} - R$ {:.2} ({} min)", s.id.unwrap_or(0), s.nome, s.preco, s.duracao_min);
+                }
+            }
+        }
+        AcaoServicos::Add { nome, preco, duracao, buffer, alinhamento, json } => {
+            if nome.trim().is_empty() || preco < 0.0 || duracao <= 0 {
+                return Err(ErroComando::novo(SAIDA_USO, "Informe nome, preço >= 0 e duração > 0."));
+            }
+            let mut servico = Servico { id: None, nome: nome.trim().into(), preco, duracao_min: duracao, buffer_min: buffer, alinhamento_min: alinhamento };
+            crate::servicos::validar_regras(&servico).map_err(|m| ErroComando::novo(SAIDA_USO, m))?;
+            let id = db::salvar_servico(&conn, &servico)?;
+            servico.id = Some(id);
+            if json { imprimir_json(&servico) } else { println!("✅ Serviço cadastrado com ID: {}", id) }
+        }
+        AcaoServicos::Edit { id, nome, preco, duracao, buffer, alinhamento, json } => {
+            let mut servico = buscar_servico(&conn, id)?;
+            if let Some(nome) = nome { servico.nome = nome.trim().into(); }
+            if let Some(preco) = preco { servico.preco = preco; }
+            if let Some(duracao) = duracao { servico.duracao_min = duracao; }
+            if let Some(buffer) = buffer { servico.buffer_min = Some(buffer); }
+            if let Some(alinhamento) = alinhamento { servico.alinhamento_min = Some(alinhamento); }
+            if servico.nome.is_empty() || servico.preco < 0.0 || servico.duracao_min <= 0 {
+                return Err(ErroComando::novo(SAIDA_USO, "Informe nome, preço >= 0 e duração > 0."));
+            }
+            crate::servicos::validar_regras(&servico).map_err(|m| ErroComando::novo(SAIDA_USO, m))?;
+            crate::servicos::validar_duracao_com_fases(&servico, &db::listar_fases_servico(&conn, id)?)
+                .map_err(|m| ErroComando::novo(SAIDA_USO, m))?;
+            db::salvar_servico(&conn, &servico)?;
+            if json { imprimir_json(&servico) } else { println!("✅ Serviço atualizado com sucesso.") }
+        }
+        AcaoServicos::Rm { id } => {
+            buscar_servico(&conn, id)?;
+            db::excluir_servico(&conn, id)?;
+            println!("✅ Serviço excluído com sucesso.");
+        }
+    }
+    Ok(())
+}
+
+fn agendamentos(acao: AcaoAgendamentos) -> Resultado {
+    let conn = abrir_db()?;
+    match acao {
+        AcaoAgendamentos::List { data, status, json } => {
+            let mut lista = match data {
+                Some(d) => db::listar_agendamentos_por_data(&conn, d.and_hms_opt(0, 0, 0).unwrap())?,
+                None => db::listar_agendamentos(&conn)?,
+            };
+            match status {
+                Some(StatusAgendamento::Pendente) => lista.retain(|a| !a.concluido),
+                Some(StatusAgendamento::Concluido) => lista.retain(|a| a.concluido),
+                None => {}
+            }
+            lista.sort_by_key(|a| a.data_hora);
+            if json {
+                imprimir_json(&lista);
+            } else if lista.is_empty() {
+                println!("📭 Nenhum agendamento encontrado.");
+            } else {
+                for a in lista {
+                    let nomes = db::buscar_nomes_servicos(&conn, &a.servicos_ids)?;
+                    println!(
+                        "  [{}] Cliente: {} | Serviço: {} | Data: {} | Preço: R$ {:.2} | Status: {}",
+                        a.id.unwrap_or(0),
+                        a.cliente_id,
+                        nomes.join(", "),
+                        a.data_hora.format("%d/%m/%Y %H:%M"),
+                        a.preco,
+                        if a.concluido { "[ok!]" } else { "[pendente]" }
+                    );
+                }
+            }
+        }
+        AcaoAgendamentos::Add { cliente, servicos, data_hora, preco, json } => {
+            buscar_cliente(&conn, cliente)?;
+            let mut total = 0.0;
+            for id in &servicos {
+                total += buscar_servico(&conn, *id)?.preco;
+            }
+            if data_hora < config::agora() {
+                return Err(ErroComando::novo(SAIDA_USO, "Não é possível agendar no passado."));
+            }
+            if db::verificar_conflito(&conn, data_hora)? {
+                return Err(ErroComando::novo(SAIDA_CONFLITO, "Já existe um agendamento nesse horário."));
+            }
+            let mut agendamento = Agendamento {
+                id: None,
+                cliente_id: cliente,
+                servicos_ids: servicos,
+                data_hora,
+                preco: preco.unwrap_or(total),
+                concluido: false,
+            };
+            if let Some(recurso) = recursos::conflito_de_recursos(&conn, &agendamento, None)? {
+                return Err(ErroComando::novo(SAIDA_CONFLITO, recursos::mensagem_conflito(&recurso)));
+            }
+            let id = db::salvar_agendamento(&conn, &agendamento)?;
+            agendamento.id = Some(id);
+            if json { imprimir_json(&agendamento) } else { println!("✅ Agendamento criado com ID: {}", id) }
+        }
+        AcaoAgendamentos::Concluir { id } => {
+            if db::buscar_agendamento_por_id(&conn, id).is_err() {
+                return Err(ErroComando::novo(SAIDA_NAO_ENCONTRADO, format!("Agendamento com ID {} não encontrado.", id)));
+            }
+            db::atualizar_agendamento(&conn, id, None, None, None, Some(true))?;
+            println!("✅ Agendamento marcado como concluído!");
+        }
+        AcaoAgendamentos::Rm { id } => {
+            let Ok(agendamento) = db::buscar_agendamento_por_id(&conn, id) else {
+                return Err(ErroComando::novo(SAIDA_NAO_ENCONTRADO, format!("Agendamento com ID {} não encontrado.", id)));
+            };
+            db::excluir_agendamento(&conn, id)?;
+            println!("✅ Agendamento excluído com sucesso.");
+            let avisados = espera::vaga_liberada(&conn, &agendamento);
+            if avisados > 0 {
+                println!("🔔 {} pedido(s) da lista de espera avisado(s) sobre a vaga.", avisados);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Relatorio {
+    periodo: String,
+    receita: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    concluidos: Option<usize>,
+}
+
+fn relatorio(periodo: PeriodoRelatorio, json: bool) -> Resultado {
+    let conn = abrir_db()?;
+    let hoje = config::agora();
+    let relatorio = match periodo {
+        PeriodoRelatorio::Total => Relatorio { periodo: "total".into(), receita: calcular_lucro_total(&conn)?, concluidos: None },
+        PeriodoRelatorio::Mensal => Relatorio {
+            periodo: hoje.format("%Y-%m").to_string(),
+            receita: calcular_lucro_mensal(&conn)?,
+            concluidos: None,
+        },
+        PeriodoRelatorio::Diario => {
+            let concluidos = db::listar_agendamentos(&conn)?
+                .into_iter()
+                .filter(|a| a.concluido && a.data_hora.date() == hoje.date())
+                .count();
+            Relatorio { periodo: hoje.format("%Y-%m-%d").to_string(), receita: calcular_lucro_do_dia(&conn)?, concluidos: Some(concluidos) }
+        }
+    };
+
+    if json {
+        imprimir_json(&relatorio);
+    } else {
+        println!("📊 Relatório ({})", relatorio.periodo);
+        if let Some(c) = relatorio.concluidos {
+            println!("✅ Serviços concluídos: {}", c);
+        }
+        println!("💰 Receita: R$ {:.2}", relatorio.receita);
+    }
+    Ok(())
+}
+
+fn licenca_cmd(acao: AcaoLicenca) -> Resultado {
+    let conn = abrir_db()?;
+    match acao {
+        AcaoLicenca::Validar { token } => match licenca::instalar_licenca(&conn, &token) {
+            Ok(dados) => println!("✅ Licença do plano {} válida até: {}", dados.plano, dados.expira_em.format("%d/%m/%Y %H:%M UTC")),
+            Err(licenca::ErroLicenca::Banco(e)) => return Err(e.into()),
+            Err(e) => return Err(ErroComando::novo(SAIDA_ERRO, format!("Licença recusada: {}.", e))),
+        },
+        AcaoLicenca::Status { json } => {
+            let status = licenca::status_licenca(&conn)?;
+            if json {
+                imprimir_json(&status);
+            } else {
+                if let (Some(barbearia), Some(plano), Some(expira_em)) = (&status.barbearia, &status.plano, status.expira_em) {
+                    println!("📄 Barbearia: {} | Plano: {} | Expira em: {}", barbearia, plano, expira_em.format("%d/%m/%Y %H:%M UTC"));
+                    if !status.recursos.is_empty() {
+                        println!("   Recursos: {}", status.recursos.join(", "));
+                    }
+                }
+                println!("   Situação: {} | Dias restantes: {}", status.situacao.como_texto(), status.dias_restantes);
+                if let Some(aviso) = &status.aviso {
+                    println!("⚠️ {}", aviso);
+                }
+            }
+            if status.somente_leitura {
+                return Err(ErroComando::novo(SAIDA_ERRO, "Sistema em modo somente leitura."));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn agenda(acao: AcaoAgenda) -> Resultado {
+    let conn = abrir_db()?;
+    match acao {
+        AcaoAgenda::Importar { arquivo, json } => {
+            let texto = std::fs::read_to_string(&arquivo)
+                .map_err(|e| ErroComando::novo(SAIDA_USO, format!("Não foi possível ler {}: {}", arquivo.display(), e)))?;
+            if !texto.contains("BEGIN:VCALENDAR") {
+                return Err(ErroComando::novo(SAIDA_USO, format!("{} não parece um arquivo iCalendar (.ics).", arquivo.display())));
+            }
+            let origem = arquivo.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| "cli".to_string());
+            let resumo = calendario::importar_ics(&conn, &texto, &origem)?;
+            if json {
+                imprimir_json(&resumo);
+            } else {
+                println!("✅ {} bloqueio(s) importado(s), {} atualizado(s), {} evento(s) ignorado(s).", resumo.importados, resumo.atualizados, resumo.ignorados);
+            }
+        }
+        AcaoAgenda::Exportar { saida } => {
+            let ics = calendario::gerar_ics("Agendou", &calendario::proximos_eventos(&conn)?);
+            match saida {
+                Some(caminho) => {
+                    std::fs::write(&caminho, ics).map_err(|e| ErroComando::novo(SAIDA_ERRO, format!("Falha ao gravar {}: {}", caminho.display(), e)))?;
+                    println!("✅ Agenda exportada para {}.", caminho.display());
+                }
+                None => print!("{}", ics),
+            }
+        }
+    }
+    Ok(())
+}
+
+impl From<planilhas::ErroPlanilha> for ErroComando {
+    fn from(e: planilhas::ErroPlanilha) -> Self {
+        match e {
+            planilhas::ErroPlanilha::Formato(m) => ErroComando::novo(SAIDA_USO, m),
+            planilhas::ErroPlanilha::Banco(e) => e.into(),
+        }
+    }
+}
+
+fn exportar(tipo: TipoExportacao, saida: Option<PathBuf>, separador: &str, busca: Option<&str>, filtro: &planilhas::FiltroAgendamentos) -> Resultado {
+    let conn = abrir_db()?;
+    let separador = planilhas::parse_separador(separador)?;
+    let csv = match tipo {
+        TipoExportacao::Clientes => planilhas::exportar_clientes(&conn, busca, separador)?,
+        TipoExportacao::Servicos => planilhas::exportar_servicos(&conn, separador)?,
+        TipoExportacao::Agendamentos => planilhas::exportar_agendamentos(&conn, filtro, separador)?,
+    };
+    match saida {
+        Some(caminho) => {
+            std::fs::write(&caminho, csv).map_err(|e| ErroComando::novo(SAIDA_ERRO, format!("Falha ao gravar {}: {}", caminho.display(), e)))?;
+            println!("✅ Planilha exportada para {}.", caminho.display());
+        }
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+fn importar(
+    tipo: TipoImportacao,
+    arquivo: &std::path::Path,
+    colunas: &[String],
+    separador: Option<&str>,
+    dry_run: bool,
+    atualizar_duplicados: bool,
+    json: bool,
+) -> Resultado {
+    let conn = abrir_db()?;
+    let texto = std::fs::read_to_string(arquivo)
+        .map_err(|e| ErroComando::novo(SAIDA_USO, format!("Não foi possível ler {} (a planilha deve estar em UTF-8): {}", arquivo.display(), e)))?;
+    let opcoes = planilhas::OpcoesImportacao {
+        separador: separador.map(planilhas::parse_separador).transpose()?,
+        colunas: planilhas::parse_colunas(colunas.iter().map(String::as_str))?,
+        atualizar_duplicados,
+        dry_run,
+    };
+    let relatorio = match tipo {
+        TipoImportacao::Clientes => planilhas::importar_clientes(&conn, &texto, &opcoes)?,
+        TipoImportacao::Servicos => planilhas::importar_servicos(&conn, &texto, &opcoes)?,
+    };
+    if json {
+        imprimir_json(&relatorio);
+    } else {
+        for erro in &relatorio.erros {
+            println!("  ❌ linha {}: {}", erro.linha, erro.mensagem);
+        }
+        for dup in &relatorio.duplicados {
+            let onde = match (dup.existente_id, dup.linha_anterior) {
+                (Some(id), _) => format!("já cadastrado (ID {})", id),
+                (None, Some(linha)) => format!("repete a linha {}", linha),
+                (None, None) => String::new(),
+            };
+            let destino = if dup.atualizado { "atualizado" } else { "ignorado" };
+            println!("  ⚠️ linha {}: {} {} - {}", dup.linha, dup.chave, onde, destino);
+        }
+        println!(
+            "{} linha(s): {} novo(s), {} atualizado(s), {} ignorado(s), {} com erro.",
+            relatorio.linhas, relatorio.novos, relatorio.atualizados, relatorio.ignorados, relatorio.erros.len()
+        );
+    }
+    if !relatorio.erros.is_empty() {
+        return Err(ErroComando::novo(SAIDA_ERRO, "Há linhas com erro; nada foi importado."));
+    }
+    if relatorio.aplicado && !json {
+        println!("✅ Importação concluída.");
+    } else if !json {
+        println!("(dry-run: nada foi gravado)");
+    }
+    Ok(())
+}
+
+impl From<transferencia::ErroTransferencia> for ErroComando {
+    fn from(e: transferencia::ErroTransferencia) -> Self {
+        match e {
+            transferencia::ErroTransferencia::Invalido(_) => ErroComando::novo(SAIDA_USO, format!("{}.", e)),
+            transferencia::ErroTransferencia::BancoNaoVazio => ErroComando::novo(SAIDA_CONFLITO, format!("{}.", e)),
+            transferencia::ErroTransferencia::Banco(e) => e.into(),
+        }
+    }
+}
+
+fn banco(acao: AcaoBanco) -> Resultado {
+    let conn = abrir_db()?;
+    match acao {
+        AcaoBanco::Exportar { saida } => {
+            let banco = transferencia::exportar_banco(&conn)?;
+            let json = serde_json::to_string_pretty(&banco).unwrap_or_default();
+            match saida {
+                Some(caminho) => {
+                    std::fs::write(&caminho, json).map_err(|e| ErroComando::novo(SAIDA_ERRO, format!("Falha ao gravar {}: {}", caminho.display(), e)))?;
+                    let resumo = banco.resumo();
+                    println!(
+                        "✅ Banco exportado para {}: {} cliente(s), {} serviço(s), {} agendamento(s).",
+                        caminho.display(),
+                        resumo.clientes,
+                        resumo.servicos,
+                        resumo.agendamentos
+                    );
+                }
+                None => println!("{}", json),
+            }
+        }
+        AcaoBanco::Importar { arquivo, json } => {
+            let texto = std::fs::read_to_string(&arquivo)
+                .map_err(|e| ErroComando::novo(SAIDA_USO, format!("Não foi possível ler {}: {}", arquivo.display(), e)))?;
+            let resumo = transferencia::importar_banco(&conn, &transferencia::ler_banco(&texto)?)?;
+            if json {
+                imprimir_json(&resumo);
+            } else {
+                println!(
+                    "✅ Banco importado: {} cliente(s), {} serviço(s), {} recurso(s), {} agendamento(s), {} janela(s) de trabalho.",
+                    resumo.clientes, resumo.servicos, resumo.recursos, resumo.agendamentos, resumo.work_windows
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subcomandos() {
+        let cli = Cli::try_parse_from(["agendou", "agendamentos", "list", "--data", "2026-10-18", "--json"]).unwrap();
+        match cli.comando {
+            Some(Comando::Agendamentos { acao: AcaoAgendamentos::List { data, json, .. } }) => {
+                assert_eq!(data, NaiveDate::from_ymd_opt(2026, 10, 18));
+                assert!(json);
+            }
+            _ => panic!("esperado agendamentos list"),
+        }
+
+        let cli = Cli::try_parse_from(["agendou", "--db", "/tmp/x.db", "agendamentos", "add", "--cliente", "1", "--servicos", "1,2", "--data-hora", "2026-10-18 14:30"]).unwrap();
+        assert_eq!(cli.flags.sobrescritas().db.as_deref(), Some("/tmp/x.db"));
+        match cli.comando {
+            Some(Comando::Agendamentos { acao: AcaoAgendamentos::Add { servicos, data_hora, .. } }) => {
+                assert_eq!(servicos, vec![1, 2]);
+                assert_eq!(data_hora.format("%H:%M").to_string(), "14:30");
+            }
+            _ => panic!("esperado agendamentos add"),
+        }
+
+        assert!(Cli::try_parse_from(["agendou"]).unwrap().comando.is_none(), "sem subcomando inicia o servidor");
+        assert!(Cli::try_parse_from(["agendou", "relatorio", "anual"]).is_err());
+        assert!(Cli::try_parse_from(["agendou", "agendamentos", "add", "--cliente", "1", "--servicos", "1", "--data-hora", "amanhã"]).is_err());
+    }
+}
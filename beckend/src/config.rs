@@ -0,0 +1,600 @@
+use chrono::{FixedOffset, Local, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+// =================================================================================
+// CONFIGURAÇÃO DO SERVIDOR
+// Camadas (a última vence): padrões -> arquivo TOML -> variáveis de ambiente -> flags.
+// =================================================================================
+
+/// Arquivo lido quando nem `--config` nem `APP_CONFIG` são informados (se existir).
+pub const ARQUIVO_PADRAO: &str = "agendou.toml";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub servidor: ConfigServidor,
+    pub banco: ConfigBanco,
+    pub agenda: ConfigAgenda,
+    pub backup: ConfigBackup,
+    pub seguranca: ConfigSeguranca,
+    pub licenca: ConfigLicenca,
+    pub notificacoes: ConfigNotificacoes,
+    pub webhooks: ConfigWebhooks,
+    pub publico: ConfigPublico,
+    pub log: ConfigLog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigServidor {
+    pub host: String,
+    pub porta: u16,
+    /// Origens liberadas no CORS; `["*"]` libera qualquer origem.
+    pub cors_origens: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigBanco {
+    pub caminho: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigAgenda {
+    /// "local" (fuso do sistema), "UTC" ou deslocamento fixo como "-03:00".
+    pub fuso_horario: String,
+    pub duracao_padrao_min: i64,
+    pub buffer_min: i64,
+    pub granularidade_min: i64,
+    /// Expediente usado quando o dia não tem `work_windows` cadastradas (HH:MM).
+    pub inicio_padrao: String,
+    pub fim_padrao: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigBackup {
+    /// Pasta dos backups; vazio = `backups/` ao lado do banco.
+    pub diretorio: String,
+    pub manter_diarios: usize,
+    pub manter_semanais: usize,
+    /// Intervalo do backup automático em horas (0 desliga).
+    pub intervalo_horas: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigSeguranca {
+    /// Token exigido no cabeçalho `X-Owner-Token` das rotas do dono (vazio = bloqueadas).
+    pub token_dono: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLicenca {
+    /// Dias após a expiração em que a API ainda aceita alterações (com aviso).
+    pub carencia_dias: i64,
+    /// A partir de quantos dias para expirar as respostas passam a trazer aviso.
+    pub aviso_dias: i64,
+    /// Intervalo da reverificação da licença pelo servidor, em minutos.
+    pub verificar_a_cada_min: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigNotificacoes {
+    pub ativo: bool,
+    /// "log" (saída padrão), "arquivo", "smtp" ou "webhook".
+    pub canal: String,
+    /// Lembretes enviados com essas antecedências (em horas) antes do horário.
+    pub antecedencias_horas: Vec<i64>,
+    pub verificar_a_cada_seg: u64,
+    /// Tentativas de envio antes de marcar a notificação como falha.
+    pub tentativas_max: i64,
+    /// Arquivo do canal "arquivo" (uma linha JSON por mensagem).
+    pub arquivo: String,
+    /// Modelos com {nome}, {servicos}, {data}, {hora}, {preco} e {antecedencia}.
+    pub modelo_assunto: String,
+    pub modelo_mensagem: String,
+    pub smtp: ConfigSmtp,
+    pub webhook: ConfigWebhookNotificacoes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigSmtp {
+    pub host: String,
+    pub porta: u16,
+    pub usuario: String,
+    pub senha: String,
+    /// Endereço do remetente, ex.: "Barbearia <contato@exemplo.com>".
+    pub remetente: String,
+    /// "starttls" (porta 587), "tls" (porta 465) ou "nenhuma" (só para testes locais).
+    pub seguranca: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigWebhookNotificacoes {
+    /// Recebe um POST JSON com telefone, e-mail, assunto e mensagem (ex.: gateway de WhatsApp).
+    pub url: String,
+    /// Enviado como `Authorization: Bearer <token>`, se definido.
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigWebhooks {
+    /// Tentativas de entrega antes de marcar a entrega como falha.
+    pub tentativas_max: i64,
+    pub timeout_seg: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigPublico {
+    /// Liga as rotas `/public/...` de autoatendimento dos clientes.
+    pub ativo: bool,
+    /// Requisições por minuto e por IP em todas as rotas públicas.
+    pub requisicoes_por_minuto: usize,
+    /// Reservas, cancelamentos e remarcações por hora e por IP.
+    pub reservas_por_hora: usize,
+    /// Usa o último IP de `X-Forwarded-For`, o que o proxy acrescentou (só atrás de um proxy confiável).
+    pub confiar_x_forwarded_for: bool,
+    /// Antecedência mínima para o cliente cancelar ou remarcar pelo link.
+    pub antecedencia_cancelamento_horas: i64,
+    /// Validade dos links de confirmar/cancelar (nunca passam do horário marcado).
+    pub validade_link_horas: i64,
+    /// Endereço público usado para montar os links (`{link}` nos lembretes); vazio = só o caminho.
+    pub url_base: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigLog {
+    /// "erro", "aviso", "info" ou "debug".
+    pub nivel: String,
+}
+
+impl Default for ConfigServidor {
+    fn default() -> Self {
+        ConfigServidor { host: "127.0.0.1".into(), porta: 3000, cors_origens: vec!["*".into()] }
+    }
+}
+
+impl Default for ConfigBanco {
+    fn default() -> Self {
+        ConfigBanco { caminho: "src/bd/Banco.db".into() }
+    }
+}
+
+impl Default for ConfigAgenda {
+    fn default() -> Self {
+        ConfigAgenda {
+            fuso_horario: "local".into(),
+            duracao_padrao_min: 30,
+            buffer_min: 15,
+            granularidade_min: 15,
+            inicio_padrao: "08:00".into(),
+            fim_padrao: "18:00".into(),
+        }
+    }
+}
+
+impl Default for ConfigBackup {
+    fn default() -> Self {
+        ConfigBackup { diretorio: String::new(), manter_diarios: 7, manter_semanais: 4, intervalo_horas: 24 }
+    }
+}
+
+impl Default for ConfigLicenca {
+    fn default() -> Self {
+        ConfigLicenca { carencia_dias: 7, aviso_dias: 7, verificar_a_cada_min: 60 }
+    }
+}
+
+impl Default for ConfigNotificacoes {
+    fn default() -> Self {
+        ConfigNotificacoes {
+            ativo: true,
+            canal: "log".into(),
+            antecedencias_horas: vec![24, 2],
+            verificar_a_cada_seg: 60,
+            tentativas_max: 5,
+            arquivo: "notificacoes.log".into(),
+            modelo_assunto: "Lembrete: seu horário em {data} às {hora}".into(),
+            modelo_mensagem: "Olá, {nome}! Lembrando do seu horário na barbearia em {data} às {hora} ({servicos}). Até lá!".into(),
+            smtp: ConfigSmtp::default(),
+            webhook: ConfigWebhookNotificacoes::default(),
+        }
+    }
+}
+
+impl Default for ConfigSmtp {
+    fn default() -> Self {
+        ConfigSmtp {
+            host: String::new(),
+            porta: 587,
+            usuario: String::new(),
+            senha: String::new(),
+            remetente: String::new(),
+            seguranca: "starttls".into(),
+        }
+    }
+}
+
+impl Default for ConfigPublico {
+    fn default() -> Self {
+        ConfigPublico {
+            ativo: true,
+            requisicoes_por_minuto: 60,
+            reservas_por_hora: 5,
+            confiar_x_forwarded_for: false,
+            antecedencia_cancelamento_horas: 2,
+            validade_link_horas: 72,
+            url_base: String::new(),
+        }
+    }
+}
+
+impl Default for ConfigWebhooks {
+    fn default() -> Self {
+        ConfigWebhooks { tentativas_max: 6, timeout_seg: 10 }
+    }
+}
+
+impl Default for ConfigLog {
+    fn default() -> Self {
+        ConfigLog { nivel: "info".into() }
+    }
+}
+
+/// Valores passados por flags na linha de comando (têm prioridade sobre tudo).
+#[derive(Debug, Default, Clone)]
+pub struct SobrescritasCli {
+    pub arquivo: Option<PathBuf>,
+    pub host: Option<String>,
+    pub porta: Option<u16>,
+    pub db: Option<String>,
+    pub log: Option<String>,
+}
+
+impl Config {
+    /// Monta a configuração efetiva a partir das camadas e a valida.
+    pub fn carregar(flags: &SobrescritasCli) -> Result<Config, Vec<String>> {
+        let mut config = Config::default();
+
+        let arquivo = flags.arquivo.clone().or_else(|| std::env::var("APP_CONFIG").ok().map(PathBuf::from));
+        match &arquivo {
+            Some(caminho) => config = Config::ler_arquivo(caminho).map_err(|e| vec![e])?,
+            None if Path::new(ARQUIVO_PADRAO).exists() => {
+                config = Config::ler_arquivo(Path::new(ARQUIVO_PADRAO)).map_err(|e| vec![e])?
+            }
+            None => {}
+        }
+
+        config.aplicar_ambiente(|nome| std::env::var(nome).ok()).map_err(|e| vec![e])?;
+        config.aplicar_flags(flags);
+        config.validar()?;
+        Ok(config)
+    }
+
+    pub fn ler_arquivo(caminho: &Path) -> Result<Config, String> {
+        let texto = std::fs::read_to_string(caminho)
+            .map_err(|e| format!("Não foi possível ler {}: {}", caminho.display(), e))?;
+        toml::from_str(&texto).map_err(|e| format!("Erro em {}: {}", caminho.display(), e))
+    }
+
+    /// Aplica as variáveis `APP_*` (a função recebe o nome e devolve o valor, se houver).
+    pub fn aplicar_ambiente(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), String> {
+        fn numero<T: std::str::FromStr>(nome: &str, valor: String) -> Result<T, String> {
+            valor.trim().parse().map_err(|_| format!("Valor inválido em {}: '{}'", nome, valor))
+        }
+
+        if let Some(v) = var("APP_HOST") { self.servidor.host = v; }
+        if let Some(v) = var("APP_PORT") { self.servidor.porta = numero("APP_PORT", v)?; }
+        if let Some(v) = var("APP_CORS_ORIGINS") {
+            self.servidor.cors_origens = v.split(',').map(|o| o.trim().to_string()).filter(|o| !o.is_empty()).collect();
+        }
+        if let Some(v) = var("APP_DB_PATH") { self.banco.caminho = v; }
+        if let Some(v) = var("APP_TIMEZONE") { self.agenda.fuso_horario = v; }
+        if let Some(v) = var("APP_DURACAO_PADRAO_MIN") { self.agenda.duracao_padrao_min = numero("APP_DURACAO_PADRAO_MIN", v)?; }
+        if let Some(v) = var("APP_BUFFER_MIN") { self.agenda.buffer_min = numero("APP_BUFFER_MIN", v)?; }
+        if let Some(v) = var("APP_GRANULARIDADE_MIN") { self.agenda.granularidade_min = numero("APP_GRANULARIDADE_MIN", v)?; }
+        if let Some(v) = var("APP_BACKUP_DIR") { self.backup.diretorio = v; }
+        if let Some(v) = var("APP_BACKUP_DIARIOS") { self.backup.manter_diarios = numero("APP_BACKUP_DIARIOS", v)?; }
+        if let Some(v) = var("APP_BACKUP_SEMANAIS") { self.backup.manter_semanais = numero("APP_BACKUP_SEMANAIS", v)?; }
+        if let Some(v) = var("APP_BACKUP_INTERVALO_HORAS") { self.backup.intervalo_horas = numero("APP_BACKUP_INTERVALO_HORAS", v)?; }
+        if let Some(v) = var("APP_OWNER_TOKEN") { self.seguranca.token_dono = v; }
+        if let Some(v) = var("APP_LICENCA_CARENCIA_DIAS") { self.licenca.carencia_dias = numero("APP_LICENCA_CARENCIA_DIAS", v)?; }
+        if let Some(v) = var("APP_NOTIFICACOES_CANAL") { self.notificacoes.canal = v; }
+        if let Some(v) = var("APP_SMTP_HOST") { self.notificacoes.smtp.host = v; }
+        if let Some(v) = var("APP_SMTP_USUARIO") { self.notificacoes.smtp.usuario = v; }
+        if let Some(v) = var("APP_SMTP_SENHA") { self.notificacoes.smtp.senha = v; }
+        if let Some(v) = var("APP_NOTIFICACOES_WEBHOOK_URL") { self.notificacoes.webhook.url = v; }
+        if let Some(v) = var("APP_NOTIFICACOES_WEBHOOK_TOKEN") { self.notificacoes.webhook.token = v; }
+        if let Some(v) = var("APP_PUBLICO_ATIVO") { self.publico.ativo = numero("APP_PUBLICO_ATIVO", v)?; }
+        if let Some(v) = var("APP_PUBLICO_URL_BASE") { self.publico.url_base = v; }
+        if let Some(v) = var("APP_LOG_LEVEL") { self.log.nivel = v; }
+        Ok(())
+    }
+
+    pub fn aplicar_flags(&mut self, flags: &SobrescritasCli) {
+        if let Some(v) = &flags.host { self.servidor.host = v.clone(); }
+        if let Some(v) = flags.porta { self.servidor.porta = v; }
+        if let Some(v) = &flags.db { self.banco.caminho = v.clone(); }
+        if let Some(v) = &flags.log { self.log.nivel = v.clone(); }
+    }
+
+    /// Confere a configuração e devolve todos os problemas encontrados de uma vez.
+    pub fn validar(&self) -> Result<(), Vec<String>> {
+        let mut erros = Vec::new();
+
+        if self.servidor.host != "localhost" && self.servidor.host.parse::<IpAddr>().is_err() {
+            erros.push(format!("servidor.host inválido: '{}'", self.servidor.host));
+        }
+        if self.servidor.porta == 0 {
+            erros.push("servidor.porta deve ser maior que zero".into());
+        }
+        if self.servidor.cors_origens.is_empty() {
+            erros.push("servidor.cors_origens não pode ser vazio (use [\"*\"] para liberar tudo)".into());
+        }
+        for origem in &self.servidor.cors_origens {
+            if origem != "*" && !(origem.starts_with("http://") || origem.starts_with("https://")) {
+                erros.push(format!("servidor.cors_origens: origem inválida '{}'", origem));
+            }
+        }
+        if self.banco.caminho.trim().is_empty() {
+            erros.push("banco.caminho não pode ser vazio".into());
+        }
+        if let Err(e) = self.agenda.fuso() {
+            erros.push(e);
+        }
+        if self.agenda.duracao_padrao_min <= 0 {
+            erros.push("agenda.duracao_padrao_min deve ser maior que zero".into());
+        }
+        if self.agenda.buffer_min < 0 {
+            erros.push("agenda.buffer_min não pode ser negativo".into());
+        }
+        if self.agenda.granularidade_min <= 0 {
+            erros.push("agenda.granularidade_min deve ser maior que zero".into());
+        }
+        match self.agenda.janela_padrao() {
+            Ok((inicio, fim)) if inicio >= fim => erros.push("agenda.inicio_padrao deve ser antes de agenda.fim_padrao".into()),
+            Ok(_) => {}
+            Err(e) => erros.push(e),
+        }
+        if self.licenca.carencia_dias < 0 || self.licenca.aviso_dias < 0 {
+            erros.push("licenca.carencia_dias e licenca.aviso_dias não podem ser negativos".into());
+        }
+        if self.licenca.verificar_a_cada_min == 0 {
+            erros.push("licenca.verificar_a_cada_min deve ser maior que zero".into());
+        }
+        let notificacoes = &self.notificacoes;
+        match notificacoes.canal.as_str() {
+            "log" | "arquivo" => {}
+            "smtp" => {
+                if notificacoes.smtp.host.trim().is_empty() || notificacoes.smtp.remetente.trim().is_empty() {
+                    erros.push("notificacoes.smtp.host e notificacoes.smtp.remetente são obrigatórios com o canal smtp".into());
+                }
+                if !matches!(notificacoes.smtp.seguranca.as_str(), "starttls" | "tls" | "nenhuma") {
+                    erros.push(format!("notificacoes.smtp.seguranca inválida: '{}'", notificacoes.smtp.seguranca));
+                }
+            }
+            "webhook" => {
+                if !(notificacoes.webhook.url.starts_with("http://") || notificacoes.webhook.url.starts_with("https://")) {
+                    erros.push("notificacoes.webhook.url deve ser uma URL http(s) com o canal webhook".into());
+                }
+            }
+            outro => erros.push(format!("notificacoes.canal inválido: '{}' (use log, arquivo, smtp ou webhook)", outro)),
+        }
+        if notificacoes.antecedencias_horas.iter().any(|h| *h <= 0) {
+            erros.push("notificacoes.antecedencias_horas devem ser maiores que zero".into());
+        }
+        if notificacoes.verificar_a_cada_seg == 0 || notificacoes.tentativas_max <= 0 {
+            erros.push("notificacoes.verificar_a_cada_seg e notificacoes.tentativas_max devem ser maiores que zero".into());
+        }
+        if self.webhooks.tentativas_max <= 0 || self.webhooks.timeout_seg == 0 {
+            erros.push("webhooks.tentativas_max e webhooks.timeout_seg devem ser maiores que zero".into());
+        }
+        if self.publico.requisicoes_por_minuto == 0 || self.publico.reservas_por_hora == 0 {
+            erros.push("publico.requisicoes_por_minuto e publico.reservas_por_hora devem ser maiores que zero".into());
+        }
+        if self.publico.antecedencia_cancelamento_horas < 0 || self.publico.validade_link_horas <= 0 {
+            erros.push("publico.antecedencia_cancelamento_horas não pode ser negativa e publico.validade_link_horas deve ser maior que zero".into());
+        }
+        if NivelLog::de_texto(&self.log.nivel).is_none() {
+            erros.push(format!("log.nivel inválido: '{}' (use erro, aviso, info ou debug)", self.log.nivel));
+        }
+
+        if erros.is_empty() { Ok(()) } else { Err(erros) }
+    }
+
+    pub fn endereco(&self) -> String {
+        format!("{}:{}", self.servidor.host, self.servidor.porta)
+    }
+
+    /// Pasta efetiva dos backups.
+    pub fn diretorio_backups(&self) -> PathBuf {
+        if self.backup.diretorio.trim().is_empty() {
+            Path::new(&self.banco.caminho).parent().unwrap_or_else(|| Path::new(".")).join("backups")
+        } else {
+            PathBuf::from(&self.backup.diretorio)
+        }
+    }
+
+    /// Configuração em TOML para exibição, com segredos mascarados.
+    pub fn para_exibicao(&self) -> String {
+        let mut copia = self.clone();
+        if !copia.seguranca.token_dono.is_empty() {
+            copia.seguranca.token_dono = "********".into();
+        }
+        if !copia.notificacoes.smtp.senha.is_empty() {
+            copia.notificacoes.smtp.senha = "********".into();
+        }
+        if !copia.notificacoes.webhook.token.is_empty() {
+            copia.notificacoes.webhook.token = "********".into();
+        }
+        toml::to_string_pretty(&copia).unwrap_or_default()
+    }
+}
+
+/// Fuso configurado para a barbearia.
+#[derive(Debug, Clone, Copy)]
+pub enum Fuso {
+    Local,
+    Fixo(FixedOffset),
+}
+
+impl ConfigAgenda {
+    pub fn fuso(&self) -> Result<Fuso, String> {
+        let valor = self.fuso_horario.trim();
+        if valor.eq_ignore_ascii_case("local") {
+            return Ok(Fuso::Local);
+        }
+        if valor.eq_ignore_ascii_case("utc") {
+            return Ok(Fuso::Fixo(FixedOffset::east_opt(0).unwrap()));
+        }
+        let invalido = || format!("agenda.fuso_horario inválido: '{}' (use \"local\", \"UTC\" ou \"-03:00\")", valor);
+        let (sinal, resto) = match valor.split_at_checked(1) {
+            Some(("+", r)) => (1, r),
+            Some(("-", r)) => (-1, r),
+            _ => return Err(invalido()),
+        };
+        let (h, m) = resto.split_once(':').ok_or_else(invalido)?;
+        let (h, m): (i32, i32) = (h.parse().map_err(|_| invalido())?, m.parse().map_err(|_| invalido())?);
+        if !(0..=14).contains(&h) || !(0..60).contains(&m) {
+            return Err(invalido());
+        }
+        FixedOffset::east_opt(sinal * (h * 3600 + m * 60)).map(Fuso::Fixo).ok_or_else(invalido)
+    }
+
+    /// Expediente padrão (início, fim) para dias sem `work_windows`.
+    pub fn janela_padrao(&self) -> Result<(NaiveTime, NaiveTime), String> {
+        let parse = |campo: &str, valor: &str| {
+            NaiveTime::parse_from_str(valor, "%H:%M").map_err(|_| format!("agenda.{} inválido: '{}' (use HH:MM)", campo, valor))
+        };
+        Ok((parse("inicio_padrao", &self.inicio_padrao)?, parse("fim_padrao", &self.fim_padrao)?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NivelLog {
+    Erro,
+    Aviso,
+    Info,
+    Debug,
+}
+
+impl NivelLog {
+    pub fn de_texto(valor: &str) -> Option<NivelLog> {
+        match valor.trim().to_ascii_lowercase().as_str() {
+            "erro" | "error" => Some(NivelLog::Erro),
+            "aviso" | "warn" => Some(NivelLog::Aviso),
+            "info" => Some(NivelLog::Info),
+            "debug" => Some(NivelLog::Debug),
+            _ => None,
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Define a configuração do processo (chamado uma vez, no início do `main`).
+pub fn definir(config: Config) {
+    let _ = CONFIG.set(config);
+}
+
+/// Configuração efetiva. Sem `definir` (ex.: testes), usa padrões + ambiente.
+pub fn atual() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let mut config = Config::default();
+        if let Err(e) = config.aplicar_ambiente(|nome| std::env::var(nome).ok()) {
+            eprintln!("[Config] {}", e);
+        }
+        config
+    })
+}
+
+/// Se mensagens do nível informado devem ser exibidas.
+pub fn loga(nivel: NivelLog) -> bool {
+    NivelLog::de_texto(&atual().log.nivel).unwrap_or(NivelLog::Info) >= nivel
+}
+
+/// Data e hora atuais no fuso da barbearia.
+pub fn agora() -> NaiveDateTime {
+    match atual().agenda.fuso().unwrap_or(Fuso::Local) {
+        Fuso::Local => Local::now().naive_local(),
+        Fuso::Fixo(offset) => Utc::now().with_timezone(&offset).naive_local(),
+    }
+}
+
+/// Converte um horário da barbearia (como gravado em `data_hora`) para UTC.
+pub fn para_utc(local: NaiveDateTime) -> NaiveDateTime {
+    match atual().agenda.fuso().unwrap_or(Fuso::Local) {
+        Fuso::Local => Local.from_local_datetime(&local).earliest().map(|d| d.naive_utc()).unwrap_or(local),
+        Fuso::Fixo(offset) => local - offset,
+    }
+}
+
+/// Converte um horário UTC para o fuso da barbearia.
+pub fn de_utc(utc: NaiveDateTime) -> NaiveDateTime {
+    match atual().agenda.fuso().unwrap_or(Fuso::Local) {
+        Fuso::Local => Local.from_utc_datetime(&utc).naive_local(),
+        Fuso::Fixo(offset) => utc + offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_camadas_arquivo_ambiente_flags() {
+        let mut config: Config = toml::from_str(
+            "[servidor]\nporta = 8080\n[agenda]\nbuffer_min = 10\nfuso_horario = \"-03:00\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.servidor.porta, 8080);
+        assert_eq!(config.servidor.host, "127.0.0.1", "campos ausentes mantêm o padrão");
+
+        let ambiente: HashMap<&str, &str> = [("APP_PORT", "9090"), ("APP_BUFFER_MIN", "5")].into_iter().collect();
+        config.aplicar_ambiente(|nome| ambiente.get(nome).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.servidor.porta, 9090);
+        assert_eq!(config.agenda.buffer_min, 5);
+
+        let flags = SobrescritasCli { porta: Some(7070), db: Some("/tmp/x.db".into()), ..Default::default() };
+        config.aplicar_flags(&flags);
+        assert_eq!(config.servidor.porta, 7070);
+        assert_eq!(config.banco.caminho, "/tmp/x.db");
+        assert!(config.validar().is_ok());
+    }
+
+    #[test]
+    fn test_validacao_reune_erros() {
+        let mut config = Config::default();
+        config.servidor.host = "não é ip".into();
+        config.agenda.granularidade_min = 0;
+        config.agenda.inicio_padrao = "19:00".into();
+        config.agenda.fuso_horario = "Marte/Olympus".into();
+        config.log.nivel = "verboso".into();
+        let erros = config.validar().unwrap_err();
+        assert_eq!(erros.len(), 5, "{:?}", erros);
+
+        assert!(toml::from_str::<Config>("[servidor]\nportaa = 1\n").is_err(), "chaves desconhecidas são rejeitadas");
+    }
+
+    #[test]
+    fn test_fuso_fixo() {
+        let agenda = ConfigAgenda { fuso_horario: "-03:00".into(), ..Default::default() };
+        match agenda.fuso().unwrap() {
+            Fuso::Fixo(offset) => assert_eq!(offset.local_minus_utc(), -3 * 3600),
+            Fuso::Local => panic!("esperado fuso fixo"),
+        }
+    }
+}
@@ -2,29 +2,44 @@
 use rusqlite::{Connection, Result, params, OpenFlags};
 use chrono::{NaiveDateTime, Datelike, DateTime, Utc};
 use std::collections::HashMap;
-use crate::models::{Cliente, Agendamento, Servico};
+use crate::models::{Cliente, Agendamento, Servico, RegistroAuditoria, RegrasAgenda, FaseServico, EtapaAgendamento, ConflitoHorario, RemarcacaoAgendamento};
+use crate::scheduling::{self, Etapa, Intervalo, Pedido};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
 use rusqlite::ToSql;
 use std::{fs, path::Path, time::Duration};
 
 
-// Caminho do banco de dados (padrão) — pode ser sobrescrito pela variável de ambiente APP_DB_PATH
-// Agora apontamos por padrão para a pasta `src/bd` conforme solicitado.
-const DB_PATH: &str = "src/bd/Banco.db";
-
 // =================================================================================
 // 1. INFRAESTRUTURA
 // =================================================================================
 
+/// Caminho do arquivo do banco (`banco.caminho` na configuração; padrão `src/bd/Banco.db`).
+pub fn caminho_db() -> String {
+    crate::config::atual().banco.caminho.clone()
+}
+
 pub fn conectar_db() -> Result<Connection> {
-    // Allow overriding DB path via env var for flexibility in dev/production
-    let db_path = std::env::var("APP_DB_PATH").unwrap_or_else(|_| DB_PATH.to_string());
+    // Nos testes cada conexão é um banco novo em memória, para não ler nem sujar o Banco.db
+    // do desenvolvedor. As chaves estrangeiras ficam desligadas (padrão do SQLite) porque os
+    // testes de CRUD gravam agendamentos com o serviço 1 sem cadastrá-lo.
+    #[cfg(test)]
+    {
+        let conn = Connection::open_in_memory()?;
+        conn.pragma_update(None, "foreign_keys", false)?;
+        return Ok(conn);
+    }
+    #[cfg_attr(test, allow(unreachable_code))]
+    let db_path = caminho_db();
     // Resolve absolute path for logging/debugging
     // canonicalize pode falhar se o arquivo ainda não existir; use PathBuf direto como fallback
     let abs_path = match std::fs::canonicalize(&db_path) {
         Ok(p) => p,
         Err(_) => std::path::PathBuf::from(&db_path),
     };
-    println!("[DB] Abrindo arquivo de banco de dados em: {}", abs_path.display());
+    if crate::config::loga(crate::config::NivelLog::Info) {
+        eprintln!("[DB] Abrindo arquivo de banco de dados em: {}", abs_path.display());
+    }
     // Garantir que o diretório pai exista (cria se necessário)
     let path = Path::new(&db_path);
     if let Some(parent) = path.parent() {
@@ -75,6 +90,30 @@ pub fn criar_tabelas(conn: &Connection) -> Result<()> {
         )", [],
     )?;
 
+    // Histórico de remarcações: de onde o agendamento saiu e para onde foi.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS agendamento_historico (
+            id                 INTEGER PRIMARY KEY AUTOINCREMENT,
+            agendamento_id     INTEGER NOT NULL,
+            data_hora_anterior INTEGER NOT NULL,
+            data_hora_nova     INTEGER NOT NULL,
+            origem             TEXT NOT NULL,
+            motivo             TEXT,
+            registrado_em      INTEGER NOT NULL,
+            FOREIGN KEY (agendamento_id) REFERENCES agendamentos(id) ON DELETE CASCADE
+        )", [],
+    )?;
+    GatilhoAuditoria {
+        tabela: "agendamento_historico",
+        evento: "AFTER INSERT",
+        entidade: "agendamento",
+        acao: ACAO_REMARCACAO,
+        id: "NEW.agendamento_id",
+        antes: "json_object('data_hora', NEW.data_hora_anterior)",
+        depois: "json_object('data_hora', NEW.data_hora_nova, 'origem', NEW.origem, 'motivo', NEW.motivo)",
+    }
+    .criar(conn)?;
+
     conn.execute(
         "CREATE TABLE IF NOT EXISTS sistema (
             id INTEGER PRIMARY KEY CHECK (id = 1),
@@ -87,9 +126,57 @@ pub fn criar_tabelas(conn: &Connection) -> Result<()> {
         "INSERT OR IGNORE INTO sistema (id, validade_licenca) VALUES (1, 0)",
         [],
     )?;
+    // Token assinado da licença (ver licenca::instalar_licenca).
+    garantir_coluna(conn, "sistema", "licenca_token", "TEXT")?;
+    // Confirmação/cancelamento pelo cliente (ver STATUS_AGENDAMENTO_*); `concluido` continua à parte.
+    garantir_coluna(conn, "agendamentos", "status", &format!("TEXT NOT NULL DEFAULT '{}'", STATUS_AGENDAMENTO_PENDENTE))?;
+    // Início do período de avaliação para instalações sem licença.
+    garantir_coluna(conn, "sistema", "instalado_em", "INTEGER")?;
+    conn.execute(
+        "UPDATE sistema SET instalado_em = ?1 WHERE id = 1 AND instalado_em IS NULL",
+        params![chrono::Utc::now().timestamp()],
+    )?;
 
     criar_tabela_servicos(conn)?;
     criar_tabela_work_schedule(conn)?;
+    criar_tabela_lgpd(conn)?;
+    criar_tabela_auditoria(conn)?;
+    crate::notificacoes::criar_tabela_notificacoes(conn)?;
+    crate::webhooks::criar_tabela_webhooks(conn)?;
+    crate::calendario::criar_tabela_bloqueios(conn)?;
+    crate::publico::criar_tabelas_publico(conn)?;
+    crate::espera::criar_tabela_espera(conn)?;
+    crate::recursos::criar_tabela_recursos(conn)?;
+    crate::patch::criar_controle_de_versao(conn)?;
+    crate::sincronizacao::criar_tabelas_sincronizacao(conn)?;
+    Ok(())
+}
+
+/// Adiciona a coluna à tabela caso ela ainda não exista (migração de bancos antigos).
+pub fn garantir_coluna(conn: &Connection, tabela: &str, coluna: &str, definicao: &str) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", tabela))?;
+    let cols = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for col in cols {
+        if col? == coluna {
+            return Ok(());
+        }
+    }
+    conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", tabela, coluna, definicao), [])?;
+    Ok(())
+}
+
+/// Tabela com o registro das solicitações de titulares (LGPD) e coluna de anonimização.
+pub fn criar_tabela_lgpd(conn: &Connection) -> Result<()> {
+    garantir_coluna(conn, "clientes", "anonimizado_em", "INTEGER")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS solicitacoes_lgpd (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            cliente_id   INTEGER NOT NULL,
+            tipo         TEXT NOT NULL,
+            realizado_em INTEGER NOT NULL
+        )",
+        [],
+    )?;
     Ok(())
 }
 
@@ -106,22 +193,6 @@ pub fn criar_tabela_work_schedule(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
-/// Retorna as janelas de trabalho (start,end) para a data informada.
-pub fn get_work_windows_for_date(conn: &Connection, date: chrono::NaiveDate) -> Result<Vec<(chrono::NaiveTime, chrono::NaiveTime)>> {
-    let weekday = date.weekday().num_days_from_monday() as i32; // 0 = Monday
-    let mut stmt = conn.prepare("SELECT start_time, end_time FROM work_windows WHERE weekday = ?1")?;
-    let rows = stmt.query_map(params![weekday], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
-
-    let mut windows = Vec::new();
-    for r in rows {
-        let (s,e) = r?;
-        if let (Ok(st), Ok(en)) = (chrono::NaiveTime::parse_from_str(&s, "%H:%M"), chrono::NaiveTime::parse_from_str(&e, "%H:%M")) {
-            windows.push((st,en));
-        }
-    }
-    Ok(windows)
-}
-
 /// Lista todas as work_windows (id, weekday, start_time, end_time)
 pub fn listar_work_windows(conn: &Connection) -> Result<Vec<(i32, i32, String, String)>> {
     let mut stmt = conn.prepare("SELECT id, weekday, start_time, end_time FROM work_windows ORDER BY weekday, start_time")?;
@@ -133,7 +204,10 @@ pub fn listar_work_windows(conn: &Connection) -> Result<Vec<(i32, i32, String, S
 
 pub fn salvar_work_window(conn: &Connection, weekday: i32, start_time: &str, end_time: &str) -> Result<i64> {
     conn.execute("INSERT INTO work_windows (weekday, start_time, end_time) VALUES (?1, ?2, ?3)", params![weekday, start_time, end_time])?;
-    Ok(conn.last_insert_rowid())
+    let id = conn.last_insert_rowid();
+    let depois = serde_json::json!({ "id": id, "weekday": weekday, "start_time": start_time, "end_time": end_time });
+    registrar_auditoria(conn, "work_window", id, ACAO_CRIACAO, None, Some(depois))?;
+    Ok(id)
 }
 
 pub fn criar_tabela_servicos(conn: &Connection) -> Result<()> {
@@ -151,7 +225,7 @@ pub fn criar_tabela_servicos(conn: &Connection) -> Result<()> {
     // If the table existed previously without `duracao_min`, add the column.
     let mut has_duracao = false;
     let mut stmt = conn.prepare("PRAGMA table_info(servicos)")?;
-    let cols = stmt.query_map([], |row| Ok(row.get::<_, String>(1)?))?;
+    let cols = stmt.query_map([], |row| row.get::<_, String>(1))?;
     for col in cols {
         if col? == "duracao_min" {
             has_duracao = true;
@@ -162,10 +236,55 @@ pub fn criar_tabela_servicos(conn: &Connection) -> Result<()> {
         // Add column with a sensible default to avoid NOT NULL violations.
         conn.execute("ALTER TABLE servicos ADD COLUMN duracao_min INTEGER NOT NULL DEFAULT 30", [])?;
     }
+    // Regras por serviço usadas na disponibilidade (NULL = padrão da agenda).
+    garantir_coluna(conn, "servicos", "buffer_min", "INTEGER")?;
+    garantir_coluna(conn, "servicos", "alinhamento_min", "INTEGER")?;
+
+    // Serviços em etapas; sem linhas aqui, o serviço ocupa o profissional do início ao fim.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS servico_fases (
+            servico_id         INTEGER NOT NULL,
+            ordem              INTEGER NOT NULL,
+            nome               TEXT NOT NULL,
+            duracao_min        INTEGER NOT NULL,
+            ocupa_profissional BOOLEAN NOT NULL CHECK (ocupa_profissional IN (0, 1)),
+            PRIMARY KEY (servico_id, ordem),
+            FOREIGN KEY (servico_id) REFERENCES servicos(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS regras_agenda (
+            id                      INTEGER PRIMARY KEY CHECK (id = 1),
+            antecedencia_minima_min INTEGER NOT NULL DEFAULT 30,
+            horizonte_dias          INTEGER NOT NULL DEFAULT 60
+        )",
+        [],
+    )?;
+    conn.execute("INSERT OR IGNORE INTO regras_agenda (id) VALUES (1)", [])?;
 
     Ok(())
 }
 
+pub fn buscar_regras_agenda(conn: &Connection) -> Result<RegrasAgenda> {
+    conn.query_row(
+        "SELECT antecedencia_minima_min, horizonte_dias FROM regras_agenda WHERE id = 1",
+        [],
+        |row| Ok(RegrasAgenda { antecedencia_minima_min: row.get(0)?, horizonte_dias: row.get(1)? }),
+    )
+}
+
+pub fn salvar_regras_agenda(conn: &Connection, regras: &RegrasAgenda) -> Result<()> {
+    let antes = buscar_regras_agenda(conn)?;
+    conn.execute(
+        "UPDATE regras_agenda SET antecedencia_minima_min = ?1, horizonte_dias = ?2 WHERE id = 1",
+        params![regras.antecedencia_minima_min, regras.horizonte_dias],
+    )?;
+    registrar_auditoria(conn, "regras_agenda", 1, ACAO_ATUALIZACAO, json_de(&antes), json_de(regras))?;
+    Ok(())
+}
+
 // =================================================================================
 // 2. CLIENTES
 // =================================================================================
@@ -174,10 +293,12 @@ pub fn salvar_cliente(conn: &Connection, cliente: &mut Cliente) -> Result<i32> {
     match cliente.id {
         Some(id) => {
             // ID existe, então UPDATE
+            let antes = buscar_cliente_por_id(conn, id)?;
             conn.execute(
                 "UPDATE clientes SET nome = ?1, telefone = ?2, email = ?3 WHERE id = ?4",
                 params![cliente.nome, cliente.telefone, cliente.email, id],
             )?;
+            registrar_auditoria(conn, "cliente", id as i64, ACAO_ATUALIZACAO, json_de(&antes), json_de(cliente))?;
             Ok(id)
         }
         None => {
@@ -188,6 +309,7 @@ pub fn salvar_cliente(conn: &Connection, cliente: &mut Cliente) -> Result<i32> {
             )?;
             let id = conn.last_insert_rowid() as i32;
             cliente.id = Some(id);
+            registrar_auditoria(conn, "cliente", id as i64, ACAO_CRIACAO, None, json_de(cliente))?;
             Ok(id)
         }
     }
@@ -206,18 +328,40 @@ pub fn listar_clientes(conn: &Connection) -> Result<Vec<Cliente>> {
     clientes
 }
 
-/// Lista clientes filtrando por nome (LIKE) com limite.
+/// Minúsculas e sem acentos, para buscas em que "joa" encontra "João".
+pub fn sem_acentos(texto: &str) -> String {
+    texto
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ã' | 'ä' => 'a',
+            'é' | 'ê' | 'è' => 'e',
+            'í' | 'î' => 'i',
+            'ó' | 'ô' | 'õ' | 'ö' => 'o',
+            'ú' | 'ü' => 'u',
+            'ç' => 'c',
+            c => c,
+        })
+        .collect()
+}
+
+/// Lista clientes cujo nome contém `search`, sem diferenciar maiúsculas nem acentos, com limite.
+/// O `LIKE` do SQLite não ignora acentos, então o filtro é feito aqui.
 pub fn listar_clientes_search(conn: &Connection, search: &str, limit: i32) -> Result<Vec<Cliente>> {
-    let pattern = format!("%{}%", search.replace('%', "\\%"));
-    let mut stmt = conn.prepare("SELECT id, nome, telefone, email FROM clientes WHERE nome LIKE ?1 ORDER BY nome LIMIT ?2")?;
-    let clientes = stmt.query_map(params![pattern, limit], |row| {
+    let busca = sem_acentos(search);
+    let mut stmt = conn.prepare("SELECT id, nome, telefone, email FROM clientes ORDER BY nome")?;
+    let clientes = stmt.query_map([], |row| {
         Ok(Cliente {
             id: row.get(0)?,
             nome: row.get(1)?,
             telefone: row.get(2)?,
             email: row.get(3)?,
         })
-    })?.filter_map(Result::ok).collect();
+    })?
+    .filter_map(Result::ok)
+    .filter(|c| sem_acentos(&c.nome).contains(&busca))
+    .take(limit.max(0) as usize)
+    .collect();
     Ok(clientes)
 }
 
@@ -237,12 +381,79 @@ pub fn buscar_cliente_por_id(conn: &Connection, id: i32) -> Result<Option<Client
     }
 }
 
+/// Anonimiza os dados pessoais do cliente mantendo o histórico de agendamentos
+/// e receita. Retorna `false` se o cliente não existir.
+pub fn anonimizar_cliente(conn: &Connection, id: i32) -> Result<bool> {
+    // Tudo ou nada: um erro no meio não deixa o cliente meio anonimizado e sem a solicitação.
+    let tx = conn.unchecked_transaction()?;
+    let linhas = tx.execute(
+        "UPDATE clientes
+         SET nome = ?1, telefone = '', email = NULL, anonimizado_em = ?2
+         WHERE id = ?3",
+        params![format!("Cliente anonimizado #{}", id), agora_ts(), id],
+    )?;
+    if linhas == 0 {
+        return Ok(false);
+    }
+    // O histórico de auditoria, os lembretes, os eventos de webhook e as reservas públicas
+    // também guardavam os dados pessoais do titular.
+    redigir_auditoria_cliente(&tx, id)?;
+    crate::notificacoes::redigir_notificacoes_cliente(&tx, id)?;
+    crate::webhooks::redigir_entregas_cliente(&tx, id)?;
+    crate::publico::redigir_reservas_cliente(&tx, id)?;
+    let depois = buscar_cliente_por_id(&tx, id)?;
+    registrar_auditoria(&tx, "cliente", id as i64, ACAO_ANONIMIZACAO, None, json_de(&depois))?;
+    inserir_solicitacao_lgpd(&tx, id, SOLICITACAO_ANONIMIZACAO)?;
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Retorna quando o cliente foi anonimizado (None se nunca foi).
+pub fn buscar_anonimizacao_cliente(conn: &Connection, id: i32) -> Result<Option<NaiveDateTime>> {
+    let ts: Option<i64> = conn.query_row(
+        "SELECT anonimizado_em FROM clientes WHERE id = ?1",
+        params![id],
+        |row| row.get(0),
+    )?;
+    Ok(ts.map(timestamp_para_naive))
+}
+
+/// Tipos de solicitação do titular gravados em `solicitacoes_lgpd`.
+pub const SOLICITACAO_EXPORTACAO: &str = "exportacao";
+pub const SOLICITACAO_ANONIMIZACAO: &str = "anonimizacao";
+
+fn inserir_solicitacao_lgpd(conn: &Connection, cliente_id: i32, tipo: &str) -> Result<i64> {
+    conn.execute(
+        "INSERT INTO solicitacoes_lgpd (cliente_id, tipo, realizado_em) VALUES (?1, ?2, ?3)",
+        params![cliente_id, tipo, agora_ts()],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Registra o pedido de exportação do titular, com a entrada de auditoria. A anonimização
+/// registra a própria solicitação em `anonimizar_cliente`.
+pub fn registrar_exportacao_lgpd(conn: &Connection, cliente_id: i32) -> Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+    let id = inserir_solicitacao_lgpd(&tx, cliente_id, SOLICITACAO_EXPORTACAO)?;
+    registrar_auditoria(&tx, "cliente", cliente_id as i64, ACAO_EXPORTACAO, None, None)?;
+    tx.commit()?;
+    Ok(id)
+}
+
+/// Lista as solicitações LGPD do cliente como (tipo, data), da mais antiga para a mais recente.
+pub fn listar_solicitacoes_lgpd(conn: &Connection, cliente_id: i32) -> Result<Vec<(String, NaiveDateTime)>> {
+    let mut stmt = conn.prepare("SELECT tipo, realizado_em FROM solicitacoes_lgpd WHERE cliente_id = ?1 ORDER BY id")?;
+    let rows = stmt.query_map(params![cliente_id], |row| {
+        Ok((row.get::<_, String>(0)?, timestamp_para_naive(row.get::<_, i64>(1)?)))
+    })?;
+    rows.collect()
+}
 
 // =================================================================================
 // 3. AGENDAMENTOS
 // =================================================================================
 
-fn timestamp_para_naive(ts: i64) -> NaiveDateTime {
+pub fn timestamp_para_naive(ts: i64) -> NaiveDateTime {
     match DateTime::<Utc>::from_timestamp(ts, 0) {
         Some(dt) => dt.naive_local(),
         None => DateTime::<Utc>::from_timestamp(0, 0).unwrap().naive_local(),
@@ -271,6 +482,10 @@ pub fn salvar_agendamento(conn: &Connection, agendamento: &Agendamento) -> Resul
         )?;
     }
 
+    let mut salvo = agendamento.clone();
+    salvo.id = Some(agendamento_id);
+    registrar_auditoria(conn, "agendamento", agendamento_id as i64, ACAO_CRIACAO, None, json_de(&salvo))?;
+
     Ok(agendamento_id)
 }
 
@@ -333,8 +548,32 @@ pub fn listar_agendamentos(conn: &Connection) -> Result<Vec<Agendamento>> {
     Ok(agendamentos_com_servicos)
 }
 
+pub const STATUS_AGENDAMENTO_PENDENTE: &str = "pendente";
+pub const STATUS_AGENDAMENTO_CONFIRMADO: &str = "confirmado";
+/// Cancelado pelo cliente: o registro fica, mas não ocupa horário nem recebe lembretes.
+pub const STATUS_AGENDAMENTO_CANCELADO: &str = "cancelado";
+
+pub fn status_agendamento(conn: &Connection, id: i32) -> Result<String> {
+    conn.query_row("SELECT status FROM agendamentos WHERE id = ?1", params![id], |row| row.get(0))
+}
+
+/// Muda o status do agendamento e registra a mudança na auditoria.
+pub fn definir_status_agendamento(conn: &Connection, id: i32, status: &str) -> Result<()> {
+    let antes = status_agendamento(conn, id)?;
+    conn.execute("UPDATE agendamentos SET status = ?1 WHERE id = ?2", params![status, id])?;
+    registrar_auditoria(
+        conn,
+        "agendamento",
+        id as i64,
+        ACAO_MUDANCA_STATUS,
+        Some(serde_json::json!({ "status": antes })),
+        Some(serde_json::json!({ "status": status })),
+    )?;
+    Ok(())
+}
+
 pub fn verificar_conflito(conn: &Connection, data_hora: NaiveDateTime) -> Result<bool> {
-    let mut stmt = conn.prepare("SELECT COUNT(*) FROM agendamentos WHERE data_hora = ?1 AND concluido = 0")?;
+    let mut stmt = conn.prepare("SELECT COUNT(*) FROM agendamentos WHERE data_hora = ?1 AND concluido = 0 AND status != 'cancelado'")?;
     let count: i32 = stmt.query_row(params![data_hora.and_utc().timestamp()], |row| row.get(0))?;
     Ok(count > 0)
 }
@@ -347,6 +586,7 @@ pub fn atualizar_agendamento(
     novo_preco: Option<f64>,
     concluido: Option<bool>,
 ) -> Result<()> {
+    let antes = buscar_agendamento_por_id(conn, id).ok();
     let mut updates = Vec::new();
     let mut params_vec: Vec<rusqlite::types::Value> = Vec::new();
 
@@ -362,6 +602,10 @@ pub fn atualizar_agendamento(
         updates.push("concluido = ?");
         params_vec.push(rusqlite::types::Value::Integer(conc as i64));
     }
+    if novo_servicos_ids.is_some() {
+        // Os serviços ficam em outra tabela; a versão do agendamento sobe aqui (ver patch.rs).
+        updates.push("versao = versao + 1");
+    }
 
     if !updates.is_empty() {
         let update_query = format!(
@@ -387,11 +631,89 @@ pub fn atualizar_agendamento(
         }
     }
 
+    if let Some(antes) = antes {
+        let depois = buscar_agendamento_por_id(conn, id)?;
+        // Só o `concluido` mudou: registramos como mudança de status.
+        let so_status = antes.concluido != depois.concluido
+            && antes.data_hora == depois.data_hora
+            && antes.preco == depois.preco
+            && antes.servicos_ids == depois.servicos_ids;
+        let acao = if so_status {
+            ACAO_MUDANCA_STATUS
+        } else {
+            ACAO_ATUALIZACAO
+        };
+        registrar_auditoria(conn, "agendamento", id as i64, acao, json_de(&antes), json_de(&depois))?;
+    }
+
+    Ok(())
+}
+
+/// Executa `gravar` num SAVEPOINT: sozinho vale como transação e, dentro de uma transação já
+/// aberta (lotes, sincronização), desfaz só o que `gravar` fez. Qualquer erro desfaz tudo.
+pub fn em_savepoint<T>(conn: &Connection, gravar: impl FnOnce(&Connection) -> Result<T>) -> Result<T> {
+    conn.execute_batch("SAVEPOINT alteracao")?;
+    match gravar(conn) {
+        Ok(valor) => {
+            conn.execute_batch("RELEASE alteracao")?;
+            Ok(valor)
+        }
+        Err(e) => {
+            conn.execute_batch("ROLLBACK TO alteracao; RELEASE alteracao")?;
+            Err(e)
+        }
+    }
+}
+
+/// Move o agendamento para `nova` e registra a remarcação juntos: o histórico nunca
+/// diverge do horário gravado.
+pub fn remarcar_agendamento(conn: &Connection, id: i32, anterior: NaiveDateTime, nova: NaiveDateTime, origem: &str, motivo: Option<&str>) -> Result<()> {
+    em_savepoint(conn, |conn| {
+        atualizar_agendamento(conn, id, Some(nova), None, None, None)?;
+        registrar_remarcacao(conn, id, anterior, nova, origem, motivo)
+    })
+}
+
+pub const ORIGEM_REAGENDAR: &str = "reagendar";
+pub const ORIGEM_EDICAO: &str = "edicao";
+pub const ORIGEM_CLIENTE: &str = "cliente";
+
+/// Guarda no histórico que o agendamento saiu de `anterior` para `nova`.
+pub fn registrar_remarcacao(conn: &Connection, id: i32, anterior: NaiveDateTime, nova: NaiveDateTime, origem: &str, motivo: Option<&str>) -> Result<()> {
+    conn.execute(
+        "INSERT INTO agendamento_historico (agendamento_id, data_hora_anterior, data_hora_nova, origem, motivo, registrado_em)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![id, anterior.and_utc().timestamp(), nova.and_utc().timestamp(), origem, motivo, agora_ts()],
+    )?;
     Ok(())
 }
 
+/// Remarcações do agendamento, da mais antiga para a mais recente.
+pub fn listar_remarcacoes(conn: &Connection, id: i32) -> Result<Vec<RemarcacaoAgendamento>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agendamento_id, data_hora_anterior, data_hora_nova, origem, motivo, registrado_em
+         FROM agendamento_historico WHERE agendamento_id = ?1 ORDER BY id"
+    )?;
+    let linhas = stmt.query_map(params![id], |row| {
+        Ok(RemarcacaoAgendamento {
+            id: row.get(0)?,
+            agendamento_id: row.get(1)?,
+            data_hora_anterior: timestamp_para_naive(row.get(2)?),
+            data_hora_nova: timestamp_para_naive(row.get(3)?),
+            origem: row.get(4)?,
+            motivo: row.get(5)?,
+            registrado_em: timestamp_para_naive(row.get(6)?),
+        })
+    })?;
+    linhas.collect()
+}
+
 pub fn excluir_agendamento(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM agendamentos WHERE id = ?1", params![id])?;
+    let antes = buscar_agendamento_por_id(conn, id).ok();
+    let linhas = conn.execute("DELETE FROM agendamentos WHERE id = ?1", params![id])?;
+    if linhas > 0 {
+        registrar_auditoria(conn, "agendamento", id as i64, ACAO_EXCLUSAO, json_de(&antes), None)?;
+    }
     Ok(())
 }
 
@@ -465,11 +787,14 @@ pub fn excluir_cliente(conn: &Connection, id: i32) -> Result<()> {
     if count > 0 {
         return Err(rusqlite::Error::ExecuteReturnedResults);
     }
-    conn.execute("DELETE FROM clientes WHERE id = ?1", params![id])?;
+    let antes = buscar_cliente_por_id(conn, id)?;
+    let linhas = conn.execute("DELETE FROM clientes WHERE id = ?1", params![id])?;
+    if linhas > 0 {
+        registrar_auditoria(conn, "cliente", id as i64, ACAO_EXCLUSAO, json_de(&antes), None)?;
+    }
     Ok(())
 }
 /// Lista todos os agendamentos para uma data específica.
-#[allow(dead_code)]
 pub fn listar_agendamentos_por_data(conn: &Connection, data: NaiveDateTime) -> Result<Vec<Agendamento>> {
     let inicio = data.date().and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
     let fim = data.date().and_hms_opt(23, 59, 59).unwrap().and_utc().timestamp();
@@ -508,7 +833,6 @@ pub fn listar_agendamentos_por_data(conn: &Connection, data: NaiveDateTime) -> R
     Ok(agendamentos_com_servicos)
 }
 /// Lista todos os agendamentos de um cliente específico.
-#[allow(dead_code)]
 pub fn listar_agendamentos_por_cliente(conn: &Connection, cliente_id: i32) -> Result<Vec<Agendamento>> {
     let mut stmt = conn.prepare(
         "SELECT id, cliente_id, data_hora, preco, concluido 
@@ -543,8 +867,634 @@ pub fn listar_agendamentos_por_cliente(conn: &Connection, cliente_id: i32) -> Re
     }
     Ok(agendamentos_com_servicos)
 }
+pub fn salvar_servico(conn: &Connection, servico: &Servico) -> Result<i32> {
+    match servico.id {
+        Some(id) => {
+            // Update existing service
+            let antes = buscar_servico_por_id(conn, id)?;
+            conn.execute(
+                "UPDATE servicos SET nome = ?1, preco = ?2, duracao_min = ?3, buffer_min = ?4, alinhamento_min = ?5 WHERE id = ?6",
+                params![servico.nome, servico.preco, servico.duracao_min, servico.buffer_min, servico.alinhamento_min, id],
+            )?;
+            registrar_auditoria(conn, "servico", id as i64, ACAO_ATUALIZACAO, json_de(&antes), json_de(servico))?;
+            Ok(id)
+        }
+        None => {
+            // Insert new service
+            conn.execute(
+                "INSERT INTO servicos (nome, preco, duracao_min, buffer_min, alinhamento_min) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![servico.nome, servico.preco, servico.duracao_min, servico.buffer_min, servico.alinhamento_min],
+            )?;
+            let id = conn.last_insert_rowid() as i32;
+            let salvo = Servico { id: Some(id), ..servico.clone() };
+            registrar_auditoria(conn, "servico", id as i64, ACAO_CRIACAO, None, json_de(&salvo))?;
+            Ok(id)
+        }
+    }
+}
+
+fn linha_para_servico(row: &rusqlite::Row) -> Result<Servico> {
+    Ok(Servico {
+        id: row.get(0)?,
+        nome: row.get(1)?,
+        preco: row.get(2)?,
+        duracao_min: row.get(3)?,
+        buffer_min: row.get(4)?,
+        alinhamento_min: row.get(5)?,
+    })
+}
+
+const COLUNAS_SERVICO: &str = "id, nome, preco, duracao_min, buffer_min, alinhamento_min";
+
+pub fn listar_servicos(conn: &Connection) -> Result<Vec<Servico>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM servicos", COLUNAS_SERVICO))?;
+    let servicos = stmt.query_map([], linha_para_servico)?
+    .filter_map(Result::ok)
+    .collect();
+    Ok(servicos)
+}
+
+/// Lista serviços filtrando por nome (LIKE) com limite.
+pub fn listar_servicos_search(conn: &Connection, search: &str, limit: i32) -> Result<Vec<Servico>> {
+    let pattern = format!("%{}%", search.replace('%', "\\%"));
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM servicos WHERE nome LIKE ?1 ORDER BY nome LIMIT ?2", COLUNAS_SERVICO))?;
+    let servicos = stmt.query_map(params![pattern, limit], linha_para_servico)?.filter_map(Result::ok).collect();
+    Ok(servicos)
+}
+
+pub fn buscar_servico_por_id(conn: &Connection, id: i32) -> Result<Option<Servico>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM servicos WHERE id = ?1", COLUNAS_SERVICO))?;
+    let mut rows = stmt.query(params![id])?;
+
+    if let Some(row) = rows.next()? {
+        Ok(Some(linha_para_servico(row)?))
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn excluir_servico(conn: &Connection, id: i32) -> Result<()> {
+    let antes = buscar_servico_por_id(conn, id)?;
+    conn.execute("DELETE FROM servico_fases WHERE servico_id = ?1", params![id])?;
+    conn.execute("DELETE FROM servico_recursos WHERE servico_id = ?1", params![id])?;
+    let linhas = conn.execute("DELETE FROM servicos WHERE id = ?1", params![id])?;
+    if linhas > 0 {
+        registrar_auditoria(conn, "servico", id as i64, ACAO_EXCLUSAO, json_de(&antes), None)?;
+    }
+    Ok(())
+}
+
+/// Fases do serviço na ordem de execução (vazio = serviço de uma etapa só).
+pub fn listar_fases_servico(conn: &Connection, servico_id: i32) -> Result<Vec<FaseServico>> {
+    let mut stmt = conn.prepare("SELECT nome, duracao_min, ocupa_profissional FROM servico_fases WHERE servico_id = ?1 ORDER BY ordem")?;
+    let fases = stmt.query_map(params![servico_id], |row| {
+        Ok(FaseServico { nome: row.get(0)?, duracao_min: row.get(1)?, ocupa_profissional: row.get(2)? })
+    })?.collect();
+    fases
+}
+
+/// Fases de todos os serviços que têm fases, para montar a agenda sem uma consulta por serviço.
+fn fases_por_servico(conn: &Connection) -> Result<HashMap<i32, Vec<FaseServico>>> {
+    let mut stmt = conn.prepare("SELECT servico_id, nome, duracao_min, ocupa_profissional FROM servico_fases ORDER BY servico_id, ordem")?;
+    let linhas = stmt.query_map([], |row| {
+        Ok((row.get::<_, i32>(0)?, FaseServico { nome: row.get(1)?, duracao_min: row.get(2)?, ocupa_profissional: row.get(3)? }))
+    })?;
+    let mut fases: HashMap<i32, Vec<FaseServico>> = HashMap::new();
+    for linha in linhas {
+        let (servico_id, fase) = linha?;
+        fases.entry(servico_id).or_default().push(fase);
+    }
+    Ok(fases)
+}
+
+/// Troca as fases do serviço. Com fases, `duracao_min` passa a ser a soma delas.
+pub fn salvar_fases_servico(conn: &Connection, servico_id: i32, fases: &[FaseServico]) -> Result<()> {
+    let antes = listar_fases_servico(conn, servico_id)?;
+    conn.execute("DELETE FROM servico_fases WHERE servico_id = ?1", params![servico_id])?;
+    for (ordem, fase) in fases.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO servico_fases (servico_id, ordem, nome, duracao_min, ocupa_profissional) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![servico_id, ordem as i64, fase.nome, fase.duracao_min, fase.ocupa_profissional],
+        )?;
+    }
+    if !fases.is_empty() {
+        let total: i32 = fases.iter().map(|f| f.duracao_min).sum();
+        conn.execute("UPDATE servicos SET duracao_min = ?1 WHERE id = ?2", params![total, servico_id])?;
+    }
+    registrar_auditoria(
+        conn,
+        "servico",
+        servico_id as i64,
+        ACAO_ATUALIZACAO,
+        Some(serde_json::json!({ "fases": antes })),
+        Some(serde_json::json!({ "fases": fases })),
+    )?;
+    Ok(())
+}
+
+/// Etapas em sequência de um serviço: as fases, ou o serviço inteiro.
+fn etapas_do_servico(fases: Option<&Vec<FaseServico>>, duracao_min: i64) -> Vec<Etapa> {
+    match fases {
+        Some(fases) if !fases.is_empty() => fases.iter().map(|f| (f.duracao_min as i64, f.ocupa_profissional)).collect(),
+        _ => vec![(duracao_min, true)],
+    }
+}
+
+/// Serviços do agendamento na ordem em que foram marcados (a ordem em que são feitos).
+fn servicos_em_ordem(conn: &Connection, agendamento_id: i32) -> Result<Vec<i32>> {
+    let mut stmt = conn.prepare("SELECT servico_id FROM agendamento_servicos WHERE agendamento_id = ?1 ORDER BY rowid")?;
+    let ids = stmt.query_map(params![agendamento_id], |row| row.get(0))?.collect();
+    ids
+}
+
+/// Linha do tempo do agendamento: cada serviço, ou cada fase dele, com início e fim.
+pub fn linha_do_tempo(conn: &Connection, agendamento_id: i32) -> Result<Vec<EtapaAgendamento>> {
+    let agendamento = buscar_agendamento_por_id(conn, agendamento_id)?;
+    let mut cursor = agendamento.data_hora;
+    let mut etapas = Vec::new();
+    for servico_id in servicos_em_ordem(conn, agendamento_id)? {
+        let Some(servico) = buscar_servico_por_id(conn, servico_id)? else { continue };
+        let fases = listar_fases_servico(conn, servico_id)?;
+        let trechos: Vec<(Option<String>, i64, bool)> = if fases.is_empty() {
+            vec![(None, servico.duracao_min as i64, true)]
+        } else {
+            fases.into_iter().map(|f| (Some(f.nome), f.duracao_min as i64, f.ocupa_profissional)).collect()
+        };
+        for (fase, minutos, ocupa_profissional) in trechos {
+            let fim = cursor + chrono::Duration::minutes(minutos);
+            etapas.push(EtapaAgendamento { servico_id, servico: servico.nome.clone(), fase, inicio: cursor, fim, ocupa_profissional });
+            cursor = fim;
+        }
+    }
+    Ok(etapas)
+}
+
+/// Retorna os nomes dos serviços vinculados a um agendamento
+pub fn buscar_nomes_servicos(conn: &Connection, ids: &[i32]) -> Result<Vec<String>> {
+    let mut nomes = Vec::new();
+    for id in ids {
+        let nome: String = conn.query_row(
+            "SELECT nome FROM servicos WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+        nomes.push(nome);
+    }
+    Ok(nomes)
+}
+
+/// Retorna slots de disponibilidade para uma data específica.
+/// Parâmetros:
+/// - data_str: YYYY-MM-DD
+/// - duracao_min: duração total necessária em minutos
+/// - buffer_min: minutos de buffer antes/depois (padrão 15)
+/// - granularity_min: granularidade dos slots em minutos (padrão 15)
+pub fn calcular_disponibilidade(conn: &Connection, data_str: &str, duracao_min: i64, buffer_min: i64, granularity_min: i64) -> Result<Vec<String>> {
+    // Parse da data
+    let date = chrono::NaiveDate::parse_from_str(data_str, "%Y-%m-%d").map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
+    let pedido = PedidoDisponibilidade {
+        pedido: Pedido::simples(duracao_min, buffer_min, granularity_min),
+        buffers_por_servico: false,
+        recursos_ids: Vec::new(),
+        ignorar: None,
+    };
+    horarios_disponiveis(conn, date, &pedido)
+}
+
+/// Pedido do motor de horários mais o que só interessa à leitura do banco.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PedidoDisponibilidade {
+    pub pedido: Pedido,
+    /// Verdadeiro: cada agendamento existente usa a folga dos próprios serviços.
+    /// Falso: todos usam `pedido.buffer_min`, como no cálculo original.
+    pub buffers_por_servico: bool,
+    /// Recursos exigidos pelos serviços: horários em que algum deles está preso também saem.
+    pub recursos_ids: Vec<i32>,
+    /// Agendamento que não ocupa a agenda: o próprio, quando está sendo remarcado.
+    pub ignorar: Option<i32>,
+}
+
+/// Monta o pedido para uma combinação de serviços, com as regras da tabela `regras_agenda`.
+/// Sem serviços, usa a duração e a folga padrão da configuração.
+pub fn pedido_para_servicos(conn: &Connection, servicos_ids: &[i32], agora: NaiveDateTime) -> Result<PedidoDisponibilidade> {
+    let padrao = &crate::config::atual().agenda;
+    let mut duracao_min = 0i64;
+    let mut buffer_min = if servicos_ids.is_empty() { padrao.buffer_min } else { 0 };
+    let mut alinhamento_min: Option<i64> = None;
+    let mut etapas: Vec<Etapa> = Vec::new();
+    for id in servicos_ids {
+        let servico = buscar_servico_por_id(conn, *id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+        duracao_min += servico.duracao_min as i64;
+        etapas.extend(etapas_do_servico(Some(&listar_fases_servico(conn, *id)?), servico.duracao_min as i64));
+        // A limpeza acontece uma vez, no fim: vale a maior entre os serviços.
+        buffer_min = buffer_min.max(servico.buffer_min.map(i64::from).unwrap_or(padrao.buffer_min));
+        if let Some(passo) = servico.alinhamento_min {
+            alinhamento_min = scheduling::combinar_alinhamentos(alinhamento_min, passo as i64);
+        }
+    }
+    if duracao_min <= 0 {
+        duracao_min = padrao.duracao_padrao_min;
+    }
+    let regras = buscar_regras_agenda(conn)?;
+    // Só vale a pena detalhar os trechos quando algum serviço tem pausa.
+    let trechos_ocupados = if etapas.iter().any(|(_, ocupa)| !ocupa) {
+        scheduling::trechos_ocupados(&etapas, buffer_min)
+    } else {
+        Vec::new()
+    };
+    Ok(PedidoDisponibilidade {
+        pedido: Pedido {
+            duracao_min,
+            buffer_min,
+            granularidade_min: padrao.granularidade_min,
+            alinhamento_min,
+            a_partir_de: Some(agora + chrono::Duration::minutes(regras.antecedencia_minima_min)),
+            ate: Some(agora + chrono::Duration::days(regras.horizonte_dias)),
+            trechos_ocupados,
+        },
+        buffers_por_servico: true,
+        recursos_ids: crate::recursos::recursos_dos_servicos(conn, servicos_ids)?,
+        ignorar: None,
+    })
+}
+
+/// Entradas do motor para um intervalo de dias, lidas do banco uma única vez.
+struct AgendaCarregada {
+    /// Janelas de `work_windows` por dia da semana (0 = segunda).
+    janelas: HashMap<u32, Vec<(chrono::NaiveTime, chrono::NaiveTime)>>,
+    /// Usada nos dias sem janela cadastrada.
+    janela_padrao: (chrono::NaiveTime, chrono::NaiveTime),
+    /// Tudo o que ocupa a agenda (as três listas abaixo juntas), ordenado pelo início.
+    ocupados: Vec<Intervalo>,
+    /// Trechos em que cada agendamento ocupa o profissional (folga incluída).
+    agendamentos: Vec<(i32, Intervalo)>,
+    /// Eventos importados de calendários externos.
+    bloqueios: Vec<Intervalo>,
+    /// `(agendamento, recurso, intervalo)` dos recursos exigidos pelo pedido.
+    recursos: Vec<(i32, i32, Intervalo)>,
+}
+
+fn carregar_agenda(conn: &Connection, inicio: chrono::NaiveDate, fim: chrono::NaiveDate, pedido: &PedidoDisponibilidade) -> Result<AgendaCarregada> {
+    use chrono::NaiveTime;
+
+    let mut janelas: HashMap<u32, Vec<(NaiveTime, NaiveTime)>> = HashMap::new();
+    for (_, weekday, s, e) in listar_work_windows(conn)? {
+        if let (Ok(st), Ok(en)) = (NaiveTime::parse_from_str(&s, "%H:%M"), NaiveTime::parse_from_str(&e, "%H:%M")) {
+            janelas.entry(weekday as u32).or_default().push((st, en));
+        }
+    }
+    // fallback to default window (agenda.inicio_padrao/fim_padrao, validados na carga da configuração)
+    let janela_padrao = crate::config::atual().agenda.janela_padrao()
+        .unwrap_or((NaiveTime::from_hms_opt(8, 0, 0).unwrap(), NaiveTime::from_hms_opt(18, 0, 0).unwrap()));
+
+    // Agendamentos do período com os seus serviços, numa só consulta.
+    let inicio_ts = inicio.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp();
+    let fim_ts = fim.and_hms_opt(23, 59, 59).unwrap().and_utc().timestamp();
+    let mut stmt = conn.prepare(
+        "SELECT a.id, a.data_hora, s.id, s.duracao_min, s.buffer_min
+         FROM agendamentos a
+         LEFT JOIN agendamento_servicos x ON x.agendamento_id = a.id
+         LEFT JOIN servicos s ON s.id = x.servico_id
+         WHERE a.data_hora BETWEEN ?1 AND ?2 AND a.concluido = 0 AND a.status != 'cancelado'
+         ORDER BY a.id, x.rowid"
+    )?;
+    // Começa um dia antes, como `recursos::ocupacao_dos_recursos`: um atendimento da véspera
+    // pode passar da meia-noite.
+    let linhas = stmt.query_map(params![inicio_ts - 86_400, fim_ts], |row| {
+        Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?, row.get::<_, Option<i32>>(2)?, row.get::<_, Option<i64>>(3)?, row.get::<_, Option<i64>>(4)?))
+    })?;
+
+    let buffer_padrao = crate::config::atual().agenda.buffer_min;
+    let fases = fases_por_servico(conn)?;
+    // (id, início, etapas em sequência, maior folga)
+    let mut agendamentos: Vec<(i32, i64, Vec<Etapa>, i64)> = Vec::new();
+    for linha in linhas {
+        let (id, ts, servico_id, duracao, buffer) = linha?;
+        if Some(id) == pedido.ignorar {
+            continue;
+        }
+        if agendamentos.last().map(|a| a.0) != Some(id) {
+            agendamentos.push((id, ts, Vec::new(), 0));
+        }
+        let atual = agendamentos.last_mut().unwrap();
+        if let (Some(servico_id), Some(duracao)) = (servico_id, duracao) {
+            atual.2.extend(etapas_do_servico(fases.get(&servico_id), duracao));
+            atual.3 = atual.3.max(buffer.unwrap_or(buffer_padrao));
+        }
+    }
+
+    // Só os trechos em que o profissional está ocupado: as pausas das fases ficam livres.
+    let mut por_agendamento: Vec<(i32, Intervalo)> = Vec::new();
+    for (id, ts, etapas, buffer_min) in agendamentos {
+        let com_servico = etapas.iter().any(|(minutos, _)| *minutos > 0);
+        let etapas = if com_servico { etapas } else { vec![(30, true)] };
+        let buffer_min = match (pedido.buffers_por_servico, com_servico) {
+            (false, _) => pedido.pedido.buffer_min,
+            (true, false) => buffer_padrao,
+            (true, true) => buffer_min,
+        };
+        let trechos = scheduling::trechos_ocupados(&etapas, buffer_min);
+        por_agendamento.extend(scheduling::ocupacao_em_trechos(timestamp_para_naive(ts), &trechos).into_iter().map(|t| (id, t)));
+    }
+    // Eventos importados de calendários externos (.ics) ocupam o horário sem buffer.
+    let bloqueios: Vec<Intervalo> = crate::calendario::bloqueios_no_intervalo(conn, inicio_ts, fim_ts)?
+        .into_iter()
+        .map(|(s, e)| (timestamp_para_naive(s), timestamp_para_naive(e)))
+        .collect();
+    // Recursos (lavatório etc.) presos por qualquer agendamento da agenda (única).
+    let mut recursos = crate::recursos::ocupacao_dos_recursos(conn, &pedido.recursos_ids, inicio_ts, fim_ts)?;
+    recursos.retain(|(id, _, _)| Some(*id) != pedido.ignorar);
+
+    let mut ocupados: Vec<Intervalo> = por_agendamento.iter().map(|(_, t)| *t)
+        .chain(bloqueios.iter().copied())
+        .chain(recursos.iter().map(|(_, _, t)| *t))
+        .collect();
+    ocupados.sort_unstable();
+
+    Ok(AgendaCarregada { janelas, janela_padrao, ocupados, agendamentos: por_agendamento, bloqueios, recursos })
+}
+
+/// Janelas de trabalho do dia em horários absolutos (a padrão quando o dia não tem nenhuma).
+fn janelas_do_dia(agenda: &AgendaCarregada, date: chrono::NaiveDate) -> Vec<Intervalo> {
+    let padrao = [agenda.janela_padrao];
+    agenda.janelas
+        .get(&date.weekday().num_days_from_monday())
+        .map(Vec::as_slice)
+        .unwrap_or(&padrao)
+        .iter()
+        .map(|(s, e)| (date.and_time(*s), date.and_time(*e)))
+        .collect()
+}
+
+fn livres_no_dia(agenda: &AgendaCarregada, date: chrono::NaiveDate, pedido: &PedidoDisponibilidade) -> Vec<NaiveDateTime> {
+    let janelas = janelas_do_dia(agenda, date);
+    // Só o que cruza o dia (com folga de um dia para atendimentos que passam da meia-noite).
+    let (dia_inicio, dia_fim) = (date.and_hms_opt(0, 0, 0).unwrap(), date.and_hms_opt(0, 0, 0).unwrap() + chrono::Duration::days(2));
+    let ocupados: Vec<Intervalo> = agenda.ocupados.iter().copied().filter(|(s, e)| *s < dia_fim && *e > dia_inicio).collect();
+    scheduling::horarios_livres(&janelas, &ocupados, &pedido.pedido)
+}
+
+fn conflito(tipo: &str, mensagem: String, agendamento_id: Option<i32>, intervalo: Option<Intervalo>) -> ConflitoHorario {
+    ConflitoHorario {
+        tipo: tipo.to_string(),
+        mensagem,
+        agendamento_id,
+        inicio: intervalo.map(|i| i.0),
+        fim: intervalo.map(|i| i.1),
+    }
+}
+
+/// Por que o agendamento não cabe em `data_hora` (vazio = cabe). O próprio agendamento
+/// (`ignorar`) não conta. Ao contrário da disponibilidade, não aplica granularidade,
+/// antecedência mínima nem horizonte: quem remarca pela agenda pode escolher qualquer minuto.
+pub fn conflitos_no_horario(conn: &Connection, data_hora: NaiveDateTime, servicos_ids: &[i32], ignorar: Option<i32>, agora: NaiveDateTime) -> Result<Vec<ConflitoHorario>> {
+    let mut conflitos = Vec::new();
+    if data_hora <= agora {
+        conflitos.push(conflito("passado", "Não é possível agendar no passado.".to_string(), None, None));
+    }
+    let pedido = pedido_para_servicos(conn, servicos_ids, agora)?;
+    let duracao = chrono::Duration::minutes(pedido.pedido.duracao_min);
+    let dia = data_hora.date();
+    let agenda = carregar_agenda(conn, dia, dia, &pedido)?;
+
+    if !janelas_do_dia(&agenda, dia).iter().any(|(s, e)| *s <= data_hora && data_hora + duracao <= *e) {
+        conflitos.push(conflito("fora_do_expediente", "O atendimento não cabe no horário de trabalho do dia.".to_string(), None, None));
+    }
+    if let Some(passo) = pedido.pedido.alinhamento_min.filter(|p| !scheduling::alinhado(data_hora, *p)) {
+        conflitos.push(conflito("alinhamento", format!("Estes serviços só começam em múltiplos de {} minutos.", passo), None, None));
+    }
+
+    let precisa = scheduling::ocupacao_em_trechos(data_hora, &pedido.pedido.trechos());
+    let cruza = |intervalo: &Intervalo| precisa.iter().any(|t| scheduling::cruzam(*t, *intervalo));
+
+    let mut vistos = Vec::new();
+    for (id, intervalo) in &agenda.agendamentos {
+        if Some(*id) != ignorar && !vistos.contains(id) && cruza(intervalo) {
+            vistos.push(*id);
+            conflitos.push(conflito("agendamento", format!("Choca com o agendamento {}.", id), Some(*id), Some(*intervalo)));
+        }
+    }
+    for intervalo in agenda.bloqueios.iter().filter(|b| cruza(b)) {
+        conflitos.push(conflito("bloqueio", "Horário bloqueado no calendário.".to_string(), None, Some(*intervalo)));
+    }
+    // O recurso fica preso do início ao fim do atendimento inteiro, pausas incluídas.
+    let inteiro = scheduling::ocupacao(data_hora, pedido.pedido.duracao_min, pedido.pedido.buffer_min);
+    for (id, recurso_id, intervalo) in &agenda.recursos {
+        if Some(*id) != ignorar && scheduling::cruzam(inteiro, *intervalo) {
+            let nome = crate::recursos::buscar_recurso(conn, *recurso_id)?.map(|r| r.nome).unwrap_or_default();
+            conflitos.push(conflito("recurso", format!("O recurso '{}' está em uso pelo agendamento {}.", nome, id), Some(*id), Some(*intervalo)));
+        }
+    }
+    Ok(conflitos)
+}
+
+fn formatar_slot(slot: &NaiveDateTime) -> String {
+    slot.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Horários livres do dia (`YYYY-MM-DDTHH:MM:SS`) dentro das janelas de trabalho.
+pub fn horarios_disponiveis(conn: &Connection, date: chrono::NaiveDate, pedido: &PedidoDisponibilidade) -> Result<Vec<String>> {
+    let agenda = carregar_agenda(conn, date, date, pedido)?;
+    Ok(livres_no_dia(&agenda, date, pedido).iter().map(formatar_slot).collect())
+}
+
+/// Horários livres de cada dia de `inicio` a `fim` (inclusive), com uma única leitura da agenda.
+pub fn disponibilidade_no_periodo(conn: &Connection, inicio: chrono::NaiveDate, fim: chrono::NaiveDate, pedido: &PedidoDisponibilidade) -> Result<Vec<(chrono::NaiveDate, Vec<String>)>> {
+    let agenda = carregar_agenda(conn, inicio, fim, pedido)?;
+    Ok(inicio.iter_days()
+        .take_while(|dia| *dia <= fim)
+        .map(|dia| (dia, livres_no_dia(&agenda, dia, pedido).iter().map(formatar_slot).collect()))
+        .collect())
+}
+
+/// Os `quantidade` primeiros horários livres a partir de `inicio`, olhando no máximo `dias` dias.
+pub fn proximos_horarios(conn: &Connection, inicio: chrono::NaiveDate, dias: i64, quantidade: usize, pedido: &PedidoDisponibilidade) -> Result<Vec<String>> {
+    let fim = inicio + chrono::Duration::days(dias.max(1) - 1);
+    let agenda = carregar_agenda(conn, inicio, fim, pedido)?;
+    Ok(inicio.iter_days()
+        .take_while(|dia| *dia <= fim)
+        .flat_map(|dia| livres_no_dia(&agenda, dia, pedido))
+        .take(quantidade)
+        .map(|slot| formatar_slot(&slot))
+        .collect())
+}
+
+// =================================================================================
+// 5. AUDITORIA
+// =================================================================================
+
+pub const ACAO_CRIACAO: &str = "criacao";
+pub const ACAO_ATUALIZACAO: &str = "atualizacao";
+pub const ACAO_EXCLUSAO: &str = "exclusao";
+pub const ACAO_MUDANCA_STATUS: &str = "mudanca_status";
+pub const ACAO_ANONIMIZACAO: &str = "anonimizacao";
+pub const ACAO_EXPORTACAO: &str = "exportacao";
+pub const ACAO_IMPORTACAO: &str = "importacao";
+pub const ACAO_REMARCACAO: &str = "remarcacao";
+
+/// Momento atual no mesmo formato usado para `data_hora` (horário da barbearia gravado como timestamp).
+pub fn agora_ts() -> i64 {
+    crate::config::agora().and_utc().timestamp()
+}
+
+pub fn json_de<T: Serialize>(valor: &T) -> Option<JsonValue> {
+    serde_json::to_value(valor).ok().filter(|v| !v.is_null())
+}
+
+/// Cria o `audit_log`. Os gatilhos impedem exclusões e alterações, exceto a
+/// redação dos dados pessoais de um cliente anonimizado (LGPD).
+///
+/// Também registra na conexão as funções SQL `ator_atual()` e `agora_ts()`, usadas
+/// pelos gatilhos que auditam tabelas diretamente no banco (ver `GatilhoAuditoria`).
+pub fn criar_tabela_auditoria(conn: &Connection) -> Result<()> {
+    use rusqlite::functions::FunctionFlags;
+    conn.create_scalar_function("ator_atual", 0, FunctionFlags::SQLITE_UTF8, |_| Ok(crate::auditoria::ator_atual()))?;
+    conn.create_scalar_function("agora_ts", 0, FunctionFlags::SQLITE_UTF8, |_| Ok(agora_ts()))?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            entidade      TEXT NOT NULL,
+            entidade_id   INTEGER NOT NULL,
+            acao          TEXT NOT NULL,
+            antes         TEXT,
+            depois        TEXT,
+            ator          TEXT NOT NULL,
+            registrado_em INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_log_entidade ON audit_log (entidade, entidade_id);
+        CREATE TRIGGER IF NOT EXISTS audit_log_sem_exclusao BEFORE DELETE ON audit_log
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log é somente inserção');
+        END;
+        CREATE TRIGGER IF NOT EXISTS audit_log_sem_alteracao BEFORE UPDATE ON audit_log
+        WHEN OLD.entidade != 'cliente'
+            OR NEW.id != OLD.id OR NEW.entidade != OLD.entidade OR NEW.entidade_id != OLD.entidade_id
+            OR NEW.acao != OLD.acao OR NEW.ator != OLD.ator OR NEW.registrado_em != OLD.registrado_em
+        BEGIN
+            SELECT RAISE(ABORT, 'audit_log é somente inserção');
+        END;",
+    )
+}
+
+/// Gatilho que registra no `audit_log`, com o ator atual, as escritas feitas direto
+/// em SQL numa tabela (sem passar por `registrar_auditoria`). `id`, `antes` e
+/// `depois` são expressões SQL sobre `NEW`/`OLD`, em geral `json_object(...)` ou `NULL`.
+pub struct GatilhoAuditoria<'a> {
+    pub tabela: &'a str,
+    /// Ex.: "AFTER INSERT", "AFTER UPDATE OF status", com `WHEN ...` opcional.
+    pub evento: &'a str,
+    pub entidade: &'a str,
+    pub acao: &'a str,
+    pub id: &'a str,
+    pub antes: &'a str,
+    pub depois: &'a str,
+}
+
+impl GatilhoAuditoria<'_> {
+    pub fn criar(&self, conn: &Connection) -> Result<()> {
+        let GatilhoAuditoria { tabela, evento, entidade, acao, id, antes, depois } = self;
+        let (evento, condicao) = match evento.split_once(" WHEN ") {
+            Some((evento, condicao)) => (evento, format!("WHEN {}", condicao)),
+            None => (*evento, String::new()),
+        };
+        conn.execute_batch(&format!(
+            "CREATE TRIGGER IF NOT EXISTS auditoria_{tabela}_{acao} {evento} ON {tabela} {condicao}
+             BEGIN
+                 INSERT INTO audit_log (entidade, entidade_id, acao, antes, depois, ator, registrado_em)
+                 VALUES ('{entidade}', {id}, '{acao}', {antes}, {depois}, ator_atual(), agora_ts());
+             END;"
+        ))
+    }
+}
+
+/// Acrescenta uma entrada ao log de auditoria, com o ator da operação atual.
+pub fn registrar_auditoria(
+    conn: &Connection,
+    entidade: &str,
+    entidade_id: i64,
+    acao: &str,
+    antes: Option<JsonValue>,
+    depois: Option<JsonValue>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (entidade, entidade_id, acao, antes, depois, ator, registrado_em)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            entidade,
+            entidade_id,
+            acao,
+            antes.map(|v| v.to_string()),
+            depois.map(|v| v.to_string()),
+            crate::auditoria::ator_atual(),
+            agora_ts(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Substitui os dados pessoais guardados nas entradas de auditoria do cliente.
+fn redigir_auditoria_cliente(conn: &Connection, cliente_id: i32) -> Result<()> {
+    let redigido = serde_json::json!({ "id": cliente_id, "anonimizado": true }).to_string();
+    conn.execute(
+        "UPDATE audit_log
+         SET antes = CASE WHEN antes IS NULL THEN NULL ELSE ?1 END,
+             depois = CASE WHEN depois IS NULL THEN NULL ELSE ?1 END
+         WHERE entidade = 'cliente' AND entidade_id = ?2",
+        params![redigido, cliente_id],
+    )?;
+    Ok(())
+}
+
+/// Filtros de consulta do log de auditoria. Campos `None` não filtram.
+#[derive(Debug, Default)]
+pub struct FiltroAuditoria {
+    pub entidade: Option<String>,
+    pub entidade_id: Option<i64>,
+    pub inicio: Option<NaiveDateTime>,
+    pub fim: Option<NaiveDateTime>,
+    pub limite: i64,
+}
+
+/// Lista as entradas de auditoria mais recentes que atendem ao filtro.
+pub fn listar_auditoria(conn: &Connection, filtro: &FiltroAuditoria) -> Result<Vec<RegistroAuditoria>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, entidade, entidade_id, acao, antes, depois, ator, registrado_em
+         FROM audit_log
+         WHERE (?1 IS NULL OR entidade = ?1)
+           AND (?2 IS NULL OR entidade_id = ?2)
+           AND (?3 IS NULL OR registrado_em >= ?3)
+           AND (?4 IS NULL OR registrado_em <= ?4)
+         ORDER BY id DESC
+         LIMIT ?5",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            filtro.entidade,
+            filtro.entidade_id,
+            filtro.inicio.map(|d| d.and_utc().timestamp()),
+            filtro.fim.map(|d| d.and_utc().timestamp()),
+            filtro.limite,
+        ],
+        |row| {
+            let antes: Option<String> = row.get(4)?;
+            let depois: Option<String> = row.get(5)?;
+            Ok(RegistroAuditoria {
+                id: row.get(0)?,
+                entidade: row.get(1)?,
+                entidade_id: row.get(2)?,
+                acao: row.get(3)?,
+                antes: antes.and_then(|t| serde_json::from_str(&t).ok()),
+                depois: depois.and_then(|t| serde_json::from_str(&t).ok()),
+                ator: row.get(6)?,
+                registrado_em: timestamp_para_naive(row.get::<_, i64>(7)?),
+            })
+        },
+    )?;
+    rows.collect()
+}
+
 // =================================================================================
-// 5. TESTES DE CRUD (para validação interna)
+// 6. TESTES DE CRUD (para validação interna)
 // =================================================================================
 
 #[cfg(test)]
@@ -592,7 +1542,7 @@ mod tests {
         criar_tabelas(&conn).unwrap();
 
     // Insert a dummy service for the test
-    let servico_teste = Servico { id: None, nome: "Corte Diário".into(), preco: 40.0, duracao_min: 30 };
+    let servico_teste = Servico { id: None, nome: "Corte Diário".into(), preco: 40.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
     let servico_id = salvar_servico(&conn, &servico_teste).unwrap();
 
         // Insert a dummy client for the test
@@ -624,7 +1574,7 @@ mod tests {
         criar_tabelas(&conn).unwrap();
 
     // Insert a dummy service for the test
-    let servico_teste = Servico { id: None, nome: "Corte Teste".into(), preco: 50.0, duracao_min: 30 };
+    let servico_teste = Servico { id: None, nome: "Corte Teste".into(), preco: 50.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
     let servico_id = salvar_servico(&conn, &servico_teste).unwrap();
 
         let mut cliente = Cliente::new("Teste Listagem Cliente".into(), "5577777777777".into(), None);
@@ -660,226 +1610,293 @@ mod tests {
         let results = listar_clientes_search(&conn, "joa", 10).unwrap();
         assert!(results.len() >= 2, "Esperado encontrar pelo menos 2 clientes com 'joa'");
     }
-}
-pub fn salvar_servico(conn: &Connection, servico: &Servico) -> Result<i32> {
-    match servico.id {
-        Some(id) => {
-            // Update existing service
-            conn.execute(
-                "UPDATE servicos SET nome = ?1, preco = ?2, duracao_min = ?3 WHERE id = ?4",
-                params![servico.nome, servico.preco, servico.duracao_min, id],
-            )?;
-            Ok(id)
-        }
-        None => {
-            // Insert new service
-            conn.execute(
-                "INSERT INTO servicos (nome, preco, duracao_min) VALUES (?1, ?2, ?3)",
-                params![servico.nome, servico.preco, servico.duracao_min],
-            )?;
-            Ok(conn.last_insert_rowid() as i32)
-        }
+
+    /// Banco em memória com as tabelas criadas e chaves estrangeiras ligadas, como em produção.
+    fn conexao_teste() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        criar_tabelas(&conn).unwrap();
+        conn
     }
-}
 
-pub fn listar_servicos(conn: &Connection) -> Result<Vec<Servico>> {
-    let mut stmt = conn.prepare("SELECT id, nome, preco, duracao_min FROM servicos")?;
-    let servicos = stmt.query_map([], |row| {
-        Ok(Servico {
-            id: row.get(0)?,
-            nome: row.get(1)?,
-            preco: row.get(2)?,
-            duracao_min: row.get(3)?,
-        })
-    })?
-    .filter_map(Result::ok)
-    .collect();
-    Ok(servicos)
-}
+    #[test]
+    fn test_anonimizar_cliente_preserva_agendamentos() {
+        let conn = conexao_teste();
 
-/// Lista serviços filtrando por nome (LIKE) com limite.
-pub fn listar_servicos_search(conn: &Connection, search: &str, limit: i32) -> Result<Vec<Servico>> {
-    let pattern = format!("%{}%", search.replace('%', "\\%"));
-    let mut stmt = conn.prepare("SELECT id, nome, preco, duracao_min FROM servicos WHERE nome LIKE ?1 ORDER BY nome LIMIT ?2")?;
-    let servicos = stmt.query_map(params![pattern, limit], |row| {
-        Ok(Servico {
-            id: row.get(0)?,
-            nome: row.get(1)?,
-            preco: row.get(2)?,
-            duracao_min: row.get(3)?,
-        })
-    })?.filter_map(Result::ok).collect();
-    Ok(servicos)
-}
+        let servico = Servico { id: None, nome: "Barba".into(), preco: 25.0, duracao_min: 20, buffer_min: None, alinhamento_min: None };
+        let servico_id = salvar_servico(&conn, &servico).unwrap();
+        let mut cliente = Cliente::new("Ana Titular".into(), "5511912345678".into(), Some("ana@exemplo.com".into()));
+        let cliente_id = salvar_cliente(&conn, &mut cliente).unwrap();
 
-pub fn buscar_servico_por_id(conn: &Connection, id: i32) -> Result<Option<Servico>> {
-    let mut stmt = conn.prepare("SELECT id, nome, preco, duracao_min FROM servicos WHERE id = ?1")?;
-    let mut rows = stmt.query(params![id])?;
+        let horario = NaiveDateTime::parse_from_str("2025-12-03 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let agendamento = Agendamento {
+            id: None,
+            cliente_id,
+            servicos_ids: vec![servico_id],
+            data_hora: horario,
+            preco: 25.0,
+            concluido: true,
+        };
+        let agendamento_id = salvar_agendamento(&conn, &agendamento).unwrap();
+        conn.execute("UPDATE agendamentos SET nome_reserva = 'Ana Titular' WHERE id = ?1", params![agendamento_id]).unwrap();
 
-    if let Some(row) = rows.next()? {
-        Ok(Some(Servico {
-            id: row.get(0)?,
-            nome: row.get(1)?,
-            preco: row.get(2)?,
-            duracao_min: row.get(3)?,
-        }))
-    } else {
-        Ok(None)
+        // Lembrete e evento de webhook com nome e contato da titular.
+        conn.execute(
+            "INSERT INTO notificacoes (agendamento_id, tipo, data_hora_agendamento, canal, destino, assunto, mensagem,
+                                       status, enviar_em, proxima_tentativa, criado_em)
+             VALUES (?1, 'lembrete_24h', 0, 'email', 'ana@exemplo.com', 'Olá, Ana Titular', 'Ana Titular, até amanhã!', 'pendente', 0, 0, 0)",
+            params![agendamento_id],
+        )
+        .unwrap();
+        crate::webhooks::salvar_webhook(&conn, "http://127.0.0.1:9/hook", "segredo", &["*".to_string()]).unwrap();
+        cliente.id = Some(cliente_id);
+        crate::webhooks::emitir(&conn, crate::webhooks::EVENTO_CLIENTE_CRIADO, &cliente).unwrap();
+        crate::webhooks::emitir(&conn, crate::webhooks::EVENTO_AGENDAMENTO_CRIADO, &agendamento).unwrap();
+        assert_eq!(crate::webhooks::listar_entregas_cliente(&conn, cliente_id).unwrap().len(), 2);
+
+        assert!(anonimizar_cliente(&conn, cliente_id).unwrap());
+
+        let notificacoes = crate::notificacoes::listar_notificacoes_cliente(&conn, cliente_id).unwrap();
+        assert_eq!(notificacoes.len(), 1);
+        assert_eq!(notificacoes[0].status, crate::notificacoes::STATUS_CANCELADA);
+        let entregas = crate::webhooks::listar_entregas_cliente(&conn, cliente_id).unwrap();
+        assert_eq!(entregas.len(), 2, "Os eventos continuam no log, só sem os dados pessoais");
+        for texto in ["Ana", "ana@exemplo.com", "5511912345678"] {
+            assert!(!format!("{:?}{:?}", notificacoes, entregas).contains(texto), "{} ficou guardado", texto);
+        }
+
+        let anonimo = buscar_cliente_por_id(&conn, cliente_id).unwrap().unwrap();
+        assert_ne!(anonimo.nome, "Ana Titular");
+        assert!(anonimo.telefone.is_empty());
+        assert!(anonimo.email.is_none());
+        assert!(buscar_anonimizacao_cliente(&conn, cliente_id).unwrap().is_some());
+
+        let agendamentos = listar_agendamentos_por_cliente(&conn, cliente_id).unwrap();
+        assert_eq!(agendamentos.len(), 1, "O histórico de agendamentos deve ser mantido");
+        assert_eq!(agendamentos[0].preco, 25.0);
+        let nome_reserva: Option<String> = conn
+            .query_row("SELECT nome_reserva FROM agendamentos WHERE id = ?1", params![agendamento_id], |row| row.get(0))
+            .unwrap();
+        assert!(nome_reserva.is_none());
+
+        let solicitacoes = listar_solicitacoes_lgpd(&conn, cliente_id).unwrap();
+        assert_eq!(solicitacoes.len(), 1);
+        assert_eq!(solicitacoes[0].0, SOLICITACAO_ANONIMIZACAO);
+
+        assert!(!anonimizar_cliente(&conn, 999_999).unwrap());
+
+        // Se a solicitação não puder ser gravada, nada do cliente é alterado.
+        let mut outro = Cliente::new("Bruno Titular".into(), "5511900001111".into(), None);
+        let outro_id = salvar_cliente(&conn, &mut outro).unwrap();
+        conn.execute_batch("DROP TABLE solicitacoes_lgpd").unwrap();
+        assert!(anonimizar_cliente(&conn, outro_id).is_err());
+        let intacto = buscar_cliente_por_id(&conn, outro_id).unwrap().unwrap();
+        assert_eq!((intacto.nome.as_str(), intacto.telefone.as_str()), ("Bruno Titular", "5511900001111"));
+        assert!(buscar_anonimizacao_cliente(&conn, outro_id).unwrap().is_none());
     }
-}
 
-pub fn excluir_servico(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM servicos WHERE id = ?1", params![id])?;
-    Ok(())
-}
-/// Retorna os nomes dos serviços vinculados a um agendamento
-pub fn buscar_nomes_servicos(conn: &Connection, ids: &[i32]) -> Result<Vec<String>> {
-    let mut nomes = Vec::new();
-    for id in ids {
-        let nome: String = conn.query_row(
-            "SELECT nome FROM servicos WHERE id = ?1",
-            params![id],
-            |row| row.get(0),
-        )?;
-        nomes.push(nome);
+    #[test]
+    fn test_auditoria_registra_alteracoes() {
+        let conn = conexao_teste();
+
+        let servico = Servico { id: None, nome: "Corte Auditado".into(), preco: 35.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
+        let servico_id = salvar_servico(&conn, &servico).unwrap();
+        let mut cliente = Cliente::new("Carlos Auditoria".into(), "5511955554444".into(), None);
+        let cliente_id = salvar_cliente(&conn, &mut cliente).unwrap();
+
+        let horario = NaiveDateTime::parse_from_str("2025-12-04 15:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let agendamento = Agendamento {
+            id: None,
+            cliente_id,
+            servicos_ids: vec![servico_id],
+            data_hora: horario,
+            preco: 35.0,
+            concluido: false,
+        };
+        let agendamento_id = salvar_agendamento(&conn, &agendamento).unwrap();
+        atualizar_agendamento(&conn, agendamento_id, None, None, None, Some(true)).unwrap();
+        excluir_agendamento(&conn, agendamento_id).unwrap();
+
+        let filtro = FiltroAuditoria { entidade: Some("agendamento".into()), entidade_id: Some(agendamento_id as i64), limite: 10, ..Default::default() };
+        let registros = listar_auditoria(&conn, &filtro).unwrap();
+        let acoes: Vec<&str> = registros.iter().map(|r| r.acao.as_str()).collect();
+        assert_eq!(acoes, vec![ACAO_EXCLUSAO, ACAO_MUDANCA_STATUS, ACAO_CRIACAO]);
+        assert_eq!(registros[1].antes.as_ref().unwrap()["concluido"], false);
+        assert_eq!(registros[1].depois.as_ref().unwrap()["concluido"], true);
+        assert!(registros[0].depois.is_none());
+        assert_eq!(registros[0].ator, "sistema");
+
+        // O log é somente inserção.
+        assert!(conn.execute("DELETE FROM audit_log", []).is_err());
+        assert!(conn.execute("UPDATE audit_log SET acao = 'x' WHERE entidade = 'agendamento'", []).is_err());
+
+        // A anonimização redige os dados pessoais já registrados.
+        anonimizar_cliente(&conn, cliente_id).unwrap();
+        let filtro = FiltroAuditoria { entidade: Some("cliente".into()), entidade_id: Some(cliente_id as i64), limite: 10, ..Default::default() };
+        let registros = listar_auditoria(&conn, &filtro).unwrap();
+        assert_eq!(registros[0].acao, ACAO_ANONIMIZACAO);
+        assert!(registros.iter().all(|r| !format!("{:?}", r).contains("Carlos Auditoria")));
     }
-    Ok(nomes)
-}
 
-/// Retorna slots de disponibilidade para uma data específica.
-/// Parâmetros:
-/// - data_str: YYYY-MM-DD
-/// - duracao_min: duração total necessária em minutos
-/// - buffer_min: minutos de buffer antes/depois (padrão 15)
-/// - granularity_min: granularidade dos slots em minutos (padrão 15)
-pub fn calcular_disponibilidade(conn: &Connection, data_str: &str, duracao_min: i64, buffer_min: i64, granularity_min: i64) -> Result<Vec<String>> {
-    use chrono::{NaiveDate, NaiveTime, Duration as ChronoDuration};
+    #[test]
+    fn test_disponibilidade_com_regras_por_servico() {
+        let conn = conexao_teste();
+        let mut cliente = Cliente::new("Bruno Regras".into(), "5511777777777".into(), None);
+        let cliente_id = salvar_cliente(&conn, &mut cliente).unwrap();
+        let coloracao = salvar_servico(&conn, &Servico { id: None, nome: "Coloração".into(), preco: 90.0, duracao_min: 60, buffer_min: Some(20), alinhamento_min: Some(30) }).unwrap();
+        let barba = salvar_servico(&conn, &Servico { id: None, nome: "Barba".into(), preco: 25.0, duracao_min: 20, buffer_min: Some(5), alinhamento_min: None }).unwrap();
+        salvar_regras_agenda(&conn, &RegrasAgenda { antecedencia_minima_min: 60, horizonte_dias: 30 }).unwrap();
 
-    // Parse da data
-    let date = NaiveDate::parse_from_str(data_str, "%Y-%m-%d").map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e)))?;
-
-    // Use work windows configured in DB for this date
-    // Converter durações (precisamos dessas antes de manipular janelas)
-    let dur = ChronoDuration::minutes(duracao_min);
-    let buf = ChronoDuration::minutes(buffer_min);
-    let gran = ChronoDuration::minutes(granularity_min);
-
-    let windows = get_work_windows_for_date(conn, date)?;
-    if windows.is_empty() {
-        // fallback to default window
-        let work_start = NaiveTime::from_hms_opt(8, 0, 0).unwrap();
-        let work_end = NaiveTime::from_hms_opt(18, 0, 0).unwrap();
-        // single window
-        // We'll construct windows vec with this default
-        // but re-use existing logic below that iterates windows
-        // to keep code paths consistent.
-        // windows = vec![(work_start, work_end)]; // can't reassign immutable
-        let mut def = Vec::new();
-        def.push((work_start, work_end));
-    // use default window contained in `def` for local computation (no need to shadow `windows`)
-        // proceed
-        // compute inicio_ts/fim_ts for entire day range using work_start and work_end
-        let start_dt = date.and_time(work_start);
-        let end_dt = date.and_time(work_end);
-        let inicio_ts = start_dt.and_utc().timestamp();
-        let fim_ts = end_dt.and_utc().timestamp();
-        // fetch agendamentos within whole day window
-        let mut stmt = conn.prepare(
-            "SELECT data_hora, id FROM agendamentos WHERE data_hora BETWEEN ?1 AND ?2 AND concluido = 0"
-        )?;
-        let rows = stmt.query_map(params![inicio_ts, fim_ts], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i32>(1)?)))?;
-
-        let mut ocupados: Vec<(i64,i64)> = Vec::new();
-        for r in rows {
-            let (ts, _id) = r?;
-            let mut stmt_s = conn.prepare("SELECT s.duracao_min FROM servicos s JOIN agendamento_servicos a ON s.id = a.servico_id WHERE a.agendamento_id = ?1")?;
-            let dur_iter = stmt_s.query_map(params![_id], |row| row.get::<_, i32>(0))?;
-            let mut total_min = 0i64;
-            for dm in dur_iter { total_min += dm? as i64; }
-            if total_min == 0 { total_min = 30; }
-
-            let ag_start = timestamp_para_naive(ts).and_utc().timestamp();
-            let ag_end = (timestamp_para_naive(ts) + ChronoDuration::minutes(total_min)).and_utc().timestamp();
-            let occ_start = ag_start;
-            let occ_end = ag_end + buf.num_seconds();
-            ocupados.push((occ_start, occ_end));
-        }
+        // Coloração das 9h às 10h ocupa até 10h20 por causa da limpeza.
+        let dia = chrono::NaiveDate::from_ymd_opt(2030, 1, 7).unwrap();
+        let agendamento = Agendamento { id: None, cliente_id, servicos_ids: vec![coloracao], data_hora: dia.and_hms_opt(9, 0, 0).unwrap(), preco: 90.0, concluido: false };
+        salvar_agendamento(&conn, &agendamento).unwrap();
+        let agora = dia.and_hms_opt(8, 0, 0).unwrap();
+
+        let pedido = pedido_para_servicos(&conn, &[barba], agora).unwrap();
+        assert_eq!((pedido.pedido.duracao_min, pedido.pedido.buffer_min, pedido.pedido.alinhamento_min), (20, 5, None));
+        let slots = horarios_disponiveis(&conn, dia, &pedido).unwrap();
+        // Antecedência de 1h: nada antes das 9h; a primeira vaga vem depois da limpeza.
+        assert_eq!(slots.first().map(String::as_str), Some("2030-01-07T10:30:00"));
+
+        let pedido = pedido_para_servicos(&conn, &[coloracao, barba], agora).unwrap();
+        assert_eq!((pedido.pedido.duracao_min, pedido.pedido.buffer_min, pedido.pedido.alinhamento_min), (80, 20, Some(30)));
+        let slots = horarios_disponiveis(&conn, dia, &pedido).unwrap();
+        assert!(slots.iter().all(|s| s.ends_with(":00:00") || s.ends_with(":30:00")));
+        assert_eq!(slots.first().map(String::as_str), Some("2030-01-07T10:30:00"));
+
+        // Além do horizonte não há horários.
+        assert!(horarios_disponiveis(&conn, dia + chrono::Duration::days(31), &pedido).unwrap().is_empty());
+
+        // Sem serviços, o cálculo antigo usa a folga global para todos.
+        let antigos = calcular_disponibilidade(&conn, "2030-01-07", 20, 15, 15).unwrap();
+        assert!(antigos.contains(&"2030-01-07T08:00:00".to_string()));
+        assert!(antigos.contains(&"2030-01-07T10:15:00".to_string()));
+    }
 
-    // now iterate the single default window
-    let mut slots: Vec<String> = Vec::new();
-    let mut cursor = start_dt;
-    while cursor + dur <= end_dt {
-            let slot_start_ts = cursor.and_utc().timestamp();
-            let slot_end_ts = (cursor + dur).and_utc().timestamp();
-            let need_start = slot_start_ts;
-            let need_end = slot_end_ts + buf.num_seconds();
-            let mut conflict = false;
-            for (occ_s, occ_e) in &ocupados {
-                if !(need_end <= *occ_s || need_start >= *occ_e) {
-                    conflict = true;
-                    break;
-                }
-            }
-            if !conflict {
-                slots.push(cursor.format("%Y-%m-%dT%H:%M:%S").to_string());
-            }
-        cursor = cursor + gran;
-        }
-        return Ok(slots);
+    #[test]
+    fn test_pausa_das_fases_libera_o_profissional() {
+        let conn = conexao_teste();
+        let mut cliente = Cliente::new("Davi Fases".into(), "5511555555555".into(), None);
+        let cliente_id = salvar_cliente(&conn, &mut cliente).unwrap();
+        let luzes = salvar_servico(&conn, &Servico { id: None, nome: "Luzes".into(), preco: 150.0, duracao_min: 60, buffer_min: Some(0), alinhamento_min: None }).unwrap();
+        let corte = salvar_servico(&conn, &Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: Some(0), alinhamento_min: None }).unwrap();
+        let fases = [
+            FaseServico { nome: "Aplicação".into(), duracao_min: 30, ocupa_profissional: true },
+            FaseServico { nome: "Pausa".into(), duracao_min: 40, ocupa_profissional: false },
+            FaseServico { nome: "Finalização".into(), duracao_min: 20, ocupa_profissional: true },
+        ];
+        salvar_fases_servico(&conn, luzes, &fases).unwrap();
+        assert_eq!(buscar_servico_por_id(&conn, luzes).unwrap().unwrap().duracao_min, 90);
+        salvar_regras_agenda(&conn, &RegrasAgenda { antecedencia_minima_min: 0, horizonte_dias: 30 }).unwrap();
+
+        let dia = chrono::NaiveDate::from_ymd_opt(2030, 1, 7).unwrap();
+        let inicio = dia.and_hms_opt(9, 0, 0).unwrap();
+        let id = salvar_agendamento(&conn, &Agendamento { id: None, cliente_id, servicos_ids: vec![luzes, corte], data_hora: inicio, preco: 190.0, concluido: false }).unwrap();
+        let agora = dia.and_hms_opt(7, 0, 0).unwrap();
+
+        // Luzes + corte: ocupado 9:00–9:30 e 10:10–11:00. Um corte de 30 cabe na pausa.
+        let slots = horarios_disponiveis(&conn, dia, &pedido_para_servicos(&conn, &[corte], agora).unwrap()).unwrap();
+        assert!(slots.contains(&"2030-01-07T09:30:00".to_string()));
+        assert!(!slots.contains(&"2030-01-07T09:45:00".to_string()));
+        assert!(slots.contains(&"2030-01-07T11:00:00".to_string()));
+
+        let linha = linha_do_tempo(&conn, id).unwrap();
+        let resumo: Vec<(String, bool)> = linha.iter()
+            .map(|e| (format!("{} {}-{}", e.fase.as_deref().unwrap_or(&e.servico), e.inicio.format("%H:%M"), e.fim.format("%H:%M")), e.ocupa_profissional))
+            .collect();
+        assert_eq!(resumo, [
+            ("Aplicação 09:00-09:30".to_string(), true),
+            ("Pausa 09:30-10:10".to_string(), false),
+            ("Finalização 10:10-10:30".to_string(), true),
+            ("Corte 10:30-11:00".to_string(), true),
+        ]);
     }
-    // Converter agendamentos do dia para intervalos ocupados (com buffer após fim)
-    // Converter agendamentos do dia para intervalos ocupados (com buffer após fim)
-    // Buscaremos todos agendamentos do dia inteiro e depois os compararemos com cada window
-    let day_start = date.and_time(chrono::NaiveTime::from_hms_opt(0,0,0).unwrap());
-    let day_end = date.and_time(chrono::NaiveTime::from_hms_opt(23,59,59).unwrap());
-    let inicio_ts = day_start.and_utc().timestamp();
-    let fim_ts = day_end.and_utc().timestamp();
 
-    let mut stmt = conn.prepare(
-        "SELECT data_hora, id FROM agendamentos WHERE data_hora BETWEEN ?1 AND ?2 AND concluido = 0"
-    )?;
-    let rows = stmt.query_map(params![inicio_ts, fim_ts], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i32>(1)?)))?;
-
-    let mut ocupados: Vec<(i64,i64)> = Vec::new();
-    for r in rows {
-        let (ts, _id) = r?;
-        let mut stmt_s = conn.prepare("SELECT s.duracao_min FROM servicos s JOIN agendamento_servicos a ON s.id = a.servico_id WHERE a.agendamento_id = ?1")?;
-        let dur_iter = stmt_s.query_map(params![_id], |row| row.get::<_, i32>(0))?;
-        let mut total_min = 0i64;
-        for dm in dur_iter { total_min += dm? as i64; }
-        if total_min == 0 { total_min = 30; }
-
-        let ag_start = timestamp_para_naive(ts).and_utc().timestamp();
-        let ag_end = (timestamp_para_naive(ts) + ChronoDuration::minutes(total_min)).and_utc().timestamp();
-        let occ_start = ag_start;
-        let occ_end = ag_end + buf.num_seconds();
-        ocupados.push((occ_start, occ_end));
-    }
-
-    // Iterate each configured window and generate slots inside it
-    let mut slots: Vec<String> = Vec::new();
-    for (wstart, wend) in windows {
-        let start_dt = date.and_time(wstart);
-        let end_dt = date.and_time(wend);
-        let mut cursor = start_dt;
-        while cursor + dur <= end_dt {
-            let slot_start_ts = cursor.and_utc().timestamp();
-            let slot_end_ts = (cursor + dur).and_utc().timestamp();
-            let need_start = slot_start_ts;
-            let need_end = slot_end_ts + buf.num_seconds();
-            let mut conflict = false;
-            for (occ_s, occ_e) in &ocupados {
-                if !(need_end <= *occ_s || need_start >= *occ_e) {
-                    conflict = true;
-                    break;
-                }
-            }
-            if !conflict {
-                slots.push(cursor.format("%Y-%m-%dT%H:%M:%S").to_string());
-            }
-            cursor = cursor + gran;
-        }
+    #[test]
+    fn test_conflitos_ao_remarcar() {
+        let conn = conexao_teste();
+        let mut cliente = Cliente::new("Edu Remarca".into(), "5511444444444".into(), None);
+        let cliente_id = salvar_cliente(&conn, &mut cliente).unwrap();
+        let corte = salvar_servico(&conn, &Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: Some(10), alinhamento_min: None }).unwrap();
+        let dia = chrono::NaiveDate::from_ymd_opt(2030, 1, 7).unwrap();
+        let marcar = |h, m| salvar_agendamento(&conn, &Agendamento { id: None, cliente_id, servicos_ids: vec![corte], data_hora: dia.and_hms_opt(h, m, 0).unwrap(), preco: 40.0, concluido: false }).unwrap();
+        let primeiro = marcar(10, 0);
+        let segundo = marcar(14, 0);
+        let agora = dia.and_hms_opt(9, 0, 0).unwrap();
+        let tipos = |h, m| -> Vec<(String, Option<i32>)> {
+            conflitos_no_horario(&conn, dia.and_hms_opt(h, m, 0).unwrap(), &[corte], Some(segundo), agora).unwrap()
+                .into_iter().map(|c| (c.tipo, c.agendamento_id)).collect()
+        };
+
+        // Qualquer minuto serve, sem granularidade nem antecedência mínima.
+        assert!(tipos(10, 40).is_empty());
+        assert!(tipos(9, 20).is_empty());
+        // 9:30 + 30 + 10 de folga encosta no das 10h; 10:35 cai na folga dele.
+        assert_eq!(tipos(9, 31), [("agendamento".to_string(), Some(primeiro))]);
+        assert_eq!(tipos(10, 35), [("agendamento".to_string(), Some(primeiro))]);
+        // O próprio agendamento não conta.
+        assert!(tipos(14, 10).is_empty());
+        assert_eq!(tipos(17, 45), [("fora_do_expediente".to_string(), None)]);
+        assert_eq!(tipos(8, 30), [("passado".to_string(), None)]);
+
+        remarcar_agendamento(&conn, segundo, dia.and_hms_opt(14, 0, 0).unwrap(), dia.and_hms_opt(10, 40, 0).unwrap(), ORIGEM_REAGENDAR, Some("cliente atrasou")).unwrap();
+        let historico = listar_remarcacoes(&conn, segundo).unwrap();
+        assert_eq!(historico.len(), 1);
+        assert_eq!(historico[0].data_hora_anterior, dia.and_hms_opt(14, 0, 0).unwrap());
+        assert_eq!(historico[0].motivo.as_deref(), Some("cliente atrasou"));
+        assert_eq!(buscar_agendamento_por_id(&conn, segundo).unwrap().data_hora, dia.and_hms_opt(10, 40, 0).unwrap());
+        let filtro = FiltroAuditoria { entidade: Some("agendamento".into()), entidade_id: Some(segundo as i64), limite: 1, ..Default::default() };
+        let auditada = listar_auditoria(&conn, &filtro).unwrap().remove(0);
+        assert_eq!(auditada.acao, ACAO_REMARCACAO);
+        assert_eq!(auditada.depois.unwrap()["motivo"], "cliente atrasou");
+
+        // Sem histórico gravado, o horário também não muda.
+        conn.execute_batch("DROP TABLE agendamento_historico").unwrap();
+        assert!(remarcar_agendamento(&conn, segundo, dia.and_hms_opt(10, 40, 0).unwrap(), dia.and_hms_opt(15, 0, 0).unwrap(), ORIGEM_REAGENDAR, None).is_err());
+        assert_eq!(buscar_agendamento_por_id(&conn, segundo).unwrap().data_hora, dia.and_hms_opt(10, 40, 0).unwrap());
     }
 
-    Ok(slots)
+    #[test]
+    fn test_periodo_e_proximos_batem_com_o_calculo_por_dia() {
+        let conn = conexao_teste();
+        let mut cliente = Cliente::new("Caio Periodo".into(), "5511666666666".into(), None);
+        let cliente_id = salvar_cliente(&conn, &mut cliente).unwrap();
+        let corte = salvar_servico(&conn, &Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: Some(10), alinhamento_min: None }).unwrap();
+        salvar_work_window(&conn, 1, "09:00", "12:00").unwrap(); // terça
+        salvar_regras_agenda(&conn, &RegrasAgenda { antecedencia_minima_min: 0, horizonte_dias: 30 }).unwrap();
+
+        let segunda = chrono::NaiveDate::from_ymd_opt(2030, 1, 7).unwrap();
+        for (dia, hora) in [(0, 8), (0, 9), (1, 9), (2, 17)] {
+            let data_hora = (segunda + chrono::Duration::days(dia)).and_hms_opt(hora, 0, 0).unwrap();
+            salvar_agendamento(&conn, &Agendamento { id: None, cliente_id, servicos_ids: vec![corte], data_hora, preco: 40.0, concluido: false }).unwrap();
+        }
+        let agora = segunda.and_hms_opt(7, 0, 0).unwrap();
+        let pedido = pedido_para_servicos(&conn, &[corte], agora).unwrap();
+
+        let fim = segunda + chrono::Duration::days(6);
+        let periodo = disponibilidade_no_periodo(&conn, segunda, fim, &pedido).unwrap();
+        assert_eq!(periodo.len(), 7);
+        for (dia, slots) in &periodo {
+            assert_eq!(slots, &horarios_disponiveis(&conn, *dia, &pedido).unwrap(), "dia {}", dia);
+        }
+        // Terça usa a janela cadastrada: 9h ocupado até 9h40, depois de 9h45 até 11h30.
+        assert_eq!(periodo[1].1.first().map(String::as_str), Some("2030-01-08T09:45:00"));
+        assert_eq!(periodo[1].1.last().map(String::as_str), Some("2030-01-08T11:30:00"));
+
+        // Segunda: 8h e 9h ocupados (com a limpeza até 9h40); o primeiro livre é 9h45.
+        let proximos = proximos_horarios(&conn, segunda, 31, 3, &pedido).unwrap();
+        assert_eq!(proximos, vec!["2030-01-07T09:45:00", "2030-01-07T10:00:00", "2030-01-07T10:15:00"]);
+        let todos_da_segunda = periodo[0].1.len();
+        let proximos = proximos_horarios(&conn, segunda, 31, todos_da_segunda + 1, &pedido).unwrap();
+        assert_eq!(proximos.last().map(String::as_str), Some("2030-01-08T09:45:00"));
+
+        // Um atendimento que começa na quarta e passa da meia-noite ocupa a quinta até as 9h.
+        let longo = salvar_servico(&conn, &Servico { id: None, nome: "Noite".into(), preco: 40.0, duracao_min: 16 * 60, buffer_min: Some(0), alinhamento_min: None }).unwrap();
+        let quarta = segunda + chrono::Duration::days(2);
+        salvar_agendamento(&conn, &Agendamento { id: None, cliente_id, servicos_ids: vec![longo], data_hora: quarta.and_hms_opt(17, 30, 0).unwrap(), preco: 40.0, concluido: false }).unwrap();
+        let quinta = quarta + chrono::Duration::days(1);
+        let slots = horarios_disponiveis(&conn, quinta, &pedido).unwrap();
+        assert!(slots.first().is_some_and(|s| s.as_str() >= "2030-01-10T09:30:00"), "{:?}", slots.first());
+        assert_eq!(disponibilidade_no_periodo(&conn, quinta, quinta, &pedido).unwrap()[0].1, slots);
+    }
 }
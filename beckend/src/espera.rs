@@ -0,0 +1,438 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime, Timelike};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::config;
+use crate::agendamentos;
+use crate::db;
+use crate::models::{Agendamento, EntradaEspera};
+use crate::webhooks;
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// LISTA DE ESPERA
+// Quem não achou horário deixa o pedido (serviços, datas, período do dia). Quando um
+// agendamento é cancelado ou excluído, o horário liberado é repartido, na ordem de
+// chegada, entre os pedidos que cabem nele; esses viram o evento `lista_espera.vaga`.
+// =================================================================================
+
+pub const STATUS_AGUARDANDO: &str = "aguardando";
+pub const STATUS_AVISADO: &str = "avisado";
+
+pub const PERIODOS: [&str; 4] = ["qualquer", "manha", "tarde", "noite"];
+
+pub fn criar_tabela_espera(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS lista_espera (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            cliente_id   INTEGER NOT NULL,
+            servicos_ids TEXT NOT NULL,  -- lista separada por vírgulas
+            duracao_min  INTEGER NOT NULL,
+            data_inicio  TEXT NOT NULL,  -- YYYY-MM-DD
+            data_fim     TEXT NOT NULL,
+            periodo      TEXT NOT NULL,
+            status       TEXT NOT NULL,
+            criado_em    INTEGER NOT NULL,
+            avisado_em   INTEGER,
+            vaga_inicio  INTEGER,
+            vaga_fim     INTEGER,
+            FOREIGN KEY (cliente_id) REFERENCES clientes(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_lista_espera_fila ON lista_espera (status, data_inicio, data_fim)",
+        [],
+    )?;
+
+    // Auditoria por gatilhos: cobre a entrada no pedido, a exclusão e o aviso de vaga.
+    let retrato = |r: &str| {
+        format!(
+            "json_object('id', {r}.id, 'cliente_id', {r}.cliente_id, 'servicos_ids', {r}.servicos_ids,
+                         'duracao_min', {r}.duracao_min, 'data_inicio', {r}.data_inicio, 'data_fim', {r}.data_fim,
+                         'periodo', {r}.periodo, 'status', {r}.status, 'avisado_em', {r}.avisado_em,
+                         'vaga_inicio', {r}.vaga_inicio, 'vaga_fim', {r}.vaga_fim)"
+        )
+    };
+    let (novo, antigo) = (retrato("NEW"), retrato("OLD"));
+    let gatilhos = [
+        ("AFTER INSERT", db::ACAO_CRIACAO, "NEW.id", "NULL", novo.as_str()),
+        ("AFTER DELETE", db::ACAO_EXCLUSAO, "OLD.id", antigo.as_str(), "NULL"),
+        ("AFTER UPDATE OF status WHEN OLD.status IS NOT NEW.status", db::ACAO_MUDANCA_STATUS, "NEW.id", antigo.as_str(), novo.as_str()),
+    ];
+    for (evento, acao, id, antes, depois) in gatilhos {
+        db::GatilhoAuditoria { tabela: "lista_espera", evento, entidade: "lista_espera", acao, id, antes, depois }.criar(conn)?;
+    }
+    Ok(())
+}
+
+/// O horário cai no período pedido? Manhã até 12h, tarde até 18h, noite depois disso.
+pub fn no_periodo(periodo: &str, inicio: NaiveDateTime) -> bool {
+    let hora = inicio.hour();
+    match periodo {
+        "manha" => hora < 12,
+        "tarde" => (12..18).contains(&hora),
+        "noite" => hora >= 18,
+        _ => true,
+    }
+}
+
+/// Soma das durações dos serviços; sem serviços conhecidos, a duração padrão da agenda.
+pub fn duracao_servicos(conn: &Connection, ids: &[i32]) -> Result<i64> {
+    let mut total = 0;
+    for id in ids {
+        if let Some(servico) = db::buscar_servico_por_id(conn, *id)? {
+            total += servico.duracao_min as i64;
+        }
+    }
+    Ok(if total > 0 { total } else { config::atual().agenda.duracao_padrao_min })
+}
+
+fn linha_para_entrada(row: &Row) -> Result<EntradaEspera> {
+    let servicos: String = row.get(2)?;
+    let data = |i: usize| -> Result<NaiveDate> {
+        let texto: String = row.get(i)?;
+        NaiveDate::parse_from_str(&texto, "%Y-%m-%d")
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e)))
+    };
+    Ok(EntradaEspera {
+        id: row.get(0)?,
+        cliente_id: row.get(1)?,
+        servicos_ids: servicos.split(',').filter_map(|s| s.parse().ok()).collect(),
+        duracao_min: row.get(3)?,
+        data_inicio: data(4)?,
+        data_fim: data(5)?,
+        periodo: row.get(6)?,
+        status: row.get(7)?,
+        criado_em: db::timestamp_para_naive(row.get(8)?),
+        avisado_em: row.get::<_, Option<i64>>(9)?.map(db::timestamp_para_naive),
+        vaga_inicio: row.get::<_, Option<i64>>(10)?.map(db::timestamp_para_naive),
+        vaga_fim: row.get::<_, Option<i64>>(11)?.map(db::timestamp_para_naive),
+    })
+}
+
+const COLUNAS: &str = "id, cliente_id, servicos_ids, duracao_min, data_inicio, data_fim, periodo, status, criado_em, avisado_em, vaga_inicio, vaga_fim";
+
+pub fn buscar_entrada(conn: &Connection, id: i64) -> Result<Option<EntradaEspera>> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM lista_espera WHERE id = ?1", COLUNAS))?;
+    let mut linhas = stmt.query_map(params![id], linha_para_entrada)?;
+    linhas.next().transpose()
+}
+
+/// Pedidos em ordem de chegada, opcionalmente filtrados por status e cliente.
+pub fn listar_entradas(conn: &Connection, status: Option<&str>, cliente_id: Option<i32>) -> Result<Vec<EntradaEspera>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM lista_espera
+         WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR cliente_id = ?2)
+         ORDER BY criado_em, id",
+        COLUNAS
+    ))?;
+    let linhas = stmt.query_map(params![status, cliente_id], linha_para_entrada)?;
+    linhas.collect()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NovaEntrada {
+    pub cliente_id: i32,
+    pub servicos_ids: Vec<i32>,
+    /// YYYY-MM-DD
+    pub data_inicio: String,
+    /// YYYY-MM-DD; se omitida, só o dia de `data_inicio`.
+    pub data_fim: Option<String>,
+    pub periodo: Option<String>,
+    /// Só para recusar: a agenda é única e não há profissionais cadastrados.
+    pub profissional: Option<String>,
+}
+
+type ErroApi = (StatusCode, String);
+
+fn erro_banco(contexto: &str, e: rusqlite::Error) -> ErroApi {
+    eprintln!("Erro ao {}: {}", contexto, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao {}.", contexto))
+}
+
+fn invalido(mensagem: String) -> ErroApi {
+    (StatusCode::BAD_REQUEST, mensagem)
+}
+
+/// Valida e grava o pedido.
+pub fn salvar_entrada(conn: &Connection, nova: &NovaEntrada) -> std::result::Result<EntradaEspera, ErroApi> {
+    let ler_data = |texto: &str| {
+        NaiveDate::parse_from_str(texto.trim(), "%Y-%m-%d").map_err(|_| invalido(format!("Data inválida '{}'. Use AAAA-MM-DD.", texto)))
+    };
+    let data_inicio = ler_data(&nova.data_inicio)?;
+    let data_fim = match &nova.data_fim {
+        Some(d) => ler_data(d)?,
+        None => data_inicio,
+    };
+    if data_fim < data_inicio {
+        return Err(invalido("data_fim não pode ser anterior a data_inicio.".to_string()));
+    }
+    if data_fim < config::agora().date() {
+        return Err(invalido("O intervalo pedido já passou.".to_string()));
+    }
+    agendamentos::recusar_profissional("profissional", &nova.profissional)?;
+    let periodo = nova.periodo.as_deref().map(str::trim).filter(|p| !p.is_empty()).unwrap_or("qualquer");
+    if !PERIODOS.contains(&periodo) {
+        return Err(invalido(format!("Período inválido '{}'. Use: {}.", periodo, PERIODOS.join(", "))));
+    }
+    let banco = |e| erro_banco("salvar pedido na lista de espera", e);
+    if db::buscar_cliente_por_id(conn, nova.cliente_id).map_err(banco)?.is_none() {
+        return Err((StatusCode::NOT_FOUND, format!("Cliente {} não encontrado.", nova.cliente_id)));
+    }
+    if nova.servicos_ids.is_empty() {
+        return Err(invalido("Informe ao menos um serviço.".to_string()));
+    }
+    for id in &nova.servicos_ids {
+        if db::buscar_servico_por_id(conn, *id).map_err(banco)?.is_none() {
+            return Err(invalido(format!("Serviço {} não encontrado.", id)));
+        }
+    }
+    let duracao_min = duracao_servicos(conn, &nova.servicos_ids).map_err(banco)?;
+    let ids: Vec<String> = nova.servicos_ids.iter().map(|i| i.to_string()).collect();
+
+    conn.execute(
+        "INSERT INTO lista_espera (cliente_id, servicos_ids, duracao_min, data_inicio, data_fim, periodo, status, criado_em)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            nova.cliente_id,
+            ids.join(","),
+            duracao_min,
+            data_inicio.format("%Y-%m-%d").to_string(),
+            data_fim.format("%Y-%m-%d").to_string(),
+            periodo,
+            STATUS_AGUARDANDO,
+            db::agora_ts(),
+        ],
+    )
+    .map_err(banco)?;
+    let id = conn.last_insert_rowid();
+    buscar_entrada(conn, id).and_then(|e| e.ok_or(rusqlite::Error::QueryReturnedNoRows)).map_err(banco)
+}
+
+pub fn excluir_entrada(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM lista_espera WHERE id = ?1", params![id])? > 0)
+}
+
+/// Marca como avisados, em ordem de chegada, os pedidos que cabem em `[inicio, fim)`.
+/// A vaga é dividida entre eles: cada avisado fica com um trecho (`vaga_inicio`..`vaga_fim`)
+/// logo após o do anterior, e quem não cabe no que sobrou continua aguardando.
+pub fn avisar_vaga(conn: &Connection, inicio: NaiveDateTime, fim: NaiveDateTime, agora: NaiveDateTime) -> Result<Vec<EntradaEspera>> {
+    if inicio <= agora || fim <= inicio {
+        return Ok(Vec::new());
+    }
+    let livre_min = (fim - inicio).num_minutes();
+    let dia = inicio.date().format("%Y-%m-%d").to_string();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM lista_espera
+         WHERE status = ?1 AND data_inicio <= ?2 AND data_fim >= ?2 AND duracao_min <= ?3
+         ORDER BY criado_em, id",
+        COLUNAS
+    ))?;
+    let candidatas: Vec<EntradaEspera> = stmt
+        .query_map(params![STATUS_AGUARDANDO, dia, livre_min], linha_para_entrada)?
+        .collect::<Result<_>>()?;
+
+    let agora_ts = agora.and_utc().timestamp();
+    let mut livre_desde = inicio;
+    let mut avisadas = Vec::new();
+    for mut entrada in candidatas {
+        let trecho_fim = livre_desde + Duration::minutes(entrada.duracao_min);
+        if trecho_fim > fim || !no_periodo(&entrada.periodo, livre_desde) {
+            continue;
+        }
+        conn.execute(
+            "UPDATE lista_espera SET status = ?1, avisado_em = ?2, vaga_inicio = ?3, vaga_fim = ?4 WHERE id = ?5",
+            params![STATUS_AVISADO, agora_ts, livre_desde.and_utc().timestamp(), trecho_fim.and_utc().timestamp(), entrada.id],
+        )?;
+        entrada.status = STATUS_AVISADO.to_string();
+        entrada.avisado_em = Some(agora);
+        entrada.vaga_inicio = Some(livre_desde);
+        entrada.vaga_fim = Some(trecho_fim);
+        avisadas.push(entrada);
+        livre_desde = trecho_fim;
+        if livre_desde >= fim {
+            break;
+        }
+    }
+    Ok(avisadas)
+}
+
+/// Chamado quando um agendamento é cancelado ou excluído: avisa a lista de espera
+/// pelo webhook. Falhas só são registradas no log, como em `webhooks::emitir_ou_logar`.
+pub fn vaga_liberada(conn: &Connection, agendamento: &Agendamento) -> usize {
+    if agendamento.concluido {
+        return 0;
+    }
+    let resultado = duracao_servicos(conn, &agendamento.servicos_ids).and_then(|duracao| {
+        let fim = agendamento.data_hora + Duration::minutes(duracao);
+        avisar_vaga(conn, agendamento.data_hora, fim, config::agora())
+    });
+    match resultado {
+        Ok(avisadas) => {
+            for entrada in &avisadas {
+                webhooks::emitir_ou_logar(conn, webhooks::EVENTO_LISTA_ESPERA_VAGA, entrada);
+            }
+            avisadas.len()
+        }
+        Err(e) => {
+            eprintln!("[Lista de espera] Erro ao avisar vaga do agendamento {:?}: {}", agendamento.id, e);
+            0
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn erro_interno<T>(contexto: &str, e: rusqlite::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("Erro ao {}: {}", contexto, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao {}: {}", contexto, e), data: None }))
+}
+
+#[derive(Deserialize)]
+pub struct EsperaQuery {
+    status: Option<String>,
+    cliente_id: Option<i32>,
+}
+
+/// GET /lista_espera?status=&cliente_id= — pedidos em ordem de chegada.
+pub async fn listar_espera_api(Query(q): Query<EsperaQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<EntradaEspera>>>) {
+    let conn = conn.lock().unwrap();
+    match listar_entradas(&conn, q.status.as_deref(), q.cliente_id) {
+        Ok(lista) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} pedido(s) na lista de espera.", lista.len()), data: Some(lista) })),
+        Err(e) => erro_interno("listar lista de espera", e),
+    }
+}
+
+/// POST /lista_espera — coloca o cliente na lista de espera.
+pub async fn criar_espera_api(State(conn): State<Db>, Json(nova): Json<NovaEntrada>) -> (StatusCode, Json<ApiResponse<EntradaEspera>>) {
+    let conn = conn.lock().unwrap();
+    match salvar_entrada(&conn, &nova) {
+        Ok(entrada) => (StatusCode::CREATED, Json(ApiResponse { success: true, message: "Cliente adicionado à lista de espera!".to_string(), data: Some(entrada) })),
+        Err((status, mensagem)) => (status, Json(ApiResponse { success: false, message: mensagem, data: None })),
+    }
+}
+
+/// DELETE /lista_espera/:id — tira o pedido da lista.
+pub async fn excluir_espera_api(Path(id): Path<i64>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
+    let conn = conn.lock().unwrap();
+    match excluir_entrada(&conn, id) {
+        Ok(true) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Pedido removido da lista de espera.".to_string(), data: None })),
+        Ok(false) => (StatusCode::NOT_FOUND, Json(ApiResponse { success: false, message: format!("Pedido {} não encontrado na lista de espera.", id), data: None })),
+        Err(e) => erro_interno("excluir pedido da lista de espera", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Cliente, Servico};
+
+    fn data(texto: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(texto, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_vaga_avisa_pedidos_que_cabem_em_ordem_de_chegada() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".into(), "11999990000".into(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let corte = db::salvar_servico(&conn, &Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: None, alinhamento_min: None }).unwrap();
+        let barba = db::salvar_servico(&conn, &Servico { id: None, nome: "Barba".into(), preco: 25.0, duracao_min: 30, buffer_min: None, alinhamento_min: None }).unwrap();
+
+        let dia = (config::agora() + Duration::days(3)).date();
+        let texto = dia.format("%Y-%m-%d").to_string();
+        let pedido = |servicos: Vec<i32>, periodo: &str, data_fim: Option<String>| NovaEntrada {
+            cliente_id,
+            servicos_ids: servicos,
+            data_inicio: texto.clone(),
+            data_fim,
+            periodo: Some(periodo.into()),
+            profissional: None,
+        };
+        let primeiro = salvar_entrada(&conn, &pedido(vec![corte], "manha", None)).unwrap();
+        let longo = salvar_entrada(&conn, &pedido(vec![corte, barba], "qualquer", None)).unwrap();
+        let tarde = salvar_entrada(&conn, &pedido(vec![corte], "tarde", None)).unwrap();
+        let segundo = salvar_entrada(&conn, &pedido(vec![barba], "qualquer", Some((dia + Duration::days(2)).format("%Y-%m-%d").to_string()))).unwrap();
+        assert_eq!(longo.duracao_min, 60);
+        assert!(matches!(salvar_entrada(&conn, &pedido(vec![corte], "madrugada", None)), Err((StatusCode::BAD_REQUEST, _))));
+
+        let com_profissional = NovaEntrada { profissional: Some("João".into()), ..pedido(vec![corte], "qualquer", None) };
+        assert!(matches!(salvar_entrada(&conn, &com_profissional), Err((StatusCode::BAD_REQUEST, _))));
+
+        // Vaga de 30 min às 10h: só o primeiro da fila cabe; o segundo continua aguardando.
+        let inicio = dia.and_hms_opt(10, 0, 0).unwrap();
+        let avisadas = avisar_vaga(&conn, inicio, inicio + Duration::minutes(30), config::agora()).unwrap();
+        assert_eq!(avisadas.iter().map(|e| e.id).collect::<Vec<_>>(), vec![primeiro.id]);
+        assert_eq!(listar_entradas(&conn, Some(STATUS_AGUARDANDO), None).unwrap().iter().map(|e| e.id).collect::<Vec<_>>(), vec![longo.id, tarde.id, segundo.id]);
+
+        // Horário passado não avisa ninguém.
+        let ontem = data("2000-01-01 10:00");
+        assert!(avisar_vaga(&conn, ontem, ontem + Duration::hours(2), config::agora()).unwrap().is_empty());
+
+        // Excluir um agendamento de uma hora à tarde libera a vaga: o pedido de uma hora,
+        // que chegou antes, ocupa tudo.
+        let agendamento = Agendamento { id: None, cliente_id, servicos_ids: vec![corte, barba], data_hora: dia.and_hms_opt(14, 0, 0).unwrap(), preco: 65.0, concluido: false };
+        assert_eq!(vaga_liberada(&conn, &agendamento), 1);
+        let avisado = buscar_entrada(&conn, longo.id).unwrap().unwrap();
+        assert_eq!((avisado.status.as_str(), avisado.vaga_inicio), (STATUS_AVISADO, Some(agendamento.data_hora)));
+
+        // Uma hora às 15h é dividida entre os dois pedidos de 30 min, na ordem de chegada.
+        let inicio = dia.and_hms_opt(15, 0, 0).unwrap();
+        let avisadas = avisar_vaga(&conn, inicio, inicio + Duration::hours(1), config::agora()).unwrap();
+        let trechos: Vec<_> = avisadas.iter().map(|e| (e.id, e.vaga_inicio.unwrap(), e.vaga_fim.unwrap())).collect();
+        assert_eq!(trechos, vec![(tarde.id, inicio, inicio + Duration::minutes(30)), (segundo.id, inicio + Duration::minutes(30), inicio + Duration::hours(1))]);
+        assert!(listar_entradas(&conn, Some(STATUS_AGUARDANDO), None).unwrap().is_empty());
+
+        assert!(excluir_entrada(&conn, primeiro.id).unwrap());
+        assert!(!excluir_entrada(&conn, primeiro.id).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_lista_espera_auditada_pelos_gatilhos() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".into(), "11999990000".into(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let corte = db::salvar_servico(&conn, &Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: None, alinhamento_min: None }).unwrap();
+        let dia = (config::agora() + Duration::days(3)).date();
+        let pedido = NovaEntrada {
+            cliente_id,
+            servicos_ids: vec![corte],
+            data_inicio: dia.format("%Y-%m-%d").to_string(),
+            data_fim: None,
+            periodo: None,
+            profissional: None,
+        };
+
+        let entrada = crate::auditoria::como_ator("recepcao", async {
+            let entrada = salvar_entrada(&conn, &pedido).unwrap();
+            let inicio = dia.and_hms_opt(10, 0, 0).unwrap();
+            assert_eq!(avisar_vaga(&conn, inicio, inicio + Duration::hours(2), config::agora()).unwrap().len(), 1);
+            assert!(excluir_entrada(&conn, entrada.id).unwrap());
+            entrada
+        })
+        .await;
+
+        let filtro = db::FiltroAuditoria { entidade: Some("lista_espera".into()), entidade_id: Some(entrada.id), limite: 10, ..Default::default() };
+        let registros = db::listar_auditoria(&conn, &filtro).unwrap();
+        let acoes: Vec<&str> = registros.iter().map(|r| r.acao.as_str()).collect();
+        assert_eq!(acoes, [db::ACAO_EXCLUSAO, db::ACAO_MUDANCA_STATUS, db::ACAO_CRIACAO]);
+        assert!(registros.iter().all(|r| r.ator == "recepcao"));
+        let aviso = &registros[1];
+        assert_eq!((aviso.antes.as_ref().unwrap()["status"].as_str(), aviso.depois.as_ref().unwrap()["status"].as_str()), (Some(STATUS_AGUARDANDO), Some(STATUS_AVISADO)));
+    }
+}
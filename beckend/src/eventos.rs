@@ -0,0 +1,277 @@
+//! Atualização ao vivo da agenda: `GET /eventos` é um fluxo Server-Sent Events com as criações,
+//! alterações e exclusões de agendamentos, clientes e serviços.
+//!
+//! Os eventos vêm de `sync_alteracoes` (ver `sincronizacao`), então cobrem todo caminho de
+//! gravação, e o `id` de cada evento é o `seq` da alteração. O navegador reconecta sozinho
+//! mandando `Last-Event-ID`, e o fluxo continua de onde parou. Depois de cada requisição que grava,
+//! o middleware `avisar_apos_alteracao` acorda os fluxos abertos. Gravações feitas fora do
+//! servidor (menu, linha de comando) aparecem na varredura periódica.
+//!
+//! Os eventos trazem dados de clientes, então o fluxo é só do dono: `X-Owner-Token` ou, para o
+//! `EventSource` do navegador (que não manda cabeçalhos), o `?token=` de `GET /eventos/link`.
+
+use axum::{
+    extract::{Query, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use futures_util::stream;
+use rusqlite::{Connection, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::watch;
+
+use crate::agendamentos;
+use crate::backup::{autorizar_dono, textos_iguais, token_derivado};
+use crate::db;
+use crate::sincronizacao::{self, AlteracaoServidor, Entidade};
+
+type Db = Arc<Mutex<Connection>>;
+
+/// Intervalo da varredura para gravações que não passaram pelo servidor HTTP.
+const VARREDURA_SEG: u64 = 5;
+/// Alterações lidas do banco por vez.
+const LOTE_EVENTOS: usize = 200;
+/// Finalidade do token de `GET /eventos/link` (ver `backup::token_derivado`).
+const FINALIDADE_TOKEN: &str = "eventos";
+
+fn aviso() -> &'static watch::Sender<()> {
+    static AVISO: OnceLock<watch::Sender<()>> = OnceLock::new();
+    AVISO.get_or_init(|| watch::channel(()).0)
+}
+
+/// Acorda os fluxos abertos para lerem as alterações novas.
+pub fn avisar() {
+    aviso().send_replace(());
+}
+
+/// Middleware: depois de toda requisição que pode gravar, avisa os fluxos de `/eventos`.
+pub async fn avisar_apos_alteracao(req: Request, next: Next) -> Response {
+    let grava = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let resposta = next.run(req).await;
+    if grava {
+        avisar();
+    }
+    resposta
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct FiltroEventos {
+    /// Só agendamentos deste dia (YYYY-MM-DD). Clientes, serviços e exclusões passam sempre.
+    pub data: Option<NaiveDate>,
+}
+
+/// Se a alteração interessa a quem filtrou pelo dia. Um agendamento remarcado para outro dia
+/// continua passando, para a tela do dia antigo tirá-lo da grade.
+fn passa_no_filtro(conn: &Connection, filtro: &FiltroEventos, alteracao: &AlteracaoServidor) -> Result<bool> {
+    let (Some(dia), Entidade::Agendamento, Some(dados)) = (filtro.data, alteracao.entidade, &alteracao.dados) else {
+        return Ok(true);
+    };
+    let data_hora: Option<NaiveDateTime> = dados.get("data_hora").and_then(|v| serde_json::from_value(v.clone()).ok());
+    if data_hora.map(|d| d.date()) == Some(dia) {
+        return Ok(true);
+    }
+    let remarcacoes = db::listar_remarcacoes(conn, alteracao.id)?;
+    Ok(remarcacoes.iter().any(|r| r.data_hora_anterior.date() == dia))
+}
+
+/// Próximas alterações depois de `cursor` que passam no filtro. Devolve o novo cursor e se
+/// ainda há alterações para ler.
+pub fn alteracoes_filtradas(conn: &Connection, cursor: i64, filtro: &FiltroEventos) -> Result<(i64, Vec<AlteracaoServidor>, bool)> {
+    let feed = sincronizacao::alteracoes_desde(conn, cursor, LOTE_EVENTOS)?;
+    let mut alteracoes = Vec::new();
+    for alteracao in feed.alteracoes {
+        if passa_no_filtro(conn, filtro, &alteracao)? {
+            alteracoes.push(alteracao);
+        }
+    }
+    Ok((feed.cursor, alteracoes, feed.mais))
+}
+
+/// Nome do evento SSE, no estilo dos webhooks: `agendamento.criado`, `cliente.atualizado`, `servico.excluido`.
+pub fn nome_evento(alteracao: &AlteracaoServidor) -> String {
+    let verbo = match alteracao.operacao {
+        sincronizacao::OPERACAO_CRIACAO => "criado",
+        sincronizacao::OPERACAO_EXCLUSAO => "excluido",
+        _ => "atualizado",
+    };
+    format!("{}.{}", alteracao.entidade.nome(), verbo)
+}
+
+fn evento(alteracao: &AlteracaoServidor) -> Event {
+    Event::default()
+        .id(alteracao.seq.to_string())
+        .event(nome_evento(alteracao))
+        .data(serde_json::to_string(alteracao).unwrap_or_default())
+}
+
+struct Fluxo {
+    conn: Db,
+    cursor: i64,
+    filtro: FiltroEventos,
+    fila: VecDeque<Event>,
+    aviso: watch::Receiver<()>,
+}
+
+#[derive(Deserialize)]
+pub struct EventosQuery {
+    pub data: Option<NaiveDate>,
+    /// Para quem não consegue mandar o cabeçalho `Last-Event-ID` (ex.: primeira conexão após recarregar a página).
+    pub last_event_id: Option<i64>,
+    /// Só para recusar (ver `agendamentos::recusar_profissional`).
+    pub profissional_id: Option<String>,
+    /// Token de `GET /eventos/link`, no lugar do `X-Owner-Token`.
+    pub token: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn resposta<T: Serialize>(status: StatusCode, message: String, data: Option<T>) -> Response {
+    (status, Json(ApiResponse { success: status.is_success(), message, data })).into_response()
+}
+
+/// O dono pelo cabeçalho ou pelo token do link.
+fn autorizar(headers: &HeaderMap, token: Option<&str>) -> Result<(), (StatusCode, String)> {
+    let pelo_cabecalho = autorizar_dono(headers);
+    match (token, token_derivado(FINALIDADE_TOKEN)) {
+        (Some(token), Some(esperado)) if pelo_cabecalho.is_err() => {
+            if textos_iguais(token, &esperado) {
+                Ok(())
+            } else {
+                Err((StatusCode::UNAUTHORIZED, "Token dos eventos inválido.".to_string()))
+            }
+        }
+        _ => pelo_cabecalho,
+    }
+}
+
+#[derive(Serialize)]
+pub struct LinkEventos {
+    pub url: String,
+}
+
+/// GET /eventos/link — endereço do fluxo com token, para o `EventSource`. Só o dono.
+pub async fn link_eventos_api(headers: HeaderMap) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let url = format!("/eventos?token={}", token_derivado(FINALIDADE_TOKEN).unwrap_or_default());
+    resposta(StatusCode::OK, "Abra este endereço com EventSource.".to_string(), Some(LinkEventos { url }))
+}
+
+/// GET /eventos?data=YYYY-MM-DD&last_event_id=&token= — fluxo SSE das alterações da agenda. Sem
+/// `Last-Event-ID`, começa pelas alterações novas. A agenda é única: com `profissional_id`
+/// responde 400 em vez de abrir o fluxo. Só o dono.
+pub async fn eventos_api(Query(q): Query<EventosQuery>, headers: HeaderMap, State(conn): State<Db>) -> Response {
+    if let Err((status, message)) = autorizar(&headers, q.token.as_deref()) {
+        return resposta::<()>(status, message, None);
+    }
+    if let Err((status, message)) = agendamentos::recusar_profissional("profissional_id", &q.profissional_id) {
+        return resposta::<()>(status, message, None);
+    }
+    let ultimo = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .or(q.last_event_id);
+    let (atual, valido) = {
+        let conn = conn.lock().unwrap();
+        let valido = ultimo.filter(|id| {
+            sincronizacao::cursor_conhecido(&conn, *id).unwrap_or_else(|e| {
+                eprintln!("Erro ao ler alterações: {}", e);
+                false
+            })
+        });
+        (sincronizacao::cursor_atual(&conn), valido)
+    };
+    let atual = atual.unwrap_or_else(|e| {
+        eprintln!("Erro ao ler alterações: {}", e);
+        0
+    });
+    let mut fila = VecDeque::new();
+    let cursor = match (ultimo, valido) {
+        (_, Some(id)) => id,
+        (Some(_), None) => {
+            // Id de outro banco (restauração, importação): o que o app tem na tela não vale mais.
+            fila.push_back(Event::default().event("reiniciar").data("O servidor não conhece o último evento recebido; recarregue a agenda."));
+            atual
+        }
+        (None, None) => atual,
+    };
+    let fluxo = Fluxo { conn, cursor, filtro: FiltroEventos { data: q.data }, fila, aviso: aviso().subscribe() };
+
+    let eventos = stream::unfold(fluxo, |mut fluxo| async move {
+        loop {
+            if let Some(evento) = fluxo.fila.pop_front() {
+                return Some((Ok::<_, Infallible>(evento), fluxo));
+            }
+            let lido = alteracoes_filtradas(&fluxo.conn.lock().unwrap(), fluxo.cursor, &fluxo.filtro);
+            match lido {
+                Ok((cursor, alteracoes, mais)) => {
+                    fluxo.cursor = cursor;
+                    fluxo.fila.extend(alteracoes.iter().map(evento));
+                    if !fluxo.fila.is_empty() || mais {
+                        continue;
+                    }
+                }
+                Err(e) => eprintln!("Erro ao ler alterações: {}", e),
+            }
+            tokio::select! {
+                _ = fluxo.aviso.changed() => {}
+                _ = tokio::time::sleep(Duration::from_secs(VARREDURA_SEG)) => {}
+            }
+        }
+    });
+    Sse::new(eventos).keep_alive(KeepAlive::default()).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Agendamento, Cliente, Servico};
+
+    #[test]
+    fn test_filtro_por_dia_e_retomada() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".to_string(), "11999990000".to_string(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let corte = Servico { id: None, nome: "Corte".to_string(), preco: 35.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
+        let corte_id = db::salvar_servico(&conn, &corte).unwrap();
+        let inicio = sincronizacao::cursor_atual(&conn).unwrap();
+
+        let dia = NaiveDate::from_ymd_opt(2030, 1, 5).unwrap();
+        let agendamento = Agendamento { id: None, cliente_id, servicos_ids: vec![corte_id], data_hora: dia.and_hms_opt(10, 0, 0).unwrap(), preco: 35.0, concluido: false };
+        let no_dia = db::salvar_agendamento(&conn, &agendamento).unwrap();
+        let outro_dia = db::salvar_agendamento(&conn, &Agendamento { data_hora: dia.succ_opt().unwrap().and_hms_opt(10, 0, 0).unwrap(), ..agendamento.clone() }).unwrap();
+
+        let filtro = FiltroEventos { data: Some(dia) };
+        let (cursor, alteracoes, mais) = alteracoes_filtradas(&conn, inicio, &filtro).unwrap();
+        assert!(!mais);
+        assert_eq!(alteracoes.iter().map(|a| a.id).collect::<Vec<_>>(), vec![no_dia]);
+        assert_eq!(nome_evento(&alteracoes[0]), "agendamento.criado");
+
+        // Retomando do cursor, só o que veio depois; remarcado para fora do dia ainda passa.
+        let nova = dia.succ_opt().unwrap().and_hms_opt(15, 0, 0).unwrap();
+        db::atualizar_agendamento(&conn, no_dia, Some(nova), None, None, None).unwrap();
+        db::registrar_remarcacao(&conn, no_dia, agendamento.data_hora, nova, db::ORIGEM_EDICAO, None).unwrap();
+        db::excluir_agendamento(&conn, outro_dia).unwrap();
+        let (_, alteracoes, _) = alteracoes_filtradas(&conn, cursor, &filtro).unwrap();
+        let nomes: Vec<String> = alteracoes.iter().map(nome_evento).collect();
+        assert_eq!(nomes, ["agendamento.atualizado", "agendamento.excluido"]);
+    }
+}
@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDateTime;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+use crate::config;
+use crate::db;
+use crate::models::{Agendamento, Cliente, EntregaWebhook, Notificacao};
+use crate::notificacoes;
+use crate::webhooks;
+
+type Db = Arc<Mutex<Connection>>;
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+/// Agendamento exportado com os nomes dos serviços, para leitura pelo titular.
+#[derive(Serialize)]
+pub struct AgendamentoExportado {
+    #[serde(flatten)]
+    pub agendamento: Agendamento,
+    pub servicos: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct SolicitacaoExportada {
+    pub tipo: String,
+    pub realizado_em: NaiveDateTime,
+}
+
+/// Tudo o que guardamos sobre um cliente (direito de acesso do titular).
+#[derive(Serialize)]
+pub struct ExportacaoCliente {
+    pub exportado_em: NaiveDateTime,
+    pub cliente: Cliente,
+    pub anonimizado_em: Option<NaiveDateTime>,
+    pub agendamentos: Vec<AgendamentoExportado>,
+    pub solicitacoes_lgpd: Vec<SolicitacaoExportada>,
+    /// Lembretes enviados ou agendados, com destino e texto.
+    pub notificacoes: Vec<Notificacao>,
+    /// Eventos de webhook que levaram dados do cliente.
+    pub entregas_webhook: Vec<EntregaWebhook>,
+}
+
+fn montar_exportacao(conn: &Connection, cliente: Cliente) -> rusqlite::Result<ExportacaoCliente> {
+    let id = cliente.id.unwrap_or(0);
+    let mut agendamentos = Vec::new();
+    for agendamento in db::listar_agendamentos_por_cliente(conn, id)? {
+        let servicos = db::buscar_nomes_servicos(conn, &agendamento.servicos_ids)?;
+        agendamentos.push(AgendamentoExportado { agendamento, servicos });
+    }
+    let solicitacoes_lgpd = db::listar_solicitacoes_lgpd(conn, id)?
+        .into_iter()
+        .map(|(tipo, realizado_em)| SolicitacaoExportada { tipo, realizado_em })
+        .collect();
+
+    Ok(ExportacaoCliente {
+        exportado_em: config::agora(),
+        anonimizado_em: db::buscar_anonimizacao_cliente(conn, id)?,
+        cliente,
+        agendamentos,
+        solicitacoes_lgpd,
+        notificacoes: notificacoes::listar_notificacoes_cliente(conn, id)?,
+        entregas_webhook: webhooks::listar_entregas_cliente(conn, id)?,
+    })
+}
+
+/// GET /clientes/:id/export — exporta os dados do titular em JSON.
+pub async fn exportar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<ExportacaoCliente>>) {
+    let conn = conn.lock().unwrap();
+    let cliente = match db::buscar_cliente_por_id(&conn, id) {
+        Ok(Some(c)) => c,
+        Ok(None) => {
+            let response = ApiResponse { success: false, message: format!("Cliente {} não encontrado.", id), data: None };
+            return (StatusCode::NOT_FOUND, Json(response));
+        }
+        Err(e) => {
+            eprintln!("Erro ao buscar cliente para exportação: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao exportar dados do cliente: {}", e), data: None };
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(response));
+        }
+    };
+
+    // A própria exportação é registrada antes de montar o documento, para constar nele.
+    let resultado = db::registrar_exportacao_lgpd(&conn, id)
+        .and_then(|_| montar_exportacao(&conn, cliente));
+
+    match resultado {
+        Ok(exportacao) => {
+            let response = ApiResponse { success: true, message: "Dados do cliente exportados com sucesso!".to_string(), data: Some(exportacao) };
+            (StatusCode::OK, Json(response))
+        }
+        Err(e) => {
+            eprintln!("Erro ao exportar dados do cliente: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao exportar dados do cliente: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
+
+/// POST /clientes/:id/anonimizar — remove os dados pessoais mantendo o histórico contábil.
+pub async fn anonimizar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Cliente>>) {
+    let conn = conn.lock().unwrap();
+    match db::anonimizar_cliente(&conn, id) {
+        Ok(true) => {
+            let cliente = db::buscar_cliente_por_id(&conn, id).unwrap_or(None);
+            let response = ApiResponse { success: true, message: "Dados pessoais do cliente anonimizados com sucesso!".to_string(), data: cliente };
+            (StatusCode::OK, Json(response))
+        }
+        Ok(false) => {
+            let response = ApiResponse { success: false, message: format!("Cliente {} não encontrado.", id), data: None };
+            (StatusCode::NOT_FOUND, Json(response))
+        }
+        Err(e) => {
+            eprintln!("Erro ao anonimizar cliente: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao anonimizar cliente: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
@@ -0,0 +1,449 @@
+use axum::{
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use ed25519_dalek::VerifyingKey;
+use rusqlite::{Connection, OptionalExtension, Result, params};
+use serde::Serialize;
+use std::fmt;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use crate::config::{self, ConfigLicenca};
+use crate::db;
+
+type Db = Arc<Mutex<Connection>>;
+
+// As funções de emissão só são usadas pela ferramenta `licenca-emitir`, que inclui o mesmo arquivo.
+#[allow(dead_code)]
+pub mod token;
+
+pub use token::{DadosLicenca, ErroToken};
+
+/// Chave pública do emissor das licenças, vinda de `AGENDOU_CHAVE_PUBLICA_LICENCA` na compilação
+/// (o par do fornecedor é gerado com `licenca-emitir gerar-chaves`). Sem a variável, builds de
+/// depuração usam a chave do par de desenvolvimento e o build de release não compila.
+///
+/// A chave privada do par de desenvolvimento é pública de propósito: 32 bytes `0x01`, ou
+/// `AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE` em base64url. Gravada num arquivo, serve para
+/// `licenca-emitir emitir --chave` emitir licenças de teste.
+pub const CHAVE_PUBLICA: &str = match option_env!("AGENDOU_CHAVE_PUBLICA_LICENCA") {
+    Some(chave) => chave,
+    #[cfg(debug_assertions)]
+    None => "iojj3XQJ8ZX9UtstPLpdcspnCb8dlBIb83SIAbQPb1w",
+    #[cfg(not(debug_assertions))]
+    None => panic!("defina AGENDOU_CHAVE_PUBLICA_LICENCA com a chave pública do fornecedor para compilar em release"),
+};
+
+#[cfg(not(test))]
+fn chave_publica() -> VerifyingKey {
+    token::chave_publica_de_texto(CHAVE_PUBLICA).expect("CHAVE_PUBLICA embutida inválida")
+}
+
+/// Nos testes as licenças são assinadas com uma chave fixa conhecida.
+#[cfg(test)]
+fn chave_publica() -> VerifyingKey {
+    tests::chave_teste().verifying_key()
+}
+
+#[derive(Debug)]
+pub enum ErroLicenca {
+    Token(ErroToken),
+    Expirada(NaiveDateTime),
+    Banco(rusqlite::Error),
+}
+
+impl fmt::Display for ErroLicenca {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErroLicenca::Token(e) => write!(f, "{}", e),
+            ErroLicenca::Expirada(data) => write!(f, "licença expirada em {}", data.format("%d/%m/%Y %H:%M UTC")),
+            ErroLicenca::Banco(e) => write!(f, "erro no banco de dados: {}", e),
+        }
+    }
+}
+
+impl From<ErroToken> for ErroLicenca {
+    fn from(e: ErroToken) -> Self {
+        ErroLicenca::Token(e)
+    }
+}
+
+impl From<rusqlite::Error> for ErroLicenca {
+    fn from(e: rusqlite::Error) -> Self {
+        ErroLicenca::Banco(e)
+    }
+}
+
+/// Confere a assinatura com a chave embutida e se a licença ainda vale.
+pub fn validar_token(token: &str) -> std::result::Result<DadosLicenca, ErroLicenca> {
+    let dados = token::verificar(token, &chave_publica())?;
+    if dados.expira_em < Utc::now().naive_utc() {
+        return Err(ErroLicenca::Expirada(dados.expira_em));
+    }
+    Ok(dados)
+}
+
+/// Valida o token e o grava na linha única de `sistema`.
+pub fn instalar_licenca(conn: &Connection, token: &str) -> std::result::Result<DadosLicenca, ErroLicenca> {
+    let dados = validar_token(token)?;
+    let antes = licenca_instalada(conn)?;
+    conn.execute(
+        "UPDATE sistema SET licenca_token = ?1, validade_licenca = ?2 WHERE id = 1",
+        params![token.trim(), dados.expira_em.and_utc().timestamp()],
+    )?;
+    db::registrar_auditoria(conn, "licenca", 1, db::ACAO_ATUALIZACAO, db::json_de(&antes), db::json_de(&dados))?;
+    Ok(dados)
+}
+
+/// Licença gravada em `sistema`, se houver e se a assinatura conferir
+/// (mesmo expirada). Editar o banco à mão não produz uma licença válida.
+pub fn licenca_instalada(conn: &Connection) -> Result<Option<DadosLicenca>> {
+    let token: Option<String> = conn
+        .query_row("SELECT licenca_token FROM sistema WHERE id = 1", [], |row| row.get(0))
+        .optional()?
+        .flatten();
+    Ok(token.and_then(|t| match token::verificar(&t, &chave_publica()) {
+        Ok(dados) => Some(dados),
+        Err(e) => {
+            eprintln!("Licença gravada ignorada: {}", e);
+            None
+        }
+    }))
+}
+
+// ---------------------------------------------------------------------------------
+// CONTROLE DE USO
+// Sem licença válida (passada a carência) a API fica somente leitura.
+// ---------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SituacaoLicenca {
+    Ativa,
+    /// Sem licença instalada, dentro da carência contada da instalação.
+    Avaliacao,
+    /// Expirada, mas ainda dentro da carência.
+    Carencia,
+    Expirada,
+    SemLicenca,
+}
+
+impl SituacaoLicenca {
+    pub fn como_texto(&self) -> &'static str {
+        match self {
+            SituacaoLicenca::Ativa => "ativa",
+            SituacaoLicenca::Avaliacao => "avaliacao",
+            SituacaoLicenca::Carencia => "carencia",
+            SituacaoLicenca::Expirada => "expirada",
+            SituacaoLicenca::SemLicenca => "sem_licenca",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusLicenca {
+    pub situacao: SituacaoLicenca,
+    pub somente_leitura: bool,
+    pub barbearia: Option<String>,
+    pub plano: Option<String>,
+    pub recursos: Vec<String>,
+    pub expira_em: Option<NaiveDateTime>,
+    /// Momento em que a API deixa (ou deixou) de aceitar alterações.
+    pub somente_leitura_a_partir_de: NaiveDateTime,
+    /// Dias até a expiração da licença (ou do período de avaliação); 0 se já passou.
+    pub dias_restantes: i64,
+    pub aviso: Option<String>,
+    /// Versão curta e só ASCII do aviso, para o cabeçalho `X-Licenca-Aviso`.
+    #[serde(skip)]
+    pub aviso_cabecalho: Option<String>,
+}
+
+fn dias_ate(agora: NaiveDateTime, limite: NaiveDateTime) -> i64 {
+    let segundos = (limite - agora).num_seconds().max(0);
+    (segundos + 86_399) / 86_400
+}
+
+/// Situação da licença num instante (datas em UTC).
+pub fn calcular_status(licenca: Option<DadosLicenca>, instalado_em: NaiveDateTime, agora: NaiveDateTime, regras: &ConfigLicenca) -> StatusLicenca {
+    let carencia = chrono::Duration::days(regras.carencia_dias);
+    let data = |d: NaiveDateTime| d.format("%d/%m/%Y").to_string();
+    let iso = |d: NaiveDateTime| d.format("%Y-%m-%d").to_string();
+
+    let Some(licenca) = licenca else {
+        let fim = instalado_em + carencia;
+        let (situacao, aviso, cabecalho) = if agora <= fim {
+            (
+                SituacaoLicenca::Avaliacao,
+                format!("Nenhuma licença instalada: o período de avaliação termina em {}.", data(fim)),
+                format!("sem licenca; somente leitura a partir de {}", iso(fim)),
+            )
+        } else {
+            (
+                SituacaoLicenca::SemLicenca,
+                "Nenhuma licença instalada: sistema em modo somente leitura.".to_string(),
+                "sem licenca; somente leitura".to_string(),
+            )
+        };
+        return StatusLicenca {
+            situacao,
+            somente_leitura: situacao == SituacaoLicenca::SemLicenca,
+            barbearia: None,
+            plano: None,
+            recursos: vec![],
+            expira_em: None,
+            somente_leitura_a_partir_de: fim,
+            dias_restantes: dias_ate(agora, fim),
+            aviso: Some(aviso),
+            aviso_cabecalho: Some(cabecalho),
+        };
+    };
+
+    let expira_em = licenca.expira_em;
+    let fim = expira_em + carencia;
+    let dias_restantes = dias_ate(agora, expira_em);
+    let (situacao, aviso, cabecalho) = if agora <= expira_em {
+        if dias_restantes <= regras.aviso_dias {
+            (
+                SituacaoLicenca::Ativa,
+                Some(format!("A licença expira em {} dia(s), em {}.", dias_restantes, data(expira_em))),
+                Some(format!("expira em {}", iso(expira_em))),
+            )
+        } else {
+            (SituacaoLicenca::Ativa, None, None)
+        }
+    } else if agora <= fim {
+        (
+            SituacaoLicenca::Carencia,
+            Some(format!("A licença expirou em {}. O sistema ficará somente leitura em {}; instale uma nova licença.", data(expira_em), data(fim))),
+            Some(format!("expirada; somente leitura a partir de {}", iso(fim))),
+        )
+    } else {
+        (
+            SituacaoLicenca::Expirada,
+            Some(format!("A licença expirou em {}. Sistema em modo somente leitura; instale uma nova licença.", data(expira_em))),
+            Some("expirada; somente leitura".to_string()),
+        )
+    };
+
+    StatusLicenca {
+        situacao,
+        somente_leitura: situacao == SituacaoLicenca::Expirada,
+        barbearia: Some(licenca.barbearia),
+        plano: Some(licenca.plano),
+        recursos: licenca.recursos,
+        expira_em: Some(expira_em),
+        somente_leitura_a_partir_de: fim,
+        dias_restantes,
+        aviso,
+        aviso_cabecalho: cabecalho,
+    }
+}
+
+/// Início da contagem do período de avaliação (gravado na primeira execução).
+pub fn instalado_em(conn: &Connection) -> Result<NaiveDateTime> {
+    let ts: Option<i64> = conn.query_row("SELECT instalado_em FROM sistema WHERE id = 1", [], |row| row.get(0))?;
+    Ok(ts.and_then(|t| DateTime::from_timestamp(t, 0)).map(|d| d.naive_utc()).unwrap_or_else(|| Utc::now().naive_utc()))
+}
+
+/// Lê a licença do banco e calcula a situação agora.
+pub fn status_licenca(conn: &Connection) -> Result<StatusLicenca> {
+    Ok(calcular_status(licenca_instalada(conn)?, instalado_em(conn)?, Utc::now().naive_utc(), &config::atual().licenca))
+}
+
+/// Licença lida do banco pela última verificação do servidor. A situação é
+/// recalculada a cada requisição, então a troca para somente leitura não espera a próxima leitura.
+static LICENCA_CARREGADA: RwLock<Option<(Option<DadosLicenca>, NaiveDateTime)>> = RwLock::new(None);
+
+fn recarregar(conn: &Connection) -> Result<StatusLicenca> {
+    let (licenca, instalado) = (licenca_instalada(conn)?, instalado_em(conn)?);
+    *LICENCA_CARREGADA.write().unwrap() = Some((licenca.clone(), instalado));
+    Ok(calcular_status(licenca, instalado, Utc::now().naive_utc(), &config::atual().licenca))
+}
+
+fn status_em_memoria() -> Option<StatusLicenca> {
+    let carregada = LICENCA_CARREGADA.read().unwrap().clone();
+    carregada.map(|(licenca, instalado)| calcular_status(licenca, instalado, Utc::now().naive_utc(), &config::atual().licenca))
+}
+
+fn anunciar(status: &StatusLicenca) {
+    match &status.aviso {
+        Some(aviso) => println!("[Licença] {} ({})", aviso, status.situacao.como_texto()),
+        None => println!("[Licença] Ativa até {} ({} dia(s)).", status.expira_em.map(|d| d.format("%d/%m/%Y").to_string()).unwrap_or_default(), status.dias_restantes),
+    }
+}
+
+/// Verifica a licença ao subir o servidor e depois a cada `licenca.verificar_a_cada_min`
+/// (uma licença instalada pelo terminal passa a valer sem reiniciar).
+pub fn iniciar_verificacao_periodica(conn: Db) -> Result<()> {
+    let status = recarregar(&conn.lock().unwrap())?;
+    anunciar(&status);
+
+    let minutos = config::atual().licenca.verificar_a_cada_min;
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(Duration::from_secs(minutos * 60));
+        intervalo.tick().await;
+        loop {
+            intervalo.tick().await;
+            let resultado = recarregar(&conn.lock().unwrap());
+            match resultado {
+                Ok(status) if status.aviso.is_some() => anunciar(&status),
+                Ok(_) => {}
+                Err(e) => eprintln!("[Licença] Falha ao verificar a licença: {}", e),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+/// Alterações que continuam liberadas sem licença: pedidos LGPD e backups do dono.
+fn liberada_sem_licenca(metodo: &Method, caminho: &str) -> bool {
+    *metodo == Method::POST && (caminho.ends_with("/anonimizar") || caminho == "/backup")
+}
+
+fn adicionar_cabecalhos(resposta: &mut Response, status: &StatusLicenca) {
+    let cabecalhos = resposta.headers_mut();
+    cabecalhos.insert(HeaderName::from_static("x-licenca-status"), HeaderValue::from_static(status.situacao.como_texto()));
+    cabecalhos.insert(HeaderName::from_static("x-licenca-dias-restantes"), HeaderValue::from(status.dias_restantes));
+    if let Some(aviso) = status.aviso_cabecalho.as_deref().and_then(|a| HeaderValue::from_str(a).ok()) {
+        cabecalhos.insert(HeaderName::from_static("x-licenca-aviso"), aviso);
+    }
+}
+
+/// Middleware: bloqueia alterações quando a licença está em modo somente leitura
+/// e anexa a situação da licença em cabeçalhos `X-Licenca-*`.
+pub async fn controlar_licenca(req: Request, next: Next) -> Response {
+    let Some(status) = status_em_memoria() else {
+        return next.run(req).await;
+    };
+
+    let leitura = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let mut resposta = if status.somente_leitura && !leitura && !liberada_sem_licenca(req.method(), req.uri().path()) {
+        let response: ApiResponse<StatusLicenca> = ApiResponse {
+            success: false,
+            message: status.aviso.clone().unwrap_or_else(|| "Sistema em modo somente leitura.".to_string()),
+            data: Some(status.clone()),
+        };
+        (StatusCode::FORBIDDEN, Json(response)).into_response()
+    } else {
+        next.run(req).await
+    };
+    adicionar_cabecalhos(&mut resposta, &status);
+    resposta
+}
+
+/// GET /sistema/licenca — situação atual da licença (relê o banco).
+pub async fn status_licenca_api(State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<StatusLicenca>>) {
+    let conn = conn.lock().unwrap();
+    match recarregar(&conn) {
+        Ok(status) => {
+            let response = ApiResponse { success: true, message: status.situacao.como_texto().to_string(), data: Some(status) };
+            (StatusCode::OK, Json(response))
+        }
+        Err(e) => {
+            eprintln!("Erro ao verificar licença: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao verificar licença: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::SigningKey;
+
+    pub fn chave_teste() -> SigningKey {
+        SigningKey::from_bytes(&[42; 32])
+    }
+
+    fn token_com_validade(dias: i64) -> String {
+        let agora = Utc::now().naive_utc();
+        let dados = DadosLicenca {
+            barbearia: "centro".into(),
+            plano: "profissional".into(),
+            emitida_em: agora - chrono::Duration::days(400),
+            expira_em: agora + chrono::Duration::days(dias),
+            recursos: vec![],
+        };
+        token::emitir(&dados, &chave_teste())
+    }
+
+    #[test]
+    fn test_status_carencia_e_somente_leitura() {
+        let data = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        let regras = ConfigLicenca { carencia_dias: 7, aviso_dias: 5, verificar_a_cada_min: 60 };
+        let licenca = DadosLicenca {
+            barbearia: "centro".into(),
+            plano: "profissional".into(),
+            emitida_em: data("2026-01-01 00:00"),
+            expira_em: data("2026-10-20 12:00"),
+            recursos: vec![],
+        };
+        let status = |agora| calcular_status(Some(licenca.clone()), data("2025-01-01 00:00"), data(agora), &regras);
+
+        let ativa = status("2026-10-01 12:00");
+        assert_eq!((ativa.situacao, ativa.somente_leitura, ativa.dias_restantes), (SituacaoLicenca::Ativa, false, 19));
+        assert!(ativa.aviso.is_none());
+
+        let perto = status("2026-10-18 13:00");
+        assert_eq!(perto.dias_restantes, 2, "dias incompletos contam como um dia");
+        assert_eq!(perto.aviso_cabecalho.as_deref(), Some("expira em 2026-10-20"));
+
+        let carencia = status("2026-10-25 12:00");
+        assert_eq!((carencia.situacao, carencia.somente_leitura, carencia.dias_restantes), (SituacaoLicenca::Carencia, false, 0));
+        assert_eq!(carencia.somente_leitura_a_partir_de, data("2026-10-27 12:00"));
+
+        let expirada = status("2026-10-27 12:01");
+        assert_eq!((expirada.situacao, expirada.somente_leitura), (SituacaoLicenca::Expirada, true));
+
+        let avaliacao = calcular_status(None, data("2026-10-15 00:00"), data("2026-10-19 00:00"), &regras);
+        assert_eq!((avaliacao.situacao, avaliacao.somente_leitura, avaliacao.dias_restantes), (SituacaoLicenca::Avaliacao, false, 3));
+        let sem = calcular_status(None, data("2026-10-01 00:00"), data("2026-10-19 00:00"), &regras);
+        assert_eq!((sem.situacao, sem.somente_leitura), (SituacaoLicenca::SemLicenca, true));
+
+        assert!(liberada_sem_licenca(&Method::POST, "/clientes/3/anonimizar"));
+        assert!(!liberada_sem_licenca(&Method::POST, "/agendamentos"));
+    }
+
+    #[test]
+    fn test_chave_publica_embutida() {
+        assert!(token::chave_publica_de_texto(CHAVE_PUBLICA).is_ok());
+        // Sem a variável de ambiente, a chave embutida é a do par de desenvolvimento.
+        if option_env!("AGENDOU_CHAVE_PUBLICA_LICENCA").is_none() {
+            let privada = token::chave_privada_de_texto("AQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQEBAQE").unwrap();
+            assert_eq!(token::chave_para_texto(privada.verifying_key().as_bytes()), CHAVE_PUBLICA);
+        }
+    }
+
+    #[test]
+    fn test_instalar_licenca_grava_em_sistema() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        assert!(licenca_instalada(&conn).unwrap().is_none(), "sem licença instalada");
+
+        assert!(matches!(instalar_licenca(&conn, &token_com_validade(-1)), Err(ErroLicenca::Expirada(_))));
+        assert!(matches!(instalar_licenca(&conn, "BARBER2025"), Err(ErroLicenca::Token(ErroToken::Formato))));
+
+        let dados = instalar_licenca(&conn, &token_com_validade(30)).unwrap();
+        assert_eq!(dados.barbearia, "centro");
+        assert_eq!(licenca_instalada(&conn).unwrap(), Some(dados.clone()));
+        let validade: i64 = conn.query_row("SELECT validade_licenca FROM sistema WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(validade, dados.expira_em.and_utc().timestamp());
+
+        // Adulterar o token no banco invalida a licença.
+        conn.execute("UPDATE sistema SET licenca_token = licenca_token || 'x' WHERE id = 1", []).unwrap();
+        assert!(licenca_instalada(&conn).unwrap().is_none());
+    }
+}
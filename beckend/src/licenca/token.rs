@@ -0,0 +1,143 @@
+// Formato dos tokens de licença, compartilhado com a ferramenta `licenca-emitir`
+// (src/bin/licenca-emitir.rs). Não depende de nada do restante do crate.
+//
+// Token: `AGD1.<dados>.<assinatura>`, onde `dados` é o JSON de `DadosLicenca` e
+// `assinatura` é a assinatura Ed25519 desses bytes, ambos em base64url sem padding.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::NaiveDateTime;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub const PREFIXO: &str = "AGD1";
+
+/// Conteúdo assinado de uma licença. Datas em UTC.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DadosLicenca {
+    /// Identificador da barbearia licenciada.
+    pub barbearia: String,
+    pub plano: String,
+    pub emitida_em: NaiveDateTime,
+    pub expira_em: NaiveDateTime,
+    /// Recursos liberados pelo plano (ex.: "notificacoes", "agenda_publica").
+    #[serde(default)]
+    pub recursos: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErroToken {
+    /// O texto não tem o formato `AGD1.<dados>.<assinatura>`.
+    Formato,
+    /// A assinatura não confere com a chave pública (token alterado ou de outro emissor).
+    Assinatura,
+    /// Chave em base64 inválida ou com tamanho errado.
+    Chave,
+}
+
+impl fmt::Display for ErroToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErroToken::Formato => write!(f, "token de licença em formato inválido"),
+            ErroToken::Assinatura => write!(f, "assinatura da licença inválida"),
+            ErroToken::Chave => write!(f, "chave de licença inválida"),
+        }
+    }
+}
+
+/// Assina os dados e devolve o token em texto.
+pub fn emitir(dados: &DadosLicenca, chave: &SigningKey) -> String {
+    let json = serde_json::to_vec(dados).expect("DadosLicenca sempre serializa");
+    let assinatura = chave.sign(&json);
+    format!("{}.{}.{}", PREFIXO, URL_SAFE_NO_PAD.encode(&json), URL_SAFE_NO_PAD.encode(assinatura.to_bytes()))
+}
+
+/// Confere a assinatura e devolve os dados. Não verifica a validade (ver `licenca::validar_token`).
+pub fn verificar(token: &str, chave: &VerifyingKey) -> Result<DadosLicenca, ErroToken> {
+    let mut partes = token.trim().split('.');
+    let (Some(PREFIXO), Some(dados), Some(assinatura), None) = (partes.next(), partes.next(), partes.next(), partes.next()) else {
+        return Err(ErroToken::Formato);
+    };
+    let json = URL_SAFE_NO_PAD.decode(dados).map_err(|_| ErroToken::Formato)?;
+    let assinatura: [u8; 64] = URL_SAFE_NO_PAD
+        .decode(assinatura)
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or(ErroToken::Formato)?;
+    chave
+        .verify(&json, &Signature::from_bytes(&assinatura))
+        .map_err(|_| ErroToken::Assinatura)?;
+    // Assinatura válida com JSON ilegível só acontece com um emissor de outra versão.
+    serde_json::from_slice(&json).map_err(|_| ErroToken::Formato)
+}
+
+pub fn chave_publica_de_texto(texto: &str) -> Result<VerifyingKey, ErroToken> {
+    let bytes: [u8; 32] = decodificar_chave(texto)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| ErroToken::Chave)
+}
+
+pub fn chave_privada_de_texto(texto: &str) -> Result<SigningKey, ErroToken> {
+    Ok(SigningKey::from_bytes(&decodificar_chave(texto)?))
+}
+
+/// Representação em texto (base64url) de uma chave pública ou privada.
+pub fn chave_para_texto(bytes: &[u8; 32]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn decodificar_chave(texto: &str) -> Result<[u8; 32], ErroToken> {
+    URL_SAFE_NO_PAD
+        .decode(texto.trim())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or(ErroToken::Chave)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dados() -> DadosLicenca {
+        let data = |s| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        DadosLicenca {
+            barbearia: "barbearia-centro".into(),
+            plano: "profissional".into(),
+            emitida_em: data("2026-10-01 12:00"),
+            expira_em: data("2027-10-01 12:00"),
+            recursos: vec!["notificacoes".into()],
+        }
+    }
+
+    #[test]
+    fn test_emitir_e_verificar() {
+        let chave = SigningKey::from_bytes(&[1; 32]);
+        let token = emitir(&dados(), &chave);
+        assert!(token.starts_with("AGD1."));
+        assert_eq!(verificar(&token, &chave.verifying_key()), Ok(dados()));
+        assert!(verificar(&token, &chave.verifying_key()).unwrap().recursos == ["notificacoes"]);
+
+        // Outro emissor.
+        let outra = SigningKey::from_bytes(&[2; 32]);
+        assert_eq!(verificar(&token, &outra.verifying_key()), Err(ErroToken::Assinatura));
+
+        // Dados alterados (plano trocado) com a assinatura original.
+        let partes: Vec<&str> = token.split('.').collect();
+        let mut alterado = dados();
+        alterado.plano = "ilimitado".into();
+        let adulterado = format!("AGD1.{}.{}", URL_SAFE_NO_PAD.encode(serde_json::to_vec(&alterado).unwrap()), partes[2]);
+        assert_eq!(verificar(&adulterado, &chave.verifying_key()), Err(ErroToken::Assinatura));
+
+        assert_eq!(verificar("BARBER2025", &chave.verifying_key()), Err(ErroToken::Formato));
+        assert_eq!(verificar(&format!("{}.x", token), &chave.verifying_key()), Err(ErroToken::Formato));
+    }
+
+    #[test]
+    fn test_chaves_em_texto() {
+        let chave = SigningKey::from_bytes(&[3; 32]);
+        let texto = chave_para_texto(chave.verifying_key().as_bytes());
+        assert_eq!(chave_publica_de_texto(&texto).unwrap(), chave.verifying_key());
+        assert_eq!(chave_privada_de_texto(&chave_para_texto(&chave.to_bytes())).unwrap().to_bytes(), chave.to_bytes());
+        assert_eq!(chave_publica_de_texto("curta").unwrap_err(), ErroToken::Chave);
+    }
+}
@@ -0,0 +1,431 @@
+//! Operações em lote: `POST /agendamentos/lote` (criar, atualizar, mover e cancelar) e
+//! `POST /servicos/lote` (cadastro, alteração e reajuste da tabela de preços).
+//!
+//! Tudo roda numa única transação do SQLite, item a item e na ordem enviada, de modo que um
+//! item já enxerga o que os anteriores gravaram (mover o sábado inteiro funciona mesmo quando
+//! os horários se cruzam). No modo `tudo_ou_nada` (padrão) a primeira falha desfaz o lote
+//! inteiro; no modo `por_item` cada item falho é desfeito sozinho e os demais são gravados.
+//! Cada item segue as mesmas regras do endpoint individual equivalente.
+
+use axum::{extract::State, http::StatusCode, Json};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::sync::{Arc, Mutex};
+
+use crate::agendamentos;
+use crate::db;
+use crate::espera;
+use crate::models::{Agendamento, ConflitoHorario, Servico};
+use crate::patch;
+use crate::servicos;
+use crate::webhooks;
+
+type Db = Arc<Mutex<Connection>>;
+
+/// Limite de operações por lote, para não prender o banco por muito tempo.
+pub const MAX_OPERACOES: usize = 500;
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModoLote {
+    #[default]
+    TudoOuNada,
+    PorItem,
+}
+
+#[derive(Deserialize)]
+pub struct PedidoLote<T> {
+    #[serde(default)]
+    pub modo: ModoLote,
+    pub operacoes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum OperacaoAgendamento {
+    /// Mesmas regras do `POST /agendamentos`.
+    Criar {
+        cliente_id: i32,
+        servicos_ids: Vec<i32>,
+        data_hora: JsonValue,
+        preco: f64,
+        #[serde(default)]
+        concluido: bool,
+    },
+    /// Merge-patch, como no `PATCH /agendamentos/:id`; `versao` faz o papel do `If-Match`.
+    Atualizar {
+        id: i32,
+        alteracoes: JsonValue,
+        versao: Option<i64>,
+    },
+    /// Remarcação com a conferência de conflitos do `POST /agendamentos/:id/reagendar`.
+    Mover {
+        id: i32,
+        data_hora: JsonValue,
+        #[serde(default)]
+        notificar: bool,
+        motivo: Option<String>,
+    },
+    /// Marca como cancelado (o registro continua na agenda) e libera a vaga para a lista de espera.
+    Cancelar { id: i32 },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum OperacaoServico {
+    /// Cadastro com os campos do `POST /servicos`.
+    Criar(Servico),
+    /// Merge-patch, como no `PATCH /servicos/:id`.
+    Atualizar {
+        id: i32,
+        alteracoes: JsonValue,
+        versao: Option<i64>,
+    },
+    /// Reajusta o preço em `percentual` (10 = +10%, -5 = -5%) de todos os serviços ou só dos
+    /// informados, arredondando para múltiplos de `arredondar_para` (padrão: centavos).
+    Reajustar {
+        percentual: f64,
+        servicos_ids: Option<Vec<i32>>,
+        arredondar_para: Option<f64>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResultadoItem {
+    pub indice: usize,
+    pub ok: bool,
+    pub status: u16,
+    pub mensagem: String,
+    /// Registros criados ou alterados pelo item.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ids: Vec<i32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub conflitos: Vec<ConflitoHorario>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResultadoLote {
+    pub modo: ModoLote,
+    /// Se algo foi gravado. No `tudo_ou_nada` com falha, os itens `ok` também foram desfeitos.
+    pub aplicado: bool,
+    pub sucesso: usize,
+    pub falhas: usize,
+    pub itens: Vec<ResultadoItem>,
+}
+
+type Sucesso = (String, Vec<i32>);
+type FalhaItem = (StatusCode, String, Vec<ConflitoHorario>);
+
+fn falha(status: StatusCode, mensagem: String) -> FalhaItem {
+    (status, mensagem, Vec::new())
+}
+
+/// Executa as operações numa transação, cada uma dentro de um savepoint próprio.
+pub fn executar_lote<T>(
+    conn: &Connection,
+    modo: ModoLote,
+    operacoes: Vec<T>,
+    mut executar: impl FnMut(&Connection, T) -> Result<Sucesso, FalhaItem>,
+) -> rusqlite::Result<ResultadoLote> {
+    let tx = conn.unchecked_transaction()?;
+    let mut itens = Vec::new();
+    for (indice, operacao) in operacoes.into_iter().enumerate() {
+        tx.execute_batch("SAVEPOINT item_lote")?;
+        let item = match executar(&tx, operacao) {
+            Ok((mensagem, ids)) => {
+                tx.execute_batch("RELEASE item_lote")?;
+                ResultadoItem { indice, ok: true, status: StatusCode::OK.as_u16(), mensagem, ids, conflitos: Vec::new() }
+            }
+            Err((status, mensagem, conflitos)) => {
+                tx.execute_batch("ROLLBACK TO item_lote; RELEASE item_lote")?;
+                ResultadoItem { indice, ok: false, status: status.as_u16(), mensagem, ids: Vec::new(), conflitos }
+            }
+        };
+        let parar = !item.ok && modo == ModoLote::TudoOuNada;
+        itens.push(item);
+        if parar {
+            break;
+        }
+    }
+    let falhas = itens.iter().filter(|i| !i.ok).count();
+    let sucesso = itens.len() - falhas;
+    let aplicado = match modo {
+        ModoLote::TudoOuNada => falhas == 0,
+        ModoLote::PorItem => sucesso > 0,
+    };
+    if aplicado {
+        tx.commit()?;
+    }
+    Ok(ResultadoLote { modo, aplicado, sucesso, falhas, itens })
+}
+
+fn buscar_agendamento(conn: &Connection, id: i32) -> Result<Agendamento, FalhaItem> {
+    db::buscar_agendamento_por_id(conn, id).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => falha(StatusCode::NOT_FOUND, format!("Agendamento {} não encontrado.", id)),
+        e => falha(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao buscar agendamento: {}", e)),
+    })
+}
+
+/// Equivalente ao `If-Match` para itens do lote.
+fn conferir_versao(conn: &Connection, tabela: &str, id: i32, esperada: Option<i64>) -> Result<(), FalhaItem> {
+    let Some(esperada) = esperada else {
+        return Ok(());
+    };
+    match patch::versao(conn, tabela, id) {
+        Ok(Some(atual)) if atual != esperada => Err(falha(
+            StatusCode::CONFLICT,
+            format!("Versão {} desatualizada (atual: {}); recarregue e tente de novo.", esperada, atual),
+        )),
+        Ok(_) => Ok(()),
+        Err(e) => Err(falha(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao buscar versão: {}", e))),
+    }
+}
+
+fn executar_operacao_agendamento(conn: &Connection, operacao: OperacaoAgendamento) -> Result<Sucesso, FalhaItem> {
+    match operacao {
+        OperacaoAgendamento::Criar { cliente_id, servicos_ids, data_hora, preco, concluido } => {
+            let data_hora = agendamentos::parse_data_hora(&data_hora)
+                .map_err(|m| falha(StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid data_hora: {}", m)))?;
+            let novo = Agendamento { id: None, cliente_id, servicos_ids, data_hora, preco, concluido };
+            let criado = agendamentos::criar_agendamento(conn, &novo).map_err(|(s, m)| falha(s, m))?;
+            Ok(("Agendamento criado.".to_string(), criado.id.into_iter().collect()))
+        }
+        OperacaoAgendamento::Atualizar { id, alteracoes, versao } => {
+            let atual = buscar_agendamento(conn, id)?;
+            conferir_versao(conn, "agendamentos", id, versao)?;
+            let mensagem = match agendamentos::alterar_agendamento(conn, &atual, alteracoes).map_err(|(s, m)| falha(s, m))? {
+                Some(_) => "Agendamento atualizado.",
+                None => "Nada a alterar.",
+            };
+            Ok((mensagem.to_string(), vec![id]))
+        }
+        OperacaoAgendamento::Mover { id, data_hora, notificar, motivo } => {
+            let nova = agendamentos::parse_data_hora(&data_hora)
+                .map_err(|m| falha(StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid data_hora: {}", m)))?;
+            let atual = buscar_agendamento(conn, id)?;
+            let motivo = motivo.as_deref().map(str::trim).filter(|m| !m.is_empty());
+            match agendamentos::remarcar(conn, &atual, nova, motivo, notificar, true) {
+                (status, mensagem, _) if status.is_success() => Ok((mensagem, vec![id])),
+                (status, mensagem, resultado) => Err((status, mensagem, resultado.map(|r| r.conflitos).unwrap_or_default())),
+            }
+        }
+        OperacaoAgendamento::Cancelar { id } => {
+            let atual = buscar_agendamento(conn, id)?;
+            if atual.concluido {
+                return Err(falha(StatusCode::CONFLICT, "Agendamento concluído não pode ser cancelado.".to_string()));
+            }
+            let cancelar = db::status_agendamento(conn, id).and_then(|status| {
+                if status == db::STATUS_AGENDAMENTO_CANCELADO {
+                    return Ok(false);
+                }
+                db::definir_status_agendamento(conn, id, db::STATUS_AGENDAMENTO_CANCELADO).map(|_| true)
+            });
+            match cancelar {
+                Ok(true) => {
+                    webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_CANCELADO, &atual);
+                    espera::vaga_liberada(conn, &atual);
+                    Ok(("Agendamento cancelado.".to_string(), vec![id]))
+                }
+                Ok(false) => Err(falha(StatusCode::CONFLICT, "Agendamento já está cancelado.".to_string())),
+                Err(e) => Err(falha(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao cancelar agendamento: {}", e))),
+            }
+        }
+    }
+}
+
+/// Novo preço após o reajuste, arredondado para múltiplos de `passo` e sem ruído de centavos.
+pub fn reajustar_preco(preco: f64, percentual: f64, passo: f64) -> f64 {
+    let reajustado = ((preco * (1.0 + percentual / 100.0)) / passo).round() * passo;
+    (reajustado * 100.0).round() / 100.0
+}
+
+fn executar_operacao_servico(conn: &Connection, operacao: OperacaoServico) -> Result<Sucesso, FalhaItem> {
+    match operacao {
+        OperacaoServico::Criar(mut servico) => {
+            servico.id = None;
+            servicos::validar_regras(&servico).map_err(|m| falha(StatusCode::BAD_REQUEST, m))?;
+            let id = db::salvar_servico(conn, &servico)
+                .map_err(|e| falha(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao cadastrar serviço: {}", e)))?;
+            Ok(("Serviço cadastrado.".to_string(), vec![id]))
+        }
+        OperacaoServico::Atualizar { id, alteracoes, versao } => {
+            let atual = match db::buscar_servico_por_id(conn, id) {
+                Ok(Some(servico)) => servico,
+                Ok(None) => return Err(falha(StatusCode::NOT_FOUND, format!("Serviço {} não encontrado.", id))),
+                Err(e) => return Err(falha(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao buscar serviço: {}", e))),
+            };
+            conferir_versao(conn, "servicos", id, versao)?;
+            servicos::alterar_servico(conn, &atual, &alteracoes).map_err(|(s, m)| falha(s, m))?;
+            Ok(("Serviço atualizado.".to_string(), vec![id]))
+        }
+        OperacaoServico::Reajustar { percentual, servicos_ids, arredondar_para } => {
+            if !percentual.is_finite() || percentual <= -100.0 {
+                return Err(falha(StatusCode::BAD_REQUEST, "percentual deve ser maior que -100.".to_string()));
+            }
+            let passo = arredondar_para.unwrap_or(0.01);
+            if !passo.is_finite() || passo <= 0.0 {
+                return Err(falha(StatusCode::BAD_REQUEST, "arredondar_para deve ser positivo.".to_string()));
+            }
+            let erro_banco = |e: rusqlite::Error| falha(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao reajustar preços: {}", e));
+            let alvos = match servicos_ids {
+                None => db::listar_servicos(conn).map_err(erro_banco)?,
+                Some(ids) => {
+                    let mut alvos = Vec::new();
+                    for id in ids {
+                        match db::buscar_servico_por_id(conn, id).map_err(erro_banco)? {
+                            Some(servico) => alvos.push(servico),
+                            None => return Err(falha(StatusCode::NOT_FOUND, format!("Serviço {} não encontrado.", id))),
+                        }
+                    }
+                    alvos
+                }
+            };
+            let mut ids = Vec::new();
+            for mut servico in alvos {
+                let novo = reajustar_preco(servico.preco, percentual, passo);
+                if novo == servico.preco {
+                    continue;
+                }
+                servico.preco = novo;
+                db::salvar_servico(conn, &servico).map_err(erro_banco)?;
+                ids.extend(servico.id);
+            }
+            Ok((format!("{} serviço(s) reajustado(s).", ids.len()), ids))
+        }
+    }
+}
+
+fn responder<T>(
+    conn: &Connection,
+    pedido: PedidoLote<T>,
+    executar: impl FnMut(&Connection, T) -> Result<Sucesso, FalhaItem>,
+) -> (StatusCode, Json<ApiResponse<ResultadoLote>>) {
+    let erro = |status: StatusCode, message: String| (status, Json(ApiResponse { success: false, message, data: None }));
+    if pedido.operacoes.is_empty() {
+        return erro(StatusCode::BAD_REQUEST, "Informe ao menos uma operação.".to_string());
+    }
+    if pedido.operacoes.len() > MAX_OPERACOES {
+        return erro(StatusCode::BAD_REQUEST, format!("No máximo {} operações por lote.", MAX_OPERACOES));
+    }
+    let resultado = match executar_lote(conn, pedido.modo, pedido.operacoes, executar) {
+        Ok(resultado) => resultado,
+        Err(e) => {
+            eprintln!("Erro ao executar lote: {}", e);
+            return erro(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao executar lote: {}", e));
+        }
+    };
+    // No tudo_ou_nada, o status da resposta é o do item que derrubou o lote.
+    let status = match (resultado.modo, resultado.itens.iter().find(|i| !i.ok)) {
+        (ModoLote::TudoOuNada, Some(item)) => StatusCode::from_u16(item.status).unwrap_or(StatusCode::CONFLICT),
+        _ => StatusCode::OK,
+    };
+    let message = match (resultado.aplicado, resultado.modo) {
+        (false, ModoLote::TudoOuNada) => format!("Lote desfeito: a operação {} falhou.", resultado.itens.len() - 1),
+        _ => format!("{} operação(ões) aplicada(s), {} com falha.", resultado.sucesso, resultado.falhas),
+    };
+    (status, Json(ApiResponse { success: resultado.falhas == 0, message, data: Some(resultado) }))
+}
+
+/// POST /agendamentos/lote — `{modo?, operacoes: [{op: "criar" | "atualizar" | "mover" | "cancelar", ...}]}`.
+pub async fn agendamentos_em_lote_api(
+    State(conn): State<Db>,
+    Json(pedido): Json<PedidoLote<OperacaoAgendamento>>,
+) -> (StatusCode, Json<ApiResponse<ResultadoLote>>) {
+    let conn = conn.lock().unwrap();
+    responder(&conn, pedido, executar_operacao_agendamento)
+}
+
+/// POST /servicos/lote — `{modo?, operacoes: [{op: "criar" | "atualizar" | "reajustar", ...}]}`.
+pub async fn servicos_em_lote_api(
+    State(conn): State<Db>,
+    Json(pedido): Json<PedidoLote<OperacaoServico>>,
+) -> (StatusCode, Json<ApiResponse<ResultadoLote>>) {
+    let conn = conn.lock().unwrap();
+    responder(&conn, pedido, executar_operacao_servico)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn operacoes<T: serde::de::DeserializeOwned>(valor: JsonValue) -> Vec<T> {
+        serde_json::from_value(valor).unwrap()
+    }
+
+    #[test]
+    fn test_lote_tudo_ou_nada_e_por_item() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = crate::models::Cliente::new("Ana".to_string(), "11999990000".to_string(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let corte = Servico { id: None, nome: "Corte".to_string(), preco: 35.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
+        let barba = Servico { nome: "Barba".to_string(), preco: 19.9, ..corte.clone() };
+        let corte_id = db::salvar_servico(&conn, &corte).unwrap();
+        let barba_id = db::salvar_servico(&conn, &barba).unwrap();
+
+        let criar = json!([
+            { "op": "criar", "cliente_id": cliente_id, "servicos_ids": [corte_id], "data_hora": "2030-01-05 10:00:00", "preco": 35.0 },
+            { "op": "cancelar", "id": 999 },
+        ]);
+        let resultado = executar_lote(&conn, ModoLote::TudoOuNada, operacoes(criar.clone()), executar_operacao_agendamento).unwrap();
+        assert!(!resultado.aplicado);
+        assert_eq!(resultado.itens[1].status, 404);
+        assert!(db::listar_agendamentos(&conn).unwrap().is_empty());
+
+        let resultado = executar_lote(&conn, ModoLote::PorItem, operacoes(criar), executar_operacao_agendamento).unwrap();
+        assert!(resultado.aplicado);
+        assert_eq!((resultado.sucesso, resultado.falhas), (1, 1));
+        let id = resultado.itens[0].ids[0];
+
+        let seguintes = json!([
+            { "op": "atualizar", "id": id, "alteracoes": { "concluido": true }, "versao": 7 },
+            { "op": "cancelar", "id": id },
+        ]);
+        let resultado = executar_lote(&conn, ModoLote::PorItem, operacoes(seguintes), executar_operacao_agendamento).unwrap();
+        assert_eq!(resultado.itens[0].status, 409);
+        assert!(resultado.itens[1].ok);
+        assert_eq!(db::status_agendamento(&conn, id).unwrap(), db::STATUS_AGENDAMENTO_CANCELADO);
+
+        let reajuste = json!([{ "op": "reajustar", "percentual": 10, "arredondar_para": 0.5 }]);
+        let resultado = executar_lote(&conn, ModoLote::TudoOuNada, operacoes(reajuste), executar_operacao_servico).unwrap();
+        assert_eq!(resultado.itens[0].ids.len(), 2);
+        assert_eq!(db::buscar_servico_por_id(&conn, corte_id).unwrap().unwrap().preco, 38.5);
+        assert_eq!(db::buscar_servico_por_id(&conn, barba_id).unwrap().unwrap().preco, 22.0);
+    }
+
+    #[test]
+    fn test_savepoint_desfaz_so_o_item_que_falhou_no_meio() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        // O item do meio grava um cliente e falha depois: só essa gravação é desfeita.
+        let executar = |conn: &Connection, nome: &str| -> Result<Sucesso, FalhaItem> {
+            let mut cliente = crate::models::Cliente::new(nome.to_string(), "11999990000".to_string(), None);
+            let id = db::salvar_cliente(conn, &mut cliente).map_err(|e| falha(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            if nome == "Falha" {
+                return Err(falha(StatusCode::UNPROCESSABLE_ENTITY, "falhou depois de gravar".to_string()));
+            }
+            Ok(("ok".to_string(), vec![id]))
+        };
+        let nomes = |clientes: Vec<crate::models::Cliente>| clientes.into_iter().map(|c| c.nome).collect::<Vec<_>>();
+
+        let resultado = executar_lote(&conn, ModoLote::PorItem, vec!["Ana", "Falha", "Bia"], executar).unwrap();
+        assert!(resultado.aplicado);
+        assert_eq!((resultado.sucesso, resultado.falhas), (2, 1));
+        assert_eq!(nomes(db::listar_clientes(&conn).unwrap()), ["Ana", "Bia"]);
+
+        // No tudo_ou_nada, para no item que falhou e desfaz também os anteriores.
+        let resultado = executar_lote(&conn, ModoLote::TudoOuNada, vec!["Caio", "Falha", "Davi"], executar).unwrap();
+        assert!(!resultado.aplicado);
+        assert_eq!(resultado.itens.len(), 2);
+        assert_eq!(nomes(db::listar_clientes(&conn).unwrap()), ["Ana", "Bia"]);
+    }
+}
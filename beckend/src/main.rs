@@ -1,14 +1,16 @@
 
 use axum::{
-    extract::{Path, State, Query},
-    http::{Method, StatusCode},
-    routing::get,
+    extract::{DefaultBodyLimit, Path, State, Query},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware,
+    routing::{delete, get, post},
     Json, Router,};
 use rusqlite::Connection;
 use std::sync::{Arc, Mutex};
 use std::env;
+use clap::Parser;
 use tokio::net::TcpListener;
-use tower_http::cors::{Any, CorsLayer};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
 
 mod db;
 mod models;
@@ -17,6 +19,24 @@ mod licenca;
 mod calc_preco;
 mod servicos;
 mod agendamentos;
+mod lgpd;
+mod auditoria;
+mod backup;
+mod config;
+mod comandos;
+mod notificacoes;
+mod webhooks;
+mod calendario;
+mod publico;
+mod espera;
+mod scheduling;
+mod recursos;
+mod patch;
+mod lote;
+mod planilhas;
+mod transferencia;
+mod sincronizacao;
+mod eventos;
  
 use db::{buscar_cliente_por_id, excluir_cliente, salvar_cliente};
 use models::Cliente;
@@ -59,6 +79,7 @@ async fn criar_cliente(State(conn): State<Db>, Json(mut cliente): Json<Cliente>)
     let conn = conn.lock().unwrap();
     match salvar_cliente(&conn, &mut cliente) {
         Ok(_) => {
+            webhooks::emitir_ou_logar(&conn, webhooks::EVENTO_CLIENTE_CRIADO, &cliente);
             let response = ApiResponse {
                 success: true,
                 message: "Cliente cadastrado com sucesso!".to_string(),
@@ -78,10 +99,11 @@ async fn criar_cliente(State(conn): State<Db>, Json(mut cliente): Json<Cliente>)
     }
 }
 
-async fn obter_cliente(Path(id): Path<i32>, State(conn): State<Db>) -> Json<Option<Cliente>> {
+async fn obter_cliente(Path(id): Path<i32>, State(conn): State<Db>) -> (HeaderMap, Json<Option<Cliente>>) {
     let conn = conn.lock().unwrap();
     let cliente = buscar_cliente_por_id(&conn, id).unwrap_or(None);
-    Json(cliente)
+    let versao = patch::versao(&conn, "clientes", id).unwrap_or(None);
+    (patch::cabecalho_etag(versao), Json(cliente))
 }
 
 async fn atualizar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>, Json(mut cliente): Json<Cliente>) -> (StatusCode, Json<ApiResponse<Cliente>>) {
@@ -108,6 +130,43 @@ async fn atualizar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>, Json
     }
 }
 
+/// PATCH /clientes/:id — altera só os campos enviados (merge-patch; `null` limpa o e-mail).
+async fn patch_cliente_api(
+    Path(id): Path<i32>,
+    State(conn): State<Db>,
+    headers: HeaderMap,
+    Json(alteracoes): Json<serde_json::Value>,
+) -> (StatusCode, HeaderMap, Json<ApiResponse<Cliente>>) {
+    let resposta = |status: StatusCode, versao: Option<i64>, message: String, data: Option<Cliente>| {
+        (status, patch::cabecalho_etag(versao), Json(ApiResponse { success: status.is_success(), message, data }))
+    };
+    let conn = conn.lock().unwrap();
+    let (atual, versao) = match (buscar_cliente_por_id(&conn, id), patch::versao(&conn, "clientes", id)) {
+        (Ok(Some(cliente)), Ok(Some(versao))) => (cliente, versao),
+        (Ok(_), Ok(_)) => return resposta(StatusCode::NOT_FOUND, None, format!("Cliente {} não encontrado.", id), None),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Erro ao buscar cliente: {}", e);
+            return resposta(StatusCode::INTERNAL_SERVER_ERROR, None, format!("Erro ao buscar cliente: {}", e), None);
+        }
+    };
+    if !patch::if_match_confere(&headers, versao) {
+        return resposta(StatusCode::CONFLICT, Some(versao), "O cliente foi alterado por outra pessoa; recarregue e tente de novo.".to_string(), Some(atual));
+    }
+    let mut cliente: Cliente = match patch::mesclar(&atual, &alteracoes, &["id"]) {
+        Ok(cliente) => cliente,
+        Err(mensagem) => return resposta(StatusCode::BAD_REQUEST, Some(versao), mensagem, None),
+    };
+    if cliente.nome.trim().is_empty() {
+        return resposta(StatusCode::BAD_REQUEST, Some(versao), "O nome do cliente não pode ficar vazio.".to_string(), None);
+    }
+    if let Err(e) = salvar_cliente(&conn, &mut cliente) {
+        eprintln!("Erro ao atualizar cliente: {}", e);
+        return resposta(StatusCode::INTERNAL_SERVER_ERROR, Some(versao), format!("Erro ao atualizar cliente: {}", e), None);
+    }
+    let nova = patch::versao(&conn, "clientes", id).unwrap_or(None);
+    resposta(StatusCode::OK, nova, "Cliente atualizado com sucesso!".to_string(), Some(cliente))
+}
+
 async fn deletar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
     let conn = conn.lock().unwrap();
     let result = excluir_cliente(&conn, id);
@@ -115,11 +174,12 @@ async fn deletar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>) -> (St
     match result {
         Ok(_) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Cliente excluído com sucesso!".to_string(), data: None })),
         Err(e) => {
-            if let Some(app_err) = e.sqlite_error() {
-                if app_err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY {
-                    let response = ApiResponse { success: false, message: "Não é possível excluir o cliente pois ele possui agendamentos.".to_string(), data: None };
-                    return (StatusCode::CONFLICT, Json(response));
-                }
+            // `excluir_cliente` devolve ExecuteReturnedResults quando há agendamentos; a FK cobre o resto.
+            let tem_vinculos = matches!(e, rusqlite::Error::ExecuteReturnedResults)
+                || e.sqlite_error().is_some_and(|erro| erro.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY);
+            if tem_vinculos {
+                let response = ApiResponse { success: false, message: "Não é possível excluir o cliente pois ele possui agendamentos. Para atender a um pedido de exclusão (LGPD), use POST /clientes/:id/anonimizar.".to_string(), data: None };
+                return (StatusCode::CONFLICT, Json(response));
             }
             eprintln!("Erro ao excluir cliente: {}", e);
             let response = ApiResponse { success: false, message: format!("Erro ao excluir cliente: {}", e), data: None };
@@ -129,31 +189,50 @@ async fn deletar_cliente_api(Path(id): Path<i32>, State(conn): State<Db>) -> (St
 }
 
 fn main() {
-    // Pega os argumentos passados na linha de comando.
-    let args: Vec<String> = env::args().collect();
+    // Compatibilidade com a forma antiga de chamar: `-cli`, `--cli`, `--server`.
+    let args: Vec<String> = env::args()
+        .enumerate()
+        .map(|(i, a)| match a.as_str() {
+            "-cli" | "--cli" | "-server" | "--server" if i == 1 => a.trim_start_matches('-').to_string(),
+            _ => a,
+        })
+        .collect();
+    let cli = comandos::Cli::parse_from(args);
 
-    // O primeiro argumento (índice 1) é o que nos interessa.
-    // Se nenhum argumento for passado, o padrão será "server".
-    // Aceitamos tanto `cli`/`server` quanto `-cli`/`--cli` etc.
-    let command_raw = args.get(1).map(|s| s.as_str()).unwrap_or("server");
-    let command = command_raw.trim_start_matches('-');
+    // Configuração efetiva: padrões -> agendou.toml -> APP_* -> flags.
+    let config_efetiva = match config::Config::carregar(&cli.flags.sobrescritas()) {
+        Ok(c) => c,
+        Err(erros) => {
+            eprintln!("❌ Configuração inválida:");
+            for e in &erros {
+                eprintln!("  - {}", e);
+            }
+            std::process::exit(comandos::SAIDA_USO);
+        }
+    };
+    config::definir(config_efetiva);
 
-    match command {
-        "cli" => {
+    // Sem subcomando, o padrão é "server".
+    match cli.comando.unwrap_or(comandos::Comando::Server) {
+        comandos::Comando::Cli => {
             println!("Iniciando modo CLI...");
+            auditoria::definir_ator_padrao("cli");
             if let Err(e) = menu::menu_principal() {
                 eprintln!("Erro ao executar o menu: {}", e);
             }
         }
-        "server" => {
+        comandos::Comando::Server => {
             println!("Iniciando modo Servidor...");
+            auditoria::definir_ator_padrao("api");
             // Inicia o runtime do Tokio e executa a lógica do servidor.
             if let Err(e) = iniciar_servidor() {
                 eprintln!("Falha ao iniciar o servidor: {}", e);
+                std::process::exit(comandos::SAIDA_ERRO);
             }
         }
-        _ => {
-            eprintln!("Comando inválido. Use 'cli' ou 'server'.");
+        comando => {
+            auditoria::definir_ator_padrao("cli");
+            std::process::exit(comandos::executar(comando));
         }
     }
 }
@@ -165,30 +244,122 @@ async fn iniciar_servidor() -> Result<(), Box<dyn std::error::Error>> {
     db::criar_tabelas(&conn)?;
  
     let db = Arc::new(Mutex::new(conn));
+    backup::iniciar_backups_automaticos(db.clone(), backup::ConfigBackup::da_config());
+    licenca::iniciar_verificacao_periodica(db.clone())?;
+    notificacoes::iniciar_notificacoes(db.clone());
+    webhooks::iniciar_webhooks(db.clone());
  
+    let origens = &config::atual().servidor.cors_origens;
+    let origem_permitida = if origens.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origens.iter().filter_map(|o| HeaderValue::from_str(o).ok()))
+    };
     let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
-        .allow_headers(Any);
+        .allow_origin(origem_permitida)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE, Method::OPTIONS])
+        .allow_headers(Any)
+        .expose_headers([
+            HeaderName::from_static("x-licenca-status"),
+            HeaderName::from_static("x-licenca-dias-restantes"),
+            HeaderName::from_static("x-licenca-aviso"),
+            header::ETAG,
+        ]);
  
     let app = Router::new()
         .route("/clientes", get(listar_clientes_api).post(criar_cliente))
-        .route("/clientes/:id", get(obter_cliente).put(atualizar_cliente_api).delete(deletar_cliente_api))
+        .route("/clientes/:id", get(obter_cliente).put(atualizar_cliente_api).patch(patch_cliente_api).delete(deletar_cliente_api))
+        .route("/clientes/:id/export", get(lgpd::exportar_cliente_api))
+        .route("/clientes/:id/anonimizar", post(lgpd::anonimizar_cliente_api))
     .route("/servicos", get(servicos::listar_servicos_query).post(servicos::criar_servico))
-    .route("/servicos/:id", get(servicos::obter_servico).put(servicos::atualizar_servico).delete(servicos::excluir_servico))
+    .route("/servicos/:id", get(servicos::obter_servico).put(servicos::atualizar_servico).patch(servicos::patch_servico).delete(servicos::excluir_servico))
     .route("/agendamentos", get(agendamentos::listar_agendamentos_api).post(agendamentos::criar_agendamento_api_incoming))
     .route("/availability", get(agendamentos::availability_api))
+    .route("/availability/range", get(agendamentos::availability_range_api))
+    .route("/availability/proximo", get(agendamentos::availability_proximo_api))
     .route("/work_windows", get(agendamentos::listar_work_windows_api).post(agendamentos::criar_work_window_api))
-    .route("/agendamentos/:id", get(agendamentos::obter_agendamento_api).put(agendamentos::atualizar_agendamento_api).delete(agendamentos::excluir_agendamento_api))
+    .route("/agenda/regras", get(agendamentos::obter_regras_agenda_api).put(agendamentos::atualizar_regras_agenda_api))
+    .route("/agendamentos/:id", get(agendamentos::obter_agendamento_api).put(agendamentos::atualizar_agendamento_api).patch(agendamentos::patch_agendamento_api).delete(agendamentos::excluir_agendamento_api))
+        .route("/auditoria", get(auditoria::listar_auditoria_api))
+        .route("/backup", get(backup::listar_backups_api).post(backup::criar_backup_api))
+        .route("/backup/:nome", get(backup::baixar_backup_api))
+        .route("/sistema/licenca", get(licenca::status_licenca_api))
+        .route("/notificacoes", get(notificacoes::listar_notificacoes_api))
+        .route("/notificacoes/:id/reenviar", post(notificacoes::reenviar_notificacao_api))
+        .route("/agenda.ics", get(calendario::feed_ics_api))
+        .route("/agenda/link", get(calendario::link_feed_api))
+        .route("/agenda/importar", post(calendario::importar_ics_api))
+        .route("/agenda/bloqueios", get(calendario::listar_bloqueios_api))
+        .route("/agenda/bloqueios/:id", delete(calendario::excluir_bloqueio_api))
+        .route("/agendamentos/:id/ics", get(calendario::agendamento_ics_api))
+        .route("/recursos", get(recursos::listar_recursos_api).post(recursos::criar_recurso_api))
+        .route("/recursos/:id", delete(recursos::excluir_recurso_api))
+        .route("/servicos/:id/fases", get(servicos::listar_fases_api).put(servicos::definir_fases_api))
+        .route("/agendamentos/lote", post(lote::agendamentos_em_lote_api))
+        .route("/servicos/lote", post(lote::servicos_em_lote_api))
+        .route("/agendamentos/:id/reagendar", post(agendamentos::reagendar_agendamento_api))
+        .route("/agendamentos/:id/historico", get(agendamentos::historico_agendamento_api))
+        .route("/agendamentos/:id/linha_do_tempo", get(agendamentos::linha_do_tempo_api))
+        .route("/servicos/:id/recursos", get(recursos::recursos_do_servico_api).put(recursos::definir_recursos_do_servico_api))
+        .route("/export/clientes.csv", get(planilhas::exportar_clientes_api))
+        .route("/export/servicos.csv", get(planilhas::exportar_servicos_api))
+        .route("/export/agendamentos.csv", get(planilhas::exportar_agendamentos_api))
+        .route("/import/clientes", post(planilhas::importar_clientes_api))
+        .route("/import/servicos", post(planilhas::importar_servicos_api))
+        .route("/eventos", get(eventos::eventos_api))
+        .route("/eventos/link", get(eventos::link_eventos_api))
+        .route("/sync", get(sincronizacao::feed_sync_api).post(sincronizacao::enviar_sync_api))
+        .route("/sistema/exportar", get(transferencia::exportar_banco_api))
+        .route("/sistema/importar", post(transferencia::importar_banco_api).layer(DefaultBodyLimit::max(64 * 1024 * 1024)))
+        .route("/lista_espera", get(espera::listar_espera_api).post(espera::criar_espera_api))
+        .route("/lista_espera/:id", delete(espera::excluir_espera_api))
+        .route("/webhooks", get(webhooks::listar_webhooks_api).post(webhooks::criar_webhook_api))
+        .route("/webhooks/:id", delete(webhooks::excluir_webhook_api))
+        .route("/webhooks/entregas", get(webhooks::listar_entregas_api))
+        .route("/webhooks/entregas/:id/reenviar", post(webhooks::reenviar_entrega_api))
+        .nest("/public", publico::rotas())
+        .layer(middleware::from_fn(eventos::avisar_apos_alteracao))
+        .layer(middleware::from_fn(licenca::controlar_licenca))
+        .layer(middleware::from_fn(auditoria::identificar_ator))
         .layer(cors)
         .with_state(db);
  
-    let listener = TcpListener::bind("127.0.0.1:3000").await?;
+    let listener = TcpListener::bind(config::atual().endereco()).await?;
     println!(
         "Servidor rodando em http://{}",
         listener.local_addr()?
     );
-    axum::serve(listener, app).await?;
+    // O IP do cliente é usado no limite de requisições das rotas públicas.
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use models::Agendamento;
+
+    #[tokio::test]
+    async fn test_excluir_cliente_com_agendamentos_sugere_anonimizar() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente { id: None, nome: "Ana".into(), telefone: "11988887777".into(), email: None };
+        salvar_cliente(&conn, &mut cliente).unwrap();
+        let id = cliente.id.unwrap();
+        let data_hora = NaiveDate::from_ymd_opt(2030, 1, 2).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        let agendamento = Agendamento { id: None, cliente_id: id, servicos_ids: vec![], data_hora, preco: 0.0, concluido: false };
+        db::salvar_agendamento(&conn, &agendamento).unwrap();
+        let db: Db = Arc::new(Mutex::new(conn));
+
+        let (status, Json(resposta)) = deletar_cliente_api(Path(id), State(db.clone())).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert!(resposta.message.contains("/anonimizar"));
+
+        let mut sem_agendamentos = Cliente { id: None, nome: "Bia".into(), telefone: "11977776666".into(), email: None };
+        salvar_cliente(&db.lock().unwrap(), &mut sem_agendamentos).unwrap();
+        let (status, _) = deletar_cliente_api(Path(sem_agendamentos.id.unwrap()), State(db)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
@@ -1,4 +1,3 @@
-use chrono::Local;
 //  Padrão da linguagem
 use std::io::{self, Write};
 
@@ -6,9 +5,11 @@ use std::io::{self, Write};
 use chrono::{Datelike, NaiveDateTime};
 
 //  Banco de dados
-use rusqlite::{params, Connection};
+use rusqlite::Connection;
 
 //  Módulos internos
+use crate::config;
+use crate::recursos;
 use crate::db::*;
 use crate::models::*;
 use crate::licenca::*;
@@ -20,6 +21,12 @@ pub fn menu_principal() -> rusqlite::Result<()> {
     criar_tabelas(&conn)?;
 
     loop {
+        let licenca = status_licenca(&conn)?;
+        match (&licenca.aviso, licenca.expira_em) {
+            (Some(aviso), _) => println!("\n⚠️ {}", aviso),
+            (None, Some(expira_em)) => println!("\n📄 Licença ativa: {} dia(s) restante(s) (até {}).", licenca.dias_restantes, expira_em.format("%d/%m/%Y")),
+            _ => {}
+        }
         println!("\n💈 Menu Principal");
         println!("0. Cadastrar serviço");
         println!("1. Cadastrar cliente");
@@ -42,6 +49,7 @@ pub fn menu_principal() -> rusqlite::Result<()> {
         println!("18. Ver agendamentos concluídos");
         println!("19. Relatório de produtividade do mês");
         println!("20. Relatório de produtividade do dia");
+        println!("21. Ver auditoria");
         print!("Escolha uma opção: ");
         io::stdout().flush().unwrap();
 
@@ -73,6 +81,7 @@ pub fn menu_principal() -> rusqlite::Result<()> {
             "18" => listar_agendamentos_por_status(&conn, true)?,
             "19" => relatorio_produtividade_mensal(&conn)?,
             "20" => relatorio_produtividade_do_dia(&conn)?,
+            "21" => ver_auditoria_terminal(&conn)?,
             _ => println!("❌ Opção inválida."),
         }
     }
@@ -219,7 +228,7 @@ println!("📌 Agendando para: {} ({})", cliente.nome, cliente.telefone);
         }
     };
 
-    if horario < Local::now().naive_local() {
+    if horario < config::agora() {
         println!("❌ Não é possível agendar no passado. Por favor, escolha uma data e hora futuras.");
         return Ok(());
     }
@@ -235,6 +244,10 @@ println!("📌 Agendando para: {} ({})", cliente.nome, cliente.telefone);
             preco: total_preco,
             concluido: false,
         };
+        if let Some(recurso) = recursos::conflito_de_recursos(conn, &agendamento, None)? {
+            println!("⚠️ {}", recursos::mensagem_conflito(&recurso));
+            return Ok(());
+        }
         let id = salvar_agendamento(conn, &agendamento)?;
         println!("✅ Agendamento criado com ID: {}", id);
     }
@@ -258,29 +271,17 @@ pub fn ver_agendamentos(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
 }
 
 pub fn validar_licenca_terminal(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
-    let mut cliente_id = String::new();
-    let chave = "BARBER2025";
-
-    print!("ID do cliente: "); io::stdout().flush().unwrap(); io::stdin().read_line(&mut cliente_id).unwrap();
-    let id: i32 = match cliente_id.trim().parse() {
-        Ok(id) if id > 0 => id,
-        _ => {
-            println!("❌ ID do cliente inválido.");
-            return Ok(());
-        }
-    };
-
-    let codigo = gerar_codigo_semanal(chave);
-    println!("🔐 Código semanal gerado: {}", codigo);
+    if let Some(atual) = licenca_instalada(conn)? {
+        println!("📄 Licença atual: {} ({}) até {}", atual.barbearia, atual.plano, atual.expira_em.format("%d/%m/%Y"));
+    }
 
-    print!("Digite o código recebido: "); io::stdout().flush().unwrap();
+    print!("Cole o token de licença recebido: "); io::stdout().flush().unwrap();
     let mut entrada = String::new(); io::stdin().read_line(&mut entrada).unwrap();
 
-    if let Some(validade) = validar_codigo(entrada.trim(), chave) {
-        atualizar_licenca(conn, id, validade)?;
-        println!("✅ Licença válida até: {}", validade);
-    } else {
-        println!("❌ Código inválido.");
+    match instalar_licenca(conn, entrada.trim()) {
+        Ok(licenca) => println!("✅ Licença do plano {} válida até: {}", licenca.plano, licenca.expira_em.format("%d/%m/%Y %H:%M UTC")),
+        Err(ErroLicenca::Banco(e)) => return Err(e),
+        Err(e) => println!("❌ Licença recusada: {}.", e),
     }
     Ok(())
 }
@@ -320,7 +321,7 @@ pub fn cadastrar_servico(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
         }
     };
 
-    let servico = Servico { id: None, nome: nome.trim().into(), preco, duracao_min };
+    let servico = Servico { id: None, nome: nome.trim().into(), preco, duracao_min, buffer_min: None, alinhamento_min: None };
     let id = salvar_servico(conn, &servico)?;
     println!("✅ Serviço cadastrado com ID: {}", id);
     Ok(())
@@ -426,8 +427,15 @@ pub fn excluir_agendamento_terminal(conn: &rusqlite::Connection) -> rusqlite::Re
             return Ok(());
         }
     };
+    let anterior = buscar_agendamento_por_id(conn, id).ok();
     excluir_agendamento(conn, id)?;
     println!("✅ Agendamento excluído com sucesso.");
+    if let Some(agendamento) = anterior {
+        let avisados = crate::espera::vaga_liberada(conn, &agendamento);
+        if avisados > 0 {
+            println!("🔔 {} pedido(s) da lista de espera avisado(s) sobre a vaga.", avisados);
+        }
+    }
     Ok(())
 }
 
@@ -473,10 +481,16 @@ pub fn editar_servico_terminal(conn: &rusqlite::Connection) -> rusqlite::Result<
         }
     };
 
-    conn.execute(
-        "UPDATE servicos SET nome = ?1, preco = ?2 WHERE id = ?3",
-        params![nome.trim(), preco, id],
-    )?;
+    let mut servico = match buscar_servico_por_id(conn, id)? {
+        Some(s) => s,
+        None => {
+            println!("❌ Serviço com ID {} não encontrado.", id);
+            return Ok(());
+        }
+    };
+    servico.nome = nome.trim().into();
+    servico.preco = preco;
+    salvar_servico(conn, &servico)?;
     println!("✅ Serviço atualizado com sucesso.");
     Ok(())
 }
@@ -591,10 +605,7 @@ pub fn editar_agendamento_terminal(conn: &rusqlite::Connection) -> rusqlite::Res
         }
     };
 
-    conn.execute(
-        "UPDATE agendamentos SET data_hora = ?1, preco = ?2 WHERE id = ?3",
-        params![horario.format("%Y-%m-%d %H:%M:%S").to_string(), preco, id],
-    )?;
+    atualizar_agendamento(conn, id, Some(horario), None, Some(preco), None)?;
     println!("✅ Agendamento atualizado com sucesso.");
     Ok(())
 }
@@ -692,12 +703,10 @@ pub fn marcar_agendamento_concluido(conn: &rusqlite::Connection) -> rusqlite::Re
         }
     };
 
-    let mut stmt = conn.prepare("UPDATE agendamentos SET concluido = 1 WHERE id = ?1")?;
-    let rows = stmt.execute(rusqlite::params![id])?;
-
-    if rows == 0 {
+    if buscar_agendamento_por_id(conn, id).is_err() {
         println!("❌ Nenhum agendamento encontrado com esse ID.");
     } else {
+        atualizar_agendamento(conn, id, None, None, None, Some(true))?;
         println!("✅ Agendamento marcado como concluído!");
     }
 
@@ -726,7 +735,7 @@ pub fn listar_agendamentos_por_status(conn: &rusqlite::Connection, concluido: bo
     Ok(())
 }
 pub fn relatorio_produtividade_mensal(conn: &Connection) -> rusqlite::Result<()> {
-    let hoje = Local::now().naive_local();
+    let hoje = config::agora();
     let mes = hoje.month();
     let ano = hoje.year();
 
@@ -738,7 +747,7 @@ pub fn relatorio_produtividade_mensal(conn: &Connection) -> rusqlite::Result<()>
 }
 
 pub fn relatorio_produtividade_do_dia(conn: &Connection) -> rusqlite::Result<()> {
-    let hoje = Local::now().naive_local().date();
+    let hoje = config::agora().date();
     let agendamentos = listar_agendamentos(conn)?;
 
     let concluidos_hoje: Vec<_> = agendamentos
@@ -754,3 +763,56 @@ pub fn relatorio_produtividade_do_dia(conn: &Connection) -> rusqlite::Result<()>
 
     Ok(())
 }
+
+pub fn ver_auditoria_terminal(conn: &Connection) -> rusqlite::Result<()> {
+    print!("Entidade (cliente, servico, agendamento, work_window ou Enter para todas): ");
+    io::stdout().flush().unwrap();
+    let mut entidade = String::new(); io::stdin().read_line(&mut entidade).unwrap();
+
+    print!("ID da entidade (ou Enter para todos): ");
+    io::stdout().flush().unwrap();
+    let mut id = String::new(); io::stdin().read_line(&mut id).unwrap();
+
+    let entidade_id = match id.trim() {
+        "" => None,
+        valor => match valor.parse::<i64>() {
+            Ok(i) => Some(i),
+            Err(_) => {
+                println!("❌ ID inválido.");
+                return Ok(());
+            }
+        },
+    };
+
+    let filtro = FiltroAuditoria {
+        entidade: Some(entidade.trim().to_string()).filter(|e| !e.is_empty()),
+        entidade_id,
+        limite: 50,
+        ..Default::default()
+    };
+    let registros = listar_auditoria(conn, &filtro)?;
+    if registros.is_empty() {
+        println!("📭 Nenhum registro de auditoria encontrado.");
+        return Ok(());
+    }
+
+    println!("🕵️ Últimas alterações:");
+    for r in registros {
+        println!(
+            "  [{}] {} | {} #{} | {} | por: {}",
+            r.id,
+            r.registrado_em.format("%d/%m/%Y %H:%M:%S"),
+            r.entidade,
+            r.entidade_id,
+            r.acao,
+            r.ator
+        );
+        if let Some(antes) = &r.antes {
+            println!("      antes:  {}", antes);
+        }
+        if let Some(depois) = &r.depois {
+            println!("      depois: {}", depois);
+        }
+    }
+    Ok(())
+}
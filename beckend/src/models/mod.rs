@@ -47,7 +47,66 @@ pub struct Servico {
     pub nome: String,
     pub preco: f64,
     pub duracao_min: i32,
+    /// Limpeza/preparo depois do serviço; `None` usa `agenda.buffer_min`.
+    #[serde(default)]
+    pub buffer_min: Option<i32>,
+    /// O serviço só começa em múltiplos destes minutos (ex.: 30 → :00 e :30).
+    #[serde(default)]
+    pub alinhamento_min: Option<i32>,
 }
+/// Fase de um serviço feito em etapas (ex.: luzes: aplicação, pausa, finalização).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FaseServico {
+    pub nome: String,
+    pub duracao_min: i32,
+    /// Falso nas pausas (tempo de ação da química): o profissional pode atender outro cliente.
+    pub ocupa_profissional: bool,
+}
+
+/// Trecho da linha do tempo de um agendamento: um serviço inteiro ou uma das suas fases.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EtapaAgendamento {
+    pub servico_id: i32,
+    pub servico: String,
+    pub fase: Option<String>,
+    pub inicio: NaiveDateTime,
+    pub fim: NaiveDateTime,
+    pub ocupa_profissional: bool,
+}
+
+/// Remarcação registrada no histórico do agendamento.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemarcacaoAgendamento {
+    pub id: i64,
+    pub agendamento_id: i32,
+    pub data_hora_anterior: NaiveDateTime,
+    pub data_hora_nova: NaiveDateTime,
+    /// "reagendar" (arrastar na agenda), "edicao" (PUT do agendamento) ou "cliente" (link público).
+    pub origem: String,
+    pub motivo: Option<String>,
+    pub registrado_em: NaiveDateTime,
+}
+
+/// Motivo de um horário não poder receber o agendamento.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConflitoHorario {
+    /// "passado", "fora_do_expediente", "alinhamento", "agendamento", "bloqueio" ou "recurso".
+    pub tipo: String,
+    pub mensagem: String,
+    pub agendamento_id: Option<i32>,
+    pub inicio: Option<NaiveDateTime>,
+    pub fim: Option<NaiveDateTime>,
+}
+
+/// Recurso físico que só atende um cliente por vez (lavatório, cadeira de química...).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recurso {
+    pub id: Option<i32>,
+    pub nome: String,
+    #[serde(default)]
+    pub descricao: Option<String>,
+}
+
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct UsuarioSistema {
@@ -55,4 +114,102 @@ pub struct UsuarioSistema {
     pub nome: String,
     pub licenca_ativa: bool,
     pub codigo_licenca: String,
-}
\ No newline at end of file
+}
+
+/// Entrada do log de auditoria (somente inserção).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegistroAuditoria {
+    pub id: i64,
+    pub entidade: String,
+    pub entidade_id: i64,
+    /// "criacao", "atualizacao", "exclusao", "mudanca_status", "anonimizacao", "exportacao",
+    /// "importacao" ou "remarcacao"
+    pub acao: String,
+    pub antes: Option<serde_json::Value>,
+    pub depois: Option<serde_json::Value>,
+    pub ator: String,
+    pub registrado_em: NaiveDateTime,
+}
+
+/// Lembrete na fila de saída (tabela `notificacoes`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notificacao {
+    pub id: i64,
+    pub agendamento_id: i32,
+    /// "lembrete_24h", "lembrete_2h", ...
+    pub tipo: String,
+    pub canal: String,
+    pub destino: String,
+    pub assunto: String,
+    pub mensagem: String,
+    /// "pendente", "enviada", "falhou" ou "cancelada"
+    pub status: String,
+    pub tentativas: i64,
+    pub enviar_em: NaiveDateTime,
+    pub proxima_tentativa: Option<NaiveDateTime>,
+    pub ultimo_erro: Option<String>,
+    pub criado_em: NaiveDateTime,
+    pub enviado_em: Option<NaiveDateTime>,
+}
+
+/// Assinatura de webhook: eventos enviados por POST para `url`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Ex.: "agendamento.criado"; "*" recebe todos.
+    pub eventos: Vec<String>,
+    pub ativo: bool,
+    pub criado_em: NaiveDateTime,
+    /// Só é devolvido na criação.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub segredo: Option<String>,
+}
+
+/// Uma entrega (ou tentativa pendente) de evento para um webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntregaWebhook {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub evento: String,
+    pub payload: serde_json::Value,
+    /// "pendente", "entregue" ou "falhou"
+    pub status: String,
+    pub tentativas: i64,
+    pub proxima_tentativa: Option<NaiveDateTime>,
+    /// Status HTTP da última resposta, se houve resposta.
+    pub resposta_status: Option<i64>,
+    pub ultimo_erro: Option<String>,
+    pub criado_em: NaiveDateTime,
+    pub entregue_em: Option<NaiveDateTime>,
+}
+
+/// Pedido na lista de espera: o cliente quer um horário em um intervalo de datas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntradaEspera {
+    pub id: i64,
+    pub cliente_id: i32,
+    pub servicos_ids: Vec<i32>,
+    /// Soma das durações dos serviços no momento do pedido.
+    pub duracao_min: i64,
+    pub data_inicio: chrono::NaiveDate,
+    pub data_fim: chrono::NaiveDate,
+    /// "manha", "tarde", "noite" ou "qualquer"
+    pub periodo: String,
+    /// "aguardando" ou "avisado"
+    pub status: String,
+    pub criado_em: NaiveDateTime,
+    pub avisado_em: Option<NaiveDateTime>,
+    /// Horário liberado que gerou o aviso.
+    pub vaga_inicio: Option<NaiveDateTime>,
+    pub vaga_fim: Option<NaiveDateTime>,
+}
+
+/// Regras de marcação guardadas no banco (tabela `regras_agenda`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegrasAgenda {
+    /// Não oferece horários que comecem antes de agora + esta antecedência.
+    pub antecedencia_minima_min: i64,
+    /// Não oferece horários além de hoje + estes dias.
+    pub horizonte_dias: i64,
+}
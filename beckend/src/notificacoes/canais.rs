@@ -0,0 +1,155 @@
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::config::ConfigNotificacoes;
+use crate::models::Cliente;
+
+/// Mensagem pronta para envio (modelo já preenchido).
+#[derive(Debug, Clone, Serialize)]
+pub struct Mensagem {
+    pub notificacao_id: i64,
+    pub agendamento_id: i32,
+    pub tipo: String,
+    pub nome: String,
+    /// E-mail ou telefone, conforme o canal.
+    pub destino: String,
+    pub assunto: String,
+    pub corpo: String,
+}
+
+/// Meio de entrega dos lembretes. Os envios são bloqueantes e rodam fora do runtime
+/// (`spawn_blocking`); um `Err` conta como tentativa falha e será repetido.
+pub trait CanalNotificacao: Send + Sync {
+    fn nome(&self) -> &'static str;
+
+    /// Contato do cliente usado por este canal (`None` se ele não tiver).
+    fn destino(&self, cliente: &Cliente) -> Option<String>;
+
+    fn enviar(&self, mensagem: &Mensagem) -> Result<(), String>;
+}
+
+fn contato(valor: &str) -> Option<String> {
+    Some(valor.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+/// Monta o canal configurado em `notificacoes.canal`.
+pub fn canal_da_config(config: &ConfigNotificacoes) -> Result<Box<dyn CanalNotificacao>, String> {
+    match config.canal.as_str() {
+        "log" => Ok(Box::new(CanalArquivo { caminho: None })),
+        "arquivo" => Ok(Box::new(CanalArquivo { caminho: Some(PathBuf::from(&config.arquivo)) })),
+        "smtp" => CanalSmtp::novo(config).map(|c| Box::new(c) as Box<dyn CanalNotificacao>),
+        "webhook" => Ok(Box::new(CanalWebhook { url: config.webhook.url.clone(), token: config.webhook.token.clone() })),
+        outro => Err(format!("canal de notificação desconhecido: '{}'", outro)),
+    }
+}
+
+/// Escreve as mensagens como linhas JSON num arquivo, ou na saída padrão (canal "log").
+/// Útil para testes e para conferir os modelos antes de ligar um canal real.
+pub struct CanalArquivo {
+    pub caminho: Option<PathBuf>,
+}
+
+impl CanalNotificacao for CanalArquivo {
+    fn nome(&self) -> &'static str {
+        if self.caminho.is_some() { "arquivo" } else { "log" }
+    }
+
+    fn destino(&self, cliente: &Cliente) -> Option<String> {
+        contato(&cliente.telefone).or_else(|| cliente.email.as_deref().and_then(contato))
+    }
+
+    fn enviar(&self, mensagem: &Mensagem) -> Result<(), String> {
+        let linha = serde_json::to_string(mensagem).map_err(|e| e.to_string())?;
+        match &self.caminho {
+            Some(caminho) => {
+                let mut arquivo = std::fs::OpenOptions::new().create(true).append(true).open(caminho).map_err(|e| e.to_string())?;
+                writeln!(arquivo, "{}", linha).map_err(|e| e.to_string())
+            }
+            None => {
+                println!("[Notificação] {}", linha);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// E-mail via SMTP (exige que o cliente tenha e-mail cadastrado).
+pub struct CanalSmtp {
+    transporte: SmtpTransport,
+    remetente: Mailbox,
+}
+
+impl CanalSmtp {
+    pub fn novo(config: &ConfigNotificacoes) -> Result<Self, String> {
+        let smtp = &config.smtp;
+        let construtor = match smtp.seguranca.as_str() {
+            "tls" => SmtpTransport::relay(&smtp.host).map_err(|e| e.to_string())?,
+            "nenhuma" => SmtpTransport::builder_dangerous(&smtp.host),
+            _ => SmtpTransport::starttls_relay(&smtp.host).map_err(|e| e.to_string())?,
+        };
+        let mut construtor = construtor.port(smtp.porta).timeout(Some(Duration::from_secs(20)));
+        if !smtp.usuario.is_empty() {
+            construtor = construtor.credentials(Credentials::new(smtp.usuario.clone(), smtp.senha.clone()));
+        }
+        let remetente = smtp.remetente.parse().map_err(|e| format!("remetente inválido '{}': {}", smtp.remetente, e))?;
+        Ok(CanalSmtp { transporte: construtor.build(), remetente })
+    }
+}
+
+impl CanalNotificacao for CanalSmtp {
+    fn nome(&self) -> &'static str {
+        "smtp"
+    }
+
+    fn destino(&self, cliente: &Cliente) -> Option<String> {
+        cliente.email.as_deref().and_then(contato).filter(|e| e.contains('@'))
+    }
+
+    fn enviar(&self, mensagem: &Mensagem) -> Result<(), String> {
+        let endereco = mensagem.destino.parse().map_err(|e| format!("e-mail inválido '{}': {}", mensagem.destino, e))?;
+        let destino = Mailbox::new(Some(mensagem.nome.clone()), endereco);
+        let email = Message::builder()
+            .from(self.remetente.clone())
+            .to(destino)
+            .subject(&mensagem.assunto)
+            .body(mensagem.corpo.clone())
+            .map_err(|e| e.to_string())?;
+        self.transporte.send(&email).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+/// POST JSON genérico; serve para gateways de WhatsApp/SMS que recebem telefone e texto.
+pub struct CanalWebhook {
+    pub url: String,
+    pub token: String,
+}
+
+impl CanalNotificacao for CanalWebhook {
+    fn nome(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn destino(&self, cliente: &Cliente) -> Option<String> {
+        contato(&cliente.telefone)
+    }
+
+    fn enviar(&self, mensagem: &Mensagem) -> Result<(), String> {
+        let mut requisicao = ureq::post(&self.url).timeout(Duration::from_secs(20)).set("Content-Type", "application/json");
+        if !self.token.is_empty() {
+            requisicao = requisicao.set("Authorization", &format!("Bearer {}", self.token));
+        }
+        let corpo = serde_json::to_string(mensagem).map_err(|e| e.to_string())?;
+        match requisicao.send_string(&corpo) {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(codigo, resposta)) => {
+                Err(format!("HTTP {}: {}", codigo, resposta.into_string().unwrap_or_default().chars().take(200).collect::<String>()))
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
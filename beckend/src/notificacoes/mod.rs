@@ -0,0 +1,549 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::NaiveDateTime;
+use rusqlite::{Connection, OptionalExtension, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::{self, ConfigNotificacoes};
+use crate::db;
+use crate::models::{Agendamento, Cliente, Notificacao};
+
+pub mod canais;
+
+use canais::{CanalNotificacao, Mensagem};
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// LEMBRETES DE AGENDAMENTO
+// Fila de saída (`notificacoes`): o agendador enfileira lembretes antes de cada
+// horário e um despachante os entrega pelo canal configurado, com novas tentativas.
+// =================================================================================
+
+pub const STATUS_PENDENTE: &str = "pendente";
+pub const STATUS_ENVIADA: &str = "enviada";
+pub const STATUS_FALHOU: &str = "falhou";
+pub const STATUS_CANCELADA: &str = "cancelada";
+
+/// O que fica no lugar do assunto e da mensagem depois da anonimização do cliente.
+pub const TEXTO_ANONIMIZADO: &str = "[anonimizado]";
+
+/// Espera máxima entre duas tentativas de envio.
+const ESPERA_MAXIMA_MIN: i64 = 6 * 60;
+
+pub fn criar_tabela_notificacoes(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notificacoes (
+            id                    INTEGER PRIMARY KEY AUTOINCREMENT,
+            agendamento_id        INTEGER NOT NULL,
+            tipo                  TEXT NOT NULL,
+            -- Horário do agendamento quando o lembrete foi criado; se ele for remarcado
+            -- o lembrete antigo é cancelado e um novo é enfileirado.
+            data_hora_agendamento INTEGER NOT NULL,
+            canal                 TEXT NOT NULL,
+            destino               TEXT NOT NULL,
+            assunto               TEXT NOT NULL,
+            mensagem              TEXT NOT NULL,
+            status                TEXT NOT NULL,
+            tentativas            INTEGER NOT NULL DEFAULT 0,
+            enviar_em             INTEGER NOT NULL,
+            proxima_tentativa     INTEGER NOT NULL,
+            ultimo_erro           TEXT,
+            criado_em             INTEGER NOT NULL,
+            enviado_em            INTEGER,
+            UNIQUE (agendamento_id, tipo, data_hora_agendamento),
+            FOREIGN KEY (agendamento_id) REFERENCES agendamentos(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_notificacoes_fila ON notificacoes (status, proxima_tentativa)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Preenche `{nome}`, `{servicos}`, `{data}`, `{hora}`, `{preco}` e `{antecedencia}`.
+pub fn renderizar(modelo: &str, cliente: &Cliente, agendamento: &Agendamento, servicos: &[String], antecedencia_h: i64) -> String {
+    modelo
+        .replace("{nome}", &cliente.nome)
+        .replace("{servicos}", &servicos.join(", "))
+        .replace("{data}", &agendamento.data_hora.format("%d/%m/%Y").to_string())
+        .replace("{hora}", &agendamento.data_hora.format("%H:%M").to_string())
+        .replace("{preco}", &format!("R$ {:.2}", agendamento.preco))
+        .replace("{antecedencia}", &format!("{}h", antecedencia_h))
+}
+
+/// Espera antes da tentativa seguinte: 5, 10, 20, 40... minutos, até 6 horas.
+pub fn espera_apos(tentativas: i64) -> chrono::Duration {
+    let minutos = 5i64.saturating_mul(1 << (tentativas - 1).clamp(0, 20));
+    chrono::Duration::minutes(minutos.min(ESPERA_MAXIMA_MIN))
+}
+
+/// Enfileira os lembretes devidos até `agora`. Para cada agendamento pendente vale só a
+/// menor antecedência já alcançada: quem marca com 20h de antecedência recebe o lembrete
+/// de 24h na hora e o de 2h depois; quem marca com 1h recebe apenas o de 2h.
+/// Devolve quantos lembretes foram criados.
+pub fn enfileirar_lembretes(conn: &Connection, agora: NaiveDateTime, regras: &ConfigNotificacoes, canal: &dyn CanalNotificacao) -> Result<usize> {
+    let Some(maior) = regras.antecedencias_horas.iter().copied().max() else {
+        return Ok(0);
+    };
+    let agora_ts = agora.and_utc().timestamp();
+    let ids: Vec<i32> = {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM agendamentos WHERE concluido = 0 AND status != 'cancelado' AND data_hora > ?1 AND data_hora <= ?2 ORDER BY data_hora",
+        )?;
+        let linhas = stmt.query_map(params![agora_ts, agora_ts + maior * 3600], |row| row.get(0))?;
+        linhas.collect::<Result<_>>()?
+    };
+
+    let mut criados = 0;
+    for id in ids {
+        let agendamento = db::buscar_agendamento_por_id(conn, id)?;
+        let faltam = agendamento.data_hora - agora;
+        let Some(horas) = regras.antecedencias_horas.iter().copied().filter(|h| faltam <= chrono::Duration::hours(*h)).min() else {
+            continue;
+        };
+        let tipo = format!("lembrete_{}h", horas);
+        let data_hora_ts = agendamento.data_hora.and_utc().timestamp();
+        let existe: Option<i64> = conn
+            .query_row(
+                "SELECT id FROM notificacoes WHERE agendamento_id = ?1 AND tipo = ?2 AND data_hora_agendamento = ?3",
+                params![id, tipo, data_hora_ts],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if existe.is_some() {
+            continue;
+        }
+        let Some(cliente) = db::buscar_cliente_por_id(conn, agendamento.cliente_id)? else {
+            continue;
+        };
+
+        let servicos = db::buscar_nomes_servicos(conn, &agendamento.servicos_ids)?;
+        let mut assunto = renderizar(&regras.modelo_assunto, &cliente, &agendamento, &servicos, horas);
+        let mut mensagem = renderizar(&regras.modelo_mensagem, &cliente, &agendamento, &servicos, horas);
+        // `{link}` leva um link de uso único para o cliente confirmar ou cancelar.
+        if assunto.contains("{link}") || mensagem.contains("{link}") {
+            let link = crate::publico::link_acao(conn, &agendamento)?;
+            assunto = assunto.replace("{link}", &link);
+            mensagem = mensagem.replace("{link}", &link);
+        }
+        let enviar_em = (agendamento.data_hora - chrono::Duration::hours(horas)).and_utc().timestamp();
+        // Sem contato para o canal o lembrete já nasce como falha, para aparecer na API.
+        let (destino, status, erro) = match canal.destino(&cliente) {
+            Some(destino) => (destino, STATUS_PENDENTE, None),
+            None => (String::new(), STATUS_FALHOU, Some(format!("Cliente sem contato para o canal {}.", canal.nome()))),
+        };
+        conn.execute(
+            "INSERT INTO notificacoes (agendamento_id, tipo, data_hora_agendamento, canal, destino, assunto, mensagem,
+                                       status, enviar_em, proxima_tentativa, ultimo_erro, criado_em)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![id, tipo, data_hora_ts, canal.nome(), destino, assunto, mensagem, status, enviar_em, agora_ts, erro, agora_ts],
+        )?;
+        criados += 1;
+    }
+    Ok(criados)
+}
+
+/// Aviso de remarcação; além dos campos de `renderizar`, aceita `{anterior}`.
+pub const MODELO_REMARCACAO_ASSUNTO: &str = "Seu horário foi remarcado";
+pub const MODELO_REMARCACAO_MENSAGEM: &str = "Olá {nome}, seu horário de {servicos} foi remarcado de {anterior} para {data} às {hora}.";
+
+/// Põe na fila, para envio imediato, o aviso de que o agendamento mudou de `anterior` para o
+/// horário atual. Devolve `None` quando as notificações estão desligadas ou sem canal válido.
+/// Se o agendamento for remarcado de novo antes do envio, o aviso antigo é cancelado como os lembretes.
+pub fn enfileirar_aviso_remarcacao(conn: &Connection, agendamento: &Agendamento, anterior: NaiveDateTime, agora: NaiveDateTime) -> Result<Option<i64>> {
+    let regras = &config::atual().notificacoes;
+    if !regras.ativo {
+        return Ok(None);
+    }
+    let canal = match canais::canal_da_config(regras) {
+        Ok(canal) => canal,
+        Err(e) => {
+            eprintln!("[Notificações] Aviso de remarcação não enviado: {}", e);
+            return Ok(None);
+        }
+    };
+    let id = agendamento.id.unwrap_or_default();
+    let Some(cliente) = db::buscar_cliente_por_id(conn, agendamento.cliente_id)? else {
+        return Ok(None);
+    };
+    let servicos = db::buscar_nomes_servicos(conn, &agendamento.servicos_ids)?;
+    let anterior_txt = anterior.format("%d/%m/%Y %H:%M").to_string();
+    let assunto = renderizar(MODELO_REMARCACAO_ASSUNTO, &cliente, agendamento, &servicos, 0).replace("{anterior}", &anterior_txt);
+    let mensagem = renderizar(MODELO_REMARCACAO_MENSAGEM, &cliente, agendamento, &servicos, 0).replace("{anterior}", &anterior_txt);
+    let (destino, status, erro) = match canal.destino(&cliente) {
+        Some(destino) => (destino, STATUS_PENDENTE, None),
+        None => (String::new(), STATUS_FALHOU, Some(format!("Cliente sem contato para o canal {}.", canal.nome()))),
+    };
+    let agora_ts = agora.and_utc().timestamp();
+    // O horário anterior entra no tipo: cada remarcação gera o seu aviso.
+    let tipo = format!("remarcacao_{}", anterior.and_utc().timestamp());
+    conn.execute(
+        "INSERT OR IGNORE INTO notificacoes (agendamento_id, tipo, data_hora_agendamento, canal, destino, assunto, mensagem,
+                                             status, enviar_em, proxima_tentativa, ultimo_erro, criado_em)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![id, tipo, agendamento.data_hora.and_utc().timestamp(), canal.nome(), destino, assunto, mensagem, status, agora_ts, agora_ts, erro, agora_ts],
+    )?;
+    conn.query_row(
+        "SELECT id FROM notificacoes WHERE agendamento_id = ?1 AND tipo = ?2 AND data_hora_agendamento = ?3",
+        params![id, tipo, agendamento.data_hora.and_utc().timestamp()],
+        |row| row.get(0),
+    ).optional()
+}
+
+/// Cancela lembretes pendentes de agendamentos excluídos, concluídos, cancelados, remarcados ou
+/// que já passaram, e devolve as mensagens prontas para envio.
+pub fn proximas_a_enviar(conn: &Connection, agora: NaiveDateTime, limite: i64) -> Result<Vec<Mensagem>> {
+    let agora_ts = agora.and_utc().timestamp();
+    conn.execute(
+        "UPDATE notificacoes SET status = ?1, proxima_tentativa = ?2,
+                ultimo_erro = 'Agendamento concluído, cancelado, remarcado ou já realizado.'
+         WHERE status = ?3 AND NOT EXISTS (
+             SELECT 1 FROM agendamentos a
+             WHERE a.id = notificacoes.agendamento_id AND a.concluido = 0 AND a.status != 'cancelado'
+               AND a.data_hora = notificacoes.data_hora_agendamento AND a.data_hora > ?2
+         )",
+        params![STATUS_CANCELADA, agora_ts, STATUS_PENDENTE],
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT n.id, n.agendamento_id, n.tipo, c.nome, n.destino, n.assunto, n.mensagem
+         FROM notificacoes n
+         JOIN agendamentos a ON a.id = n.agendamento_id
+         JOIN clientes c ON c.id = a.cliente_id
+         WHERE n.status = ?1 AND n.proxima_tentativa <= ?2
+         ORDER BY n.proxima_tentativa, n.id
+         LIMIT ?3",
+    )?;
+    let mensagens = stmt.query_map(params![STATUS_PENDENTE, agora_ts, limite], |row| {
+        Ok(Mensagem {
+            notificacao_id: row.get(0)?,
+            agendamento_id: row.get(1)?,
+            tipo: row.get(2)?,
+            nome: row.get(3)?,
+            destino: row.get(4)?,
+            assunto: row.get(5)?,
+            corpo: row.get(6)?,
+        })
+    })?;
+    mensagens.collect()
+}
+
+/// Grava o resultado de uma tentativa: enviada, nova tentativa com espera crescente
+/// ou falha definitiva depois de `tentativas_max`.
+pub fn registrar_envio(conn: &Connection, id: i64, resultado: &std::result::Result<(), String>, agora: NaiveDateTime, tentativas_max: i64) -> Result<()> {
+    let agora_ts = agora.and_utc().timestamp();
+    match resultado {
+        Ok(()) => {
+            conn.execute(
+                "UPDATE notificacoes SET status = ?1, tentativas = tentativas + 1, enviado_em = ?2, ultimo_erro = NULL WHERE id = ?3",
+                params![STATUS_ENVIADA, agora_ts, id],
+            )?;
+        }
+        Err(erro) => {
+            let tentativas: i64 = conn.query_row("SELECT tentativas FROM notificacoes WHERE id = ?1", params![id], |row| row.get(0))?;
+            let tentativas = tentativas + 1;
+            let status = if tentativas >= tentativas_max { STATUS_FALHOU } else { STATUS_PENDENTE };
+            let proxima = (agora + espera_apos(tentativas)).and_utc().timestamp();
+            conn.execute(
+                "UPDATE notificacoes SET status = ?1, tentativas = ?2, proxima_tentativa = ?3, ultimo_erro = ?4 WHERE id = ?5",
+                params![status, tentativas, proxima, erro, id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn linha_para_notificacao(row: &Row) -> Result<Notificacao> {
+    let status: String = row.get(8)?;
+    let pendente = status == STATUS_PENDENTE;
+    Ok(Notificacao {
+        id: row.get(0)?,
+        agendamento_id: row.get(1)?,
+        tipo: row.get(2)?,
+        canal: row.get(3)?,
+        destino: row.get(4)?,
+        assunto: row.get(5)?,
+        mensagem: row.get(6)?,
+        tentativas: row.get(7)?,
+        status,
+        enviar_em: db::timestamp_para_naive(row.get(9)?),
+        proxima_tentativa: if pendente { Some(db::timestamp_para_naive(row.get(10)?)) } else { None },
+        ultimo_erro: row.get(11)?,
+        criado_em: db::timestamp_para_naive(row.get(12)?),
+        enviado_em: row.get::<_, Option<i64>>(13)?.map(db::timestamp_para_naive),
+    })
+}
+
+/// Lista a fila, mais recentes primeiro.
+pub fn listar_notificacoes(conn: &Connection, status: Option<&str>, agendamento_id: Option<i32>, limite: i64) -> Result<Vec<Notificacao>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agendamento_id, tipo, canal, destino, assunto, mensagem, tentativas, status,
+                enviar_em, proxima_tentativa, ultimo_erro, criado_em, enviado_em
+         FROM notificacoes
+         WHERE (?1 IS NULL OR status = ?1) AND (?2 IS NULL OR agendamento_id = ?2)
+         ORDER BY id DESC
+         LIMIT ?3",
+    )?;
+    let linhas = stmt.query_map(params![status, agendamento_id, limite], linha_para_notificacao)?;
+    linhas.collect()
+}
+
+/// Notificações dos agendamentos do cliente (exportação LGPD), da mais antiga para a mais recente.
+pub fn listar_notificacoes_cliente(conn: &Connection, cliente_id: i32) -> Result<Vec<Notificacao>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, agendamento_id, tipo, canal, destino, assunto, mensagem, tentativas, status,
+                enviar_em, proxima_tentativa, ultimo_erro, criado_em, enviado_em
+         FROM notificacoes
+         WHERE agendamento_id IN (SELECT id FROM agendamentos WHERE cliente_id = ?1)
+         ORDER BY id",
+    )?;
+    let linhas = stmt.query_map(params![cliente_id], linha_para_notificacao)?;
+    linhas.collect()
+}
+
+/// Anonimização: apaga destino e textos (que levam nome e contato) das notificações do
+/// cliente. As pendentes são canceladas, pois não há mais para onde enviar.
+pub fn redigir_notificacoes_cliente(conn: &Connection, cliente_id: i32) -> Result<usize> {
+    conn.execute(
+        "UPDATE notificacoes
+         SET destino = '', assunto = ?1, mensagem = ?1,
+             status = CASE WHEN status = ?2 THEN ?3 ELSE status END
+         WHERE agendamento_id IN (SELECT id FROM agendamentos WHERE cliente_id = ?4)",
+        params![TEXTO_ANONIMIZADO, STATUS_PENDENTE, STATUS_CANCELADA, cliente_id],
+    )
+}
+
+/// Devolve uma notificação que falhou para a fila, com as tentativas zeradas.
+pub fn reenviar_notificacao(conn: &Connection, id: i64, agora: NaiveDateTime) -> Result<bool> {
+    let linhas = conn.execute(
+        "UPDATE notificacoes SET status = ?1, tentativas = 0, proxima_tentativa = ?2 WHERE id = ?3 AND status = ?4",
+        params![STATUS_PENDENTE, agora.and_utc().timestamp(), id, STATUS_FALHOU],
+    )?;
+    Ok(linhas > 0)
+}
+
+/// Sobe o agendador/despachante dos lembretes (a cada `notificacoes.verificar_a_cada_seg`).
+/// O envio roda em `spawn_blocking` sem segurar a conexão, para não travar a API.
+pub fn iniciar_notificacoes(conn: Db) {
+    let regras = config::atual().notificacoes.clone();
+    if !regras.ativo {
+        println!("[Notificações] Lembretes desativados.");
+        return;
+    }
+    let canal: Arc<dyn CanalNotificacao> = match canais::canal_da_config(&regras) {
+        Ok(canal) => Arc::from(canal),
+        Err(e) => {
+            eprintln!("[Notificações] Lembretes desativados: {}", e);
+            return;
+        }
+    };
+    println!("[Notificações] Lembretes {:?}h antes, pelo canal {}.", regras.antecedencias_horas, canal.nome());
+
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(Duration::from_secs(regras.verificar_a_cada_seg));
+        loop {
+            intervalo.tick().await;
+            let a_enviar = {
+                let conn = conn.lock().unwrap();
+                let agora = config::agora();
+                enfileirar_lembretes(&conn, agora, &regras, canal.as_ref()).and_then(|_| proximas_a_enviar(&conn, agora, 50))
+            };
+            let a_enviar = match a_enviar {
+                Ok(mensagens) if mensagens.is_empty() => continue,
+                Ok(mensagens) => mensagens,
+                Err(e) => {
+                    eprintln!("[Notificações] Erro ao montar a fila: {}", e);
+                    continue;
+                }
+            };
+
+            let canal_envio = canal.clone();
+            let resultados = tokio::task::spawn_blocking(move || {
+                a_enviar.into_iter().map(|m| (m.notificacao_id, canal_envio.enviar(&m))).collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default();
+
+            let conn = conn.lock().unwrap();
+            for (id, resultado) in &resultados {
+                if let Err(e) = resultado {
+                    eprintln!("[Notificações] Falha ao enviar a notificação {}: {}", id, e);
+                }
+                if let Err(e) = registrar_envio(&conn, *id, resultado, config::agora(), regras.tentativas_max) {
+                    eprintln!("[Notificações] Erro ao registrar envio {}: {}", id, e);
+                }
+            }
+        }
+    });
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+pub struct NotificacoesQuery {
+    status: Option<String>,
+    agendamento_id: Option<i32>,
+    limite: Option<i64>,
+}
+
+/// GET /notificacoes?status=falhou&agendamento_id=&limite= — fila de lembretes.
+pub async fn listar_notificacoes_api(Query(q): Query<NotificacoesQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<Notificacao>>>) {
+    let conn = conn.lock().unwrap();
+    match listar_notificacoes(&conn, q.status.as_deref(), q.agendamento_id, q.limite.unwrap_or(200)) {
+        Ok(lista) => {
+            let response = ApiResponse { success: true, message: format!("{} notificação(ões).", lista.len()), data: Some(lista) };
+            (StatusCode::OK, Json(response))
+        }
+        Err(e) => {
+            eprintln!("Erro ao listar notificações: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao listar notificações: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
+
+/// POST /notificacoes/:id/reenviar — recoloca na fila uma notificação que falhou.
+pub async fn reenviar_notificacao_api(Path(id): Path<i64>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
+    let conn = conn.lock().unwrap();
+    match reenviar_notificacao(&conn, id, config::agora()) {
+        Ok(true) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Notificação recolocada na fila.".to_string(), data: None })),
+        Ok(false) => {
+            let response = ApiResponse { success: false, message: format!("Notificação {} não encontrada ou não está com falha.", id), data: None };
+            (StatusCode::NOT_FOUND, Json(response))
+        }
+        Err(e) => {
+            eprintln!("Erro ao reenviar notificação: {}", e);
+            let response = ApiResponse { success: false, message: format!("Erro ao reenviar notificação: {}", e), data: None };
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Servico;
+
+    /// Canal de teste: guarda as mensagens e falha enquanto `falhas` > 0.
+    struct CanalTeste {
+        enviadas: Mutex<Vec<Mensagem>>,
+        falhas: Mutex<u32>,
+    }
+
+    impl CanalNotificacao for CanalTeste {
+        fn nome(&self) -> &'static str {
+            "teste"
+        }
+
+        fn destino(&self, cliente: &Cliente) -> Option<String> {
+            Some(cliente.telefone.clone()).filter(|t| !t.is_empty())
+        }
+
+        fn enviar(&self, mensagem: &Mensagem) -> std::result::Result<(), String> {
+            let mut falhas = self.falhas.lock().unwrap();
+            if *falhas > 0 {
+                *falhas -= 1;
+                return Err("gateway fora do ar".into());
+            }
+            self.enviadas.lock().unwrap().push(mensagem.clone());
+            Ok(())
+        }
+    }
+
+    fn data(texto: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(texto, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn despachar(conn: &Connection, canal: &CanalTeste, agora: NaiveDateTime, regras: &ConfigNotificacoes) {
+        enfileirar_lembretes(conn, agora, regras, canal).unwrap();
+        for mensagem in proximas_a_enviar(conn, agora, 50).unwrap() {
+            let resultado = canal.enviar(&mensagem);
+            registrar_envio(conn, mensagem.notificacao_id, &resultado, agora, regras.tentativas_max).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_lembretes_24h_e_2h_com_novas_tentativas() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Carlos".into(), "11999990000".into(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let servico_id = db::salvar_servico(&conn, &Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: None, alinhamento_min: None }).unwrap();
+        let agendamento = Agendamento {
+            id: None,
+            cliente_id,
+            servicos_ids: vec![servico_id],
+            data_hora: data("2026-10-20 15:00"),
+            preco: 40.0,
+            concluido: false,
+        };
+        db::salvar_agendamento(&conn, &agendamento).unwrap();
+
+        let regras = ConfigNotificacoes { tentativas_max: 2, ..Default::default() };
+        let canal = CanalTeste { enviadas: Mutex::new(vec![]), falhas: Mutex::new(1) };
+
+        // Fora da janela de 24h nada é enfileirado.
+        despachar(&conn, &canal, data("2026-10-19 14:00"), &regras);
+        assert!(listar_notificacoes(&conn, None, None, 10).unwrap().is_empty());
+
+        // 22h antes: lembrete de 24h, que falha na primeira tentativa e volta com espera.
+        despachar(&conn, &canal, data("2026-10-19 17:00"), &regras);
+        let fila = listar_notificacoes(&conn, None, None, 10).unwrap();
+        assert_eq!(fila.len(), 1);
+        assert_eq!((fila[0].tipo.as_str(), fila[0].status.as_str(), fila[0].tentativas), ("lembrete_24h", STATUS_PENDENTE, 1));
+        assert_eq!(fila[0].proxima_tentativa, Some(data("2026-10-19 17:05")));
+        assert_eq!(fila[0].mensagem, "Olá, Carlos! Lembrando do seu horário na barbearia em 20/10/2026 às 15:00 (Corte). Até lá!");
+
+        despachar(&conn, &canal, data("2026-10-19 17:02"), &regras);
+        assert!(canal.enviadas.lock().unwrap().is_empty(), "ainda dentro da espera");
+        despachar(&conn, &canal, data("2026-10-19 17:05"), &regras);
+        assert_eq!(canal.enviadas.lock().unwrap().len(), 1);
+
+        // 1h30 antes: só o lembrete de 2h, sem repetir o de 24h.
+        despachar(&conn, &canal, data("2026-10-20 13:30"), &regras);
+        let enviadas = canal.enviadas.lock().unwrap();
+        assert_eq!(enviadas.iter().map(|m| m.tipo.as_str()).collect::<Vec<_>>(), vec!["lembrete_24h", "lembrete_2h"]);
+        assert_eq!(enviadas[1].destino, "11999990000");
+    }
+
+    #[test]
+    fn test_falha_definitiva_reenvio_e_cancelamento() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Davi".into(), "1188".into(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let agendamento = Agendamento { id: None, cliente_id, servicos_ids: vec![], data_hora: data("2026-10-20 15:00"), preco: 0.0, concluido: false };
+        let id = db::salvar_agendamento(&conn, &agendamento).unwrap();
+
+        let regras = ConfigNotificacoes { tentativas_max: 2, ..Default::default() };
+        let canal = CanalTeste { enviadas: Mutex::new(vec![]), falhas: Mutex::new(2) };
+        despachar(&conn, &canal, data("2026-10-20 10:00"), &regras);
+        despachar(&conn, &canal, data("2026-10-20 10:05"), &regras);
+        let falhas = listar_notificacoes(&conn, Some(STATUS_FALHOU), None, 10).unwrap();
+        assert_eq!(falhas.len(), 1);
+        assert_eq!(falhas[0].ultimo_erro.as_deref(), Some("gateway fora do ar"));
+
+        assert!(reenviar_notificacao(&conn, falhas[0].id, data("2026-10-20 11:00")).unwrap());
+        assert!(!reenviar_notificacao(&conn, falhas[0].id, data("2026-10-20 11:00")).unwrap(), "só notificações com falha");
+
+        // Remarcado antes do reenvio: o lembrete antigo é cancelado e um novo é criado.
+        db::atualizar_agendamento(&conn, id, Some(data("2026-10-20 16:00")), None, None, None).unwrap();
+        despachar(&conn, &canal, data("2026-10-20 11:00"), &regras);
+        let fila = listar_notificacoes(&conn, None, Some(id), 10).unwrap();
+        assert_eq!(fila.iter().map(|n| n.status.as_str()).collect::<Vec<_>>(), vec![STATUS_ENVIADA, STATUS_CANCELADA]);
+        assert!(canal.enviadas.lock().unwrap()[0].corpo.contains("16:00"));
+    }
+}
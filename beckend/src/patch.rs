@@ -0,0 +1,132 @@
+//! Atualização parcial (PATCH) com JSON merge-patch (RFC 7396) e controle otimista de
+//! concorrência. Clientes, serviços e agendamentos têm uma coluna `versao` que sobe a cada
+//! alteração (gatilhos em `criar_controle_de_versao`). O GET e o PATCH devolvem a versão no
+//! cabeçalho `ETag`; quem mandar `If-Match` com uma versão antiga recebe 409.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Tabelas com controle de versão.
+pub const TABELAS_VERSIONADAS: [&str; 3] = ["clientes", "servicos", "agendamentos"];
+
+/// Coluna `versao` e gatilhos que a incrementam em qualquer UPDATE que não a altere por conta
+/// própria (quem já soma 1, como `db::atualizar_agendamento` ao trocar serviços, não conta duas vezes).
+pub fn criar_controle_de_versao(conn: &Connection) -> Result<()> {
+    for tabela in TABELAS_VERSIONADAS {
+        crate::db::garantir_coluna(conn, tabela, "versao", "INTEGER NOT NULL DEFAULT 1")?;
+        conn.execute_batch(&format!(
+            "CREATE TRIGGER IF NOT EXISTS {t}_versao AFTER UPDATE ON {t}
+             WHEN NEW.versao = OLD.versao
+             BEGIN
+                 UPDATE {t} SET versao = OLD.versao + 1 WHERE id = NEW.id;
+             END;",
+            t = tabela
+        ))?;
+    }
+    Ok(())
+}
+
+/// Versão atual do registro; `None` quando ele não existe.
+pub fn versao(conn: &Connection, tabela: &str, id: i32) -> Result<Option<i64>> {
+    debug_assert!(TABELAS_VERSIONADAS.contains(&tabela));
+    conn.query_row(&format!("SELECT versao FROM {} WHERE id = ?1", tabela), params![id], |row| row.get(0))
+        .optional()
+}
+
+/// Cabeçalhos com o `ETag` da versão (vazio quando não há versão).
+pub fn cabecalho_etag(versao: Option<i64>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(v) = versao {
+        if let Ok(valor) = HeaderValue::from_str(&format!("\"{}\"", v)) {
+            headers.insert(header::ETAG, valor);
+        }
+    }
+    headers
+}
+
+/// Confere o `If-Match` contra a versão atual. Sem o cabeçalho (ou com `*`) a alteração segue
+/// como antes, sem controle; aceita `"3"`, `W/"3"`, `3` e listas separadas por vírgula.
+pub fn if_match_confere(headers: &HeaderMap, versao_atual: i64) -> bool {
+    let Some(valor) = headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) else {
+        return true;
+    };
+    valor.split(',').map(str::trim).any(|etag| {
+        etag == "*" || etag.trim_start_matches("W/").trim_matches('"').parse::<i64>() == Ok(versao_atual)
+    })
+}
+
+/// Aplica o merge-patch em `alvo`: `null` remove o campo, objetos se mesclam campo a campo e
+/// qualquer outro valor substitui o anterior (inclusive listas, que não se mesclam).
+pub fn aplicar_merge_patch(alvo: &mut Value, patch: &Value) {
+    let Value::Object(campos) = patch else {
+        *alvo = patch.clone();
+        return;
+    };
+    if !alvo.is_object() {
+        *alvo = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(atual) = alvo {
+        for (chave, valor) in campos {
+            if valor.is_null() {
+                atual.remove(chave);
+            } else {
+                aplicar_merge_patch(atual.entry(chave.clone()).or_insert(Value::Null), valor);
+            }
+        }
+    }
+}
+
+/// Mescla o patch no registro atual e devolve o resultado já tipado. Os campos em `imutaveis`
+/// não podem mudar; a mensagem de erro serve direto para um 400.
+pub fn mesclar<T: Serialize + DeserializeOwned>(atual: &T, patch: &Value, imutaveis: &[&str]) -> Result<T, String> {
+    if !patch.is_object() {
+        return Err("O corpo do PATCH deve ser um objeto JSON (merge-patch).".to_string());
+    }
+    let mut documento = serde_json::to_value(atual).map_err(|e| e.to_string())?;
+    let antes = documento.clone();
+    aplicar_merge_patch(&mut documento, patch);
+    for campo in imutaveis {
+        if documento.get(campo) != antes.get(campo) {
+            return Err(format!("O campo {} não pode ser alterado.", campo));
+        }
+    }
+    serde_json::from_value(documento).map_err(|e| format!("Campos inválidos: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Cliente;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_patch_e_versao() {
+        let mut doc = json!({ "a": 1, "b": { "c": 2, "d": 3 }, "l": [1, 2] });
+        aplicar_merge_patch(&mut doc, &json!({ "a": null, "b": { "c": 5 }, "l": [9] }));
+        assert_eq!(doc, json!({ "b": { "c": 5, "d": 3 }, "l": [9] }));
+
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".to_string(), "1199".to_string(), Some("ana@x.com".to_string()));
+        let id = crate::db::salvar_cliente(&conn, &mut cliente).unwrap();
+        assert_eq!(versao(&conn, "clientes", id).unwrap(), Some(1));
+
+        let mut novo: Cliente = mesclar(&cliente, &json!({ "email": null }), &["id"]).unwrap();
+        assert_eq!(novo.email, None);
+        assert_eq!(novo.nome, "Ana");
+        assert!(mesclar::<Cliente>(&cliente, &json!({ "id": 99 }), &["id"]).is_err());
+        assert!(mesclar::<Cliente>(&cliente, &json!({ "nome": null }), &["id"]).is_err());
+
+        crate::db::salvar_cliente(&conn, &mut novo).unwrap();
+        assert_eq!(versao(&conn, "clientes", id).unwrap(), Some(2));
+
+        let mut headers = HeaderMap::new();
+        assert!(if_match_confere(&headers, 2));
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("\"1\""));
+        assert!(!if_match_confere(&headers, 2));
+        headers.insert(header::IF_MATCH, HeaderValue::from_static("W/\"1\", \"2\""));
+        assert!(if_match_confere(&headers, 2));
+    }
+}
@@ -0,0 +1,735 @@
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{NaiveDate, NaiveTime};
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::backup::autorizar_dono;
+use crate::db;
+use crate::models::{Cliente, Servico};
+use crate::publico::somente_digitos;
+use crate::servicos;
+use crate::webhooks;
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// PLANILHAS (CSV)
+// Exportação de clientes, serviços e agendamentos e importação de clientes e serviços
+// vindos de planilhas ou da agenda de papel. A importação valida tudo antes (relatório
+// com erros por linha e duplicados) e só grava, numa transação, se não houver erro.
+// =================================================================================
+
+/// Marca de ordem de bytes: sem ela o Excel abre o UTF-8 com acentos quebrados.
+const BOM: &str = "\u{feff}";
+
+#[derive(Debug)]
+pub enum ErroPlanilha {
+    /// Arquivo, cabeçalho, mapeamento ou filtro inválido (400 / `SAIDA_USO`).
+    Formato(String),
+    Banco(rusqlite::Error),
+}
+
+impl fmt::Display for ErroPlanilha {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErroPlanilha::Formato(m) => write!(f, "{}", m),
+            ErroPlanilha::Banco(e) => write!(f, "erro no banco de dados: {}", e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ErroPlanilha {
+    fn from(e: rusqlite::Error) -> Self {
+        ErroPlanilha::Banco(e)
+    }
+}
+
+/// `;`, `,`, `|` ou `tab`.
+pub fn parse_separador(valor: &str) -> Result<u8, ErroPlanilha> {
+    match valor {
+        "tab" | "\\t" | "\t" => Ok(b'\t'),
+        v if v.len() == 1 && matches!(v, ";" | "," | "|") => Ok(v.as_bytes()[0]),
+        _ => Err(ErroPlanilha::Formato("separador deve ser ;, ,, | ou tab.".to_string())),
+    }
+}
+
+/// Mapeamento `campo=Cabeçalho` (vários separados por vírgula na API; repetidos na CLI).
+pub fn parse_colunas<'a>(pares: impl IntoIterator<Item = &'a str>) -> Result<HashMap<String, String>, ErroPlanilha> {
+    let mut colunas = HashMap::new();
+    for par in pares.into_iter().map(str::trim).filter(|p| !p.is_empty()) {
+        let Some((campo, cabecalho)) = par.split_once('=') else {
+            return Err(ErroPlanilha::Formato(format!("Mapeamento \"{}\" inválido: use campo=Cabeçalho.", par)));
+        };
+        colunas.insert(campo.trim().to_lowercase(), cabecalho.trim().to_string());
+    }
+    Ok(colunas)
+}
+
+/// Minúsculas e sem acentos, para comparar cabeçalhos ("Preço" == "preco").
+fn normalizar(texto: &str) -> String {
+    db::sem_acentos(texto.trim()).replace(['-', ' '], "_")
+}
+
+/// Número em formato brasileiro ou não: "R$ 1.234,50", "1.234", "35,5", "35.50".
+/// Sem vírgula, pontos que separam grupos de três dígitos são de milhar.
+fn parse_numero(texto: &str) -> Option<f64> {
+    let limpo: String = texto.trim().trim_start_matches("R$").chars().filter(|c| !c.is_whitespace()).collect();
+    let (inteiro, grupos) = limpo.split_once('.').unwrap_or((&limpo, ""));
+    let milhar = !grupos.is_empty()
+        && (1..=3).contains(&inteiro.trim_start_matches('-').len())
+        && grupos.split('.').all(|g| g.len() == 3 && g.chars().all(|c| c.is_ascii_digit()));
+    let normalizado = if limpo.contains(',') || milhar { limpo.replace('.', "").replace(',', ".") } else { limpo };
+    normalizado.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn gerar_csv(separador: u8, cabecalho: &[&str], linhas: Vec<Vec<String>>) -> String {
+    let mut escritor = csv::WriterBuilder::new().delimiter(separador).from_writer(Vec::new());
+    // Escrita em memória: só falharia por falta de memória.
+    escritor.write_record(cabecalho).expect("CSV em memória");
+    for linha in linhas {
+        escritor.write_record(&linha).expect("CSV em memória");
+    }
+    let bytes = escritor.into_inner().expect("CSV em memória");
+    format!("{}{}", BOM, String::from_utf8_lossy(&bytes))
+}
+
+// ---------------------------------------------------------------------------------
+// Exportação
+// ---------------------------------------------------------------------------------
+
+pub fn exportar_clientes(conn: &Connection, busca: Option<&str>, separador: u8) -> Result<String, ErroPlanilha> {
+    let clientes = match busca.map(str::trim).filter(|b| !b.is_empty()) {
+        Some(b) => db::listar_clientes_search(conn, b, i32::MAX)?,
+        None => db::listar_clientes(conn)?,
+    };
+    let linhas = clientes
+        .into_iter()
+        .map(|c| vec![c.id.unwrap_or_default().to_string(), c.nome, c.telefone, c.email.unwrap_or_default()])
+        .collect();
+    Ok(gerar_csv(separador, &["id", "nome", "telefone", "email"], linhas))
+}
+
+fn opcional<T: ToString>(valor: Option<T>) -> String {
+    valor.map(|v| v.to_string()).unwrap_or_default()
+}
+
+pub fn exportar_servicos(conn: &Connection, separador: u8) -> Result<String, ErroPlanilha> {
+    let linhas = db::listar_servicos(conn)?
+        .into_iter()
+        .map(|s| {
+            vec![
+                s.id.unwrap_or_default().to_string(),
+                s.nome,
+                format!("{:.2}", s.preco),
+                s.duracao_min.to_string(),
+                opcional(s.buffer_min),
+                opcional(s.alinhamento_min),
+            ]
+        })
+        .collect();
+    Ok(gerar_csv(separador, &["id", "nome", "preco", "duracao_min", "buffer_min", "alinhamento_min"], linhas))
+}
+
+#[derive(Debug, Default)]
+pub struct FiltroAgendamentos {
+    /// Primeiro e último dia (YYYY-MM-DD), inclusive.
+    pub inicio: Option<NaiveDate>,
+    pub fim: Option<NaiveDate>,
+    pub cliente_id: Option<i32>,
+    /// `pendente`, `confirmado`, `cancelado` ou `concluido`.
+    pub status: Option<String>,
+}
+
+const STATUS_EXPORTACAO: [&str; 4] = [db::STATUS_AGENDAMENTO_PENDENTE, db::STATUS_AGENDAMENTO_CONFIRMADO, db::STATUS_AGENDAMENTO_CANCELADO, "concluido"];
+
+pub fn exportar_agendamentos(conn: &Connection, filtro: &FiltroAgendamentos, separador: u8) -> Result<String, ErroPlanilha> {
+    if let Some(status) = filtro.status.as_deref().filter(|s| !STATUS_EXPORTACAO.contains(s)) {
+        return Err(ErroPlanilha::Formato(format!("status \"{}\" inválido: use {}.", status, STATUS_EXPORTACAO.join(", "))));
+    }
+    let servicos: HashMap<i32, String> = db::listar_servicos(conn)?.into_iter().filter_map(|s| Some((s.id?, s.nome))).collect();
+    let meia_noite = |dia: NaiveDate| dia.and_time(NaiveTime::MIN).and_utc().timestamp();
+    let inicio_ts = filtro.inicio.map(meia_noite);
+    let fim_ts = filtro.fim.and_then(|f| f.succ_opt()).map(meia_noite);
+
+    // Status, cliente e serviços vêm na mesma consulta; o filtro de status usa o status já resolvido.
+    let mut stmt = conn.prepare(
+        "SELECT id, data_hora, cliente_id, nome, telefone, servicos_ids, preco, status FROM (
+            SELECT a.id, a.data_hora, a.cliente_id, COALESCE(c.nome, '') AS nome, COALESCE(c.telefone, '') AS telefone,
+                   (SELECT GROUP_CONCAT(s.servico_id, ' ') FROM agendamento_servicos s WHERE s.agendamento_id = a.id) AS servicos_ids,
+                   a.preco, CASE WHEN a.concluido THEN 'concluido' ELSE a.status END AS status
+            FROM agendamentos a
+            LEFT JOIN clientes c ON c.id = a.cliente_id
+            WHERE (?1 IS NULL OR a.data_hora >= ?1) AND (?2 IS NULL OR a.data_hora < ?2) AND (?3 IS NULL OR a.cliente_id = ?3)
+        )
+        WHERE ?4 IS NULL OR status = ?4
+        ORDER BY id",
+    )?;
+    let linhas = stmt
+        .query_map(params![inicio_ts, fim_ts, filtro.cliente_id, filtro.status], |row| {
+            let servicos_ids: String = row.get::<_, Option<String>>(5)?.unwrap_or_default();
+            let nomes: Vec<&str> = servicos_ids
+                .split_whitespace()
+                .map(|s| s.parse::<i32>().ok().and_then(|id| servicos.get(&id)).map(String::as_str).unwrap_or("?"))
+                .collect();
+            let nomes = nomes.join(" + ");
+            Ok(vec![
+                row.get::<_, i32>(0)?.to_string(),
+                db::timestamp_para_naive(row.get(1)?).format("%Y-%m-%d %H:%M").to_string(),
+                row.get::<_, i32>(2)?.to_string(),
+                row.get(3)?,
+                row.get(4)?,
+                servicos_ids,
+                nomes,
+                format!("{:.2}", row.get::<_, f64>(6)?),
+                row.get(7)?,
+            ])
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let cabecalho = ["id", "data_hora", "cliente_id", "cliente", "telefone", "servicos_ids", "servicos", "preco", "status"];
+    Ok(gerar_csv(separador, &cabecalho, linhas))
+}
+
+// ---------------------------------------------------------------------------------
+// Importação
+// ---------------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+pub struct OpcoesImportacao {
+    /// Sem separador, usa o que mais aparece no cabeçalho entre `;`, `,` e tab.
+    pub separador: Option<u8>,
+    /// Campo -> cabeçalho da planilha; os campos sem mapeamento são procurados pelo nome.
+    pub colunas: HashMap<String, String>,
+    /// Duplicados que já estão no banco são atualizados em vez de ignorados.
+    pub atualizar_duplicados: bool,
+    /// Só valida e devolve o relatório.
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErroLinha {
+    pub linha: u64,
+    pub mensagem: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicadoLinha {
+    pub linha: u64,
+    /// Telefone (clientes) ou nome (serviços).
+    pub chave: String,
+    /// Cadastro já existente com a mesma chave.
+    pub existente_id: Option<i32>,
+    /// Linha anterior da própria planilha com a mesma chave.
+    pub linha_anterior: Option<u64>,
+    pub atualizado: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RelatorioImportacao {
+    pub linhas: usize,
+    pub novos: usize,
+    pub atualizados: usize,
+    pub ignorados: usize,
+    pub erros: Vec<ErroLinha>,
+    pub duplicados: Vec<DuplicadoLinha>,
+    /// Falso no `dry_run` e quando há erro (nada é gravado).
+    pub aplicado: bool,
+    pub ids: Vec<i32>,
+}
+
+struct Campo {
+    nome: &'static str,
+    apelidos: &'static [&'static str],
+    obrigatorio: bool,
+}
+
+const CAMPOS_CLIENTE: [Campo; 3] = [
+    Campo { nome: "nome", apelidos: &["cliente", "nome_completo"], obrigatorio: true },
+    Campo { nome: "telefone", apelidos: &["celular", "fone", "whatsapp", "tel"], obrigatorio: true },
+    Campo { nome: "email", apelidos: &["e_mail"], obrigatorio: false },
+];
+
+const CAMPOS_SERVICO: [Campo; 5] = [
+    Campo { nome: "nome", apelidos: &["servico"], obrigatorio: true },
+    Campo { nome: "preco", apelidos: &["valor"], obrigatorio: true },
+    Campo { nome: "duracao_min", apelidos: &["duracao", "minutos", "tempo"], obrigatorio: true },
+    Campo { nome: "buffer_min", apelidos: &["folga"], obrigatorio: false },
+    Campo { nome: "alinhamento_min", apelidos: &["alinhamento"], obrigatorio: false },
+];
+
+/// Linha lida da planilha: número da linha e valor (aparado) de cada campo encontrado.
+struct LinhaPlanilha {
+    numero: u64,
+    valores: HashMap<&'static str, String>,
+}
+
+impl LinhaPlanilha {
+    fn valor(&self, campo: &str) -> Option<&str> {
+        self.valores.get(campo).map(String::as_str).filter(|v| !v.is_empty())
+    }
+}
+
+fn detectar_separador(texto: &str) -> u8 {
+    let cabecalho = texto.lines().next().unwrap_or("");
+    [b';', b',', b'\t']
+        .into_iter()
+        .max_by_key(|s| cabecalho.bytes().filter(|b| b == s).count())
+        .unwrap_or(b',')
+}
+
+fn ler_planilha(texto: &str, campos: &[Campo], opcoes: &OpcoesImportacao) -> Result<Vec<LinhaPlanilha>, ErroPlanilha> {
+    for campo in opcoes.colunas.keys() {
+        if !campos.iter().any(|c| c.nome == campo) {
+            let validos: Vec<&str> = campos.iter().map(|c| c.nome).collect();
+            return Err(ErroPlanilha::Formato(format!("Campo \"{}\" desconhecido no mapeamento; use {}.", campo, validos.join(", "))));
+        }
+    }
+    let texto = texto.trim_start_matches(BOM);
+    let separador = opcoes.separador.unwrap_or_else(|| detectar_separador(texto));
+    let mut leitor = csv::ReaderBuilder::new().delimiter(separador).flexible(true).from_reader(texto.as_bytes());
+    let cabecalhos: Vec<String> = leitor
+        .headers()
+        .map_err(|e| ErroPlanilha::Formato(format!("Cabeçalho inválido: {}", e)))?
+        .iter()
+        .map(normalizar)
+        .collect();
+
+    let mut indices = Vec::new();
+    for campo in campos {
+        let procurados: Vec<String> = match opcoes.colunas.get(campo.nome) {
+            Some(cabecalho) => vec![normalizar(cabecalho)],
+            None => std::iter::once(campo.nome).chain(campo.apelidos.iter().copied()).map(normalizar).collect(),
+        };
+        match cabecalhos.iter().position(|c| procurados.contains(c)) {
+            Some(indice) => indices.push((campo.nome, indice)),
+            None if campo.obrigatorio || opcoes.colunas.contains_key(campo.nome) => {
+                return Err(ErroPlanilha::Formato(format!(
+                    "Coluna de \"{}\" não encontrada no cabeçalho; informe o mapeamento {}=<cabeçalho>.",
+                    campo.nome, campo.nome
+                )));
+            }
+            None => {}
+        }
+    }
+
+    let mut linhas = Vec::new();
+    for registro in leitor.records() {
+        let registro = registro.map_err(|e| ErroPlanilha::Formato(format!("CSV inválido: {}", e)))?;
+        if registro.iter().all(|v| v.trim().is_empty()) {
+            continue;
+        }
+        let numero = registro.position().map(|p| p.line()).unwrap_or_default();
+        let valores = indices.iter().map(|(campo, i)| (*campo, registro.get(*i).unwrap_or("").trim().to_string())).collect();
+        linhas.push(LinhaPlanilha { numero, valores });
+    }
+    Ok(linhas)
+}
+
+/// O que fazer com cada linha válida.
+enum Acao<T> {
+    Criar(T),
+    Atualizar(T),
+}
+
+/// Separa duplicados (no banco e na própria planilha) e monta as ações.
+fn planejar<T>(
+    relatorio: &mut RelatorioImportacao,
+    validas: Vec<(u64, String, T)>,
+    existentes: &HashMap<String, i32>,
+    atualizar_duplicados: bool,
+    com_id: impl Fn(T, i32) -> T,
+) -> Vec<Acao<T>> {
+    let mut vistas: HashMap<String, u64> = HashMap::new();
+    let mut acoes = Vec::new();
+    for (linha, chave, item) in validas {
+        if let Some(anterior) = vistas.get(&chave) {
+            relatorio.duplicados.push(DuplicadoLinha { linha, chave, existente_id: None, linha_anterior: Some(*anterior), atualizado: false });
+            relatorio.ignorados += 1;
+            continue;
+        }
+        vistas.insert(chave.clone(), linha);
+        match existentes.get(&chave) {
+            Some(id) => {
+                relatorio.duplicados.push(DuplicadoLinha { linha, chave, existente_id: Some(*id), linha_anterior: None, atualizado: atualizar_duplicados });
+                if atualizar_duplicados {
+                    relatorio.atualizados += 1;
+                    acoes.push(Acao::Atualizar(com_id(item, *id)));
+                } else {
+                    relatorio.ignorados += 1;
+                }
+            }
+            None => {
+                relatorio.novos += 1;
+                acoes.push(Acao::Criar(item));
+            }
+        }
+    }
+    acoes
+}
+
+/// Grava as ações numa transação, só quando não há erro e não é `dry_run`.
+fn aplicar<T>(
+    conn: &Connection,
+    relatorio: &mut RelatorioImportacao,
+    acoes: Vec<Acao<T>>,
+    dry_run: bool,
+    mut salvar: impl FnMut(&Connection, Acao<T>) -> rusqlite::Result<i32>,
+) -> Result<(), ErroPlanilha> {
+    if dry_run || !relatorio.erros.is_empty() {
+        return Ok(());
+    }
+    let tx = conn.unchecked_transaction()?;
+    for acao in acoes {
+        relatorio.ids.push(salvar(&tx, acao)?);
+    }
+    tx.commit()?;
+    relatorio.aplicado = true;
+    Ok(())
+}
+
+pub fn importar_clientes(conn: &Connection, texto: &str, opcoes: &OpcoesImportacao) -> Result<RelatorioImportacao, ErroPlanilha> {
+    let linhas = ler_planilha(texto, &CAMPOS_CLIENTE, opcoes)?;
+    let mut relatorio = RelatorioImportacao { linhas: linhas.len(), ..Default::default() };
+    let mut validas = Vec::new();
+    for linha in linhas {
+        let nome = linha.valor("nome").unwrap_or_default();
+        let telefone = linha.valor("telefone").unwrap_or_default();
+        let email = linha.valor("email").map(str::to_string);
+        let digitos = somente_digitos(telefone);
+        let erro = if nome.is_empty() {
+            Some("nome vazio".to_string())
+        } else if !(8..=15).contains(&digitos.len()) {
+            Some(format!("telefone \"{}\" inválido (use DDD + número)", telefone))
+        } else {
+            email.as_deref().filter(|e| !e.contains('@')).map(|e| format!("e-mail \"{}\" inválido", e))
+        };
+        match erro {
+            Some(mensagem) => relatorio.erros.push(ErroLinha { linha: linha.numero, mensagem }),
+            None => validas.push((linha.numero, digitos, Cliente::new(nome.to_string(), telefone.to_string(), email))),
+        }
+    }
+
+    let existentes: HashMap<String, i32> = db::listar_clientes(conn)?
+        .into_iter()
+        .filter_map(|c| Some((somente_digitos(&c.telefone), c.id?)))
+        .filter(|(telefone, _)| !telefone.is_empty())
+        .collect();
+    let acoes = planejar(&mut relatorio, validas, &existentes, opcoes.atualizar_duplicados, |c, id| Cliente { id: Some(id), ..c });
+    // Mesmo caminho do POST /clientes: salvar_cliente audita e o cliente novo dispara o webhook.
+    aplicar(conn, &mut relatorio, acoes, opcoes.dry_run, |conn, acao| match acao {
+        Acao::Criar(mut cliente) => {
+            let id = db::salvar_cliente(conn, &mut cliente)?;
+            webhooks::emitir_ou_logar(conn, webhooks::EVENTO_CLIENTE_CRIADO, &cliente);
+            Ok(id)
+        }
+        Acao::Atualizar(mut cliente) => db::salvar_cliente(conn, &mut cliente),
+    })?;
+    Ok(relatorio)
+}
+
+fn servico_da_linha(linha: &LinhaPlanilha) -> Result<Servico, String> {
+    let nome = linha.valor("nome").ok_or("nome vazio")?;
+    let preco = linha.valor("preco").and_then(parse_numero).filter(|p| *p >= 0.0).ok_or("preço inválido")?;
+    let duracao_min = linha.valor("duracao_min").and_then(|d| d.parse::<i32>().ok()).filter(|d| *d > 0).ok_or("duração inválida (minutos)")?;
+    let inteiro = |campo: &str| -> Result<Option<i32>, String> {
+        match linha.valor(campo) {
+            None => Ok(None),
+            Some(v) => v.parse().map(Some).map_err(|_| format!("{} inválido", campo)),
+        }
+    };
+    let servico = Servico {
+        id: None,
+        nome: nome.to_string(),
+        preco,
+        duracao_min,
+        buffer_min: inteiro("buffer_min")?,
+        alinhamento_min: inteiro("alinhamento_min")?,
+    };
+    servicos::validar_regras(&servico)?;
+    Ok(servico)
+}
+
+pub fn importar_servicos(conn: &Connection, texto: &str, opcoes: &OpcoesImportacao) -> Result<RelatorioImportacao, ErroPlanilha> {
+    let linhas = ler_planilha(texto, &CAMPOS_SERVICO, opcoes)?;
+    let mut relatorio = RelatorioImportacao { linhas: linhas.len(), ..Default::default() };
+    let mut validas = Vec::new();
+    for linha in &linhas {
+        match servico_da_linha(linha) {
+            Ok(servico) => validas.push((linha.numero, normalizar(&servico.nome), servico)),
+            Err(mensagem) => relatorio.erros.push(ErroLinha { linha: linha.numero, mensagem }),
+        }
+    }
+
+    let existentes: HashMap<String, i32> = db::listar_servicos(conn)?.into_iter().filter_map(|s| Some((normalizar(&s.nome), s.id?))).collect();
+    let acoes = planejar(&mut relatorio, validas, &existentes, opcoes.atualizar_duplicados, |s, id| Servico { id: Some(id), ..s });
+    // Serviço em fases não muda de duração pela planilha.
+    for acao in &acoes {
+        if let Acao::Atualizar(servico) = acao {
+            let fases = db::listar_fases_servico(conn, servico.id.unwrap_or_default())?;
+            if let Err(mensagem) = servicos::validar_duracao_com_fases(servico, &fases) {
+                let linha = relatorio.duplicados.iter().find(|d| d.existente_id == servico.id).map(|d| d.linha).unwrap_or_default();
+                relatorio.erros.push(ErroLinha { linha, mensagem });
+            }
+        }
+    }
+    aplicar(conn, &mut relatorio, acoes, opcoes.dry_run, |conn, acao| match acao {
+        Acao::Criar(servico) | Acao::Atualizar(servico) => db::salvar_servico(conn, &servico),
+    })?;
+    Ok(relatorio)
+}
+
+// ---------------------------------------------------------------------------------
+// API
+// ---------------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn resposta<T: Serialize>(status: StatusCode, message: String, data: Option<T>) -> Response {
+    (status, Json(ApiResponse { success: status.is_success(), message, data })).into_response()
+}
+
+fn resposta_erro(contexto: &str, erro: ErroPlanilha) -> Response {
+    match erro {
+        ErroPlanilha::Formato(m) => resposta::<()>(StatusCode::BAD_REQUEST, m, None),
+        ErroPlanilha::Banco(e) => {
+            eprintln!("Erro ao {}: {}", contexto, e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao {}: {}", contexto, e), None)
+        }
+    }
+}
+
+fn planilha(nome_arquivo: &str, csv: String) -> Response {
+    let mut response = csv.into_response();
+    response.headers_mut().insert(header::CONTENT_TYPE, "text/csv; charset=utf-8".parse().unwrap());
+    response
+        .headers_mut()
+        .insert(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", nome_arquivo).parse().unwrap());
+    response
+}
+
+#[derive(Deserialize)]
+pub struct ExportarQuery {
+    busca: Option<String>,
+    separador: Option<String>,
+    inicio: Option<NaiveDate>,
+    fim: Option<NaiveDate>,
+    cliente_id: Option<i32>,
+    status: Option<String>,
+}
+
+impl ExportarQuery {
+    fn filtro(&self) -> FiltroAgendamentos {
+        FiltroAgendamentos { inicio: self.inicio, fim: self.fim, cliente_id: self.cliente_id, status: self.status.clone() }
+    }
+}
+
+fn separador_ou_padrao(valor: Option<&str>) -> Result<u8, ErroPlanilha> {
+    valor.map(parse_separador).unwrap_or(Ok(b','))
+}
+
+fn exportar(headers: &HeaderMap, conn: &Db, nome_arquivo: &str, gerar: impl FnOnce(&Connection) -> Result<String, ErroPlanilha>) -> Response {
+    if let Err((status, message)) = autorizar_dono(headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let conn = conn.lock().unwrap();
+    match gerar(&conn) {
+        Ok(csv) => planilha(nome_arquivo, csv),
+        Err(e) => resposta_erro("exportar planilha", e),
+    }
+}
+
+/// GET /export/clientes.csv?busca=&separador= — só o dono (dados pessoais).
+pub async fn exportar_clientes_api(headers: HeaderMap, Query(q): Query<ExportarQuery>, State(conn): State<Db>) -> Response {
+    exportar(&headers, &conn, "clientes.csv", |conn| {
+        exportar_clientes(conn, q.busca.as_deref(), separador_ou_padrao(q.separador.as_deref())?)
+    })
+}
+
+/// GET /export/servicos.csv?separador= — só o dono.
+pub async fn exportar_servicos_api(headers: HeaderMap, Query(q): Query<ExportarQuery>, State(conn): State<Db>) -> Response {
+    exportar(&headers, &conn, "servicos.csv", |conn| exportar_servicos(conn, separador_ou_padrao(q.separador.as_deref())?))
+}
+
+/// GET /export/agendamentos.csv?inicio=&fim=&cliente_id=&status=&separador= — só o dono.
+pub async fn exportar_agendamentos_api(headers: HeaderMap, Query(q): Query<ExportarQuery>, State(conn): State<Db>) -> Response {
+    exportar(&headers, &conn, "agendamentos.csv", |conn| {
+        exportar_agendamentos(conn, &q.filtro(), separador_ou_padrao(q.separador.as_deref())?)
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ImportarQuery {
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    atualizar_duplicados: bool,
+    separador: Option<String>,
+    /// `campo=Cabeçalho,campo=Cabeçalho`.
+    colunas: Option<String>,
+}
+
+impl ImportarQuery {
+    fn opcoes(&self) -> Result<OpcoesImportacao, ErroPlanilha> {
+        Ok(OpcoesImportacao {
+            separador: self.separador.as_deref().map(parse_separador).transpose()?,
+            colunas: parse_colunas(self.colunas.as_deref().unwrap_or("").split(','))?,
+            atualizar_duplicados: self.atualizar_duplicados,
+            dry_run: self.dry_run,
+        })
+    }
+}
+
+fn importar(
+    headers: &HeaderMap,
+    conn: &Db,
+    q: &ImportarQuery,
+    corpo: &[u8],
+    executar: impl FnOnce(&Connection, &str, &OpcoesImportacao) -> Result<RelatorioImportacao, ErroPlanilha>,
+) -> Response {
+    if let Err((status, message)) = autorizar_dono(headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let Ok(texto) = std::str::from_utf8(corpo) else {
+        return resposta::<()>(StatusCode::BAD_REQUEST, "A planilha deve estar em UTF-8 (no Excel: CSV UTF-8).".to_string(), None);
+    };
+    let opcoes = match q.opcoes() {
+        Ok(opcoes) => opcoes,
+        Err(e) => return resposta_erro("importar planilha", e),
+    };
+    let conn = conn.lock().unwrap();
+    match executar(&conn, texto, &opcoes) {
+        Ok(relatorio) if !relatorio.erros.is_empty() => {
+            let message = format!("{} linha(s) com erro; nada foi importado.", relatorio.erros.len());
+            let status = if opcoes.dry_run { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+            resposta(status, message, Some(relatorio))
+        }
+        Ok(relatorio) => {
+            let prefixo = if relatorio.aplicado { "Importado" } else { "Validado (dry_run)" };
+            let message = format!(
+                "{}: {} novo(s), {} atualizado(s), {} ignorado(s).",
+                prefixo, relatorio.novos, relatorio.atualizados, relatorio.ignorados
+            );
+            resposta(StatusCode::OK, message, Some(relatorio))
+        }
+        Err(e) => resposta_erro("importar planilha", e),
+    }
+}
+
+/// POST /import/clientes?dry_run=&atualizar_duplicados=&separador=&colunas= — corpo é o CSV.
+/// Duplicados são pelo telefone (só dígitos). Só o dono.
+pub async fn importar_clientes_api(headers: HeaderMap, Query(q): Query<ImportarQuery>, State(conn): State<Db>, corpo: Bytes) -> Response {
+    importar(&headers, &conn, &q, &corpo, importar_clientes)
+}
+
+/// POST /import/servicos?... — como o de clientes; duplicados são pelo nome.
+pub async fn importar_servicos_api(headers: HeaderMap, Query(q): Query<ImportarQuery>, State(conn): State<Db>, corpo: Bytes) -> Response {
+    importar(&headers, &conn, &q, &corpo, importar_servicos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agendamento;
+
+    #[test]
+    fn test_importar_clientes_com_mapeamento_e_duplicados() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut existente = Cliente::new("João".to_string(), "(11) 98888-7777".to_string(), None);
+        let joao = db::salvar_cliente(&conn, &mut existente).unwrap();
+
+        let csv = "\u{feff}Nome Completo;Celular;E-mail\n\
+                   Ana Souza;11 99999-0000;ana@x.com\n\
+                   João;11988887777;\n\
+                   Ana de novo;(11) 99999-0000;\n\
+                   ;123;\n";
+        let mut opcoes = OpcoesImportacao {
+            colunas: parse_colunas(["nome=Nome Completo"]).unwrap(),
+            dry_run: true,
+            ..Default::default()
+        };
+        let relatorio = importar_clientes(&conn, csv, &opcoes).unwrap();
+        assert_eq!(relatorio.linhas, 4);
+        assert_eq!(relatorio.erros.len(), 1);
+        assert_eq!(relatorio.erros[0].linha, 5);
+        assert_eq!(relatorio.duplicados.len(), 2);
+        assert_eq!(relatorio.duplicados[0].existente_id, Some(joao));
+        assert_eq!(relatorio.duplicados[1].linha_anterior, Some(2));
+        assert!(!relatorio.aplicado);
+
+        // Com erro nada é gravado; sem a linha ruim, grava só a Ana.
+        opcoes.dry_run = false;
+        assert!(!importar_clientes(&conn, csv, &opcoes).unwrap().aplicado);
+        let sem_erro = csv.trim_end().rsplit_once('\n').unwrap().0;
+        webhooks::salvar_webhook(&conn, "http://127.0.0.1:9/hook", "segredo", &["*".to_string()]).unwrap();
+        let relatorio = importar_clientes(&conn, sem_erro, &opcoes).unwrap();
+        assert!(relatorio.aplicado);
+        assert_eq!((relatorio.novos, relatorio.ignorados), (1, 2));
+        assert_eq!(db::listar_clientes(&conn).unwrap().len(), 2);
+
+        // Como no cadastro pela API: webhook cliente.criado e criação na auditoria.
+        let ana = relatorio.ids[0];
+        let entregas = webhooks::listar_entregas_cliente(&conn, ana).unwrap();
+        assert_eq!(entregas.iter().map(|e| e.evento.as_str()).collect::<Vec<_>>(), [webhooks::EVENTO_CLIENTE_CRIADO]);
+        let filtro = db::FiltroAuditoria { entidade: Some("cliente".into()), entidade_id: Some(ana as i64), limite: 10, ..Default::default() };
+        let auditoria = db::listar_auditoria(&conn, &filtro).unwrap();
+        assert_eq!(auditoria.iter().map(|r| r.acao.as_str()).collect::<Vec<_>>(), [db::ACAO_CRIACAO]);
+
+        let exportado = exportar_clientes(&conn, None, b';').unwrap();
+        assert!(exportado.starts_with(BOM));
+        assert!(exportado.contains("Ana Souza;11 99999-0000;ana@x.com"));
+    }
+
+    #[test]
+    fn test_exportar_agendamentos_com_status_e_filtros() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".to_string(), "11999990000".to_string(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let servico = Servico { id: None, nome: "Corte".into(), preco: 40.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
+        let servico_id = db::salvar_servico(&conn, &servico).unwrap();
+        let dia = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let mut ids = Vec::new();
+        for (hora, concluido) in [(9, false), (10, false), (11, true)] {
+            let data_hora = dia.and_hms_opt(hora, 0, 0).unwrap();
+            ids.push(db::salvar_agendamento(&conn, &Agendamento { id: None, cliente_id, servicos_ids: vec![servico_id], data_hora, preco: 40.0, concluido }).unwrap());
+        }
+        db::definir_status_agendamento(&conn, ids[0], db::STATUS_AGENDAMENTO_CONFIRMADO).unwrap();
+        db::definir_status_agendamento(&conn, ids[1], db::STATUS_AGENDAMENTO_CANCELADO).unwrap();
+
+        let filtro = FiltroAgendamentos { inicio: Some(dia), fim: Some(dia), ..Default::default() };
+        let csv = exportar_agendamentos(&conn, &filtro, b';').unwrap();
+        let status: Vec<&str> = csv.lines().skip(1).map(|l| l.rsplit(';').next().unwrap()).collect();
+        assert_eq!(status, ["confirmado", "cancelado", "concluido"]);
+        assert!(csv.contains(&format!("{};2025-03-10 09:00;{};Ana;11999990000;{};Corte;40.00;confirmado", ids[0], cliente_id, servico_id)));
+
+        let filtro = FiltroAgendamentos { status: Some("cancelado".into()), ..Default::default() };
+        assert_eq!(exportar_agendamentos(&conn, &filtro, b';').unwrap().lines().count(), 2);
+        let filtro = FiltroAgendamentos { inicio: dia.succ_opt(), ..Default::default() };
+        assert_eq!(exportar_agendamentos(&conn, &filtro, b';').unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_parse_numero() {
+        assert_eq!(parse_numero("R$ 1.234,50"), Some(1234.5));
+        assert_eq!(parse_numero("35,5"), Some(35.5));
+        assert_eq!(parse_numero("35.50"), Some(35.5));
+        assert_eq!(parse_numero("1.234"), Some(1234.0));
+        assert_eq!(parse_numero("R$ 1.234.567"), Some(1234567.0));
+        assert_eq!(parse_numero("1.5"), Some(1.5));
+        assert_eq!(parse_numero("abc"), None);
+    }
+}
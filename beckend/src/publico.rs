@@ -0,0 +1,828 @@
+use axum::{
+    extract::{ConnectInfo, Path, Query, Request, State},
+    http::{header, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{NaiveDate, NaiveDateTime};
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::auditoria;
+use crate::backup::textos_iguais;
+use crate::config;
+use crate::db;
+use crate::models::{Agendamento, Cliente, Servico};
+use crate::webhooks;
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// AGENDAMENTO PÚBLICO (AUTOATENDIMENTO)
+// Rotas `/public/...` para o cliente ver serviços e horários e marcar sozinho.
+// Cada reserva recebe um código de confirmação e um token assinado (HMAC) que dá
+// acesso a consultar, cancelar e remarcar, sem login. Reservas e lembretes também
+// levam um link de uso único, com validade, para confirmar ou cancelar.
+// =================================================================================
+
+/// Sem 0/O e 1/I para o código poder ser ditado por telefone.
+const ALFABETO_CODIGO: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const TAMANHO_CODIGO: usize = 6;
+
+pub fn criar_tabelas_publico(conn: &Connection) -> rusqlite::Result<()> {
+    db::garantir_coluna(conn, "sistema", "segredo_publico", "TEXT")?;
+    db::garantir_coluna(conn, "agendamentos", "codigo_confirmacao", "TEXT")?;
+    // Nome digitado na reserva pública; é o que a reserva mostra, nunca o nome já cadastrado.
+    db::garantir_coluna(conn, "agendamentos", "nome_reserva", "TEXT")?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_agendamentos_codigo ON agendamentos (codigo_confirmacao)",
+        [],
+    )?;
+    db::GatilhoAuditoria {
+        tabela: "agendamentos",
+        evento: "AFTER UPDATE OF codigo_confirmacao WHEN OLD.codigo_confirmacao IS NOT NEW.codigo_confirmacao",
+        entidade: "agendamento",
+        acao: db::ACAO_ATUALIZACAO,
+        id: "NEW.id",
+        antes: "json_object('codigo_confirmacao', OLD.codigo_confirmacao)",
+        depois: "json_object('codigo_confirmacao', NEW.codigo_confirmacao)",
+    }
+    .criar(conn)?;
+    // Ações já feitas com cada link: o mesmo link confirma uma vez e cancela uma vez, então a
+    // chave é o par (nonce, ação).
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tokens_publicos_usados (
+            nonce          TEXT NOT NULL,
+            agendamento_id INTEGER NOT NULL,
+            acao           TEXT NOT NULL,
+            usado_em       INTEGER NOT NULL,
+            PRIMARY KEY (nonce, acao)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn rotas() -> Router<Db> {
+    Router::new()
+        .route("/servicos", get(listar_servicos_api))
+        .route("/disponibilidade", get(disponibilidade_api))
+        .route("/agendamentos", post(reservar_api))
+        .route("/agendamentos/:chave", get(consultar_reserva_api).delete(cancelar_reserva_api))
+        .route("/agendamentos/:chave/confirmar", post(confirmar_api))
+        .route("/agendamentos/:chave/cancelar", post(cancelar_api))
+        .route("/agendamentos/:chave/reagendar", post(reagendar_api))
+        .route_layer(middleware::from_fn(limitar_requisicoes))
+}
+
+// ---------------------------------------------------------------------------------
+// Código, token e segredo
+// ---------------------------------------------------------------------------------
+
+/// Segredo das assinaturas de reserva: gerado na primeira vez e guardado em `sistema`.
+pub fn segredo_publico(conn: &Connection) -> rusqlite::Result<String> {
+    let atual: Option<String> = conn.query_row("SELECT segredo_publico FROM sistema WHERE id = 1", [], |row| row.get(0))?;
+    if let Some(segredo) = atual.filter(|s| !s.is_empty()) {
+        return Ok(segredo);
+    }
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).expect("falha ao gerar segredo aleatório");
+    let segredo = webhooks::hex(&bytes);
+    conn.execute("UPDATE sistema SET segredo_publico = ?1 WHERE id = 1", params![segredo])?;
+    Ok(segredo)
+}
+
+/// Token que autoriza mexer na reserva (vai no link enviado ao cliente).
+pub fn token_reserva(segredo: &str, agendamento_id: i32, codigo: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(segredo.as_bytes()).expect("HMAC aceita chave de qualquer tamanho");
+    mac.update(format!("reserva:{}:{}", agendamento_id, codigo).as_bytes());
+    webhooks::hex(&mac.finalize().into_bytes()[..16])
+}
+
+fn assinar_acao(segredo: &str, agendamento_id: i32, expira_ts: i64, nonce: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(segredo.as_bytes()).expect("HMAC aceita chave de qualquer tamanho");
+    mac.update(format!("acao:{}:{}:{}", agendamento_id, expira_ts, nonce).as_bytes());
+    webhooks::hex(&mac.finalize().into_bytes()[..16])
+}
+
+/// Token para ver, confirmar e cancelar: `<id>.<expira>.<nonce>.<assinatura>`. Cada ação vale
+/// uma vez por token; ver a reserva não gasta o link.
+/// Vale por `publico.validade_link_horas`, sem passar do horário marcado.
+pub fn emitir_token_acao(conn: &Connection, agendamento: &Agendamento) -> rusqlite::Result<String> {
+    let id = agendamento.id.unwrap_or_default();
+    let validade = config::agora() + chrono::Duration::hours(config::atual().publico.validade_link_horas);
+    let expira_ts = validade.min(agendamento.data_hora).and_utc().timestamp();
+    let mut bytes = [0u8; 8];
+    getrandom::getrandom(&mut bytes).expect("falha ao gerar número aleatório");
+    let nonce = webhooks::hex(&bytes);
+    let assinatura = assinar_acao(&segredo_publico(conn)?, id, expira_ts, &nonce);
+    Ok(format!("{}.{}.{}.{}", id, expira_ts, nonce, assinatura))
+}
+
+/// Link de confirmar/cancelar para mandar ao cliente (placeholder `{link}` dos lembretes).
+pub fn link_acao(conn: &Connection, agendamento: &Agendamento) -> rusqlite::Result<String> {
+    let base = config::atual().publico.url_base.trim_end_matches('/');
+    Ok(format!("{}/public/agendamentos/{}", base, emitir_token_acao(conn, agendamento)?))
+}
+
+/// Confere assinatura e validade de um token de ação. Devolve o agendamento e o nonce.
+fn ler_token_acao(conn: &Connection, token: &str) -> Result<(Agendamento, String), ErroApi> {
+    let invalido = || (StatusCode::NOT_FOUND, "Link inválido.".to_string());
+    let partes: Vec<&str> = token.split('.').collect();
+    let [id, expira_ts, nonce, assinatura] = partes[..] else {
+        return Err(invalido());
+    };
+    let (Ok(id), Ok(expira_ts)) = (id.parse::<i32>(), expira_ts.parse::<i64>()) else {
+        return Err(invalido());
+    };
+    let segredo = segredo_publico(conn).map_err(|e| erro_banco("ler segredo", e))?;
+    if !textos_iguais(assinatura, &assinar_acao(&segredo, id, expira_ts, nonce)) {
+        return Err(invalido());
+    }
+    if expira_ts < db::agora_ts() {
+        return Err((StatusCode::GONE, "Este link expirou. Fale com a barbearia.".to_string()));
+    }
+    match db::buscar_agendamento_por_id(conn, id) {
+        Ok(agendamento) => Ok((agendamento, nonce.to_string())),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Err((StatusCode::NOT_FOUND, "Reserva não encontrada.".to_string())),
+        Err(e) => Err(erro_banco("buscar reserva", e)),
+    }
+}
+
+/// Marca o token como usado para `acao`; repetir a mesma ação com ele responde 410.
+fn consumir_token(conn: &Connection, nonce: &str, agendamento_id: i32, acao: &str) -> Result<(), ErroApi> {
+    let inseridos = conn
+        .execute(
+            "INSERT OR IGNORE INTO tokens_publicos_usados (nonce, agendamento_id, acao, usado_em) VALUES (?1, ?2, ?3, ?4)",
+            params![nonce, agendamento_id, acao, db::agora_ts()],
+        )
+        .map_err(|e| erro_banco("registrar uso do link", e))?;
+    if inseridos == 0 {
+        return Err((StatusCode::GONE, "Este link já foi usado.".to_string()));
+    }
+    Ok(())
+}
+
+fn gerar_codigo() -> String {
+    let mut bytes = [0u8; TAMANHO_CODIGO];
+    getrandom::getrandom(&mut bytes).expect("falha ao gerar código aleatório");
+    bytes.iter().map(|b| ALFABETO_CODIGO[*b as usize % ALFABETO_CODIGO.len()] as char).collect()
+}
+
+pub(crate) fn somente_digitos(telefone: &str) -> String {
+    telefone.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// Cliente com o mesmo telefone (comparando só os dígitos), se houver.
+pub fn buscar_cliente_por_telefone(conn: &Connection, telefone: &str) -> rusqlite::Result<Option<Cliente>> {
+    let procurado = somente_digitos(telefone);
+    let mut stmt = conn.prepare("SELECT id, nome, telefone, email FROM clientes ORDER BY id")?;
+    let clientes = stmt.query_map([], |row| {
+        Ok(Cliente { id: row.get(0)?, nome: row.get(1)?, telefone: row.get(2)?, email: row.get(3)? })
+    })?;
+    for cliente in clientes {
+        let cliente = cliente?;
+        if somente_digitos(&cliente.telefone) == procurado {
+            return Ok(Some(cliente));
+        }
+    }
+    Ok(None)
+}
+
+// ---------------------------------------------------------------------------------
+// Limite de requisições por IP
+// ---------------------------------------------------------------------------------
+
+/// Janela deslizante por (IP, categoria).
+#[derive(Default)]
+pub struct LimiteTaxa {
+    registros: Mutex<HashMap<(IpAddr, &'static str), VecDeque<Instant>>>,
+}
+
+impl LimiteTaxa {
+    /// Registra a requisição se couber no limite; senão devolve em quantos segundos tentar de novo.
+    pub fn permitir(&self, ip: IpAddr, categoria: &'static str, maximo: usize, janela: std::time::Duration, agora: Instant) -> Result<(), u64> {
+        let mut registros = self.registros.lock().unwrap();
+        if registros.len() > 10_000 {
+            registros.retain(|_, fila| fila.back().is_some_and(|t| agora.duration_since(*t) < janela));
+        }
+        let fila = registros.entry((ip, categoria)).or_default();
+        while fila.front().is_some_and(|t| agora.duration_since(*t) >= janela) {
+            fila.pop_front();
+        }
+        if fila.len() >= maximo {
+            let liberado_em = janela - agora.duration_since(fila[0]);
+            return Err(liberado_em.as_secs().max(1));
+        }
+        fila.push_back(agora);
+        Ok(())
+    }
+}
+
+fn limites() -> &'static LimiteTaxa {
+    static LIMITES: OnceLock<LimiteTaxa> = OnceLock::new();
+    LIMITES.get_or_init(LimiteTaxa::default)
+}
+
+/// IP que o proxy viu: o último de `X-Forwarded-For`. Os anteriores vêm do próprio cliente
+/// e podem ser forjados para escapar do limite por IP.
+fn ip_encaminhado(valor: &str) -> Option<IpAddr> {
+    valor.rsplit(',').next().and_then(|ip| ip.trim().parse().ok())
+}
+
+fn ip_do_cliente(req: &Request) -> IpAddr {
+    if config::atual().publico.confiar_x_forwarded_for {
+        let encaminhado = req.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok()).and_then(ip_encaminhado);
+        if let Some(ip) = encaminhado {
+            return ip;
+        }
+    }
+    req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip()).unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+}
+
+/// Middleware das rotas públicas: desliga tudo se `publico.ativo = false`, aplica os
+/// limites por IP e grava as alterações na auditoria com o ator "publico".
+pub async fn limitar_requisicoes(req: Request, next: Next) -> Response {
+    let regras = &config::atual().publico;
+    if !regras.ativo {
+        return resposta::<()>(StatusCode::NOT_FOUND, "Agendamento online desativado.".to_string(), None);
+    }
+    let ip = ip_do_cliente(&req);
+    let agora = Instant::now();
+    let mut verificacao = limites().permitir(ip, "geral", regras.requisicoes_por_minuto, std::time::Duration::from_secs(60), agora);
+    if verificacao.is_ok() && req.method() != Method::GET && req.method() != Method::HEAD {
+        verificacao = limites().permitir(ip, "reserva", regras.reservas_por_hora, std::time::Duration::from_secs(3600), agora);
+    }
+    if let Err(segundos) = verificacao {
+        let mut response = resposta::<()>(StatusCode::TOO_MANY_REQUESTS, format!("Muitas requisições. Tente de novo em {} segundo(s).", segundos), None);
+        response.headers_mut().insert(header::RETRY_AFTER, segundos.into());
+        return response;
+    }
+    auditoria::como_ator("publico", next.run(req)).await
+}
+
+// ---------------------------------------------------------------------------------
+// Regras da reserva
+// ---------------------------------------------------------------------------------
+
+/// Reserva como o cliente a vê (sem dados de outros clientes).
+#[derive(Debug, Serialize)]
+pub struct Reserva {
+    /// Só existe para reservas feitas pelas rotas públicas.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub codigo: Option<String>,
+    pub nome: String,
+    pub data_hora: NaiveDateTime,
+    pub duracao_min: i64,
+    pub servicos: Vec<String>,
+    pub preco: f64,
+    /// pendente, confirmado ou cancelado.
+    pub status: String,
+    pub concluido: bool,
+    /// Só na criação: o token que autoriza cancelar e remarcar.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    /// Caminho para consultar/cancelar (DELETE) e, com `/reagendar`, remarcar.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    /// Só na criação: links de uso único para confirmar ou cancelar.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acoes: Option<LinksAcao>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LinksAcao {
+    pub ver: String,
+    pub confirmar: String,
+    pub cancelar: String,
+}
+
+type ErroApi = (StatusCode, String);
+
+fn erro_banco(contexto: &str, e: rusqlite::Error) -> ErroApi {
+    eprintln!("Erro ao {}: {}", contexto, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao {}.", contexto))
+}
+
+fn servicos_da_reserva(conn: &Connection, ids: &[i32]) -> Result<Vec<Servico>, ErroApi> {
+    if ids.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Escolha ao menos um serviço.".to_string()));
+    }
+    let mut servicos = Vec::new();
+    for id in ids {
+        match db::buscar_servico_por_id(conn, *id) {
+            Ok(Some(servico)) => servicos.push(servico),
+            Ok(None) => return Err((StatusCode::BAD_REQUEST, format!("Serviço {} não existe.", id))),
+            Err(e) => return Err(erro_banco("buscar serviços", e)),
+        }
+    }
+    Ok(servicos)
+}
+
+fn duracao_total(servicos: &[Servico]) -> i64 {
+    let total: i64 = servicos.iter().map(|s| s.duracao_min as i64).sum();
+    if total > 0 { total } else { config::atual().agenda.duracao_padrao_min }
+}
+
+/// Horários livres do dia para a duração pedida, só os que ainda não passaram. `ignorar` é a
+/// própria reserva quando ela está sendo remarcada.
+pub fn horarios_livres(conn: &Connection, data: NaiveDate, servicos_ids: &[i32], ignorar: Option<i32>) -> rusqlite::Result<Vec<NaiveDateTime>> {
+    let agora = config::agora();
+    let pedido = db::PedidoDisponibilidade { ignorar, ..db::pedido_para_servicos(conn, servicos_ids, agora)? };
+    let slots = db::horarios_disponiveis(conn, data, &pedido)?;
+    Ok(slots.iter()
+        .filter_map(|s| NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S").ok())
+        .filter(|s| *s > agora)
+        .collect())
+}
+
+fn ler_data_hora(valor: &str) -> Result<NaiveDateTime, ErroApi> {
+    let valor = valor.trim();
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|formato| NaiveDateTime::parse_from_str(valor, formato).ok())
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("data_hora inválida '{}': use AAAA-MM-DDTHH:MM.", valor)))
+}
+
+fn garantir_horario_livre(conn: &Connection, data_hora: NaiveDateTime, servicos_ids: &[i32], ignorar: Option<i32>) -> Result<(), ErroApi> {
+    if data_hora <= config::agora() {
+        return Err((StatusCode::BAD_REQUEST, "Não é possível agendar no passado.".to_string()));
+    }
+    let livres = horarios_livres(conn, data_hora.date(), servicos_ids, ignorar).map_err(|e| erro_banco("calcular disponibilidade", e))?;
+    if livres.contains(&data_hora) {
+        Ok(())
+    } else {
+        Err((StatusCode::CONFLICT, "Esse horário não está disponível. Consulte /public/disponibilidade.".to_string()))
+    }
+}
+
+/// Monta a visão pública. Reservas feitas pelo site mostram o nome digitado nelas: quem
+/// reservou com o telefone de outra pessoa não descobre o nome cadastrado.
+fn montar_reserva(conn: &Connection, agendamento: &Agendamento) -> Result<Reserva, ErroApi> {
+    let id = agendamento.id.unwrap_or_default();
+    let servicos = servicos_da_reserva(conn, &agendamento.servicos_ids).unwrap_or_default();
+    let (codigo, status, nome_reserva): (Option<String>, String, Option<String>) = conn
+        .query_row("SELECT codigo_confirmacao, status, nome_reserva FROM agendamentos WHERE id = ?1", params![id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| erro_banco("buscar reserva", e))?;
+    let nome = match nome_reserva {
+        Some(nome) => nome,
+        None => db::buscar_cliente_por_id(conn, agendamento.cliente_id)
+            .map_err(|e| erro_banco("buscar cliente", e))?
+            .map(|c| c.nome)
+            .unwrap_or_default(),
+    };
+    Ok(Reserva {
+        codigo,
+        nome,
+        data_hora: agendamento.data_hora,
+        duracao_min: duracao_total(&servicos),
+        servicos: servicos.into_iter().map(|s| s.nome).collect(),
+        preco: agendamento.preco,
+        status,
+        concluido: agendamento.concluido,
+        token: None,
+        link: None,
+        acoes: None,
+    })
+}
+
+fn link_reserva(codigo: &str, token: &str) -> String {
+    format!("/public/agendamentos/{}?token={}", codigo, token)
+}
+
+/// Localiza a reserva pelo código e confere o token. Código inexistente e token errado
+/// dão a mesma resposta, para não revelar quais códigos existem.
+fn reserva_autorizada(conn: &Connection, codigo: &str, token: Option<&str>) -> Result<Agendamento, ErroApi> {
+    let codigo = codigo.trim().to_ascii_uppercase();
+    let nao_encontrada = || (StatusCode::NOT_FOUND, "Reserva não encontrada.".to_string());
+    let id: i32 = conn
+        .query_row("SELECT id FROM agendamentos WHERE codigo_confirmacao = ?1", params![codigo], |row| row.get(0))
+        .optional()
+        .map_err(|e| erro_banco("buscar reserva", e))?
+        .ok_or_else(nao_encontrada)?;
+    let segredo = segredo_publico(conn).map_err(|e| erro_banco("ler segredo", e))?;
+    if !textos_iguais(token.unwrap_or(""), &token_reserva(&segredo, id, &codigo)) {
+        return Err(nao_encontrada());
+    }
+    db::buscar_agendamento_por_id(conn, id).map_err(|e| erro_banco("buscar reserva", e))
+}
+
+/// Reserva apontada pela rota: `:chave` é um token de ação (tem pontos) ou o código de
+/// confirmação acompanhado de `?token=`. Só o token de ação traz nonce (uso único).
+fn localizar(conn: &Connection, chave: &str, token: Option<&str>) -> Result<(Agendamento, Option<String>), ErroApi> {
+    if chave.contains('.') {
+        ler_token_acao(conn, chave).map(|(agendamento, nonce)| (agendamento, Some(nonce)))
+    } else {
+        reserva_autorizada(conn, chave, token).map(|agendamento| (agendamento, None))
+    }
+}
+
+/// Regras para o cliente mexer na reserva: não pode estar concluída, cancelada nem ter
+/// passado; cancelar e remarcar exigem `publico.antecedencia_cancelamento_horas`.
+fn garantir_alteravel(conn: &Connection, agendamento: &Agendamento, exigir_antecedencia: bool) -> Result<(), ErroApi> {
+    if agendamento.concluido {
+        return Err((StatusCode::CONFLICT, "Este atendimento já foi concluído.".to_string()));
+    }
+    let status = db::status_agendamento(conn, agendamento.id.unwrap_or_default()).map_err(|e| erro_banco("buscar reserva", e))?;
+    if status == db::STATUS_AGENDAMENTO_CANCELADO {
+        return Err((StatusCode::CONFLICT, "Esta reserva já foi cancelada.".to_string()));
+    }
+    let agora = config::agora();
+    if agendamento.data_hora <= agora {
+        return Err((StatusCode::CONFLICT, "Este horário já passou; fale com a barbearia.".to_string()));
+    }
+    let antecedencia = config::atual().publico.antecedencia_cancelamento_horas;
+    if exigir_antecedencia && agendamento.data_hora - agora < chrono::Duration::hours(antecedencia) {
+        let message = format!("Cancelamentos e remarcações só com {}h de antecedência; fale com a barbearia.", antecedencia);
+        return Err((StatusCode::CONFLICT, message));
+    }
+    Ok(())
+}
+
+/// O cliente confirma que vem.
+pub fn confirmar_reserva(conn: &Connection, agendamento: &Agendamento, nonce: Option<&str>) -> Result<(), ErroApi> {
+    garantir_alteravel(conn, agendamento, false)?;
+    let id = agendamento.id.unwrap_or_default();
+    if db::status_agendamento(conn, id).map_err(|e| erro_banco("buscar reserva", e))? == db::STATUS_AGENDAMENTO_CONFIRMADO {
+        return Err((StatusCode::CONFLICT, "Esta reserva já está confirmada.".to_string()));
+    }
+    if let Some(nonce) = nonce {
+        consumir_token(conn, nonce, id, "confirmar")?;
+    }
+    db::definir_status_agendamento(conn, id, db::STATUS_AGENDAMENTO_CONFIRMADO).map_err(|e| erro_banco("confirmar reserva", e))?;
+    webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_CONFIRMADO, agendamento);
+    Ok(())
+}
+
+/// O cliente cancela: o agendamento fica registrado como cancelado e libera o horário.
+pub fn cancelar_reserva(conn: &Connection, agendamento: &Agendamento, nonce: Option<&str>) -> Result<(), ErroApi> {
+    garantir_alteravel(conn, agendamento, true)?;
+    let id = agendamento.id.unwrap_or_default();
+    if let Some(nonce) = nonce {
+        consumir_token(conn, nonce, id, "cancelar")?;
+    }
+    db::definir_status_agendamento(conn, id, db::STATUS_AGENDAMENTO_CANCELADO).map_err(|e| erro_banco("cancelar reserva", e))?;
+    webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_CANCELADO, agendamento);
+    crate::espera::vaga_liberada(conn, agendamento);
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NovaReserva {
+    pub nome: String,
+    pub telefone: String,
+    pub email: Option<String>,
+    pub servicos_ids: Vec<i32>,
+    pub data_hora: String,
+}
+
+/// Cria a reserva: acha (pelo telefone) ou cadastra o cliente, confere o horário e grava. A
+/// resposta é a mesma com telefone novo ou já cadastrado.
+pub fn reservar(conn: &Connection, nova: &NovaReserva) -> Result<Reserva, ErroApi> {
+    let nome = nova.nome.trim();
+    if nome.is_empty() || nome.chars().count() > 100 {
+        return Err((StatusCode::BAD_REQUEST, "Informe seu nome (até 100 caracteres).".to_string()));
+    }
+    if !(8..=15).contains(&somente_digitos(&nova.telefone).len()) {
+        return Err((StatusCode::BAD_REQUEST, "Informe um telefone válido, com DDD.".to_string()));
+    }
+    let servicos = servicos_da_reserva(conn, &nova.servicos_ids)?;
+    let data_hora = ler_data_hora(&nova.data_hora)?;
+
+    // Cliente, agendamento, código e webhooks entram juntos: um erro no meio não deixa cliente
+    // nem agendamento sem código para trás.
+    let tx = conn.unchecked_transaction().map_err(|e| erro_banco("iniciar reserva", e))?;
+    garantir_horario_livre(&tx, data_hora, &nova.servicos_ids, None)?;
+
+    let cliente_id = match buscar_cliente_por_telefone(&tx, &nova.telefone).map_err(|e| erro_banco("buscar cliente", e))? {
+        Some(cliente) => cliente.id.unwrap_or_default(),
+        None => {
+            let email = nova.email.as_deref().map(str::trim).filter(|e| !e.is_empty()).map(str::to_string);
+            let mut cliente = Cliente { id: None, nome: nome.to_string(), telefone: nova.telefone.trim().to_string(), email };
+            let id = db::salvar_cliente(&tx, &mut cliente).map_err(|e| erro_banco("cadastrar cliente", e))?;
+            webhooks::emitir_ou_logar(&tx, webhooks::EVENTO_CLIENTE_CRIADO, &cliente);
+            id
+        }
+    };
+
+    let mut agendamento = Agendamento {
+        id: None,
+        cliente_id,
+        servicos_ids: nova.servicos_ids.clone(),
+        data_hora,
+        preco: servicos.iter().map(|s| s.preco).sum(),
+        concluido: false,
+    };
+    let id = db::salvar_agendamento(&tx, &agendamento).map_err(|e| erro_banco("salvar agendamento", e))?;
+    agendamento.id = Some(id);
+
+    // O índice único garante que o código não se repete; em caso de colisão, sorteia outro.
+    let mut codigo = gerar_codigo();
+    loop {
+        match tx.execute("UPDATE agendamentos SET codigo_confirmacao = ?1, nome_reserva = ?2 WHERE id = ?3", params![codigo, nome, id]) {
+            Ok(_) => break,
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => codigo = gerar_codigo(),
+            Err(e) => return Err(erro_banco("gravar código da reserva", e)),
+        }
+    }
+    webhooks::emitir_ou_logar(&tx, webhooks::EVENTO_AGENDAMENTO_CRIADO, &agendamento);
+    tx.commit().map_err(|e| erro_banco("gravar reserva", e))?;
+
+    let segredo = segredo_publico(conn).map_err(|e| erro_banco("ler segredo", e))?;
+    let token = token_reserva(&segredo, id, &codigo);
+    let ver = link_acao(conn, &agendamento).map_err(|e| erro_banco("gerar link", e))?;
+    let mut reserva = montar_reserva(conn, &agendamento)?;
+    reserva.link = Some(link_reserva(&codigo, &token));
+    reserva.token = Some(token);
+    reserva.acoes = Some(LinksAcao { confirmar: format!("{}/confirmar", ver), cancelar: format!("{}/cancelar", ver), ver });
+    Ok(reserva)
+}
+
+/// Remarca para `data_hora`, aceitando horários que hoje se sobrepõem à própria reserva.
+pub fn reagendar(conn: &Connection, agendamento: &Agendamento, data_hora: NaiveDateTime) -> Result<Agendamento, ErroApi> {
+    let id = agendamento.id.unwrap_or_default();
+    servicos_da_reserva(conn, &agendamento.servicos_ids)?;
+    garantir_horario_livre(conn, data_hora, &agendamento.servicos_ids, Some(id))?;
+
+    db::remarcar_agendamento(conn, id, agendamento.data_hora, data_hora, db::ORIGEM_CLIENTE, None).map_err(|e| erro_banco("remarcar", e))?;
+    let atualizado = db::buscar_agendamento_por_id(conn, id).map_err(|e| erro_banco("buscar reserva", e))?;
+    webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_ATUALIZADO, &atualizado);
+    Ok(atualizado)
+}
+
+/// Apaga os nomes digitados nas reservas públicas do cliente (anonimização LGPD).
+pub fn redigir_reservas_cliente(conn: &Connection, cliente_id: i32) -> rusqlite::Result<()> {
+    conn.execute("UPDATE agendamentos SET nome_reserva = NULL WHERE cliente_id = ?1", params![cliente_id])?;
+    Ok(())
+}
+
+// ---------------------------------------------------------------------------------
+// Handlers
+// ---------------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn resposta<T: Serialize>(status: StatusCode, message: String, data: Option<T>) -> Response {
+    (status, Json(ApiResponse { success: status.is_success(), message, data })).into_response()
+}
+
+fn falha((status, message): ErroApi) -> Response {
+    resposta::<()>(status, message, None)
+}
+
+#[derive(Serialize)]
+pub struct ServicoPublico {
+    pub id: i32,
+    pub nome: String,
+    pub preco: f64,
+    pub duracao_min: i32,
+}
+
+/// GET /public/servicos — serviços que podem ser agendados.
+pub async fn listar_servicos_api(State(conn): State<Db>) -> Response {
+    let conn = conn.lock().unwrap();
+    match db::listar_servicos(&conn) {
+        Ok(lista) => {
+            let lista: Vec<ServicoPublico> = lista.into_iter()
+                .map(|s| ServicoPublico { id: s.id.unwrap_or_default(), nome: s.nome, preco: s.preco, duracao_min: s.duracao_min })
+                .collect();
+            resposta(StatusCode::OK, format!("{} serviço(s).", lista.len()), Some(lista))
+        }
+        Err(e) => falha(erro_banco("listar serviços", e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DisponibilidadeQuery {
+    data: NaiveDate,
+    /// IDs separados por vírgula; a duração é a soma dos serviços.
+    servicos: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct Disponibilidade {
+    pub data: NaiveDate,
+    pub duracao_min: i64,
+    pub horarios: Vec<NaiveDateTime>,
+}
+
+/// GET /public/disponibilidade?data=AAAA-MM-DD&servicos=1,2 — horários livres do dia.
+pub async fn disponibilidade_api(Query(q): Query<DisponibilidadeQuery>, State(conn): State<Db>) -> Response {
+    let ids: Result<Vec<i32>, _> = q.servicos.as_deref().unwrap_or("").split(',')
+        .map(str::trim).filter(|s| !s.is_empty()).map(str::parse).collect();
+    let Ok(ids) = ids else {
+        return falha((StatusCode::BAD_REQUEST, "servicos deve ser uma lista de IDs separados por vírgula.".to_string()));
+    };
+    let conn = conn.lock().unwrap();
+    let duracao_min = if ids.is_empty() {
+        config::atual().agenda.duracao_padrao_min
+    } else {
+        match servicos_da_reserva(&conn, &ids) {
+            Ok(servicos) => duracao_total(&servicos),
+            Err(e) => return falha(e),
+        }
+    };
+    match horarios_livres(&conn, q.data, &ids, None) {
+        Ok(horarios) => resposta(StatusCode::OK, format!("{} horário(s) livre(s).", horarios.len()), Some(Disponibilidade { data: q.data, duracao_min, horarios })),
+        Err(e) => falha(erro_banco("calcular disponibilidade", e)),
+    }
+}
+
+/// POST /public/agendamentos — o cliente marca um horário.
+pub async fn reservar_api(State(conn): State<Db>, Json(nova): Json<NovaReserva>) -> Response {
+    let conn = conn.lock().unwrap();
+    match reservar(&conn, &nova) {
+        Ok(reserva) => {
+            let message = format!(
+                "Pedido de agendamento recebido, aguardando confirmação. Código: {}. Guarde o link para confirmar, cancelar ou remarcar.",
+                reserva.codigo.as_deref().unwrap_or_default()
+            );
+            resposta(StatusCode::CREATED, message, Some(reserva))
+        }
+        Err(e) => falha(e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TokenQuery {
+    token: Option<String>,
+}
+
+/// GET /public/agendamentos/:chave — consulta a reserva, pelo link de ação
+/// (`/public/agendamentos/<token>`) ou pelo código (`/public/agendamentos/<codigo>?token=`).
+pub async fn consultar_reserva_api(Path(chave): Path<String>, Query(q): Query<TokenQuery>, State(conn): State<Db>) -> Response {
+    let conn = conn.lock().unwrap();
+    match localizar(&conn, &chave, q.token.as_deref()).and_then(|(agendamento, _)| montar_reserva(&conn, &agendamento)) {
+        Ok(reserva) => resposta(StatusCode::OK, "Reserva encontrada.".to_string(), Some(reserva)),
+        Err(e) => falha(e),
+    }
+}
+
+fn executar_acao(conn: &Db, chave: &str, token: Option<&str>, acao: fn(&Connection, &Agendamento, Option<&str>) -> Result<(), ErroApi>) -> Result<Reserva, ErroApi> {
+    let conn = conn.lock().unwrap();
+    let (agendamento, nonce) = localizar(&conn, chave, token)?;
+    acao(&conn, &agendamento, nonce.as_deref())?;
+    montar_reserva(&conn, &agendamento)
+}
+
+/// POST /public/agendamentos/:chave/confirmar — o cliente confirma o horário.
+pub async fn confirmar_api(Path(chave): Path<String>, Query(q): Query<TokenQuery>, State(conn): State<Db>) -> Response {
+    match executar_acao(&conn, &chave, q.token.as_deref(), confirmar_reserva) {
+        Ok(reserva) => resposta(StatusCode::OK, "Presença confirmada. Até lá!".to_string(), Some(reserva)),
+        Err(e) => falha(e),
+    }
+}
+
+/// POST /public/agendamentos/:chave/cancelar — o cliente cancela.
+pub async fn cancelar_api(Path(chave): Path<String>, Query(q): Query<TokenQuery>, State(conn): State<Db>) -> Response {
+    match executar_acao(&conn, &chave, q.token.as_deref(), cancelar_reserva) {
+        Ok(reserva) => resposta(StatusCode::OK, "Agendamento cancelado.".to_string(), Some(reserva)),
+        Err(e) => falha(e),
+    }
+}
+
+/// DELETE /public/agendamentos/:chave — o mesmo que `POST .../cancelar`.
+pub async fn cancelar_reserva_api(path: Path<String>, query: Query<TokenQuery>, state: State<Db>) -> Response {
+    cancelar_api(path, query, state).await
+}
+
+#[derive(Deserialize)]
+pub struct NovoHorario {
+    pub data_hora: String,
+}
+
+/// POST /public/agendamentos/:codigo/reagendar?token= — o cliente troca o horário
+/// (só pelo link da reserva; os links de uso único servem para confirmar ou cancelar).
+pub async fn reagendar_api(
+    Path(codigo): Path<String>,
+    Query(q): Query<TokenQuery>,
+    State(conn): State<Db>,
+    Json(novo): Json<NovoHorario>,
+) -> Response {
+    let conn = conn.lock().unwrap();
+    let resultado = reserva_autorizada(&conn, &codigo, q.token.as_deref()).and_then(|agendamento| {
+        garantir_alteravel(&conn, &agendamento, true)?;
+        let atualizado = reagendar(&conn, &agendamento, ler_data_hora(&novo.data_hora)?)?;
+        montar_reserva(&conn, &atualizado)
+    });
+    match resultado {
+        Ok(reserva) => resposta(StatusCode::OK, "Horário alterado.".to_string(), Some(reserva)),
+        Err(e) => falha(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_limite_por_ip_em_janela_deslizante() {
+        let limite = LimiteTaxa::default();
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let janela = std::time::Duration::from_secs(60);
+        let inicio = Instant::now();
+        assert!(limite.permitir(ip, "reserva", 2, janela, inicio).is_ok());
+        assert!(limite.permitir(ip, "reserva", 2, janela, inicio + std::time::Duration::from_secs(10)).is_ok());
+        assert_eq!(limite.permitir(ip, "reserva", 2, janela, inicio + std::time::Duration::from_secs(20)), Err(40));
+        // Outro IP e outra categoria têm contagem própria; a janela libera com o tempo.
+        assert!(limite.permitir("10.0.0.2".parse().unwrap(), "reserva", 2, janela, inicio).is_ok());
+        assert!(limite.permitir(ip, "geral", 2, janela, inicio).is_ok());
+        assert!(limite.permitir(ip, "reserva", 2, janela, inicio + std::time::Duration::from_secs(61)).is_ok());
+
+        // Atrás do proxy vale o último IP da lista; o cliente controla os anteriores.
+        assert_eq!(ip_encaminhado("1.2.3.4, 10.0.0.9"), Some("10.0.0.9".parse().unwrap()));
+        assert_eq!(ip_encaminhado("10.0.0.9"), Some("10.0.0.9".parse().unwrap()));
+        assert_eq!(ip_encaminhado("1.2.3.4, lixo"), None);
+    }
+
+    #[test]
+    fn test_reserva_publica_cancelamento_e_remarcacao() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let corte = Servico { id: None, nome: "Corte".into(), preco: 35.0, duracao_min: 60, buffer_min: None, alinhamento_min: None };
+        let corte_id = db::salvar_servico(&conn, &corte).unwrap();
+        let mut existente = Cliente { id: None, nome: "Ana".into(), telefone: "(11) 98888-7777".into(), email: None };
+        db::salvar_cliente(&conn, &mut existente).unwrap();
+
+        let dia = (config::agora() + Duration::days(3)).date();
+        let nova = NovaReserva {
+            nome: "Ana Souza".into(),
+            telefone: "11988887777".into(),
+            email: None,
+            servicos_ids: vec![corte_id],
+            data_hora: format!("{}T10:00", dia),
+        };
+        let reserva = reservar(&conn, &nova).unwrap();
+        assert_eq!(reserva.codigo.as_ref().unwrap().len(), TAMANHO_CODIGO);
+        // O telefone já era da Ana, mas a resposta só repete o nome enviado.
+        assert_eq!((reserva.nome.as_str(), reserva.preco, reserva.duracao_min), ("Ana Souza", 35.0, 60));
+        // O mesmo horário não pode ser reservado de novo.
+        assert_eq!(reservar(&conn, &nova).unwrap_err().0, StatusCode::CONFLICT);
+
+        let token = reserva.token.clone().unwrap();
+        assert_eq!(reserva_autorizada(&conn, reserva.codigo.as_ref().unwrap(), Some("errado")).unwrap_err().0, StatusCode::NOT_FOUND);
+        let agendamento = reserva_autorizada(&conn, &reserva.codigo.as_ref().unwrap().to_lowercase(), Some(&token)).unwrap();
+        assert_eq!(montar_reserva(&conn, &agendamento).unwrap().nome, "Ana Souza");
+
+        // Remarcar para 10:30 sobrepõe o próprio horário atual e deve ser aceito.
+        let novo = dia.and_hms_opt(10, 30, 0).unwrap();
+        assert_eq!(reagendar(&conn, &agendamento, novo).unwrap().data_hora, novo);
+        assert!(!db::buscar_agendamento_por_id(&conn, agendamento.id.unwrap()).unwrap().concluido);
+        assert!(reagendar(&conn, &agendamento, config::agora() - Duration::hours(1)).is_err());
+    }
+
+    #[test]
+    fn test_links_de_uso_unico_e_antecedencia_minima() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente { id: None, nome: "Caio".into(), telefone: "11977776666".into(), email: None };
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let mut agendamento = Agendamento {
+            id: None,
+            cliente_id,
+            servicos_ids: vec![],
+            data_hora: (config::agora() + Duration::days(2)).date().and_hms_opt(15, 0, 0).unwrap(),
+            preco: 40.0,
+            concluido: false,
+        };
+        agendamento.id = Some(db::salvar_agendamento(&conn, &agendamento).unwrap());
+
+        let token = emitir_token_acao(&conn, &agendamento).unwrap();
+        let (achado, nonce) = localizar(&conn, &token, None).unwrap();
+        assert_eq!(achado.id, agendamento.id);
+        confirmar_reserva(&conn, &achado, nonce.as_deref()).unwrap();
+        assert_eq!(db::status_agendamento(&conn, achado.id.unwrap()).unwrap(), db::STATUS_AGENDAMENTO_CONFIRMADO);
+
+        // O mesmo link ainda cancela depois de confirmar, mas não repete a mesma ação.
+        let nonce = nonce.unwrap();
+        assert_eq!(consumir_token(&conn, &nonce, achado.id.unwrap(), "confirmar").unwrap_err().0, StatusCode::GONE);
+        cancelar_reserva(&conn, &achado, Some(&nonce)).unwrap();
+        assert_eq!(db::status_agendamento(&conn, achado.id.unwrap()).unwrap(), db::STATUS_AGENDAMENTO_CANCELADO);
+        assert_eq!(consumir_token(&conn, &nonce, achado.id.unwrap(), "cancelar").unwrap_err().0, StatusCode::GONE);
+        assert!(!db::verificar_conflito(&conn, agendamento.data_hora).unwrap());
+
+        // Assinatura adulterada e prazo de cancelamento.
+        let adulterado = token.replacen(&agendamento.id.unwrap().to_string(), "999", 1);
+        assert_eq!(localizar(&conn, &adulterado, None).unwrap_err().0, StatusCode::NOT_FOUND);
+        agendamento.data_hora = config::agora() + Duration::minutes(30);
+        agendamento.id = Some(db::salvar_agendamento(&conn, &agendamento).unwrap());
+        assert_eq!(cancelar_reserva(&conn, &agendamento, None).unwrap_err().0, StatusCode::CONFLICT);
+        confirmar_reserva(&conn, &agendamento, None).unwrap();
+    }
+}
@@ -0,0 +1,389 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+use crate::agendamentos::recusar_profissional;
+use crate::config;
+use crate::db;
+use crate::models::{Agendamento, Recurso};
+use crate::scheduling::{self, Intervalo};
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// RECURSOS
+// Lavatório, cadeira de química e afins: cada serviço pode exigir recursos, e um recurso
+// só atende um agendamento por vez. A disponibilidade (`db::carregar_agenda`) e a criação
+// de agendamentos consultam a ocupação daqui.
+//
+// Restrições por profissional ficam de fora de propósito: não há cadastro de profissionais
+// e a agenda é única, então o próprio horário já impede o barbeiro de atender dois clientes
+// ao mesmo tempo, e a ocupação de um recurso vale para a barbearia inteira. Pedidos que
+// tentam prender um recurso ou serviço a um profissional (`profissional_id`) são recusados
+// com 400, como nas demais rotas (`agendamentos::recusar_profissional`), em vez de ignorados.
+// =================================================================================
+
+pub fn criar_tabela_recursos(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS recursos (
+            id        INTEGER PRIMARY KEY AUTOINCREMENT,
+            nome      TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            descricao TEXT
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS servico_recursos (
+            servico_id INTEGER NOT NULL,
+            recurso_id INTEGER NOT NULL,
+            PRIMARY KEY (servico_id, recurso_id),
+            FOREIGN KEY (servico_id) REFERENCES servicos(id) ON DELETE CASCADE,
+            FOREIGN KEY (recurso_id) REFERENCES recursos(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn linha_para_recurso(row: &Row) -> Result<Recurso> {
+    Ok(Recurso { id: row.get(0)?, nome: row.get(1)?, descricao: row.get(2)? })
+}
+
+pub fn listar_recursos(conn: &Connection) -> Result<Vec<Recurso>> {
+    let mut stmt = conn.prepare("SELECT id, nome, descricao FROM recursos ORDER BY nome")?;
+    let recursos = stmt.query_map([], linha_para_recurso)?.collect();
+    recursos
+}
+
+pub fn buscar_recurso(conn: &Connection, id: i32) -> Result<Option<Recurso>> {
+    match conn.query_row("SELECT id, nome, descricao FROM recursos WHERE id = ?1", params![id], linha_para_recurso) {
+        Ok(recurso) => Ok(Some(recurso)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub fn salvar_recurso(conn: &Connection, recurso: &Recurso) -> Result<Recurso> {
+    conn.execute(
+        "INSERT INTO recursos (nome, descricao) VALUES (?1, ?2)",
+        params![recurso.nome, recurso.descricao],
+    )?;
+    let salvo = Recurso { id: Some(conn.last_insert_rowid() as i32), ..recurso.clone() };
+    db::registrar_auditoria(conn, "recurso", conn.last_insert_rowid(), db::ACAO_CRIACAO, None, db::json_de(&salvo))?;
+    Ok(salvo)
+}
+
+pub fn excluir_recurso(conn: &Connection, id: i32) -> Result<bool> {
+    let antes = buscar_recurso(conn, id)?;
+    // Sem depender de `PRAGMA foreign_keys`: o vínculo com os serviços sai junto.
+    conn.execute("DELETE FROM servico_recursos WHERE recurso_id = ?1", params![id])?;
+    let removidos = conn.execute("DELETE FROM recursos WHERE id = ?1", params![id])?;
+    if removidos > 0 {
+        db::registrar_auditoria(conn, "recurso", id as i64, db::ACAO_EXCLUSAO, db::json_de(&antes), None)?;
+    }
+    Ok(removidos > 0)
+}
+
+/// Recursos exigidos por um serviço.
+pub fn recursos_do_servico(conn: &Connection, servico_id: i32) -> Result<Vec<i32>> {
+    recursos_dos_servicos(conn, &[servico_id])
+}
+
+/// Recursos exigidos por qualquer um dos serviços, sem repetição.
+pub fn recursos_dos_servicos(conn: &Connection, servicos_ids: &[i32]) -> Result<Vec<i32>> {
+    let mut stmt = conn.prepare("SELECT recurso_id FROM servico_recursos WHERE servico_id = ?1")?;
+    let mut recursos = Vec::new();
+    for servico_id in servicos_ids {
+        for recurso in stmt.query_map(params![servico_id], |row| row.get::<_, i32>(0))? {
+            recursos.push(recurso?);
+        }
+    }
+    recursos.sort_unstable();
+    recursos.dedup();
+    Ok(recursos)
+}
+
+/// Troca a lista de recursos exigidos pelo serviço e registra a mudança na auditoria.
+pub fn definir_recursos_do_servico(conn: &Connection, servico_id: i32, recursos_ids: &[i32]) -> Result<Vec<i32>> {
+    let antes = recursos_do_servico(conn, servico_id)?;
+    conn.execute("DELETE FROM servico_recursos WHERE servico_id = ?1", params![servico_id])?;
+    for recurso_id in recursos_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO servico_recursos (servico_id, recurso_id) VALUES (?1, ?2)",
+            params![servico_id, recurso_id],
+        )?;
+    }
+    let depois = recursos_do_servico(conn, servico_id)?;
+    db::registrar_auditoria(
+        conn,
+        "servico",
+        servico_id as i64,
+        db::ACAO_ATUALIZACAO,
+        Some(serde_json::json!({ "recursos_ids": antes })),
+        Some(serde_json::json!({ "recursos_ids": depois })),
+    )?;
+    Ok(depois)
+}
+
+/// Intervalos em que os `recursos_ids` estão presos por agendamentos entre `inicio_ts` e `fim_ts`:
+/// `(agendamento, recurso, [início, fim + folga))`. Cancelados e concluídos não contam.
+pub fn ocupacao_dos_recursos(conn: &Connection, recursos_ids: &[i32], inicio_ts: i64, fim_ts: i64) -> Result<Vec<(i32, i32, Intervalo)>> {
+    if recursos_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT a.id, a.data_hora, sr.recurso_id,
+                (SELECT COALESCE(SUM(s.duracao_min), 0) FROM agendamento_servicos y JOIN servicos s ON s.id = y.servico_id WHERE y.agendamento_id = a.id),
+                (SELECT MAX(COALESCE(s.buffer_min, ?3)) FROM agendamento_servicos y JOIN servicos s ON s.id = y.servico_id WHERE y.agendamento_id = a.id)
+         FROM agendamentos a
+         JOIN agendamento_servicos x ON x.agendamento_id = a.id
+         JOIN servico_recursos sr ON sr.servico_id = x.servico_id
+         WHERE a.data_hora BETWEEN ?1 AND ?2 AND a.concluido = 0 AND a.status != 'cancelado'
+         ORDER BY a.data_hora, a.id"
+    )?;
+    // Começa um dia antes: um atendimento da véspera ainda pode estar usando o recurso.
+    let linhas = stmt.query_map(params![inicio_ts - 86_400, fim_ts, config::atual().agenda.buffer_min], |row| {
+        Ok((row.get::<_, i32>(0)?, row.get::<_, i64>(1)?, row.get::<_, i32>(2)?, row.get::<_, i64>(3)?, row.get::<_, Option<i64>>(4)?))
+    })?;
+    let mut ocupados = Vec::new();
+    for linha in linhas {
+        let (agendamento_id, ts, recurso_id, duracao, buffer) = linha?;
+        if recursos_ids.contains(&recurso_id) {
+            let duracao = if duracao == 0 { 30 } else { duracao };
+            let intervalo = scheduling::ocupacao(db::timestamp_para_naive(ts), duracao, buffer.unwrap_or(0));
+            ocupados.push((agendamento_id, recurso_id, intervalo));
+        }
+    }
+    Ok(ocupados)
+}
+
+/// Primeiro recurso do agendamento que já está preso por outro agendamento no mesmo horário.
+/// `ignorar` é o próprio agendamento quando ele está sendo alterado. Só confere recursos; sem
+/// profissionais cadastrados, não há restrição por profissional.
+pub fn conflito_de_recursos(conn: &Connection, agendamento: &Agendamento, ignorar: Option<i32>) -> Result<Option<Recurso>> {
+    if agendamento.concluido {
+        return Ok(None);
+    }
+    let recursos_ids = recursos_dos_servicos(conn, &agendamento.servicos_ids)?;
+    if recursos_ids.is_empty() {
+        return Ok(None);
+    }
+    let padrao = &config::atual().agenda;
+    let (mut duracao, mut buffer) = (0i64, 0i64);
+    for id in &agendamento.servicos_ids {
+        if let Some(servico) = db::buscar_servico_por_id(conn, *id)? {
+            duracao += servico.duracao_min as i64;
+            buffer = buffer.max(servico.buffer_min.map(i64::from).unwrap_or(padrao.buffer_min));
+        }
+    }
+    let pedido = scheduling::ocupacao(agendamento.data_hora, duracao, buffer);
+
+    let inicio_ts = pedido.0.and_utc().timestamp();
+    let fim_ts = pedido.1.and_utc().timestamp();
+    let conflito = ocupacao_dos_recursos(conn, &recursos_ids, inicio_ts, fim_ts)?
+        .into_iter()
+        .find(|(outro, _, intervalo)| Some(*outro) != ignorar && scheduling::cruzam(pedido, *intervalo));
+    match conflito {
+        Some((_, recurso_id, _)) => buscar_recurso(conn, recurso_id),
+        None => Ok(None),
+    }
+}
+
+/// Mensagem de conflito usada pela API e pelos comandos.
+pub fn mensagem_conflito(recurso: &Recurso) -> String {
+    format!("O recurso '{}' já está em uso nesse horário.", recurso.nome)
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn responder<T>(status: StatusCode, mensagem: String, data: Option<T>) -> (StatusCode, Json<ApiResponse<T>>) {
+    (status, Json(ApiResponse { success: status.is_success(), message: mensagem, data }))
+}
+
+fn erro_interno<T>(contexto: &str, e: rusqlite::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("Erro ao {}: {}", contexto, e);
+    responder(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao {}: {}", contexto, e), None)
+}
+
+/// GET /recursos
+pub async fn listar_recursos_api(State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<Recurso>>>) {
+    let conn = conn.lock().unwrap();
+    match listar_recursos(&conn) {
+        Ok(lista) => responder(StatusCode::OK, format!("{} recurso(s).", lista.len()), Some(lista)),
+        Err(e) => erro_interno("listar recursos", e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NovoRecurso {
+    pub nome: String,
+    pub descricao: Option<String>,
+    /// Só para recusar: recursos valem para a barbearia inteira.
+    pub profissional_id: Option<serde_json::Value>,
+}
+
+/// POST /recursos — `{ "nome": "Lavatório", "descricao": "..." }`.
+pub async fn criar_recurso_api(State(conn): State<Db>, Json(recurso): Json<NovoRecurso>) -> (StatusCode, Json<ApiResponse<Recurso>>) {
+    if let Err((status, mensagem)) = recusar_profissional("profissional_id", &recurso.profissional_id) {
+        return responder(status, mensagem, None);
+    }
+    let nome = recurso.nome.trim();
+    if nome.is_empty() || nome.chars().count() > 60 {
+        return responder(StatusCode::BAD_REQUEST, "Informe o nome do recurso (até 60 caracteres).".to_string(), None);
+    }
+    let novo = Recurso {
+        id: None,
+        nome: nome.to_string(),
+        descricao: recurso.descricao.as_deref().map(str::trim).filter(|d| !d.is_empty()).map(str::to_string),
+    };
+    let conn = conn.lock().unwrap();
+    match salvar_recurso(&conn, &novo) {
+        Ok(salvo) => responder(StatusCode::CREATED, "Recurso criado com sucesso!".to_string(), Some(salvo)),
+        Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+            responder(StatusCode::CONFLICT, format!("Já existe um recurso chamado '{}'.", novo.nome), None)
+        }
+        Err(e) => erro_interno("criar recurso", e),
+    }
+}
+
+/// DELETE /recursos/:id — também tira o recurso dos serviços que o exigiam.
+pub async fn excluir_recurso_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
+    let conn = conn.lock().unwrap();
+    match excluir_recurso(&conn, id) {
+        Ok(true) => responder(StatusCode::OK, "Recurso excluído com sucesso!".to_string(), None),
+        Ok(false) => responder(StatusCode::NOT_FOUND, format!("Recurso {} não encontrado.", id), None),
+        Err(e) => erro_interno("excluir recurso", e),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RecursosServico {
+    pub recursos_ids: Vec<i32>,
+    /// Só para recusar: o que o serviço exige vale para qualquer profissional.
+    #[serde(default, skip_serializing)]
+    pub profissional_id: Option<serde_json::Value>,
+}
+
+fn servico_existe(conn: &Connection, id: i32) -> Result<bool> {
+    Ok(db::buscar_servico_por_id(conn, id)?.is_some())
+}
+
+/// GET /servicos/:id/recursos
+pub async fn recursos_do_servico_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<RecursosServico>>) {
+    let conn = conn.lock().unwrap();
+    match servico_existe(&conn, id) {
+        Ok(true) => {}
+        Ok(false) => return responder(StatusCode::NOT_FOUND, format!("Serviço {} não encontrado.", id), None),
+        Err(e) => return erro_interno("buscar serviço", e),
+    }
+    match recursos_do_servico(&conn, id) {
+        Ok(recursos_ids) => responder(StatusCode::OK, format!("{} recurso(s) exigido(s).", recursos_ids.len()), Some(RecursosServico { recursos_ids, profissional_id: None })),
+        Err(e) => erro_interno("listar recursos do serviço", e),
+    }
+}
+
+/// PUT /servicos/:id/recursos — `{ "recursos_ids": [1, 2] }` substitui a lista inteira.
+pub async fn definir_recursos_do_servico_api(Path(id): Path<i32>, State(conn): State<Db>, Json(corpo): Json<RecursosServico>) -> (StatusCode, Json<ApiResponse<RecursosServico>>) {
+    if let Err((status, mensagem)) = recusar_profissional("profissional_id", &corpo.profissional_id) {
+        return responder(status, mensagem, None);
+    }
+    let conn = conn.lock().unwrap();
+    match servico_existe(&conn, id) {
+        Ok(true) => {}
+        Ok(false) => return responder(StatusCode::NOT_FOUND, format!("Serviço {} não encontrado.", id), None),
+        Err(e) => return erro_interno("buscar serviço", e),
+    }
+    for recurso_id in &corpo.recursos_ids {
+        match buscar_recurso(&conn, *recurso_id) {
+            Ok(Some(_)) => {}
+            Ok(None) => return responder(StatusCode::BAD_REQUEST, format!("Recurso {} não encontrado.", recurso_id), None),
+            Err(e) => return erro_interno("buscar recurso", e),
+        }
+    }
+    match definir_recursos_do_servico(&conn, id, &corpo.recursos_ids) {
+        Ok(recursos_ids) => responder(StatusCode::OK, "Recursos do serviço atualizados!".to_string(), Some(RecursosServico { recursos_ids, profissional_id: None })),
+        Err(e) => erro_interno("atualizar recursos do serviço", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Cliente, Servico};
+    use chrono::NaiveDateTime;
+
+    fn data(texto: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(texto, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn servico(conn: &Connection, nome: &str, duracao_min: i32) -> i32 {
+        let servico = Servico { id: None, nome: nome.into(), preco: 50.0, duracao_min, buffer_min: Some(0), alinhamento_min: None };
+        db::salvar_servico(conn, &servico).unwrap()
+    }
+
+    #[test]
+    fn test_recurso_nao_atende_dois_agendamentos_ao_mesmo_tempo() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".into(), "11999990000".into(), None);
+        let cliente_id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let lavagem = servico(&conn, "Lavagem", 30);
+        let quimica = servico(&conn, "Química", 60);
+        let corte = servico(&conn, "Corte", 30);
+        let lavatorio = salvar_recurso(&conn, &Recurso { id: None, nome: "Lavatório".into(), descricao: None }).unwrap();
+        let lavatorio_id = lavatorio.id.unwrap();
+        definir_recursos_do_servico(&conn, lavagem, &[lavatorio_id]).unwrap();
+        definir_recursos_do_servico(&conn, quimica, &[lavatorio_id, lavatorio_id]).unwrap();
+        assert_eq!(recursos_dos_servicos(&conn, &[lavagem, quimica, corte]).unwrap(), vec![lavatorio_id]);
+
+        let novo = |servicos_ids: Vec<i32>, quando: &str| Agendamento {
+            id: None,
+            cliente_id,
+            servicos_ids,
+            data_hora: data(quando),
+            preco: 50.0,
+            concluido: false,
+        };
+        let primeiro = db::salvar_agendamento(&conn, &novo(vec![quimica], "2030-01-07 10:00")).unwrap();
+
+        // Aqui só contam os recursos: um corte não esbarra no lavatório (o choque de horário na
+        // agenda, que é única, é conferido à parte), já uma lavagem esbarra.
+        assert!(conflito_de_recursos(&conn, &novo(vec![corte], "2030-01-07 10:30"), None).unwrap().is_none());
+        let conflito = conflito_de_recursos(&conn, &novo(vec![lavagem], "2030-01-07 10:30"), None).unwrap();
+        assert_eq!(conflito.map(|r| r.nome), Some("Lavatório".to_string()));
+        assert!(conflito_de_recursos(&conn, &novo(vec![lavagem], "2030-01-07 11:00"), None).unwrap().is_none());
+        assert!(conflito_de_recursos(&conn, &novo(vec![lavagem], "2030-01-07 09:30"), None).unwrap().is_none());
+        // Alterar o próprio agendamento não conflita com ele mesmo.
+        assert!(conflito_de_recursos(&conn, &novo(vec![quimica], "2030-01-07 10:15"), Some(primeiro)).unwrap().is_none());
+
+        db::definir_status_agendamento(&conn, primeiro, db::STATUS_AGENDAMENTO_CANCELADO).unwrap();
+        assert!(conflito_de_recursos(&conn, &novo(vec![lavagem], "2030-01-07 10:30"), None).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_recursos_por_profissional_sao_recusados() {
+        let conn: Db = Arc::new(Mutex::new(Connection::open_in_memory().unwrap()));
+        db::criar_tabelas(&conn.lock().unwrap()).unwrap();
+        let corte = servico(&conn.lock().unwrap(), "Corte", 30);
+
+        let novo = NovoRecurso { nome: "Lavatório".into(), descricao: None, profissional_id: Some(2.into()) };
+        let (status, _) = criar_recurso_api(State(conn.clone()), Json(novo)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(listar_recursos(&conn.lock().unwrap()).unwrap().is_empty());
+
+        let corpo = RecursosServico { recursos_ids: vec![], profissional_id: Some(2.into()) };
+        let (status, Json(resposta)) = definir_recursos_do_servico_api(Path(corte), State(conn), Json(corpo)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(resposta.message.contains("profissional_id"));
+    }
+}
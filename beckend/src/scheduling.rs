@@ -0,0 +1,347 @@
+use chrono::{Duration, NaiveDateTime, Timelike};
+
+// =================================================================================
+// MOTOR DE HORÁRIOS
+// Funções puras: recebem janelas de trabalho, intervalos ocupados e o pedido, e
+// devolvem os inícios livres. Quem lê o banco é `db::horarios_disponiveis` e afins.
+// =================================================================================
+
+/// Intervalo meio aberto `[inicio, fim)`.
+pub type Intervalo = (NaiveDateTime, NaiveDateTime);
+
+/// O que se procura na agenda: duração, folgas e limites de horário.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pedido {
+    pub duracao_min: i64,
+    /// Folga depois do novo atendimento.
+    pub buffer_min: i64,
+    /// Passo entre um início e o seguinte, contado do início de cada janela (com alinhamento,
+    /// do primeiro início alinhado dela).
+    pub granularidade_min: i64,
+    /// Início só em múltiplos destes minutos contados da meia-noite.
+    pub alinhamento_min: Option<i64>,
+    /// Antecedência mínima: nada começa antes disso.
+    pub a_partir_de: Option<NaiveDateTime>,
+    /// Horizonte: nada começa depois disso.
+    pub ate: Option<NaiveDateTime>,
+    /// Trechos `[de, até)`, em minutos contados do início, em que o profissional fica preso
+    /// (folga incluída). Vazio: o atendimento inteiro mais a folga, sem pausas.
+    pub trechos_ocupados: Vec<(i64, i64)>,
+}
+
+impl Pedido {
+    /// Só duração, folga e granularidade, como no `/availability` original.
+    pub fn simples(duracao_min: i64, buffer_min: i64, granularidade_min: i64) -> Self {
+        Pedido { duracao_min, buffer_min, granularidade_min, alinhamento_min: None, a_partir_de: None, ate: None, trechos_ocupados: Vec::new() }
+    }
+
+    /// Trechos a conferir: os informados ou, sem eles, o atendimento inteiro mais a folga.
+    pub fn trechos(&self) -> Vec<(i64, i64)> {
+        if self.trechos_ocupados.is_empty() {
+            vec![(0, self.duracao_min.max(1) + self.buffer_min.max(0))]
+        } else {
+            self.trechos_ocupados.clone()
+        }
+    }
+}
+
+/// Tempo que um atendimento já marcado ocupa: do início até o fim mais a folga.
+pub fn ocupacao(inicio: NaiveDateTime, duracao_min: i64, buffer_min: i64) -> Intervalo {
+    (inicio, inicio + Duration::minutes(duracao_min + buffer_min))
+}
+
+/// Etapa de um atendimento: `(minutos, ocupa o profissional)`.
+pub type Etapa = (i64, bool);
+
+/// Trechos em que o profissional fica preso, a partir das etapas em sequência e da folga
+/// no fim. Etapas ocupadas vizinhas viram um trecho só.
+pub fn trechos_ocupados(etapas: &[Etapa], buffer_min: i64) -> Vec<(i64, i64)> {
+    fn ocupar(trechos: &mut Vec<(i64, i64)>, de: i64, ate: i64) {
+        match trechos.last_mut() {
+            Some(ultimo) if ultimo.1 == de => ultimo.1 = ate,
+            _ => trechos.push((de, ate)),
+        }
+    }
+    let mut trechos = Vec::new();
+    let mut cursor = 0;
+    for (minutos, ocupa) in etapas {
+        let minutos = (*minutos).max(0);
+        if *ocupa && minutos > 0 {
+            ocupar(&mut trechos, cursor, cursor + minutos);
+        }
+        cursor += minutos;
+    }
+    if buffer_min > 0 {
+        ocupar(&mut trechos, cursor, cursor + buffer_min);
+    }
+    trechos
+}
+
+/// Trechos relativos de um atendimento que começa em `inicio`, em horários absolutos.
+pub fn ocupacao_em_trechos(inicio: NaiveDateTime, trechos: &[(i64, i64)]) -> Vec<Intervalo> {
+    trechos.iter().map(|(de, ate)| (inicio + Duration::minutes(*de), inicio + Duration::minutes(*ate))).collect()
+}
+
+/// O início cai num múltiplo de `passo` minutos contados da meia-noite?
+pub fn alinhado(inicio: NaiveDateTime, passo: i64) -> bool {
+    passo <= 0 || (inicio.num_seconds_from_midnight() as i64 / 60) % passo == 0
+}
+
+/// Primeiro instante a partir de `inicio` que cai num múltiplo de `passo` minutos da meia-noite
+/// (no máximo a meia-noite seguinte, que sempre está alinhada).
+pub fn primeiro_alinhado(inicio: NaiveDateTime, passo: i64) -> NaiveDateTime {
+    if passo <= 0 {
+        return inicio;
+    }
+    let resto = inicio.num_seconds_from_midnight() as i64 % (passo * 60);
+    if resto == 0 {
+        return inicio;
+    }
+    let alinhado = inicio + Duration::seconds(passo * 60 - resto);
+    if alinhado.date() == inicio.date() { alinhado } else { alinhado.date().and_time(chrono::NaiveTime::MIN) }
+}
+
+/// Os dois intervalos têm algum instante em comum? Encostar (`fim == início`) não conta.
+pub fn cruzam(a: Intervalo, b: Intervalo) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+fn mdc(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { mdc(b, a % b) }
+}
+
+/// Alinhamento que respeita os dois passos ao mesmo tempo (mínimo múltiplo comum).
+pub fn combinar_alinhamentos(atual: Option<i64>, passo: i64) -> Option<i64> {
+    if passo <= 0 {
+        return atual;
+    }
+    Some(match atual {
+        Some(atual) => atual / mdc(atual, passo) * passo,
+        None => passo,
+    })
+}
+
+/// Ocupados ordenados pelo início, com o maior fim visto até cada posição:
+/// basta uma busca binária para saber se algum cruza `[inicio, fim)`.
+struct Ocupacao {
+    inicios: Vec<NaiveDateTime>,
+    maior_fim: Vec<NaiveDateTime>,
+}
+
+impl Ocupacao {
+    fn nova(ocupados: &[Intervalo]) -> Self {
+        let mut ordenados: Vec<Intervalo> = ocupados.iter().copied().filter(|(s, e)| s < e).collect();
+        ordenados.sort_unstable();
+        let mut maior_fim: Vec<NaiveDateTime> = Vec::with_capacity(ordenados.len());
+        for (_, fim) in &ordenados {
+            let anterior = maior_fim.last().copied().unwrap_or(*fim);
+            maior_fim.push(anterior.max(*fim));
+        }
+        Ocupacao { inicios: ordenados.into_iter().map(|(s, _)| s).collect(), maior_fim }
+    }
+
+    fn cruza(&self, inicio: NaiveDateTime, fim: NaiveDateTime) -> bool {
+        // Só os que começam antes de `fim` podem cruzar; entre eles, basta um terminar depois de `inicio`.
+        let antes = self.inicios.partition_point(|s| *s < fim);
+        antes > 0 && self.maior_fim[antes - 1] > inicio
+    }
+}
+
+/// Inícios livres: percorre cada janela em passos de `granularidade_min` e aceita o início
+/// quando o atendimento cabe na janela e nenhum dos seus trechos ocupados (por padrão
+/// `[início, fim + folga)`) cruza um ocupado. Pausas de outros atendimentos ficam livres.
+/// Com alinhamento, começa no primeiro minuto alinhado da janela e anda pelo mínimo múltiplo
+/// comum dos dois passos, para uma janela fora do alinhamento (ex.: 09:10) não perder horários.
+pub fn horarios_livres(janelas: &[Intervalo], ocupados: &[Intervalo], pedido: &Pedido) -> Vec<NaiveDateTime> {
+    let dur = Duration::minutes(pedido.duracao_min.max(1));
+    let granularidade = pedido.granularidade_min.max(1);
+    let alinhamento = pedido.alinhamento_min.filter(|passo| *passo > 0);
+    let gran = Duration::minutes(granularidade);
+    let passo_alinhado = Duration::minutes(alinhamento.map_or(granularidade, |passo| combinar_alinhamentos(Some(granularidade), passo).unwrap_or(granularidade)));
+    let ocupacao = Ocupacao::nova(ocupados);
+    let trechos: Vec<(Duration, Duration)> = pedido.trechos().iter().map(|(de, ate)| (Duration::minutes(*de), Duration::minutes(*ate))).collect();
+
+    let mut slots = Vec::new();
+    for (inicio, fim) in janelas {
+        let mut cursor = alinhamento.map_or(*inicio, |passo| primeiro_alinhado(*inicio, passo));
+        while cursor + dur <= *fim {
+            if alinhamento.is_some_and(|passo| !alinhado(cursor, passo)) {
+                // Só depois da meia-noite: anda pela granularidade até realinhar com o novo dia.
+                cursor += gran;
+                continue;
+            }
+            let fora_dos_limites = pedido.a_partir_de.is_some_and(|minimo| cursor < minimo)
+                || pedido.ate.is_some_and(|maximo| cursor > maximo);
+            if !fora_dos_limites && !trechos.iter().any(|(de, ate)| ocupacao.cruza(cursor + *de, cursor + *ate)) {
+                slots.push(cursor);
+            }
+            // Na virada do dia o alinhamento recomeça, então o passo longo poderia pular a meia-noite.
+            let proximo = cursor + passo_alinhado;
+            cursor = if proximo.date() == cursor.date() { proximo } else { cursor + gran };
+        }
+    }
+    slots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use proptest::prelude::*;
+
+    fn hora(h: u32, m: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2030, 1, 7).unwrap().and_hms_opt(h, m, 0).unwrap()
+    }
+
+    fn horas(slots: &[NaiveDateTime]) -> Vec<String> {
+        slots.iter().map(|s| s.format("%H:%M").to_string()).collect()
+    }
+
+    #[test]
+    fn test_folgas_janelas_e_limites() {
+        let janelas = [(hora(9, 0), hora(12, 0)), (hora(14, 0), hora(15, 0))];
+        let ocupados = [ocupacao(hora(10, 0), 30, 15), (hora(11, 30), hora(13, 0))];
+
+        // Janela livre inteira com passo de 30: o último início ainda termina às 15h.
+        let livres = horarios_livres(&janelas, &ocupados, &Pedido::simples(30, 0, 30));
+        assert_eq!(horas(&livres), ["09:00", "09:30", "11:00", "14:00", "14:30"]);
+
+        // A folga do novo atendimento não pode encostar no próximo ocupado.
+        let livres = horarios_livres(&janelas, &ocupados, &Pedido::simples(30, 15, 15));
+        assert_eq!(horas(&livres), ["09:00", "09:15", "10:45", "14:00", "14:15", "14:30"]);
+
+        let mut pedido = Pedido::simples(60, 0, 15);
+        pedido.alinhamento_min = Some(30);
+        pedido.a_partir_de = Some(hora(9, 10));
+        pedido.ate = Some(hora(14, 0));
+        assert_eq!(horas(&horarios_livres(&janelas, &ocupados, &pedido)), ["14:00"]);
+        pedido.ate = None;
+        pedido.a_partir_de = None;
+        assert_eq!(horas(&horarios_livres(&janelas, &ocupados, &pedido)), ["09:00", "14:00"]);
+    }
+
+    #[test]
+    fn test_pausa_de_processamento_fica_livre() {
+        // Luzes: 30 min aplicando, 40 de pausa, 20 finalizando, 10 de limpeza.
+        let trechos = trechos_ocupados(&[(30, true), (40, false), (20, true)], 10);
+        assert_eq!(trechos, [(0, 30), (70, 100)]);
+        assert_eq!(trechos_ocupados(&[(30, true), (15, true)], 0), [(0, 45)]);
+
+        let janelas = [(hora(9, 0), hora(12, 0))];
+        let luzes = ocupacao_em_trechos(hora(9, 0), &trechos);
+        // Um corte de 30 com 10 de folga cabe na pausa (9:30 às 10:10); às 10:30 cruzaria a finalização.
+        let livres = horarios_livres(&janelas, &luzes, &Pedido::simples(30, 10, 30));
+        assert_eq!(horas(&livres), ["09:30", "11:00", "11:30"]);
+
+        // Outras luzes podem começar na pausa destas, desde que as finalizações não se cruzem.
+        let mut outras = Pedido::simples(90, 10, 10);
+        outras.trechos_ocupados = trechos;
+        assert_eq!(horas(&horarios_livres(&janelas, &luzes, &outras)), ["09:30", "09:40"]);
+    }
+
+    #[test]
+    fn test_janela_fora_do_alinhamento() {
+        // Janela das 9:10 com passo de 15 e alinhamento de 30: 9:10, 9:25... nunca caem na meia hora.
+        let janelas = [(hora(9, 10), hora(11, 0))];
+        let mut pedido = Pedido::simples(30, 0, 15);
+        pedido.alinhamento_min = Some(30);
+        assert_eq!(horas(&horarios_livres(&janelas, &[], &pedido)), ["09:30", "10:00", "10:30"]);
+
+        // Passo de 20 com alinhamento de 30 anda de hora em hora a partir do primeiro alinhado.
+        pedido.granularidade_min = 20;
+        assert_eq!(horas(&horarios_livres(&janelas, &[], &pedido)), ["09:30", "10:30"]);
+        assert_eq!(primeiro_alinhado(hora(9, 30), 30), hora(9, 30));
+    }
+
+    #[test]
+    fn test_combinar_alinhamentos() {
+        assert_eq!(combinar_alinhamentos(None, 30), Some(30));
+        assert_eq!(combinar_alinhamentos(Some(20), 30), Some(60));
+        assert_eq!(combinar_alinhamentos(Some(15), 0), Some(15));
+    }
+
+    /// Minutos do dia -> horário no dia de teste.
+    fn minuto(m: i64) -> NaiveDateTime {
+        hora(0, 0) + Duration::minutes(m)
+    }
+
+    fn intervalos(max: usize) -> impl Strategy<Value = Vec<Intervalo>> {
+        prop::collection::vec((0i64..1440, 1i64..240), 0..max)
+            .prop_map(|v| v.into_iter().map(|(inicio, dur)| (minuto(inicio), minuto(inicio + dur))).collect())
+    }
+
+    fn pedidos() -> impl Strategy<Value = Pedido> {
+        let etapas = prop::option::of(prop::collection::vec((1i64..90, any::<bool>()), 1..4));
+        (1i64..180, 0i64..45, 1i64..60, prop::option::of(1i64..61), prop::option::of(0i64..1440), prop::option::of(0i64..1440), etapas)
+            .prop_map(|(duracao, buffer, gran, alinhamento, minimo, maximo, etapas)| {
+                // Com etapas, a duração é a soma delas e o profissional fica livre nas pausas.
+                let (duracao, trechos) = match etapas {
+                    Some(etapas) => (etapas.iter().map(|(m, _)| m).sum(), trechos_ocupados(&etapas, buffer)),
+                    None => (duracao, Vec::new()),
+                };
+                Pedido {
+                    duracao_min: duracao,
+                    buffer_min: buffer,
+                    granularidade_min: gran,
+                    alinhamento_min: alinhamento,
+                    a_partir_de: minimo.map(minuto),
+                    ate: maximo.map(minuto),
+                    trechos_ocupados: trechos,
+                }
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_nenhum_horario_cruza_ocupado(janelas in intervalos(4), ocupados in intervalos(12), pedido in pedidos()) {
+            for slot in horarios_livres(&janelas, &ocupados, &pedido) {
+                for trecho in ocupacao_em_trechos(slot, &pedido.trechos()) {
+                    for ocupado in &ocupados {
+                        prop_assert!(!cruzam(trecho, *ocupado), "{} cruza {:?}", slot, ocupado);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn prop_horarios_dentro_das_janelas_e_regras(janelas in intervalos(4), ocupados in intervalos(12), pedido in pedidos()) {
+            for slot in horarios_livres(&janelas, &ocupados, &pedido) {
+                let fim = slot + Duration::minutes(pedido.duracao_min);
+                let janela = janelas.iter().find(|(s, e)| *s <= slot && fim <= *e);
+                prop_assert!(janela.is_some(), "{} fora das janelas", slot);
+                prop_assert!(pedido.a_partir_de.is_none_or(|m| slot >= m));
+                prop_assert!(pedido.ate.is_none_or(|m| slot <= m));
+                prop_assert!(pedido.alinhamento_min.is_none_or(|p| (slot.num_seconds_from_midnight() as i64 / 60) % p == 0));
+            }
+        }
+
+        #[test]
+        fn prop_igual_a_busca_ingenua(janelas in intervalos(3), ocupados in intervalos(12), pedido in pedidos()) {
+            // Mesmo resultado de testar minuto a minuto, comparando cada início com todos os ocupados.
+            let dur = Duration::minutes(pedido.duracao_min);
+            let mut esperado = Vec::new();
+            for (inicio, fim) in &janelas {
+                // Primeiro início da grade: o início da janela ou, com alinhamento, o primeiro minuto alinhado.
+                let mut base = *inicio;
+                while pedido.alinhamento_min.is_some_and(|p| (base.num_seconds_from_midnight() as i64 / 60) % p != 0) {
+                    base += Duration::minutes(1);
+                }
+                let mut cursor = base;
+                while cursor + dur <= *fim {
+                    let na_grade = (cursor - base).num_minutes() % pedido.granularidade_min == 0;
+                    let livre = pedido.trechos().iter().all(|(de, ate)| {
+                        let (inicio, fim) = (cursor + Duration::minutes(*de), cursor + Duration::minutes(*ate));
+                        ocupados.iter().all(|(s, e)| fim <= *s || inicio >= *e)
+                    });
+                    let nos_limites = pedido.a_partir_de.is_none_or(|m| cursor >= m) && pedido.ate.is_none_or(|m| cursor <= m);
+                    let alinhado = pedido.alinhamento_min.is_none_or(|p| (cursor.num_seconds_from_midnight() as i64 / 60) % p == 0);
+                    if na_grade && livre && nos_limites && alinhado {
+                        esperado.push(cursor);
+                    }
+                    cursor += Duration::minutes(1);
+                }
+            }
+            prop_assert_eq!(horarios_livres(&janelas, &ocupados, &pedido), esperado);
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use axum::{
     extract::{Path, State, Query},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use std::sync::{Arc, Mutex};
 use rusqlite::Connection;
 
-use crate::models::Servico;
+use crate::models::{FaseServico, Servico};
 use crate::db;
+use crate::patch;
 
 type Db = Arc<Mutex<Connection>>;
 
@@ -18,6 +19,47 @@ pub struct ApiResponse<T> {
     data: Option<T>,
 }
 
+/// Folga não pode ser negativa e o alinhamento precisa ser de 1 a 60 minutos.
+pub fn validar_regras(servico: &Servico) -> Result<(), String> {
+    if servico.buffer_min.is_some_and(|b| b < 0) {
+        return Err("buffer_min não pode ser negativo.".to_string());
+    }
+    if servico.alinhamento_min.is_some_and(|a| !(1..=60).contains(&a)) {
+        return Err("alinhamento_min deve ficar entre 1 e 60 minutos.".to_string());
+    }
+    Ok(())
+}
+
+/// Cada fase precisa de nome e duração, e ao menos uma ocupa o profissional.
+pub fn validar_fases(fases: &[FaseServico]) -> Result<(), String> {
+    if fases.iter().any(|f| f.nome.trim().is_empty() || f.nome.chars().count() > 60) {
+        return Err("Cada fase precisa de um nome (até 60 caracteres).".to_string());
+    }
+    if fases.iter().any(|f| f.duracao_min <= 0) {
+        return Err("A duração de cada fase deve ser maior que zero.".to_string());
+    }
+    if fases.iter().map(|f| f.duracao_min as i64).sum::<i64>() > 24 * 60 {
+        return Err("As fases somam mais de 24 horas.".to_string());
+    }
+    if !fases.is_empty() && !fases.iter().any(|f| f.ocupa_profissional) {
+        return Err("Ao menos uma fase deve ocupar o profissional.".to_string());
+    }
+    Ok(())
+}
+
+/// Serviço com fases tem a duração igual à soma delas; para mudar, altere as fases.
+pub fn validar_duracao_com_fases(servico: &Servico, fases: &[FaseServico]) -> Result<(), String> {
+    let total: i32 = fases.iter().map(|f| f.duracao_min).sum();
+    if !fases.is_empty() && servico.duracao_min != total {
+        return Err(format!("Este serviço tem fases: a duração é a soma delas ({} min). Altere as fases para mudá-la.", total));
+    }
+    Ok(())
+}
+
+fn regras_invalidas(mensagem: String) -> (StatusCode, Json<ApiResponse<Servico>>) {
+    (StatusCode::BAD_REQUEST, Json(ApiResponse { success: false, message: mensagem, data: None }))
+}
+
 #[allow(dead_code)]
 pub async fn listar_servicos(State(conn): State<Db>) -> Json<Vec<Servico>> {
     let conn = conn.lock().unwrap();
@@ -53,9 +95,13 @@ pub async fn listar_servicos_query(Query(q): Query<ServicosQuery>, State(conn):
 }
 
 pub async fn criar_servico(State(conn): State<Db>, Json(mut servico): Json<Servico>) -> (StatusCode, Json<ApiResponse<Servico>>) {
+    if let Err(mensagem) = validar_regras(&servico) {
+        return regras_invalidas(mensagem);
+    }
     let conn = conn.lock().unwrap();
-    match db::salvar_servico(&conn, &mut servico) {
-        Ok(_) => {
+    match db::salvar_servico(&conn, &servico) {
+        Ok(id) => {
+            servico.id = Some(id);
             let response = ApiResponse {
                 success: true,
                 message: "Serviço cadastrado com sucesso!".to_string(),
@@ -75,16 +121,31 @@ pub async fn criar_servico(State(conn): State<Db>, Json(mut servico): Json<Servi
     }
 }
 
-pub async fn obter_servico(Path(id): Path<i32>, State(conn): State<Db>) -> Json<Option<Servico>> {
+pub async fn obter_servico(Path(id): Path<i32>, State(conn): State<Db>) -> (HeaderMap, Json<Option<Servico>>) {
     let conn = conn.lock().unwrap();
     let servico = db::buscar_servico_por_id(&conn, id).unwrap_or(None);
-    Json(servico)
+    let versao = patch::versao(&conn, "servicos", id).unwrap_or(None);
+    (patch::cabecalho_etag(versao), Json(servico))
 }
 
 pub async fn atualizar_servico(Path(id): Path<i32>, State(conn): State<Db>, Json(mut servico): Json<Servico>) -> (StatusCode, Json<ApiResponse<Servico>>) {
     servico.id = Some(id);
+    if let Err(mensagem) = validar_regras(&servico) {
+        return regras_invalidas(mensagem);
+    }
     let conn = conn.lock().unwrap();
-    match db::salvar_servico(&conn, &mut servico) {
+    match db::listar_fases_servico(&conn, id) {
+        Ok(fases) => {
+            if let Err(mensagem) = validar_duracao_com_fases(&servico, &fases) {
+                return regras_invalidas(mensagem);
+            }
+        }
+        Err(e) => {
+            eprintln!("Erro ao buscar fases do serviço: {}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao buscar fases do serviço: {}", e), data: None }));
+        }
+    }
+    match db::salvar_servico(&conn, &servico) {
         Ok(_) => {
             let response = ApiResponse {
                 success: true,
@@ -105,6 +166,56 @@ pub async fn atualizar_servico(Path(id): Path<i32>, State(conn): State<Db>, Json
     }
 }
 
+/// PATCH /servicos/:id — merge-patch sobre o serviço atual (`null` volta buffer/alinhamento
+/// ao padrão da agenda). Com `If-Match` desatualizado responde 409 e o `ETag` vigente.
+pub async fn patch_servico(
+    Path(id): Path<i32>,
+    State(conn): State<Db>,
+    headers: HeaderMap,
+    Json(alteracoes): Json<serde_json::Value>,
+) -> (StatusCode, HeaderMap, Json<ApiResponse<Servico>>) {
+    let resposta = |status: StatusCode, versao: Option<i64>, message: String, data: Option<Servico>| {
+        (status, patch::cabecalho_etag(versao), Json(ApiResponse { success: status.is_success(), message, data }))
+    };
+    let conn = conn.lock().unwrap();
+    let (atual, versao) = match (db::buscar_servico_por_id(&conn, id), patch::versao(&conn, "servicos", id)) {
+        (Ok(Some(servico)), Ok(Some(versao))) => (servico, versao),
+        (Ok(_), Ok(_)) => return resposta(StatusCode::NOT_FOUND, None, format!("Serviço {} não encontrado.", id), None),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("Erro ao buscar serviço: {}", e);
+            return resposta(StatusCode::INTERNAL_SERVER_ERROR, None, format!("Erro ao buscar serviço: {}", e), None);
+        }
+    };
+    if !patch::if_match_confere(&headers, versao) {
+        return resposta(StatusCode::CONFLICT, Some(versao), "O serviço foi alterado por outra pessoa; recarregue e tente de novo.".to_string(), Some(atual));
+    }
+    match alterar_servico(&conn, &atual, &alteracoes) {
+        Ok(servico) => {
+            let nova = patch::versao(&conn, "servicos", id).unwrap_or(None);
+            resposta(StatusCode::OK, nova, "Serviço atualizado com sucesso!".to_string(), Some(servico))
+        }
+        Err((status, mensagem)) => resposta(status, Some(versao), mensagem, None),
+    }
+}
+
+/// Mescla o patch no serviço, valida como o `PUT` e grava (usado também pelo lote).
+pub(crate) fn alterar_servico(conn: &Connection, atual: &Servico, alteracoes: &serde_json::Value) -> Result<Servico, (StatusCode, String)> {
+    let servico: Servico = patch::mesclar(atual, alteracoes, &["id"]).map_err(|m| (StatusCode::BAD_REQUEST, m))?;
+    validar_regras(&servico).map_err(|m| (StatusCode::BAD_REQUEST, m))?;
+    match db::listar_fases_servico(conn, servico.id.unwrap_or_default()) {
+        Ok(fases) => validar_duracao_com_fases(&servico, &fases).map_err(|m| (StatusCode::BAD_REQUEST, m))?,
+        Err(e) => {
+            eprintln!("Erro ao buscar fases do serviço: {}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao buscar fases do serviço: {}", e)));
+        }
+    }
+    if let Err(e) = db::salvar_servico(conn, &servico) {
+        eprintln!("Erro ao atualizar serviço: {}", e);
+        return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao atualizar serviço: {}", e)));
+    }
+    Ok(servico)
+}
+
 pub async fn excluir_servico(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
     let conn = conn.lock().unwrap();
     match db::excluir_servico(&conn, id) {
@@ -115,4 +226,52 @@ pub async fn excluir_servico(Path(id): Path<i32>, State(conn): State<Db>) -> (St
             (StatusCode::INTERNAL_SERVER_ERROR, Json(response))
         }
     }
-}
\ No newline at end of file
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct FasesServico {
+    pub fases: Vec<FaseServico>,
+}
+
+fn servico_nao_encontrado<T>(id: i32) -> (StatusCode, Json<ApiResponse<T>>) {
+    (StatusCode::NOT_FOUND, Json(ApiResponse { success: false, message: format!("Serviço {} não encontrado.", id), data: None }))
+}
+
+fn erro_fases<T>(contexto: &str, e: rusqlite::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("Erro ao {}: {}", contexto, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao {}: {}", contexto, e), data: None }))
+}
+
+/// GET /servicos/:id/fases — fases na ordem de execução (vazio = uma etapa só).
+pub async fn listar_fases_api(Path(id): Path<i32>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<FasesServico>>) {
+    let conn = conn.lock().unwrap();
+    match db::buscar_servico_por_id(&conn, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return servico_nao_encontrado(id),
+        Err(e) => return erro_fases("buscar serviço", e),
+    }
+    match db::listar_fases_servico(&conn, id) {
+        Ok(fases) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} fase(s).", fases.len()), data: Some(FasesServico { fases }) })),
+        Err(e) => erro_fases("listar fases do serviço", e),
+    }
+}
+
+/// PUT /servicos/:id/fases — `{ "fases": [{nome, duracao_min, ocupa_profissional}] }` substitui as fases;
+/// a duração do serviço passa a ser a soma. Lista vazia volta ao serviço de uma etapa só.
+pub async fn definir_fases_api(Path(id): Path<i32>, State(conn): State<Db>, Json(mut corpo): Json<FasesServico>) -> (StatusCode, Json<ApiResponse<Servico>>) {
+    if let Err(mensagem) = validar_fases(&corpo.fases) {
+        return regras_invalidas(mensagem);
+    }
+    for fase in &mut corpo.fases {
+        fase.nome = fase.nome.trim().to_string();
+    }
+    let conn = conn.lock().unwrap();
+    match db::buscar_servico_por_id(&conn, id) {
+        Ok(Some(_)) => {}
+        Ok(None) => return servico_nao_encontrado(id),
+        Err(e) => return erro_fases("buscar serviço", e),
+    }
+    match db::salvar_fases_servico(&conn, id, &corpo.fases).and_then(|_| db::buscar_servico_por_id(&conn, id)) {
+        Ok(servico) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Fases do serviço atualizadas!".to_string(), data: servico })),
+        Err(e) => erro_fases("atualizar fases do serviço", e),
+    }
+}
@@ -0,0 +1,816 @@
+//! Sincronização do app web offline-first.
+//!
+//! Clientes, serviços e agendamentos ganham um UUID estável (`sync_registros`). O app pode gerar
+//! o UUID dos registros que cria sem rede. Gatilhos do SQLite anotam toda criação, alteração e
+//! exclusão em `sync_alteracoes`, venha ela da API, do menu, da linha de comando ou do lote. O
+//! `seq` dessa tabela é o cursor de `GET /sync?since=`. `POST /sync` aplica a fila offline
+//! registro a registro, conferindo a versão que o app tinha (`versao_base`). A regra de conflito
+//! é fixa: o que já está no servidor prevalece, e a exclusão prevalece sobre a alteração.
+//!
+//! A transferência do banco (`transferencia.rs`) leva os UUIDs e as versões junto. Na
+//! importação o feed recomeça depois do último cursor da origem (`sistema.sync_inicio`): os
+//! cursores antigos passam a responder 410 e o app recarrega tudo com os mesmos UUIDs.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::agendamentos::{self, Falha};
+use crate::backup::autorizar_dono;
+use crate::db;
+use crate::espera;
+use crate::lote::MAX_OPERACOES;
+use crate::models::{Agendamento, Cliente, Servico};
+use crate::patch;
+use crate::servicos;
+use crate::webhooks;
+
+type Db = Arc<Mutex<Connection>>;
+
+/// Alterações devolvidas por página quando `limite` não é informado.
+pub const LIMITE_PADRAO: usize = 500;
+
+pub const OPERACAO_CRIACAO: &str = "criacao";
+pub const OPERACAO_ATUALIZACAO: &str = "atualizacao";
+pub const OPERACAO_EXCLUSAO: &str = "exclusao";
+
+/// UUID v4 gerado pelo próprio SQLite, para registros criados fora do app.
+const NOVO_UUID_SQL: &str = "lower(hex(randomblob(4))) || '-' || lower(hex(randomblob(2))) || '-4' || substr(lower(hex(randomblob(2))), 2)
+    || '-' || substr('89ab', 1 + (abs(random()) % 4), 1) || substr(lower(hex(randomblob(2))), 2) || '-' || lower(hex(randomblob(6)))";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entidade {
+    Cliente,
+    Servico,
+    Agendamento,
+}
+
+impl Entidade {
+    const TODAS: [Entidade; 3] = [Entidade::Cliente, Entidade::Servico, Entidade::Agendamento];
+
+    pub(crate) fn nome(self) -> &'static str {
+        match self {
+            Entidade::Cliente => "cliente",
+            Entidade::Servico => "servico",
+            Entidade::Agendamento => "agendamento",
+        }
+    }
+
+    fn tabela(self) -> &'static str {
+        match self {
+            Entidade::Cliente => "clientes",
+            Entidade::Servico => "servicos",
+            Entidade::Agendamento => "agendamentos",
+        }
+    }
+
+    fn de_nome(nome: &str) -> Option<Entidade> {
+        Entidade::TODAS.into_iter().find(|e| e.nome() == nome)
+    }
+}
+
+/// Tabelas de UUID e de alterações, os gatilhos que as alimentam e o UUID dos registros antigos.
+/// Roda depois de `patch::criar_controle_de_versao`: toda alteração sobe a `versao` uma vez só,
+/// e é nesse UPDATE que a alteração é anotada.
+pub fn criar_tabelas_sincronizacao(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sync_registros (
+            entidade    TEXT NOT NULL,
+            registro_id INTEGER NOT NULL,
+            uuid        TEXT NOT NULL UNIQUE,
+            PRIMARY KEY (entidade, registro_id)
+        );
+        CREATE TABLE IF NOT EXISTS sync_alteracoes (
+            seq         INTEGER PRIMARY KEY AUTOINCREMENT,
+            entidade    TEXT NOT NULL,
+            uuid        TEXT NOT NULL,
+            registro_id INTEGER NOT NULL,
+            operacao    TEXT NOT NULL CHECK (operacao IN ('criacao', 'atualizacao', 'exclusao'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_sync_alteracoes_uuid ON sync_alteracoes (uuid);",
+    )?;
+    // Cursores até este valor são de antes da última importação (ver `recomecar_feed_apos`).
+    db::garantir_coluna(conn, "sistema", "sync_inicio", "INTEGER NOT NULL DEFAULT 0")?;
+    for entidade in Entidade::TODAS {
+        conn.execute_batch(&format!(
+            "CREATE TRIGGER IF NOT EXISTS {t}_sync_criacao AFTER INSERT ON {t}
+             BEGIN
+                 INSERT OR REPLACE INTO sync_registros (entidade, registro_id, uuid) VALUES ('{e}', NEW.id, {uuid});
+                 INSERT INTO sync_alteracoes (entidade, uuid, registro_id, operacao)
+                     SELECT '{e}', uuid, NEW.id, 'criacao' FROM sync_registros WHERE entidade = '{e}' AND registro_id = NEW.id;
+             END;
+             CREATE TRIGGER IF NOT EXISTS {t}_sync_atualizacao AFTER UPDATE ON {t}
+             WHEN NEW.versao <> OLD.versao
+             BEGIN
+                 INSERT INTO sync_alteracoes (entidade, uuid, registro_id, operacao)
+                     SELECT '{e}', uuid, NEW.id, 'atualizacao' FROM sync_registros WHERE entidade = '{e}' AND registro_id = NEW.id;
+             END;
+             CREATE TRIGGER IF NOT EXISTS {t}_sync_exclusao AFTER DELETE ON {t}
+             BEGIN
+                 INSERT INTO sync_alteracoes (entidade, uuid, registro_id, operacao)
+                     SELECT '{e}', uuid, OLD.id, 'exclusao' FROM sync_registros WHERE entidade = '{e}' AND registro_id = OLD.id;
+                 DELETE FROM sync_registros WHERE entidade = '{e}' AND registro_id = OLD.id;
+             END;",
+            t = entidade.tabela(),
+            e = entidade.nome(),
+            uuid = NOVO_UUID_SQL
+        ))?;
+        // Bancos anteriores à sincronização: os registros existentes entram no feed como criações.
+        conn.execute(
+            &format!(
+                "INSERT INTO sync_registros (entidade, registro_id, uuid)
+                 SELECT '{e}', id, {uuid} FROM {t} WHERE id NOT IN (SELECT registro_id FROM sync_registros WHERE entidade = '{e}')",
+                t = entidade.tabela(),
+                e = entidade.nome(),
+                uuid = NOVO_UUID_SQL
+            ),
+            [],
+        )?;
+    }
+    conn.execute(
+        "INSERT INTO sync_alteracoes (entidade, uuid, registro_id, operacao)
+         SELECT r.entidade, r.uuid, r.registro_id, 'criacao' FROM sync_registros r
+         WHERE NOT EXISTS (SELECT 1 FROM sync_alteracoes a WHERE a.uuid = r.uuid)
+         ORDER BY r.entidade, r.registro_id",
+        [],
+    )?;
+    // Serviços removidos em cascata somem de agendamento_servicos sem tocar no agendamento.
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS agendamento_servicos_sync AFTER DELETE ON agendamento_servicos
+         WHEN NOT EXISTS (SELECT 1 FROM servicos WHERE id = OLD.servico_id)
+         BEGIN
+             INSERT INTO sync_alteracoes (entidade, uuid, registro_id, operacao)
+                 SELECT 'agendamento', uuid, OLD.agendamento_id, 'atualizacao' FROM sync_registros
+                 WHERE entidade = 'agendamento' AND registro_id = OLD.agendamento_id;
+         END;",
+    )?;
+    Ok(())
+}
+
+/// Confere e normaliza (minúsculas) um UUID no formato 8-4-4-4-12.
+pub fn normalizar_uuid(uuid: &str) -> Option<String> {
+    let uuid = uuid.trim().to_ascii_lowercase();
+    let partes: Vec<&str> = uuid.split('-').collect();
+    let tamanhos_ok = partes.iter().map(|p| p.len()).eq([8, 4, 4, 4, 12]);
+    (tamanhos_ok && partes.iter().all(|p| p.chars().all(|c| c.is_ascii_hexdigit()))).then_some(uuid)
+}
+
+pub fn uuid_do_registro(conn: &Connection, entidade: Entidade, id: i32) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT uuid FROM sync_registros WHERE entidade = ?1 AND registro_id = ?2",
+        params![entidade.nome(), id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+pub fn id_do_uuid(conn: &Connection, entidade: Entidade, uuid: &str) -> Result<Option<i32>> {
+    conn.query_row(
+        "SELECT registro_id FROM sync_registros WHERE entidade = ?1 AND uuid = ?2",
+        params![entidade.nome(), uuid],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+fn foi_excluido(conn: &Connection, entidade: Entidade, uuid: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sync_alteracoes WHERE uuid = ?1 AND entidade = ?2 AND operacao = 'exclusao')",
+        params![uuid, entidade.nome()],
+        |row| row.get(0),
+    )
+}
+
+pub(crate) fn uuid_em_uso(conn: &Connection, uuid: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sync_alteracoes WHERE uuid = ?1) OR EXISTS (SELECT 1 FROM sync_registros WHERE uuid = ?1)",
+        params![uuid],
+        |row| row.get(0),
+    )
+}
+
+/// Troca o UUID gerado pelo gatilho pelo que o app criou offline ou pelo que veio na
+/// transferência (mesma transação, ninguém o viu).
+pub(crate) fn adotar_uuid(conn: &Connection, entidade: Entidade, id: i32, uuid: &str) -> Result<()> {
+    if let Some(gerado) = uuid_do_registro(conn, entidade, id)? {
+        conn.execute("UPDATE sync_alteracoes SET uuid = ?1 WHERE uuid = ?2", params![uuid, gerado])?;
+    }
+    conn.execute(
+        "UPDATE sync_registros SET uuid = ?1 WHERE entidade = ?2 AND registro_id = ?3",
+        params![uuid, entidade.nome(), id],
+    )?;
+    Ok(())
+}
+
+/// Último cursor emitido (0 num banco sem alterações).
+pub fn cursor_atual(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COALESCE(MAX(seq), 0) FROM sync_alteracoes", [], |row| row.get(0))
+}
+
+/// O cursor foi emitido por este banco: 0 (carga inicial) ou um `seq` entre a última
+/// importação e o fim do feed. Cursores de outro banco ou de antes da importação não valem.
+pub fn cursor_conhecido(conn: &Connection, cursor: i64) -> Result<bool> {
+    if cursor <= 0 {
+        return Ok(true);
+    }
+    let inicio: i64 = conn.query_row("SELECT sync_inicio FROM sistema WHERE id = 1", [], |row| row.get(0))?;
+    Ok(cursor > inicio && cursor <= cursor_atual(conn)?)
+}
+
+/// Faz as próximas alterações começarem depois de `cursor` e invalida os cursores até ele.
+/// A importação chama antes de carregar os registros, com o último cursor da origem.
+pub(crate) fn recomecar_feed_apos(conn: &Connection, cursor: i64) -> Result<()> {
+    let inicio = cursor.max(cursor_atual(conn)?);
+    conn.execute("DELETE FROM sqlite_sequence WHERE name = 'sync_alteracoes'", [])?;
+    conn.execute("INSERT INTO sqlite_sequence (name, seq) VALUES ('sync_alteracoes', ?1)", params![inicio])?;
+    conn.execute("UPDATE sistema SET sync_inicio = ?1 WHERE id = 1", params![inicio])?;
+    Ok(())
+}
+
+/// O registro como o app o enxerga: os campos da API, mais `uuid` e `versao`. No agendamento,
+/// também `status` e as referências por UUID (`cliente_uuid`, `servicos_uuids`).
+pub fn representar(conn: &Connection, entidade: Entidade, id: i32) -> Result<Option<JsonValue>> {
+    let valor = match entidade {
+        Entidade::Cliente => db::buscar_cliente_por_id(conn, id)?.map(|c| db::json_de(&c)),
+        Entidade::Servico => db::buscar_servico_por_id(conn, id)?.map(|s| db::json_de(&s)),
+        Entidade::Agendamento => match db::buscar_agendamento_por_id(conn, id) {
+            Ok(agendamento) => {
+                let mut valor = db::json_de(&agendamento);
+                if let Some(JsonValue::Object(campos)) = valor.as_mut() {
+                    let mut servicos_uuids = Vec::new();
+                    for servico_id in &agendamento.servicos_ids {
+                        servicos_uuids.extend(uuid_do_registro(conn, Entidade::Servico, *servico_id)?);
+                    }
+                    campos.insert("status".into(), db::status_agendamento(conn, id)?.into());
+                    campos.insert("cliente_uuid".into(), uuid_do_registro(conn, Entidade::Cliente, agendamento.cliente_id)?.into());
+                    campos.insert("servicos_uuids".into(), servicos_uuids.into());
+                }
+                Some(valor)
+            }
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e),
+        },
+    };
+    let Some(Some(mut valor)) = valor else {
+        return Ok(None);
+    };
+    if let JsonValue::Object(campos) = &mut valor {
+        campos.insert("uuid".into(), uuid_do_registro(conn, entidade, id)?.into());
+        campos.insert("versao".into(), patch::versao(conn, entidade.tabela(), id)?.into());
+    }
+    Ok(Some(valor))
+}
+
+// ---------------------------------------------------------------------------------
+// GET /sync — feed de alterações
+// ---------------------------------------------------------------------------------
+
+#[derive(Debug, Serialize)]
+pub struct AlteracaoServidor {
+    pub seq: i64,
+    pub entidade: Entidade,
+    pub uuid: String,
+    /// `criacao`, `atualizacao` ou `exclusao` (lápide: só `uuid` e `id`, sem `dados`).
+    pub operacao: &'static str,
+    pub id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dados: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FeedSync {
+    /// Mande de volta em `since` na próxima chamada.
+    pub cursor: i64,
+    /// Há mais alterações depois deste cursor; chame de novo.
+    pub mais: bool,
+    pub alteracoes: Vec<AlteracaoServidor>,
+}
+
+/// Alterações depois de `since`, uma por registro (a mais recente), em ordem de `seq`.
+/// Registro criado e excluído dentro da mesma página não aparece.
+pub fn alteracoes_desde(conn: &Connection, since: i64, limite: usize) -> Result<FeedSync> {
+    let mut stmt = conn.prepare(
+        "SELECT seq, entidade, uuid, registro_id, operacao FROM sync_alteracoes WHERE seq > ?1 ORDER BY seq LIMIT ?2",
+    )?;
+    let mut linhas = stmt
+        .query_map(params![since, limite as i64 + 1], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i32>(3)?, row.get::<_, String>(4)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    let mais = linhas.len() > limite;
+    linhas.truncate(limite);
+    let cursor = linhas.last().map(|l| l.0).unwrap_or(since.max(0));
+
+    // Por UUID: se foi criado nesta página e qual foi a última alteração.
+    let mut por_uuid: HashMap<&str, (bool, usize)> = HashMap::new();
+    for (i, (_, _, uuid, _, operacao)) in linhas.iter().enumerate() {
+        let entrada = por_uuid.entry(uuid.as_str()).or_insert((operacao == OPERACAO_CRIACAO, i));
+        entrada.1 = i;
+    }
+    let mut alteracoes = Vec::new();
+    for (i, (seq, entidade, uuid, registro_id, operacao)) in linhas.iter().enumerate() {
+        let (criado, ultima) = por_uuid[uuid.as_str()];
+        let Some(entidade) = Entidade::de_nome(entidade) else { continue };
+        if i != ultima || (criado && operacao == OPERACAO_EXCLUSAO) {
+            continue;
+        }
+        if operacao == OPERACAO_EXCLUSAO {
+            alteracoes.push(AlteracaoServidor { seq: *seq, entidade, uuid: uuid.clone(), operacao: OPERACAO_EXCLUSAO, id: *registro_id, dados: None });
+            continue;
+        }
+        // Se sumiu depois desta página, a lápide vem na próxima.
+        let Some(dados) = representar(conn, entidade, *registro_id)? else { continue };
+        let operacao = if criado { OPERACAO_CRIACAO } else { OPERACAO_ATUALIZACAO };
+        alteracoes.push(AlteracaoServidor { seq: *seq, entidade, uuid: uuid.clone(), operacao, id: *registro_id, dados: Some(dados) });
+    }
+    Ok(FeedSync { cursor, mais, alteracoes })
+}
+
+// ---------------------------------------------------------------------------------
+// POST /sync — fila offline do app
+// ---------------------------------------------------------------------------------
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperacaoLocal {
+    /// Cria (UUID novo) ou altera (merge-patch em `dados`) o registro.
+    Salvar,
+    Excluir,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AlteracaoLocal {
+    pub entidade: Entidade,
+    pub uuid: String,
+    pub operacao: OperacaoLocal,
+    /// Versão que o app tinha quando alterou offline; sem ela, a alteração sobrescreve (como o `PUT`).
+    pub versao_base: Option<i64>,
+    #[serde(default)]
+    pub dados: JsonValue,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PedidoSync {
+    pub alteracoes: Vec<AlteracaoLocal>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolucao {
+    Aplicado,
+    /// Nada a gravar: o servidor já está como o app queria.
+    SemAlteracao,
+    /// O servidor prevaleceu; `atual` traz o registro vigente (ausente se foi excluído).
+    Conflito,
+    Erro,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResultadoRegistro {
+    pub indice: usize,
+    pub entidade: Entidade,
+    pub uuid: String,
+    pub resultado: Resolucao,
+    pub status: u16,
+    pub mensagem: String,
+    pub id: Option<i32>,
+    /// O registro como ficou no servidor, para o app substituir a cópia local.
+    pub atual: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RespostaSync {
+    pub cursor: i64,
+    pub aplicados: usize,
+    pub conflitos: usize,
+    pub erros: usize,
+    pub registros: Vec<ResultadoRegistro>,
+}
+
+type Aplicacao = (Resolucao, String, Option<i32>);
+
+fn erro_banco(e: rusqlite::Error) -> Falha {
+    eprintln!("Erro na sincronização: {}", e);
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("Erro no banco de dados: {}", e))
+}
+
+fn invalido(mensagem: impl Into<String>) -> Falha {
+    (StatusCode::BAD_REQUEST, mensagem.into())
+}
+
+/// `dados` sem os campos que o servidor controla.
+fn sem_metadados(dados: &JsonValue) -> JsonValue {
+    let mut dados = dados.clone();
+    if let JsonValue::Object(campos) = &mut dados {
+        for campo in ["id", "uuid", "versao"] {
+            campos.remove(campo);
+        }
+    }
+    dados
+}
+
+fn resolver_uuid(conn: &Connection, entidade: Entidade, uuid: &JsonValue) -> Result<i32, Falha> {
+    let texto = uuid.as_str().and_then(normalizar_uuid).ok_or_else(|| invalido(format!("UUID inválido: {}", uuid)))?;
+    id_do_uuid(conn, entidade, &texto)
+        .map_err(erro_banco)?
+        .ok_or_else(|| (StatusCode::UNPROCESSABLE_ENTITY, format!("{} {} não existe no servidor.", entidade.nome(), texto)))
+}
+
+/// Troca `cliente_uuid`/`servicos_uuids` por `cliente_id`/`servicos_ids` e separa o `status`.
+fn agendamento_por_ids(conn: &Connection, dados: &JsonValue) -> Result<(JsonValue, Option<String>), Falha> {
+    let mut dados = sem_metadados(dados);
+    let Some(campos) = dados.as_object_mut() else {
+        return Err(invalido("dados deve ser um objeto JSON."));
+    };
+    if let Some(uuid) = campos.remove("cliente_uuid") {
+        campos.insert("cliente_id".into(), resolver_uuid(conn, Entidade::Cliente, &uuid)?.into());
+    }
+    if let Some(uuids) = campos.remove("servicos_uuids") {
+        let uuids = uuids.as_array().ok_or_else(|| invalido("servicos_uuids deve ser uma lista."))?;
+        let ids = uuids.iter().map(|u| resolver_uuid(conn, Entidade::Servico, u)).collect::<Result<Vec<_>, _>>()?;
+        campos.insert("servicos_ids".into(), ids.into());
+    }
+    let status = match campos.remove("status") {
+        None | Some(JsonValue::Null) => None,
+        Some(JsonValue::String(s)) if [db::STATUS_AGENDAMENTO_PENDENTE, db::STATUS_AGENDAMENTO_CONFIRMADO, db::STATUS_AGENDAMENTO_CANCELADO].contains(&s.as_str()) => Some(s),
+        Some(outro) => return Err(invalido(format!("status inválido: {}", outro))),
+    };
+    Ok((dados, status))
+}
+
+/// Grava o status vindo do app; cancelar avisa como o cancelamento pela API.
+fn aplicar_status(conn: &Connection, id: i32, status: Option<String>) -> Result<bool, Falha> {
+    let Some(status) = status else { return Ok(false) };
+    if db::status_agendamento(conn, id).map_err(erro_banco)? == status {
+        return Ok(false);
+    }
+    db::definir_status_agendamento(conn, id, &status).map_err(erro_banco)?;
+    if status == db::STATUS_AGENDAMENTO_CANCELADO {
+        let agendamento = db::buscar_agendamento_por_id(conn, id).map_err(erro_banco)?;
+        webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_CANCELADO, &agendamento);
+        espera::vaga_liberada(conn, &agendamento);
+    }
+    Ok(true)
+}
+
+fn criar(conn: &Connection, entidade: Entidade, dados: &JsonValue) -> Result<i32, Falha> {
+    match entidade {
+        Entidade::Cliente => {
+            let mut cliente: Cliente = serde_json::from_value(sem_metadados(dados)).map_err(|e| invalido(format!("Campos inválidos: {}", e)))?;
+            if cliente.nome.trim().is_empty() {
+                return Err(invalido("O nome do cliente não pode ficar vazio."));
+            }
+            let id = db::salvar_cliente(conn, &mut cliente).map_err(erro_banco)?;
+            webhooks::emitir_ou_logar(conn, webhooks::EVENTO_CLIENTE_CRIADO, &cliente);
+            Ok(id)
+        }
+        Entidade::Servico => {
+            let servico: Servico = serde_json::from_value(sem_metadados(dados)).map_err(|e| invalido(format!("Campos inválidos: {}", e)))?;
+            servicos::validar_regras(&servico).map_err(invalido)?;
+            db::salvar_servico(conn, &servico).map_err(erro_banco)
+        }
+        Entidade::Agendamento => {
+            let (mut dados, status) = agendamento_por_ids(conn, dados)?;
+            if let Some(valor) = dados.get_mut("data_hora") {
+                let data_hora = agendamentos::parse_data_hora(valor)
+                    .map_err(|m| (StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid data_hora: {}", m)))?;
+                *valor = serde_json::to_value(data_hora).unwrap_or(JsonValue::Null);
+            }
+            if let Some(campos) = dados.as_object_mut() {
+                campos.entry("concluido").or_insert(JsonValue::Bool(false));
+            }
+            let novo: Agendamento = serde_json::from_value(dados).map_err(|e| invalido(format!("Campos inválidos: {}", e)))?;
+            if novo.servicos_ids.is_empty() {
+                return Err(invalido("O agendamento precisa de pelo menos um serviço."));
+            }
+            let id = agendamentos::criar_agendamento(conn, &novo)?.id.unwrap_or_default();
+            aplicar_status(conn, id, status)?;
+            Ok(id)
+        }
+    }
+}
+
+/// Aplica `dados` como merge-patch, com as validações do `PATCH`. Devolve se algo mudou.
+fn alterar(conn: &Connection, entidade: Entidade, id: i32, dados: &JsonValue) -> Result<bool, Falha> {
+    match entidade {
+        Entidade::Cliente => {
+            let atual = db::buscar_cliente_por_id(conn, id).map_err(erro_banco)?.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Cliente {} não encontrado.", id)))?;
+            let mut cliente: Cliente = patch::mesclar(&atual, &sem_metadados(dados), &["id"]).map_err(invalido)?;
+            if cliente.nome.trim().is_empty() {
+                return Err(invalido("O nome do cliente não pode ficar vazio."));
+            }
+            if db::json_de(&cliente) == db::json_de(&atual) {
+                return Ok(false);
+            }
+            db::salvar_cliente(conn, &mut cliente).map_err(erro_banco)?;
+            Ok(true)
+        }
+        Entidade::Servico => {
+            let atual = db::buscar_servico_por_id(conn, id).map_err(erro_banco)?.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Serviço {} não encontrado.", id)))?;
+            let dados = sem_metadados(dados);
+            let novo: Servico = patch::mesclar(&atual, &dados, &["id"]).map_err(invalido)?;
+            if db::json_de(&novo) == db::json_de(&atual) {
+                return Ok(false);
+            }
+            servicos::alterar_servico(conn, &atual, &dados)?;
+            Ok(true)
+        }
+        Entidade::Agendamento => {
+            let atual = db::buscar_agendamento_por_id(conn, id).map_err(erro_banco)?;
+            let (dados, status) = agendamento_por_ids(conn, dados)?;
+            let alterado = agendamentos::alterar_agendamento(conn, &atual, dados)?.is_some();
+            Ok(aplicar_status(conn, id, status)? || alterado)
+        }
+    }
+}
+
+fn excluir(conn: &Connection, entidade: Entidade, id: i32) -> Result<(), Falha> {
+    match entidade {
+        Entidade::Cliente => db::excluir_cliente(conn, id).map_err(|e| match e {
+            rusqlite::Error::ExecuteReturnedResults => {
+                (StatusCode::CONFLICT, "Não é possível excluir o cliente pois ele possui agendamentos.".to_string())
+            }
+            e => erro_banco(e),
+        }),
+        Entidade::Servico => db::excluir_servico(conn, id).map_err(erro_banco),
+        Entidade::Agendamento => {
+            let anterior = db::buscar_agendamento_por_id(conn, id).map_err(erro_banco)?;
+            db::excluir_agendamento(conn, id).map_err(erro_banco)?;
+            webhooks::emitir_ou_logar(conn, webhooks::EVENTO_AGENDAMENTO_CANCELADO, &anterior);
+            espera::vaga_liberada(conn, &anterior);
+            Ok(())
+        }
+    }
+}
+
+/// Regra de conflito de um registro da fila (ver o comentário do módulo).
+fn aplicar(conn: &Connection, item: &AlteracaoLocal, uuid: &str) -> Result<Aplicacao, Falha> {
+    let entidade = item.entidade;
+    let id = id_do_uuid(conn, entidade, uuid).map_err(erro_banco)?;
+    let versao = match id {
+        Some(id) => patch::versao(conn, entidade.tabela(), id).map_err(erro_banco)?,
+        None => None,
+    };
+    let desatualizado = matches!((item.versao_base, versao), (Some(base), Some(atual)) if base != atual);
+    match (item.operacao, id) {
+        (OperacaoLocal::Salvar, None) => {
+            if foi_excluido(conn, entidade, uuid).map_err(erro_banco)? {
+                return Ok((Resolucao::Conflito, "O registro foi excluído no servidor; a exclusão prevalece.".to_string(), None));
+            }
+            if uuid_em_uso(conn, uuid).map_err(erro_banco)? {
+                return Err((StatusCode::CONFLICT, format!("O UUID {} já pertence a outro registro.", uuid)));
+            }
+            let id = criar(conn, entidade, &item.dados)?;
+            adotar_uuid(conn, entidade, id, uuid).map_err(erro_banco)?;
+            Ok((Resolucao::Aplicado, "Registro criado.".to_string(), Some(id)))
+        }
+        (OperacaoLocal::Salvar, Some(id)) if desatualizado => {
+            // Mesmo com a versão velha, se o servidor já está como o app quer, não é conflito.
+            let atual = representar(conn, entidade, id).map_err(erro_banco)?.unwrap_or_default();
+            let mut desejado = atual.clone();
+            patch::aplicar_merge_patch(&mut desejado, &sem_metadados(&item.dados));
+            if desejado == atual {
+                return Ok((Resolucao::SemAlteracao, "Nada a alterar.".to_string(), Some(id)));
+            }
+            let mensagem = format!("Versão {} desatualizada (atual: {}); o servidor prevalece.", item.versao_base.unwrap_or_default(), versao.unwrap_or_default());
+            Ok((Resolucao::Conflito, mensagem, Some(id)))
+        }
+        (OperacaoLocal::Salvar, Some(id)) => match alterar(conn, entidade, id, &item.dados)? {
+            true => Ok((Resolucao::Aplicado, "Registro atualizado.".to_string(), Some(id))),
+            false => Ok((Resolucao::SemAlteracao, "Nada a alterar.".to_string(), Some(id))),
+        },
+        (OperacaoLocal::Excluir, None) => Ok((Resolucao::SemAlteracao, "O registro já não existe.".to_string(), None)),
+        (OperacaoLocal::Excluir, Some(id)) if desatualizado => {
+            Ok((Resolucao::Conflito, "O registro foi alterado no servidor depois da cópia do app; a exclusão não foi feita.".to_string(), Some(id)))
+        }
+        (OperacaoLocal::Excluir, Some(id)) => {
+            excluir(conn, entidade, id)?;
+            Ok((Resolucao::Aplicado, "Registro excluído.".to_string(), Some(id)))
+        }
+    }
+}
+
+/// Aplica a fila na ordem enviada, numa transação, cada registro no seu savepoint: um registro
+/// com erro ou conflito não impede os demais. Um agendamento pode apontar para o cliente criado
+/// num item anterior da mesma fila.
+pub fn aplicar_fila(conn: &Connection, alteracoes: &[AlteracaoLocal]) -> Result<RespostaSync> {
+    let tx = conn.unchecked_transaction()?;
+    let mut registros = Vec::new();
+    for (indice, item) in alteracoes.iter().enumerate() {
+        tx.execute_batch("SAVEPOINT item_sync")?;
+        let resultado = match normalizar_uuid(&item.uuid) {
+            Some(uuid) => aplicar(&tx, item, &uuid),
+            None => Err(invalido(format!("UUID inválido: {}", item.uuid))),
+        };
+        let (resultado, status, mensagem, id) = match resultado {
+            Ok((resolucao, mensagem, id)) => {
+                let status = if resolucao == Resolucao::Conflito { StatusCode::CONFLICT } else { StatusCode::OK };
+                (resolucao, status, mensagem, id)
+            }
+            Err((status, mensagem)) => (Resolucao::Erro, status, mensagem, None),
+        };
+        if resultado == Resolucao::Erro {
+            tx.execute_batch("ROLLBACK TO item_sync; RELEASE item_sync")?;
+        } else {
+            tx.execute_batch("RELEASE item_sync")?;
+        }
+        let atual = match id {
+            Some(id) => representar(&tx, item.entidade, id)?,
+            None => None,
+        };
+        registros.push(ResultadoRegistro { indice, entidade: item.entidade, uuid: item.uuid.clone(), resultado, status: status.as_u16(), mensagem, id, atual });
+    }
+    tx.commit()?;
+    let contar = |r: Resolucao| registros.iter().filter(|i| i.resultado == r).count();
+    Ok(RespostaSync {
+        cursor: cursor_atual(conn)?,
+        aplicados: contar(Resolucao::Aplicado),
+        conflitos: contar(Resolucao::Conflito),
+        erros: contar(Resolucao::Erro),
+        registros,
+    })
+}
+
+// ---------------------------------------------------------------------------------
+// API
+// ---------------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+pub struct SyncQuery {
+    pub since: Option<i64>,
+    pub limite: Option<usize>,
+}
+
+/// GET /sync?since=<cursor>&limite= — alterações de clientes, serviços e agendamentos depois do
+/// cursor. Sem `since` (ou 0) vem tudo, como carga inicial. Só o dono: o feed traz nome,
+/// telefone e e-mail dos clientes.
+pub async fn feed_sync_api(headers: HeaderMap, Query(q): Query<SyncQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<FeedSync>>) {
+    let erro = |status: StatusCode, message: String| (status, Json(ApiResponse { success: false, message, data: None }));
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return erro(status, message);
+    }
+    let since = q.since.unwrap_or(0);
+    let limite = q.limite.unwrap_or(LIMITE_PADRAO).clamp(1, MAX_OPERACOES * 2);
+    let conn = conn.lock().unwrap();
+    match cursor_conhecido(&conn, since) {
+        // Cursor de outro banco (restauração, importação): o app precisa recomeçar do zero.
+        Ok(false) => {
+            return erro(StatusCode::GONE, format!("Cursor {} desconhecido neste servidor; sincronize de novo com since=0.", since));
+        }
+        Ok(true) => {}
+        Err(e) => {
+            eprintln!("Erro ao ler alterações: {}", e);
+            return erro(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao ler alterações: {}", e));
+        }
+    }
+    match alteracoes_desde(&conn, since, limite) {
+        Ok(feed) => {
+            let message = format!("{} alteração(ões).", feed.alteracoes.len());
+            (StatusCode::OK, Json(ApiResponse { success: true, message, data: Some(feed) }))
+        }
+        Err(e) => {
+            eprintln!("Erro ao ler alterações: {}", e);
+            erro(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao ler alterações: {}", e))
+        }
+    }
+}
+
+/// POST /sync — `{alteracoes: [{entidade, uuid, operacao: "salvar" | "excluir", versao_base?, dados?}]}`.
+/// Só o dono, como o feed: a resposta devolve os registros como ficaram (`atual`).
+pub async fn enviar_sync_api(headers: HeaderMap, State(conn): State<Db>, Json(pedido): Json<PedidoSync>) -> (StatusCode, Json<ApiResponse<RespostaSync>>) {
+    let erro = |status: StatusCode, message: String| (status, Json(ApiResponse { success: false, message, data: None }));
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return erro(status, message);
+    }
+    if pedido.alteracoes.len() > MAX_OPERACOES {
+        return erro(StatusCode::BAD_REQUEST, format!("No máximo {} alterações por envio.", MAX_OPERACOES));
+    }
+    let conn = conn.lock().unwrap();
+    match aplicar_fila(&conn, &pedido.alteracoes) {
+        Ok(resposta) => {
+            let message = format!(
+                "{} aplicada(s), {} conflito(s), {} erro(s).",
+                resposta.aplicados, resposta.conflitos, resposta.erros
+            );
+            (StatusCode::OK, Json(ApiResponse { success: resposta.erros == 0, message, data: Some(resposta) }))
+        }
+        Err(e) => {
+            eprintln!("Erro ao sincronizar: {}", e);
+            erro(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao sincronizar: {}", e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fila(valor: JsonValue) -> Vec<AlteracaoLocal> {
+        serde_json::from_value(valor).unwrap()
+    }
+
+    #[test]
+    fn test_feed_e_fila_offline() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let corte = Servico { id: None, nome: "Corte".to_string(), preco: 35.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
+        let corte_id = db::salvar_servico(&conn, &corte).unwrap();
+        let corte_uuid = uuid_do_registro(&conn, Entidade::Servico, corte_id).unwrap().unwrap();
+        assert!(normalizar_uuid(&corte_uuid).is_some());
+
+        let feed = alteracoes_desde(&conn, 0, LIMITE_PADRAO).unwrap();
+        assert_eq!(feed.alteracoes.len(), 1);
+        assert_eq!(feed.alteracoes[0].operacao, OPERACAO_CRIACAO);
+        let cursor = feed.cursor;
+
+        // Cliente e agendamento criados offline, com UUIDs do app; o agendamento aponta para o cliente novo.
+        let cliente_uuid = "0b7e0f3a-5d2c-4c1e-9a11-2f7c3b8d9e01";
+        let agendamento_uuid = "6f1d2c3b-4a5e-4f60-8b7a-1c2d3e4f5a6b";
+        let resposta = aplicar_fila(&conn, &fila(json!([
+            { "entidade": "cliente", "uuid": cliente_uuid, "operacao": "salvar", "dados": { "nome": "Ana", "telefone": "11999990000", "email": null } },
+            { "entidade": "agendamento", "uuid": agendamento_uuid, "operacao": "salvar",
+              "dados": { "cliente_uuid": cliente_uuid, "servicos_uuids": [corte_uuid], "data_hora": "2030-01-05 10:00:00", "preco": 35.0 } },
+            { "entidade": "servico", "uuid": corte_uuid, "operacao": "salvar", "versao_base": 7, "dados": { "preco": 50.0 } },
+        ]))).unwrap();
+        let resultados: Vec<Resolucao> = resposta.registros.iter().map(|r| r.resultado).collect();
+        assert_eq!(resultados, [Resolucao::Aplicado, Resolucao::Aplicado, Resolucao::Conflito]);
+        assert_eq!(resposta.registros[2].atual.as_ref().unwrap()["preco"], json!(35.0));
+        let agendamento_id = id_do_uuid(&conn, Entidade::Agendamento, agendamento_uuid).unwrap().unwrap();
+        assert_eq!(db::buscar_agendamento_por_id(&conn, agendamento_id).unwrap().servicos_ids, vec![corte_id]);
+
+        // Exclusão pelo app, depois alteração atrasada do mesmo registro: a exclusão prevalece.
+        let resposta = aplicar_fila(&conn, &fila(json!([
+            { "entidade": "agendamento", "uuid": agendamento_uuid, "operacao": "excluir", "versao_base": 1 },
+            { "entidade": "agendamento", "uuid": agendamento_uuid, "operacao": "salvar", "dados": { "preco": 40.0 } },
+        ]))).unwrap();
+        assert_eq!(resposta.registros[0].resultado, Resolucao::Aplicado);
+        assert_eq!(resposta.registros[1].resultado, Resolucao::Conflito);
+
+        let feed = alteracoes_desde(&conn, cursor, LIMITE_PADRAO).unwrap();
+        let operacoes: Vec<(&str, &str)> = feed.alteracoes.iter().map(|a| (a.uuid.as_str(), a.operacao)).collect();
+        assert_eq!(operacoes, [(cliente_uuid, OPERACAO_CRIACAO)]);
+        let feed = alteracoes_desde(&conn, resposta.cursor - 1, LIMITE_PADRAO).unwrap();
+        assert_eq!(feed.alteracoes[0].operacao, OPERACAO_EXCLUSAO);
+        assert_eq!(feed.alteracoes[0].uuid, agendamento_uuid);
+    }
+
+    #[test]
+    fn test_conflito_de_versao_e_exclusao_definitiva() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let mut cliente = Cliente::new("Ana".to_string(), "11999990000".to_string(), None);
+        let id = db::salvar_cliente(&conn, &mut cliente).unwrap();
+        let uuid = uuid_do_registro(&conn, Entidade::Cliente, id).unwrap().unwrap();
+        // O app copiou a versão 1; o balcão renomeia e o servidor passa para a 2.
+        cliente.nome = "Ana Souza".into();
+        db::salvar_cliente(&conn, &mut cliente).unwrap();
+
+        let resposta = aplicar_fila(&conn, &fila(json!([
+            { "entidade": "cliente", "uuid": uuid, "operacao": "salvar", "versao_base": 1, "dados": { "email": "ana@exemplo.com" } },
+            { "entidade": "cliente", "uuid": uuid, "operacao": "salvar", "versao_base": 1, "dados": { "nome": "Ana Souza" } },
+            { "entidade": "cliente", "uuid": uuid, "operacao": "excluir", "versao_base": 1 },
+        ]))).unwrap();
+        let resultados: Vec<Resolucao> = resposta.registros.iter().map(|r| r.resultado).collect();
+        assert_eq!(resultados, [Resolucao::Conflito, Resolucao::SemAlteracao, Resolucao::Conflito]);
+        let atual = resposta.registros[0].atual.as_ref().unwrap();
+        assert_eq!((atual["nome"].clone(), atual["email"].clone(), atual["versao"].clone()), (json!("Ana Souza"), JsonValue::Null, json!(2)));
+        assert_eq!(db::listar_clientes(&conn).unwrap().len(), 1, "Exclusão com versão velha não apaga");
+
+        // Com a versão vigente, altera e depois exclui.
+        let resposta = aplicar_fila(&conn, &fila(json!([
+            { "entidade": "cliente", "uuid": uuid, "operacao": "salvar", "versao_base": 2, "dados": { "email": "ana@exemplo.com" } },
+            { "entidade": "cliente", "uuid": uuid, "operacao": "excluir", "versao_base": 3 },
+        ]))).unwrap();
+        assert_eq!(resposta.registros[0].atual.as_ref().unwrap()["versao"], json!(3));
+        assert_eq!(resposta.registros[1].resultado, Resolucao::Aplicado);
+        assert!(db::listar_clientes(&conn).unwrap().is_empty());
+
+        // A exclusão fica no feed sem os dados e prevalece sobre o que chegar depois com o mesmo UUID.
+        let feed = alteracoes_desde(&conn, resposta.cursor - 1, LIMITE_PADRAO).unwrap();
+        assert_eq!((feed.alteracoes[0].uuid.as_str(), feed.alteracoes[0].operacao), (uuid.as_str(), OPERACAO_EXCLUSAO));
+        assert!(feed.alteracoes[0].dados.is_none());
+        let resposta = aplicar_fila(&conn, &fila(json!([
+            { "entidade": "cliente", "uuid": uuid, "operacao": "excluir" },
+            { "entidade": "cliente", "uuid": uuid, "operacao": "salvar", "dados": { "nome": "Ana", "telefone": "11999990000", "email": null } },
+        ]))).unwrap();
+        let resultados: Vec<Resolucao> = resposta.registros.iter().map(|r| r.resultado).collect();
+        assert_eq!(resultados, [Resolucao::SemAlteracao, Resolucao::Conflito]);
+        assert!(db::listar_clientes(&conn).unwrap().is_empty());
+    }
+}
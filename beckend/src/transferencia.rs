@@ -0,0 +1,597 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use crate::backup::autorizar_dono;
+use crate::config;
+use crate::db;
+use crate::licenca;
+use crate::models::{Agendamento, Cliente, FaseServico, Recurso, RegrasAgenda, Servico};
+use crate::recursos;
+use crate::servicos;
+use crate::sincronizacao::{self, Entidade};
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// TRANSFERÊNCIA DO BANCO
+// Cópia completa dos cadastros num JSON versionado, para levar a barbearia para outra
+// máquina ou montar um ambiente de demonstração. A importação confere o formato e a
+// versão, renumera os ids e só carrega num banco vazio. Os UUIDs e as versões da
+// sincronização vão junto, para o app offline continuar reconhecendo os registros.
+// Ficam de fora: auditoria, notificações, webhooks (têm segredos), lista de espera,
+// histórico de remarcações e bloqueios importados de .ics.
+// =================================================================================
+
+pub const FORMATO: &str = "agendou";
+/// Sobe quando o formato muda de um jeito que versões antigas não sabem ler.
+pub const VERSAO_FORMATO: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClienteExportado {
+    #[serde(flatten)]
+    pub cliente: Cliente,
+    #[serde(default)]
+    pub anonimizado_em: Option<NaiveDateTime>,
+    #[serde(flatten)]
+    pub sync: SyncExportado,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServicoExportado {
+    #[serde(flatten)]
+    pub servico: Servico,
+    #[serde(default)]
+    pub fases: Vec<FaseServico>,
+    #[serde(default)]
+    pub recursos_ids: Vec<i32>,
+    #[serde(flatten)]
+    pub sync: SyncExportado,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AgendamentoExportado {
+    #[serde(flatten)]
+    pub agendamento: Agendamento,
+    pub status: String,
+    #[serde(flatten)]
+    pub sync: SyncExportado,
+}
+
+/// Identidade do registro na sincronização. Arquivos sem esses campos continuam válidos: o
+/// destino gera UUIDs novos e começa na versão 1.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncExportado {
+    #[serde(default)]
+    pub uuid: Option<String>,
+    #[serde(default)]
+    pub versao: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JanelaExportada {
+    pub weekday: i32,
+    pub start_time: String,
+    pub end_time: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SistemaExportado {
+    /// Token da licença, para a barbearia continuar licenciada na máquina nova.
+    pub licenca_token: Option<String>,
+    /// Início do período de avaliação (timestamp).
+    pub instalado_em: Option<i64>,
+    /// Último cursor do feed de sincronização na origem.
+    #[serde(default)]
+    pub sync_cursor: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BancoExportado {
+    pub formato: String,
+    pub versao: u32,
+    pub gerado_em: NaiveDateTime,
+    pub clientes: Vec<ClienteExportado>,
+    pub servicos: Vec<ServicoExportado>,
+    pub recursos: Vec<Recurso>,
+    pub agendamentos: Vec<AgendamentoExportado>,
+    pub work_windows: Vec<JanelaExportada>,
+    pub regras_agenda: RegrasAgenda,
+    #[serde(default)]
+    pub sistema: SistemaExportado,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct ResumoTransferencia {
+    pub clientes: usize,
+    pub servicos: usize,
+    pub recursos: usize,
+    pub agendamentos: usize,
+    pub work_windows: usize,
+}
+
+#[derive(Debug)]
+pub enum ErroTransferencia {
+    /// Arquivo fora do formato, de outra versão ou com referências quebradas.
+    Invalido(Vec<String>),
+    /// O banco de destino já tem dados.
+    BancoNaoVazio,
+    Banco(rusqlite::Error),
+}
+
+impl fmt::Display for ErroTransferencia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErroTransferencia::Invalido(erros) => write!(f, "arquivo inválido: {}", erros.join("; ")),
+            ErroTransferencia::BancoNaoVazio => write!(f, "o banco de destino já tem cadastros; a importação só carrega num banco vazio"),
+            ErroTransferencia::Banco(e) => write!(f, "erro no banco de dados: {}", e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for ErroTransferencia {
+    fn from(e: rusqlite::Error) -> Self {
+        ErroTransferencia::Banco(e)
+    }
+}
+
+fn invalido(mensagem: impl Into<String>) -> ErroTransferencia {
+    ErroTransferencia::Invalido(vec![mensagem.into()])
+}
+
+impl BancoExportado {
+    pub fn resumo(&self) -> ResumoTransferencia {
+        ResumoTransferencia {
+            clientes: self.clientes.len(),
+            servicos: self.servicos.len(),
+            recursos: self.recursos.len(),
+            agendamentos: self.agendamentos.len(),
+            work_windows: self.work_windows.len(),
+        }
+    }
+}
+
+fn sync_do_registro(conn: &Connection, entidade: Entidade, tabela: &str, id: i32) -> rusqlite::Result<SyncExportado> {
+    Ok(SyncExportado { uuid: sincronizacao::uuid_do_registro(conn, entidade, id)?, versao: crate::patch::versao(conn, tabela, id)? })
+}
+
+pub fn exportar_banco(conn: &Connection) -> Result<BancoExportado, ErroTransferencia> {
+    let mut clientes = Vec::new();
+    for cliente in db::listar_clientes(conn)? {
+        let id = cliente.id.unwrap_or_default();
+        let anonimizado_em = db::buscar_anonimizacao_cliente(conn, id)?;
+        let sync = sync_do_registro(conn, Entidade::Cliente, "clientes", id)?;
+        clientes.push(ClienteExportado { cliente, anonimizado_em, sync });
+    }
+    let mut servicos = Vec::new();
+    for servico in db::listar_servicos(conn)? {
+        let id = servico.id.unwrap_or_default();
+        let fases = db::listar_fases_servico(conn, id)?;
+        let recursos_ids = recursos::recursos_do_servico(conn, id)?;
+        let sync = sync_do_registro(conn, Entidade::Servico, "servicos", id)?;
+        servicos.push(ServicoExportado { servico, fases, recursos_ids, sync });
+    }
+    let mut agendamentos = Vec::new();
+    for agendamento in db::listar_agendamentos(conn)? {
+        let id = agendamento.id.unwrap_or_default();
+        let status = db::status_agendamento(conn, id)?;
+        let sync = sync_do_registro(conn, Entidade::Agendamento, "agendamentos", id)?;
+        agendamentos.push(AgendamentoExportado { agendamento, status, sync });
+    }
+    let work_windows = db::listar_work_windows(conn)?
+        .into_iter()
+        .map(|(_, weekday, start_time, end_time)| JanelaExportada { weekday, start_time, end_time })
+        .collect();
+    let sistema = conn.query_row("SELECT licenca_token, instalado_em FROM sistema WHERE id = 1", [], |row| {
+        Ok(SistemaExportado { licenca_token: row.get(0)?, instalado_em: row.get(1)?, sync_cursor: None })
+    })?;
+    let sistema = SistemaExportado { sync_cursor: Some(sincronizacao::cursor_atual(conn)?), ..sistema };
+
+    let banco = BancoExportado {
+        formato: FORMATO.to_string(),
+        versao: VERSAO_FORMATO,
+        gerado_em: config::agora(),
+        clientes,
+        servicos,
+        recursos: recursos::listar_recursos(conn)?,
+        agendamentos,
+        work_windows,
+        regras_agenda: db::buscar_regras_agenda(conn)?,
+        sistema,
+    };
+    db::registrar_auditoria(conn, "banco", 0, db::ACAO_EXPORTACAO, None, db::json_de(&banco.resumo()))?;
+    Ok(banco)
+}
+
+/// Lê o JSON conferindo primeiro o formato e a versão, para a mensagem de erro ser útil
+/// mesmo quando o arquivo é de uma versão com outros campos.
+pub fn ler_banco(texto: &str) -> Result<BancoExportado, ErroTransferencia> {
+    let valor: JsonValue = serde_json::from_str(texto).map_err(|e| invalido(format!("JSON inválido: {}", e)))?;
+    if valor.get("formato").and_then(JsonValue::as_str) != Some(FORMATO) {
+        return Err(invalido(format!("não é uma exportação do Agendou (campo formato deve ser \"{}\")", FORMATO)));
+    }
+    match valor.get("versao").and_then(JsonValue::as_u64) {
+        Some(v) if v == VERSAO_FORMATO as u64 => {}
+        Some(v) if v > VERSAO_FORMATO as u64 => {
+            return Err(invalido(format!("versão {} do formato é mais nova que a suportada ({}); atualize o sistema", v, VERSAO_FORMATO)));
+        }
+        Some(v) => return Err(invalido(format!("versão {} do formato não é suportada", v))),
+        None => return Err(invalido("campo versao ausente")),
+    }
+    serde_json::from_value(valor).map_err(|e| invalido(format!("conteúdo inválido: {}", e)))
+}
+
+/// Ids repetidos e referências para registros que não estão no arquivo.
+fn validar(banco: &BancoExportado) -> Vec<String> {
+    let mut erros = Vec::new();
+    let mut ids = |nome: &str, lista: Vec<Option<i32>>| -> HashSet<i32> {
+        let mut vistos = HashSet::new();
+        for id in lista {
+            match id {
+                Some(id) if !vistos.insert(id) => erros.push(format!("{} com id {} repetido", nome, id)),
+                Some(_) => {}
+                None => erros.push(format!("{} sem id", nome)),
+            }
+        }
+        vistos
+    };
+    let clientes = ids("cliente", banco.clientes.iter().map(|c| c.cliente.id).collect());
+    let servicos_ids = ids("serviço", banco.servicos.iter().map(|s| s.servico.id).collect());
+    let recursos_ids = ids("recurso", banco.recursos.iter().map(|r| r.id).collect());
+    ids("agendamento", banco.agendamentos.iter().map(|a| a.agendamento.id).collect());
+
+    for s in &banco.servicos {
+        let id = s.servico.id.unwrap_or_default();
+        if let Err(m) = servicos::validar_regras(&s.servico) {
+            erros.push(format!("serviço {}: {}", id, m));
+        }
+        if !s.fases.is_empty() {
+            if let Err(m) = servicos::validar_fases(&s.fases) {
+                erros.push(format!("serviço {}: {}", id, m));
+            }
+        }
+        for r in s.recursos_ids.iter().filter(|r| !recursos_ids.contains(r)) {
+            erros.push(format!("serviço {} exige o recurso {}, que não está no arquivo", id, r));
+        }
+    }
+    // UUIDs são únicos entre todas as entidades (ver `sync_registros`).
+    let mut uuids = HashSet::new();
+    let sync = banco.clientes.iter().map(|c| ("cliente", c.cliente.id, &c.sync))
+        .chain(banco.servicos.iter().map(|s| ("serviço", s.servico.id, &s.sync)))
+        .chain(banco.agendamentos.iter().map(|a| ("agendamento", a.agendamento.id, &a.sync)));
+    for (nome, id, sync) in sync {
+        let id = id.unwrap_or_default();
+        if let Some(uuid) = &sync.uuid {
+            match sincronizacao::normalizar_uuid(uuid) {
+                Some(uuid) if !uuids.insert(uuid.clone()) => erros.push(format!("{} {} com uuid {} repetido", nome, id, uuid)),
+                Some(_) => {}
+                None => erros.push(format!("{} {} com uuid \"{}\" inválido", nome, id, uuid)),
+            }
+        }
+        if sync.versao.is_some_and(|v| v < 1) {
+            erros.push(format!("{} {} com versao menor que 1", nome, id));
+        }
+    }
+    let status_validos = [db::STATUS_AGENDAMENTO_PENDENTE, db::STATUS_AGENDAMENTO_CONFIRMADO, db::STATUS_AGENDAMENTO_CANCELADO];
+    for a in &banco.agendamentos {
+        let id = a.agendamento.id.unwrap_or_default();
+        if !clientes.contains(&a.agendamento.cliente_id) {
+            erros.push(format!("agendamento {} aponta para o cliente {}, que não está no arquivo", id, a.agendamento.cliente_id));
+        }
+        for s in a.agendamento.servicos_ids.iter().filter(|s| !servicos_ids.contains(s)) {
+            erros.push(format!("agendamento {} usa o serviço {}, que não está no arquivo", id, s));
+        }
+        if !status_validos.contains(&a.status.as_str()) {
+            erros.push(format!("agendamento {} com status \"{}\" desconhecido", id, a.status));
+        }
+    }
+    erros
+}
+
+fn banco_vazio(conn: &Connection) -> rusqlite::Result<bool> {
+    let total: i64 = conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM clientes) + (SELECT COUNT(*) FROM servicos) + (SELECT COUNT(*) FROM agendamentos)
+              + (SELECT COUNT(*) FROM recursos) + (SELECT COUNT(*) FROM work_windows)",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(total == 0)
+}
+
+/// Troca o UUID gerado pelo gatilho pelo da origem. Um UUID que o destino já usou (registro
+/// excluído antes da importação) torna o arquivo inválido para este banco.
+fn restaurar_uuid(conn: &Connection, entidade: Entidade, id: i32, sync: &SyncExportado) -> Result<(), ErroTransferencia> {
+    let Some(uuid) = sync.uuid.as_deref().and_then(sincronizacao::normalizar_uuid) else {
+        return Ok(());
+    };
+    if sincronizacao::uuid_em_uso(conn, &uuid)? {
+        return Err(invalido(format!("o uuid {} já foi usado neste banco", uuid)));
+    }
+    sincronizacao::adotar_uuid(conn, entidade, id, &uuid)?;
+    Ok(())
+}
+
+/// Carrega a exportação num banco vazio, numa transação. Os ids são renumerados pelo
+/// banco de destino e as referências (cliente, serviços, recursos) seguem a nova numeração.
+pub fn importar_banco(conn: &Connection, banco: &BancoExportado) -> Result<ResumoTransferencia, ErroTransferencia> {
+    let erros = validar(banco);
+    if !erros.is_empty() {
+        return Err(ErroTransferencia::Invalido(erros));
+    }
+    if !banco_vazio(conn)? {
+        return Err(ErroTransferencia::BancoNaoVazio);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    if let Some(cursor) = banco.sistema.sync_cursor {
+        sincronizacao::recomecar_feed_apos(&tx, cursor)?;
+    }
+    let mut novos_recursos = HashMap::new();
+    for r in &banco.recursos {
+        tx.execute("INSERT INTO recursos (nome, descricao) VALUES (?1, ?2)", params![r.nome, r.descricao])?;
+        novos_recursos.insert(r.id.unwrap_or_default(), tx.last_insert_rowid() as i32);
+    }
+    let mut novos_clientes = HashMap::new();
+    for c in &banco.clientes {
+        tx.execute(
+            "INSERT INTO clientes (nome, telefone, email, anonimizado_em, versao) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![c.cliente.nome, c.cliente.telefone, c.cliente.email, c.anonimizado_em.map(|d| d.and_utc().timestamp()), c.sync.versao.unwrap_or(1)],
+        )?;
+        let novo_id = tx.last_insert_rowid() as i32;
+        restaurar_uuid(&tx, Entidade::Cliente, novo_id, &c.sync)?;
+        novos_clientes.insert(c.cliente.id.unwrap_or_default(), novo_id);
+    }
+    let mut novos_servicos = HashMap::new();
+    for s in &banco.servicos {
+        let servico = &s.servico;
+        tx.execute(
+            "INSERT INTO servicos (nome, preco, duracao_min, buffer_min, alinhamento_min, versao) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![servico.nome, servico.preco, servico.duracao_min, servico.buffer_min, servico.alinhamento_min, s.sync.versao.unwrap_or(1)],
+        )?;
+        let novo_id = tx.last_insert_rowid() as i32;
+        restaurar_uuid(&tx, Entidade::Servico, novo_id, &s.sync)?;
+        novos_servicos.insert(servico.id.unwrap_or_default(), novo_id);
+        for (ordem, fase) in s.fases.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO servico_fases (servico_id, ordem, nome, duracao_min, ocupa_profissional) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![novo_id, ordem as i64, fase.nome, fase.duracao_min, fase.ocupa_profissional],
+            )?;
+        }
+        for recurso in &s.recursos_ids {
+            tx.execute("INSERT OR IGNORE INTO servico_recursos (servico_id, recurso_id) VALUES (?1, ?2)", params![novo_id, novos_recursos[recurso]])?;
+        }
+    }
+    for a in &banco.agendamentos {
+        let ag = &a.agendamento;
+        tx.execute(
+            "INSERT INTO agendamentos (cliente_id, data_hora, preco, concluido, status, versao) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![novos_clientes[&ag.cliente_id], ag.data_hora.and_utc().timestamp(), ag.preco, ag.concluido, a.status, a.sync.versao.unwrap_or(1)],
+        )?;
+        let novo_id = tx.last_insert_rowid();
+        restaurar_uuid(&tx, Entidade::Agendamento, novo_id as i32, &a.sync)?;
+        for servico in &ag.servicos_ids {
+            tx.execute(
+                "INSERT OR IGNORE INTO agendamento_servicos (agendamento_id, servico_id) VALUES (?1, ?2)",
+                params![novo_id, novos_servicos[servico]],
+            )?;
+        }
+    }
+    for j in &banco.work_windows {
+        tx.execute("INSERT INTO work_windows (weekday, start_time, end_time) VALUES (?1, ?2, ?3)", params![j.weekday, j.start_time, j.end_time])?;
+    }
+    tx.execute(
+        "UPDATE regras_agenda SET antecedencia_minima_min = ?1, horizonte_dias = ?2 WHERE id = 1",
+        params![banco.regras_agenda.antecedencia_minima_min, banco.regras_agenda.horizonte_dias],
+    )?;
+    if let Some(token) = &banco.sistema.licenca_token {
+        match licenca::instalar_licenca(&tx, token) {
+            Ok(_) => {}
+            Err(licenca::ErroLicenca::Banco(e)) => return Err(e.into()),
+            Err(e) => return Err(invalido(format!("licença do arquivo: {}", e))),
+        }
+    }
+    if let Some(instalado_em) = banco.sistema.instalado_em {
+        tx.execute("UPDATE sistema SET instalado_em = ?1 WHERE id = 1", params![instalado_em])?;
+    }
+
+    let resumo = banco.resumo();
+    db::registrar_auditoria(&tx, "banco", 0, db::ACAO_IMPORTACAO, None, db::json_de(&resumo))?;
+    tx.commit()?;
+    Ok(resumo)
+}
+
+// ---------------------------------------------------------------------------------
+// API
+// ---------------------------------------------------------------------------------
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn resposta<T: Serialize>(status: StatusCode, message: String, data: Option<T>) -> Response {
+    (status, Json(ApiResponse { success: status.is_success(), message, data })).into_response()
+}
+
+/// GET /sistema/exportar — o banco inteiro no formato de transferência, como anexo. Só o dono.
+pub async fn exportar_banco_api(headers: HeaderMap, State(conn): State<Db>) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let conn = conn.lock().unwrap();
+    match exportar_banco(&conn) {
+        Ok(banco) => {
+            let nome = format!("agendou-{}.json", banco.gerado_em.format("%Y%m%d-%H%M"));
+            let mut response = Json(banco).into_response();
+            response
+                .headers_mut()
+                .insert(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", nome).parse().unwrap());
+            response
+        }
+        Err(e) => {
+            eprintln!("Erro ao exportar banco: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao exportar banco: {}", e), None)
+        }
+    }
+}
+
+/// POST /sistema/importar — corpo é o JSON de `GET /sistema/exportar`. Só num banco vazio. Só o dono.
+pub async fn importar_banco_api(headers: HeaderMap, State(conn): State<Db>, corpo: Bytes) -> Response {
+    if let Err((status, message)) = autorizar_dono(&headers) {
+        return resposta::<()>(status, message, None);
+    }
+    let Ok(texto) = std::str::from_utf8(&corpo) else {
+        return resposta::<()>(StatusCode::BAD_REQUEST, "O arquivo deve estar em UTF-8.".to_string(), None);
+    };
+    let conn = conn.lock().unwrap();
+    match ler_banco(texto).and_then(|banco| importar_banco(&conn, &banco)) {
+        Ok(resumo) => {
+            let message = format!(
+                "Banco importado: {} cliente(s), {} serviço(s), {} agendamento(s).",
+                resumo.clientes, resumo.servicos, resumo.agendamentos
+            );
+            resposta(StatusCode::OK, message, Some(resumo))
+        }
+        Err(ErroTransferencia::Invalido(erros)) => resposta(StatusCode::BAD_REQUEST, "Arquivo inválido; nada foi importado.".to_string(), Some(erros)),
+        Err(ErroTransferencia::BancoNaoVazio) => resposta::<()>(
+            StatusCode::CONFLICT,
+            "O banco de destino já tem cadastros; a importação só carrega num banco vazio.".to_string(),
+            None,
+        ),
+        Err(ErroTransferencia::Banco(e)) => {
+            eprintln!("Erro ao importar banco: {}", e);
+            resposta::<()>(StatusCode::INTERNAL_SERVER_ERROR, format!("Erro ao importar banco: {}", e), None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exportar_e_importar_renumerando() {
+        let origem = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&origem).unwrap();
+        // Ids "altos" na origem, para a renumeração aparecer no destino.
+        origem.execute("INSERT INTO clientes (id, nome, telefone) VALUES (40, 'Ana', '11999990000')", []).unwrap();
+        let lavatorio = recursos::salvar_recurso(&origem, &Recurso { id: None, nome: "Lavatório".to_string(), descricao: None }).unwrap();
+        let servico = Servico { id: None, nome: "Luzes".to_string(), preco: 120.0, duracao_min: 90, buffer_min: Some(10), alinhamento_min: None };
+        let servico_id = db::salvar_servico(&origem, &servico).unwrap();
+        recursos::definir_recursos_do_servico(&origem, servico_id, &[lavatorio.id.unwrap()]).unwrap();
+        let data_hora = chrono::NaiveDate::from_ymd_opt(2030, 1, 7).unwrap().and_hms_opt(10, 0, 0).unwrap();
+        let agendamento = Agendamento { id: None, cliente_id: 40, servicos_ids: vec![servico_id], data_hora, preco: 120.0, concluido: false };
+        let agendamento_id = db::salvar_agendamento(&origem, &agendamento).unwrap();
+        db::definir_status_agendamento(&origem, agendamento_id, db::STATUS_AGENDAMENTO_CONFIRMADO).unwrap();
+        db::salvar_work_window(&origem, 1, "09:00", "18:00").unwrap();
+
+        let texto = serde_json::to_string(&exportar_banco(&origem).unwrap()).unwrap();
+        let destino = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&destino).unwrap();
+        let resumo = importar_banco(&destino, &ler_banco(&texto).unwrap()).unwrap();
+        assert_eq!(resumo, ResumoTransferencia { clientes: 1, servicos: 1, recursos: 1, agendamentos: 1, work_windows: 1 });
+
+        let importado = &db::listar_agendamentos(&destino).unwrap()[0];
+        assert_eq!(importado.cliente_id, 1);
+        assert_eq!(importado.data_hora, data_hora);
+        assert_eq!(db::status_agendamento(&destino, importado.id.unwrap()).unwrap(), db::STATUS_AGENDAMENTO_CONFIRMADO);
+        assert_eq!(recursos::recursos_do_servico(&destino, importado.servicos_ids[0]).unwrap().len(), 1);
+
+        // Banco com dados, versão futura e referência quebrada são recusados.
+        let de_novo = importar_banco(&destino, &ler_banco(&texto).unwrap());
+        assert!(matches!(de_novo, Err(ErroTransferencia::BancoNaoVazio)));
+        let futura = texto.replacen("\"versao\":1", "\"versao\":2", 1);
+        assert!(matches!(ler_banco(&futura), Err(ErroTransferencia::Invalido(_))));
+        let quebrado = texto.replacen("\"cliente_id\":40", "\"cliente_id\":41", 1);
+        let vazio = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&vazio).unwrap();
+        assert!(matches!(importar_banco(&vazio, &ler_banco(&quebrado).unwrap()), Err(ErroTransferencia::Invalido(_))));
+    }
+
+    #[test]
+    fn test_app_offline_continua_sincronizando_depois_da_transferencia() {
+        use serde_json::json;
+        let origem = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&origem).unwrap();
+        let cliente_uuid = "0b7e0f3a-5d2c-4c1e-9a11-2f7c3b8d9e01";
+        let fila: Vec<sincronizacao::AlteracaoLocal> = serde_json::from_value(json!([
+            { "entidade": "cliente", "uuid": cliente_uuid, "operacao": "salvar", "dados": { "nome": "Ana", "telefone": "11999990000", "email": null } },
+        ])).unwrap();
+        sincronizacao::aplicar_fila(&origem, &fila).unwrap();
+        let id_origem = sincronizacao::id_do_uuid(&origem, Entidade::Cliente, cliente_uuid).unwrap().unwrap();
+        db::salvar_cliente(&origem, &mut Cliente { id: Some(id_origem), nome: "Ana Souza".into(), telefone: "11999990000".into(), email: None }).unwrap();
+        let cursor_do_app = sincronizacao::cursor_atual(&origem).unwrap();
+
+        let texto = serde_json::to_string(&exportar_banco(&origem).unwrap()).unwrap();
+        let destino = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&destino).unwrap();
+        importar_banco(&destino, &ler_banco(&texto).unwrap()).unwrap();
+
+        // O cursor antigo não vale mais (410 no GET /sync); a carga inicial traz os mesmos UUIDs e versões.
+        assert!(!sincronizacao::cursor_conhecido(&destino, cursor_do_app).unwrap());
+        let feed = sincronizacao::alteracoes_desde(&destino, 0, sincronizacao::LIMITE_PADRAO).unwrap();
+        assert!(feed.cursor > cursor_do_app);
+        assert!(sincronizacao::cursor_conhecido(&destino, feed.cursor).unwrap());
+        assert_eq!(feed.alteracoes.len(), 1);
+        assert_eq!(feed.alteracoes[0].uuid, cliente_uuid);
+        assert_eq!(feed.alteracoes[0].dados.as_ref().unwrap()["versao"], json!(2));
+
+        // A fila offline pendente altera o mesmo cliente em vez de criar outro.
+        let fila: Vec<sincronizacao::AlteracaoLocal> = serde_json::from_value(json!([
+            { "entidade": "cliente", "uuid": cliente_uuid, "operacao": "salvar", "versao_base": 2, "dados": { "email": "ana@exemplo.com" } },
+        ])).unwrap();
+        let resposta = sincronizacao::aplicar_fila(&destino, &fila).unwrap();
+        assert_eq!(resposta.registros[0].resultado, sincronizacao::Resolucao::Aplicado);
+        let clientes = db::listar_clientes(&destino).unwrap();
+        assert_eq!(clientes.len(), 1);
+        assert_eq!(clientes[0].email.as_deref(), Some("ana@exemplo.com"));
+    }
+
+    #[test]
+    fn test_importar_em_banco_com_dados_nao_grava_nada() {
+        let origem = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&origem).unwrap();
+        origem.execute("INSERT INTO clientes (nome, telefone) VALUES ('Ana', '11999990000')", []).unwrap();
+        let texto = serde_json::to_string(&exportar_banco(&origem).unwrap()).unwrap();
+        let banco = ler_banco(&texto).unwrap();
+
+        // Qualquer cadastro, até só um horário de trabalho, torna o destino "não vazio".
+        let preparos: [fn(&Connection); 4] = [
+            |c| {
+                db::salvar_work_window(c, 1, "09:00", "18:00").unwrap();
+            },
+            |c| {
+                let servico = Servico { id: None, nome: "Corte".to_string(), preco: 35.0, duracao_min: 30, buffer_min: None, alinhamento_min: None };
+                db::salvar_servico(c, &servico).unwrap();
+            },
+            |c| {
+                recursos::salvar_recurso(c, &Recurso { id: None, nome: "Lavatório".to_string(), descricao: None }).unwrap();
+            },
+            |c| {
+                db::salvar_cliente(c, &mut Cliente::new("Bia".to_string(), "11988887777".to_string(), None)).unwrap();
+            },
+        ];
+        for preparar in preparos {
+            let destino = Connection::open_in_memory().unwrap();
+            db::criar_tabelas(&destino).unwrap();
+            preparar(&destino);
+            let antes = exportar_banco(&destino).unwrap().resumo();
+            assert!(matches!(importar_banco(&destino, &banco), Err(ErroTransferencia::BancoNaoVazio)));
+            assert_eq!(exportar_banco(&destino).unwrap().resumo(), antes);
+            assert!(db::listar_clientes(&destino).unwrap().iter().all(|c| c.nome != "Ana"));
+        }
+    }
+}
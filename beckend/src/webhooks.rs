@@ -0,0 +1,531 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::NaiveDateTime;
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, Result, Row, params};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::backup::autorizar_dono;
+use crate::config;
+use crate::db;
+use crate::models::{EntregaWebhook, Webhook};
+use crate::notificacoes::espera_apos;
+
+type Db = Arc<Mutex<Connection>>;
+
+// =================================================================================
+// WEBHOOKS DE SAÍDA
+// Os handlers gravam uma entrega por assinatura interessada (`emitir`) e um despachante
+// em segundo plano faz o POST assinado com HMAC-SHA256, repetindo em caso de falha.
+// =================================================================================
+
+pub const EVENTO_AGENDAMENTO_CRIADO: &str = "agendamento.criado";
+pub const EVENTO_AGENDAMENTO_ATUALIZADO: &str = "agendamento.atualizado";
+pub const EVENTO_AGENDAMENTO_CANCELADO: &str = "agendamento.cancelado";
+pub const EVENTO_AGENDAMENTO_CONFIRMADO: &str = "agendamento.confirmado";
+pub const EVENTO_CLIENTE_CRIADO: &str = "cliente.criado";
+/// Um horário liberado serve para um pedido da lista de espera (ver `espera`).
+pub const EVENTO_LISTA_ESPERA_VAGA: &str = "lista_espera.vaga";
+
+pub const EVENTOS: [&str; 6] = [
+    EVENTO_AGENDAMENTO_CRIADO,
+    EVENTO_AGENDAMENTO_ATUALIZADO,
+    EVENTO_AGENDAMENTO_CANCELADO,
+    EVENTO_AGENDAMENTO_CONFIRMADO,
+    EVENTO_CLIENTE_CRIADO,
+    EVENTO_LISTA_ESPERA_VAGA,
+];
+
+pub const STATUS_PENDENTE: &str = "pendente";
+pub const STATUS_ENTREGUE: &str = "entregue";
+pub const STATUS_FALHOU: &str = "falhou";
+
+/// Intervalo da varredura de entregas pendentes (novas entregas acordam o despachante na hora).
+const VARREDURA_SEG: u64 = 5;
+
+fn despertador() -> &'static Notify {
+    static DESPERTADOR: OnceLock<Notify> = OnceLock::new();
+    DESPERTADOR.get_or_init(Notify::new)
+}
+
+pub fn criar_tabela_webhooks(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id        INTEGER PRIMARY KEY AUTOINCREMENT,
+            url       TEXT NOT NULL,
+            segredo   TEXT NOT NULL,
+            eventos   TEXT NOT NULL,  -- lista separada por vírgulas
+            ativo     BOOLEAN NOT NULL DEFAULT 1,
+            criado_em INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhook_entregas (
+            id                INTEGER PRIMARY KEY AUTOINCREMENT,
+            webhook_id        INTEGER NOT NULL,
+            evento            TEXT NOT NULL,
+            payload           TEXT NOT NULL,
+            status            TEXT NOT NULL,
+            tentativas        INTEGER NOT NULL DEFAULT 0,
+            proxima_tentativa INTEGER NOT NULL,
+            resposta_status   INTEGER,
+            ultimo_erro       TEXT,
+            criado_em         INTEGER NOT NULL,
+            entregue_em       INTEGER,
+            FOREIGN KEY (webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_webhook_entregas_fila ON webhook_entregas (status, proxima_tentativa)",
+        [],
+    )?;
+    Ok(())
+}
+
+fn linha_para_webhook(row: &Row) -> Result<Webhook> {
+    let eventos: String = row.get(2)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        eventos: eventos.split(',').map(str::to_string).collect(),
+        ativo: row.get(3)?,
+        criado_em: db::timestamp_para_naive(row.get(4)?),
+        segredo: None,
+    })
+}
+
+pub fn listar_webhooks(conn: &Connection) -> Result<Vec<Webhook>> {
+    let mut stmt = conn.prepare("SELECT id, url, eventos, ativo, criado_em FROM webhooks ORDER BY id")?;
+    let linhas = stmt.query_map([], linha_para_webhook)?;
+    linhas.collect()
+}
+
+/// Cadastra uma assinatura. Os eventos já devem ter sido validados.
+pub fn salvar_webhook(conn: &Connection, url: &str, segredo: &str, eventos: &[String]) -> Result<Webhook> {
+    let criado_em = db::agora_ts();
+    conn.execute(
+        "INSERT INTO webhooks (url, segredo, eventos, ativo, criado_em) VALUES (?1, ?2, ?3, 1, ?4)",
+        params![url, segredo, eventos.join(","), criado_em],
+    )?;
+    Ok(Webhook {
+        id: conn.last_insert_rowid(),
+        url: url.to_string(),
+        eventos: eventos.to_vec(),
+        ativo: true,
+        criado_em: db::timestamp_para_naive(criado_em),
+        segredo: Some(segredo.to_string()),
+    })
+}
+
+pub fn excluir_webhook(conn: &Connection, id: i64) -> Result<bool> {
+    Ok(conn.execute("DELETE FROM webhooks WHERE id = ?1", params![id])? > 0)
+}
+
+/// Enfileira o evento para cada assinatura ativa que o escuta. Devolve quantas entregas criou.
+pub fn emitir<T: Serialize>(conn: &Connection, evento: &str, dados: &T) -> Result<usize> {
+    let agora = db::agora_ts();
+    let payload = serde_json::json!({
+        "evento": evento,
+        "ocorrido_em": db::timestamp_para_naive(agora),
+        "dados": dados,
+    })
+    .to_string();
+
+    let interessados: Vec<i64> = listar_webhooks(conn)?
+        .into_iter()
+        .filter(|w| w.ativo && w.eventos.iter().any(|e| e == "*" || e == evento))
+        .map(|w| w.id)
+        .collect();
+    for webhook_id in &interessados {
+        conn.execute(
+            "INSERT INTO webhook_entregas (webhook_id, evento, payload, status, proxima_tentativa, criado_em)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![webhook_id, evento, payload, STATUS_PENDENTE, agora],
+        )?;
+    }
+    if !interessados.is_empty() {
+        despertador().notify_one();
+    }
+    Ok(interessados.len())
+}
+
+/// Para os handlers: uma falha ao enfileirar não deve desfazer a operação principal.
+pub fn emitir_ou_logar<T: Serialize>(conn: &Connection, evento: &str, dados: &T) {
+    if let Err(e) = emitir(conn, evento, dados) {
+        eprintln!("Erro ao enfileirar webhook {}: {}", evento, e);
+    }
+}
+
+/// Assinatura enviada em `X-Agendou-Assinatura`: `sha256=` + HMAC-SHA256 em hex de
+/// `"<timestamp>.<corpo>"` com o segredo da assinatura.
+pub fn assinar(segredo: &str, timestamp: i64, corpo: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(segredo.as_bytes()).expect("HMAC aceita chave de qualquer tamanho");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(corpo.as_bytes());
+    format!("sha256={}", hex(&mac.finalize().into_bytes()))
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn gerar_segredo() -> String {
+    let mut bytes = [0u8; 24];
+    getrandom::getrandom(&mut bytes).expect("falha ao gerar segredo aleatório");
+    format!("whsec_{}", hex(&bytes))
+}
+
+/// Entrega pronta para o POST.
+#[derive(Debug, Clone)]
+pub struct EntregaPendente {
+    pub id: i64,
+    pub url: String,
+    pub segredo: String,
+    pub evento: String,
+    pub payload: String,
+}
+
+pub fn entregas_a_fazer(conn: &Connection, agora_ts: i64, limite: i64) -> Result<Vec<EntregaPendente>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.id, w.url, w.segredo, e.evento, e.payload
+         FROM webhook_entregas e JOIN webhooks w ON w.id = e.webhook_id
+         WHERE e.status = ?1 AND e.proxima_tentativa <= ?2 AND w.ativo = 1
+         ORDER BY e.id
+         LIMIT ?3",
+    )?;
+    let linhas = stmt.query_map(params![STATUS_PENDENTE, agora_ts, limite], |row| {
+        Ok(EntregaPendente { id: row.get(0)?, url: row.get(1)?, segredo: row.get(2)?, evento: row.get(3)?, payload: row.get(4)? })
+    })?;
+    linhas.collect()
+}
+
+/// Resultado de um POST: status HTTP (se houve resposta) e erro (se não foi 2xx).
+pub type ResultadoEntrega = (Option<i64>, Option<String>);
+
+/// Faz o POST assinado (bloqueante).
+pub fn entregar(entrega: &EntregaPendente, timeout: Duration) -> ResultadoEntrega {
+    let timestamp = chrono::Utc::now().timestamp();
+    let resposta = ureq::post(&entrega.url)
+        .timeout(timeout)
+        .set("Content-Type", "application/json")
+        .set("User-Agent", "Agendou-Webhooks/1")
+        .set("X-Agendou-Evento", &entrega.evento)
+        .set("X-Agendou-Entrega", &entrega.id.to_string())
+        .set("X-Agendou-Timestamp", &timestamp.to_string())
+        .set("X-Agendou-Assinatura", &assinar(&entrega.segredo, timestamp, &entrega.payload))
+        .send_string(&entrega.payload);
+    match resposta {
+        Ok(r) => (Some(r.status() as i64), None),
+        Err(ureq::Error::Status(codigo, r)) => {
+            let corpo: String = r.into_string().unwrap_or_default().chars().take(200).collect();
+            (Some(codigo as i64), Some(format!("HTTP {}: {}", codigo, corpo)))
+        }
+        Err(e) => (None, Some(e.to_string())),
+    }
+}
+
+pub fn registrar_entrega(conn: &Connection, id: i64, resultado: &ResultadoEntrega, agora: NaiveDateTime, tentativas_max: i64) -> Result<()> {
+    let (resposta_status, erro) = resultado;
+    let agora_ts = agora.and_utc().timestamp();
+    match erro {
+        None => {
+            conn.execute(
+                "UPDATE webhook_entregas SET status = ?1, tentativas = tentativas + 1, resposta_status = ?2,
+                        ultimo_erro = NULL, entregue_em = ?3 WHERE id = ?4",
+                params![STATUS_ENTREGUE, resposta_status, agora_ts, id],
+            )?;
+        }
+        Some(erro) => {
+            let tentativas: i64 = conn.query_row("SELECT tentativas FROM webhook_entregas WHERE id = ?1", params![id], |row| row.get(0))?;
+            let tentativas = tentativas + 1;
+            let status = if tentativas >= tentativas_max { STATUS_FALHOU } else { STATUS_PENDENTE };
+            conn.execute(
+                "UPDATE webhook_entregas SET status = ?1, tentativas = ?2, proxima_tentativa = ?3, resposta_status = ?4, ultimo_erro = ?5
+                 WHERE id = ?6",
+                params![status, tentativas, (agora + espera_apos(tentativas)).and_utc().timestamp(), resposta_status, erro, id],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn linha_para_entrega(row: &Row) -> Result<EntregaWebhook> {
+    let payload: String = row.get(3)?;
+    let status: String = row.get(4)?;
+    let pendente = status == STATUS_PENDENTE;
+    Ok(EntregaWebhook {
+        id: row.get(0)?,
+        webhook_id: row.get(1)?,
+        evento: row.get(2)?,
+        payload: serde_json::from_str(&payload).unwrap_or(serde_json::Value::String(payload)),
+        status,
+        tentativas: row.get(5)?,
+        proxima_tentativa: if pendente { Some(db::timestamp_para_naive(row.get(6)?)) } else { None },
+        resposta_status: row.get(7)?,
+        ultimo_erro: row.get(8)?,
+        criado_em: db::timestamp_para_naive(row.get(9)?),
+        entregue_em: row.get::<_, Option<i64>>(10)?.map(db::timestamp_para_naive),
+    })
+}
+
+/// Log de entregas, mais recentes primeiro.
+pub fn listar_entregas(conn: &Connection, webhook_id: Option<i64>, status: Option<&str>, limite: i64) -> Result<Vec<EntregaWebhook>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, webhook_id, evento, payload, status, tentativas, proxima_tentativa, resposta_status, ultimo_erro, criado_em, entregue_em
+         FROM webhook_entregas
+         WHERE (?1 IS NULL OR webhook_id = ?1) AND (?2 IS NULL OR status = ?2)
+         ORDER BY id DESC
+         LIMIT ?3",
+    )?;
+    let linhas = stmt.query_map(params![webhook_id, status, limite], linha_para_entrega)?;
+    linhas.collect()
+}
+
+/// Condição SQL das entregas cujo payload fala do cliente `?1`: eventos de cliente pelo `id`,
+/// os demais (agendamento, lista de espera) pelo `cliente_id`.
+const ENTREGAS_DO_CLIENTE: &str = "(evento LIKE 'cliente.%' AND json_extract(payload, '$.dados.id') = ?1)
+     OR json_extract(payload, '$.dados.cliente_id') = ?1";
+
+/// Entregas com dados do cliente (exportação LGPD), da mais antiga para a mais recente.
+pub fn listar_entregas_cliente(conn: &Connection, cliente_id: i32) -> Result<Vec<EntregaWebhook>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, webhook_id, evento, payload, status, tentativas, proxima_tentativa, resposta_status, ultimo_erro, criado_em, entregue_em
+         FROM webhook_entregas
+         WHERE {}
+         ORDER BY id",
+        ENTREGAS_DO_CLIENTE
+    ))?;
+    let linhas = stmt.query_map(params![cliente_id], linha_para_entrega)?;
+    linhas.collect()
+}
+
+/// Anonimização: troca os dados dos eventos de cliente por `{id, anonimizado}`, como na
+/// auditoria. Os de agendamento só levam o `cliente_id` e ficam como estão.
+pub fn redigir_entregas_cliente(conn: &Connection, cliente_id: i32) -> Result<usize> {
+    let redigido = serde_json::json!({ "id": cliente_id, "anonimizado": true }).to_string();
+    conn.execute(
+        "UPDATE webhook_entregas SET payload = json_set(payload, '$.dados', json(?2))
+         WHERE evento LIKE 'cliente.%' AND json_extract(payload, '$.dados.id') = ?1",
+        params![cliente_id, redigido],
+    )
+}
+
+/// Recoloca na fila uma entrega que falhou.
+pub fn reenviar_entrega(conn: &Connection, id: i64) -> Result<bool> {
+    let linhas = conn.execute(
+        "UPDATE webhook_entregas SET status = ?1, tentativas = 0, proxima_tentativa = ?2 WHERE id = ?3 AND status = ?4",
+        params![STATUS_PENDENTE, db::agora_ts(), id, STATUS_FALHOU],
+    )?;
+    if linhas > 0 {
+        despertador().notify_one();
+    }
+    Ok(linhas > 0)
+}
+
+/// Sobe o despachante das entregas. Os POSTs rodam em `spawn_blocking`, sem segurar a conexão.
+pub fn iniciar_webhooks(conn: Db) {
+    let regras = config::atual().webhooks.clone();
+    tokio::spawn(async move {
+        loop {
+            let _ = tokio::time::timeout(Duration::from_secs(VARREDURA_SEG), despertador().notified()).await;
+            let pendentes = {
+                let conn = conn.lock().unwrap();
+                entregas_a_fazer(&conn, db::agora_ts(), 50)
+            };
+            let pendentes = match pendentes {
+                Ok(p) if p.is_empty() => continue,
+                Ok(p) => p,
+                Err(e) => {
+                    eprintln!("[Webhooks] Erro ao ler entregas pendentes: {}", e);
+                    continue;
+                }
+            };
+
+            let timeout = Duration::from_secs(regras.timeout_seg);
+            let resultados = tokio::task::spawn_blocking(move || {
+                pendentes.iter().map(|e| (e.id, entregar(e, timeout))).collect::<Vec<_>>()
+            })
+            .await
+            .unwrap_or_default();
+
+            let conn = conn.lock().unwrap();
+            for (id, resultado) in &resultados {
+                if let Some(erro) = &resultado.1 {
+                    eprintln!("[Webhooks] Falha na entrega {}: {}", id, erro);
+                }
+                if let Err(e) = registrar_entrega(&conn, *id, resultado, config::agora(), regras.tentativas_max) {
+                    eprintln!("[Webhooks] Erro ao registrar entrega {}: {}", id, e);
+                }
+            }
+        }
+    });
+}
+
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    success: bool,
+    message: String,
+    data: Option<T>,
+}
+
+fn negar<T>(erro: (StatusCode, String)) -> (StatusCode, Json<ApiResponse<T>>) {
+    (erro.0, Json(ApiResponse { success: false, message: erro.1, data: None }))
+}
+
+fn erro_interno<T>(contexto: &str, e: rusqlite::Error) -> (StatusCode, Json<ApiResponse<T>>) {
+    eprintln!("Erro ao {}: {}", contexto, e);
+    (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse { success: false, message: format!("Erro ao {}: {}", contexto, e), data: None }))
+}
+
+/// GET /webhooks — assinaturas cadastradas (sem os segredos). Só o dono.
+pub async fn listar_webhooks_api(headers: HeaderMap, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<Webhook>>>) {
+    if let Err(e) = autorizar_dono(&headers) {
+        return negar(e);
+    }
+    let conn = conn.lock().unwrap();
+    match listar_webhooks(&conn) {
+        Ok(lista) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} webhook(s).", lista.len()), data: Some(lista) })),
+        Err(e) => erro_interno("listar webhooks", e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NovoWebhook {
+    pub url: String,
+    pub eventos: Vec<String>,
+    /// Se omitido, um segredo aleatório é gerado e devolvido uma única vez.
+    pub segredo: Option<String>,
+}
+
+/// POST /webhooks — cadastra uma assinatura. Só o dono.
+pub async fn criar_webhook_api(headers: HeaderMap, State(conn): State<Db>, Json(novo): Json<NovoWebhook>) -> (StatusCode, Json<ApiResponse<Webhook>>) {
+    if let Err(e) = autorizar_dono(&headers) {
+        return negar(e);
+    }
+    let url = novo.url.trim();
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return negar((StatusCode::BAD_REQUEST, "A url deve começar com http:// ou https://.".to_string()));
+    }
+    let eventos: Vec<String> = novo.eventos.iter().map(|e| e.trim().to_string()).filter(|e| !e.is_empty()).collect();
+    if eventos.is_empty() {
+        return negar((StatusCode::BAD_REQUEST, "Informe ao menos um evento.".to_string()));
+    }
+    if let Some(invalido) = eventos.iter().find(|e| *e != "*" && !EVENTOS.contains(&e.as_str())) {
+        return negar((StatusCode::BAD_REQUEST, format!("Evento desconhecido '{}'. Use: {} ou *.", invalido, EVENTOS.join(", "))));
+    }
+    let segredo = novo.segredo.filter(|s| !s.trim().is_empty()).unwrap_or_else(gerar_segredo);
+
+    let conn = conn.lock().unwrap();
+    match salvar_webhook(&conn, url, &segredo, &eventos) {
+        Ok(webhook) => {
+            let response = ApiResponse { success: true, message: "Webhook cadastrado. Guarde o segredo: ele não será mostrado de novo.".to_string(), data: Some(webhook) };
+            (StatusCode::CREATED, Json(response))
+        }
+        Err(e) => erro_interno("cadastrar webhook", e),
+    }
+}
+
+/// DELETE /webhooks/:id — remove a assinatura e seu log de entregas. Só o dono.
+pub async fn excluir_webhook_api(headers: HeaderMap, Path(id): Path<i64>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
+    if let Err(e) = autorizar_dono(&headers) {
+        return negar(e);
+    }
+    let conn = conn.lock().unwrap();
+    match excluir_webhook(&conn, id) {
+        Ok(true) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Webhook excluído com sucesso!".to_string(), data: None })),
+        Ok(false) => negar((StatusCode::NOT_FOUND, format!("Webhook {} não encontrado.", id))),
+        Err(e) => erro_interno("excluir webhook", e),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EntregasQuery {
+    webhook_id: Option<i64>,
+    status: Option<String>,
+    limite: Option<i64>,
+}
+
+/// GET /webhooks/entregas?webhook_id=&status=&limite= — log de entregas. Só o dono.
+pub async fn listar_entregas_api(headers: HeaderMap, Query(q): Query<EntregasQuery>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<Vec<EntregaWebhook>>>) {
+    if let Err(e) = autorizar_dono(&headers) {
+        return negar(e);
+    }
+    let conn = conn.lock().unwrap();
+    match listar_entregas(&conn, q.webhook_id, q.status.as_deref(), q.limite.unwrap_or(200)) {
+        Ok(lista) => (StatusCode::OK, Json(ApiResponse { success: true, message: format!("{} entrega(s).", lista.len()), data: Some(lista) })),
+        Err(e) => erro_interno("listar entregas de webhook", e),
+    }
+}
+
+/// POST /webhooks/entregas/:id/reenviar — recoloca uma entrega com falha na fila. Só o dono.
+pub async fn reenviar_entrega_api(headers: HeaderMap, Path(id): Path<i64>, State(conn): State<Db>) -> (StatusCode, Json<ApiResponse<()>>) {
+    if let Err(e) = autorizar_dono(&headers) {
+        return negar(e);
+    }
+    let conn = conn.lock().unwrap();
+    match reenviar_entrega(&conn, id) {
+        Ok(true) => (StatusCode::OK, Json(ApiResponse { success: true, message: "Entrega recolocada na fila.".to_string(), data: None })),
+        Ok(false) => negar((StatusCode::NOT_FOUND, format!("Entrega {} não encontrada ou não está com falha.", id))),
+        Err(e) => erro_interno("reenviar entrega", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assinatura_hmac() {
+        // Vetor conferido com: printf '1700000000.{"a":1}' | openssl dgst -sha256 -hmac segredo
+        assert_eq!(
+            assinar("segredo", 1_700_000_000, "{\"a\":1}"),
+            "sha256=41634607aa61528682a37f94a43a64ca2d3a6a30bc8f2fb88f8f754286d90e85"
+        );
+    }
+
+    #[test]
+    fn test_emitir_filtra_eventos_e_repete_falhas() {
+        let conn = Connection::open_in_memory().unwrap();
+        db::criar_tabelas(&conn).unwrap();
+        let agendamentos = salvar_webhook(&conn, "http://exemplo/a", "s1", &["agendamento.criado".into(), "agendamento.cancelado".into()]).unwrap();
+        let todos = salvar_webhook(&conn, "http://exemplo/b", "s2", &["*".into()]).unwrap();
+
+        assert_eq!(emitir(&conn, EVENTO_AGENDAMENTO_CRIADO, &serde_json::json!({"id": 1})).unwrap(), 2);
+        assert_eq!(emitir(&conn, EVENTO_CLIENTE_CRIADO, &serde_json::json!({"id": 7})).unwrap(), 1);
+
+        let fila = entregas_a_fazer(&conn, db::agora_ts(), 10).unwrap();
+        assert_eq!(fila.len(), 3);
+        let payload: serde_json::Value = serde_json::from_str(&fila[0].payload).unwrap();
+        assert_eq!(payload["evento"], "agendamento.criado");
+        assert_eq!(payload["dados"]["id"], 1);
+
+        // Falha com resposta 500: volta para a fila com espera; na segunda, falha definitiva.
+        let agora = config::agora();
+        let erro: ResultadoEntrega = (Some(500), Some("HTTP 500".into()));
+        registrar_entrega(&conn, fila[0].id, &erro, agora, 2).unwrap();
+        assert_eq!(entregas_a_fazer(&conn, db::agora_ts(), 10).unwrap().len(), 2);
+        registrar_entrega(&conn, fila[0].id, &erro, agora, 2).unwrap();
+        registrar_entrega(&conn, fila[1].id, &(Some(204), None), agora, 2).unwrap();
+
+        let log = listar_entregas(&conn, Some(agendamentos.id), None, 10).unwrap();
+        assert_eq!((log[0].status.as_str(), log[0].tentativas, log[0].resposta_status), (STATUS_FALHOU, 2, Some(500)));
+        assert_eq!(listar_entregas(&conn, Some(todos.id), Some(STATUS_ENTREGUE), 10).unwrap().len(), 1);
+
+        assert!(reenviar_entrega(&conn, log[0].id).unwrap());
+        assert_eq!(listar_entregas(&conn, None, Some(STATUS_PENDENTE), 10).unwrap().len(), 2);
+
+        // Excluir a assinatura leva junto o log.
+        assert!(excluir_webhook(&conn, agendamentos.id).unwrap());
+        assert!(listar_entregas(&conn, Some(agendamentos.id), None, 10).unwrap().is_empty());
+    }
+}